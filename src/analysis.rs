@@ -0,0 +1,491 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Analysis of a SAS topology.
+//
+// The checks in here look for things in the snapshot that indicate a problem
+// with the hardware rather than with the rendering.  Each problem found is
+// reported as a Finding, which is listed in a table on the HTML page and
+// flagged with a warning badge on the vertices involved.
+//
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::connections::connections;
+use crate::dual_port::same_drive;
+use crate::fmri::{device_key, wwn as device_wwn};
+use crate::properties::{categorize, PropCategory};
+use crate::zones::{cross_zone_edges as cross_zone_edges_of, vertex_zones};
+use crate::{html_escape, SasDigraph, SasDigraphVertex, PORT};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum FindingKind {
+    // Two different devices report the same serial number
+    DuplicateSerial,
+    // Two different devices report the same WWN
+    DuplicateWwn,
+    // An edge connects vertices in different SAS zones
+    CrossZoneEdge,
+    // A link negotiated a lower rate than both of its ends support
+    LinkDownshift,
+}
+
+impl FindingKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FindingKind::DuplicateSerial => "duplicate serial number",
+            FindingKind::DuplicateWwn => "duplicate WWN",
+            FindingKind::CrossZoneEdge => "cross-zone edge",
+            FindingKind::LinkDownshift => "link downshift",
+        }
+    }
+}
+
+//
+// Overall condition of a vertex, as shown in the legend.
+//
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum VertexStatus {
+    Ok,
+    // e.g. PHY errors were counted, or the device is involved in a finding
+    Degraded,
+    // the snapshot reports the device as faulted or offline, or a link as down
+    Faulted,
+}
+
+impl VertexStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VertexStatus::Ok => "ok",
+            VertexStatus::Degraded => "degraded",
+            VertexStatus::Faulted => "faulted",
+        }
+    }
+
+    //
+    // Color the vertex is outlined with and its edges are drawn in, if it
+    // isn't healthy.
+    //
+    pub(crate) fn color(self) -> Option<&'static str> {
+        match self {
+            VertexStatus::Ok => None,
+            VertexStatus::Degraded => Some("#FB8C00"),
+            VertexStatus::Faulted => Some("#E53935"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub message: String,
+    // FMRIs of the vertices involved
+    pub fmris: Vec<String>,
+}
+
+pub(crate) fn prop<'a>(vtx: &'a SasDigraphVertex, name: &str) -> Option<&'a str> {
+    vtx.properties
+        .iter()
+        .find(|p| p.name == name)
+        .map(|p| p.value.as_str())
+}
+
+//
+// Serial numbers and WWNs are supposed to be unique.  Two different devices
+// reporting the same one indicates counterfeit or misprogrammed hardware.
+//
+fn duplicate_hardware(digraph: &SasDigraph) -> Vec<Finding> {
+    let mut by_serial: BTreeMap<&str, BTreeMap<&str, Vec<&str>>> = BTreeMap::new();
+    let mut by_wwn: BTreeMap<&str, BTreeMap<(&str, &str), Vec<&str>>> = BTreeMap::new();
+
+    for vtx in digraph.vertices.values() {
+        if vtx.name == PORT {
+            continue;
+        }
+        let serial = prop(vtx, "serial-number").unwrap_or("");
+        if !serial.is_empty() {
+            by_serial
+                .entry(serial)
+                .or_default()
+                .entry(device_key(&vtx.fmri))
+                .or_default()
+                .push(&vtx.fmri);
+        }
+        if let Some(wwn) = device_wwn(&vtx.fmri) {
+            by_wwn
+                .entry(wwn)
+                .or_default()
+                .entry((vtx.name.as_str(), serial))
+                .or_default()
+                .push(&vtx.fmri);
+        }
+    }
+
+    let mut findings = Vec::new();
+    for (serial, devices) in by_serial {
+        //
+        // The two ports of a dual-ported drive show up as two devices with
+        // the same serial number, which is fine.
+        //
+        let vertices: Vec<&SasDigraphVertex> = devices
+            .values()
+            .map(|fmris| &digraph.vertices[fmris[0]])
+            .collect();
+        if vertices.len() == 2 && same_drive(vertices[0], vertices[1]) {
+            continue;
+        }
+        if devices.len() > 1 {
+            let mut fmris: Vec<String> =
+                devices.values().flatten().map(|f| f.to_string()).collect();
+            fmris.sort();
+            findings.push(Finding {
+                kind: FindingKind::DuplicateSerial,
+                message: format!(
+                    "serial number {} is reported by {} devices: {}",
+                    serial,
+                    devices.len(),
+                    devices.keys().cloned().collect::<Vec<&str>>().join(", ")
+                ),
+                fmris,
+            });
+        }
+    }
+    for (wwn, devices) in by_wwn {
+        //
+        // A device whose serial number is unknown (e.g. because the snapshot
+        // lacks its property groups) isn't considered to be different from
+        // one that has the same WWN and a serial number.
+        //
+        let names: BTreeSet<&str> = devices.keys().map(|(name, _)| *name).collect();
+        let serials: BTreeSet<&str> = devices
+            .keys()
+            .map(|(_, serial)| *serial)
+            .filter(|serial| !serial.is_empty())
+            .collect();
+        if names.len() > 1 || serials.len() > 1 {
+            let mut fmris: Vec<String> =
+                devices.values().flatten().map(|f| f.to_string()).collect();
+            fmris.sort();
+            let descs: BTreeSet<String> = devices
+                .keys()
+                .filter(|(_, serial)| serials.is_empty() || !serial.is_empty())
+                .map(|(name, serial)| match *serial {
+                    "" => name.to_string(),
+                    _ => format!("{} (serial {})", name, serial),
+                })
+                .collect();
+            findings.push(Finding {
+                kind: FindingKind::DuplicateWwn,
+                message: format!(
+                    "WWN {} is reported by {} devices: {}",
+                    wwn,
+                    descs.len(),
+                    descs.into_iter().collect::<Vec<String>>().join(", ")
+                ),
+                fmris,
+            });
+        }
+    }
+    findings
+}
+
+//
+// Counters are either decimal or hex (with a leading "0x").
+//
+pub(crate) fn parse_count(value: &str) -> Option<u64> {
+    let value = value.trim();
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+//
+// Returns true if any of the PHY error counters of the vertex are non-zero.
+// The counters are arrays with one value per PHY.
+//
+fn has_phy_errors(vtx: &SasDigraphVertex) -> bool {
+    vtx.properties
+        .iter()
+        .filter(|p| categorize(&p.name) == PropCategory::Errors)
+        .flat_map(|p| p.value.split(','))
+        .any(|v| matches!(parse_count(v), Some(count) if count > 0))
+}
+
+//
+// Properties some topo modules use to report the state of a device or link,
+// and the values of them that mean it is faulted or degraded.  Values are
+// compared case-insensitively.
+//
+const STATE_PROPERTIES: &[&str] = &["state", "status", "device-state", "link-state"];
+const FAULTED_STATES: &[&str] = &["faulted", "failed", "offline", "removed", "down"];
+const DEGRADED_STATES: &[&str] = &["degraded", "predictive-failure", "resetting"];
+
+//
+// Negotiated link rate codes of a PHY that is disabled or failed to
+// negotiate a rate (see link_rate_strings in sastopo2svg.js).
+//
+const LINK_DOWN_RATES: &[u64] = &[0x1, 0x2];
+// the PHY came up, but something is wrong with it
+const LINK_PROBLEM_RATES: &[u64] = &[0x2, 0x6];
+
+//
+// Returns the status the snapshot itself reports for the vertex, from its
+// state and fault properties and, for ports, the negotiated link rates of
+// its PHYs.  A port all of whose PHYs are down is faulted.
+//
+fn reported_status(vtx: &SasDigraphVertex) -> VertexStatus {
+    let mut status = VertexStatus::Ok;
+    for name in STATE_PROPERTIES {
+        if let Some(value) = prop(vtx, name) {
+            let value = value.trim().to_lowercase();
+            if FAULTED_STATES.contains(&value.as_str()) {
+                return VertexStatus::Faulted;
+            } else if DEGRADED_STATES.contains(&value.as_str()) {
+                status = VertexStatus::Degraded;
+            }
+        }
+    }
+    if let Some(value) = prop(vtx, "fault") {
+        if matches!(value.trim(), "true" | "1" | "yes") {
+            return VertexStatus::Faulted;
+        }
+    }
+
+    if vtx.name == PORT {
+        if let Some(rates) = prop(vtx, "negotiated-link-rate") {
+            let rates: Vec<u64> = rates.split(',').filter_map(parse_count).collect();
+            if !rates.is_empty() && rates.iter().all(|r| LINK_DOWN_RATES.contains(r)) {
+                return VertexStatus::Faulted;
+            } else if rates.iter().any(|r| LINK_PROBLEM_RATES.contains(r)) {
+                status = VertexStatus::Degraded;
+            }
+        }
+    }
+    status
+}
+
+pub(crate) fn vertex_status(vtx: &SasDigraphVertex, findings: &[Finding]) -> VertexStatus {
+    let status = reported_status(vtx);
+    if has_phy_errors(vtx) || findings.iter().any(|f| f.fmris.contains(&vtx.fmri)) {
+        cmp::max(status, VertexStatus::Degraded)
+    } else {
+        status
+    }
+}
+
+//
+// Link rates are reported per PHY, as SAS negotiated link rate codes.
+//
+pub(crate) fn link_rate_label(rate: Option<u64>) -> String {
+    match rate {
+        Some(0x8) => "1.5G".to_string(),
+        Some(0x9) => "3G".to_string(),
+        Some(0xa) => "6G".to_string(),
+        Some(0xb) => "12G".to_string(),
+        Some(0xc) => "22.5G".to_string(),
+        Some(code) => format!("rate {:#x}", code),
+        None => "unknown".to_string(),
+    }
+}
+
+//
+// The rate of a link rate code in Gbit/s, for adding up the lanes of a wide
+// port.  PHYs that are down have none.
+//
+pub(crate) fn link_rate_gbps(rate: u64) -> Option<f64> {
+    match rate {
+        0x8 => Some(1.5),
+        0x9 => Some(3.0),
+        0xa => Some(6.0),
+        0xb => Some(12.0),
+        0xc => Some(22.5),
+        _ => None,
+    }
+}
+
+//
+// Count the physical links of the fabric by negotiated rate and status.  A
+// wide link is counted at the rate of its slowest PHY, and as degraded if
+// either of the ports it connects is.  Like the findings, links are counted
+// once no matter how many HBAs they are reachable from.
+//
+pub(crate) fn link_summary(
+    digraph: &SasDigraph,
+    findings: &[Finding],
+) -> BTreeMap<(Option<u64>, VertexStatus), usize> {
+    let mut links = BTreeMap::new();
+    for conn in connections(digraph) {
+        let key = (
+            device_key(&conn.source.port_fmri).to_string(),
+            device_key(&conn.target.port_fmri).to_string(),
+        );
+        let rate = conn
+            .negotiated_link_rate
+            .as_deref()
+            .and_then(|rates| rates.split(',').filter_map(parse_count).min());
+        let status = cmp::max(
+            vertex_status(&digraph.vertices[&conn.source.port_fmri], findings),
+            vertex_status(&digraph.vertices[&conn.target.port_fmri], findings),
+        );
+        links.insert(key, (rate, status));
+    }
+
+    let mut summary = BTreeMap::new();
+    for class in links.into_values() {
+        *summary.entry(class).or_insert(0) += 1;
+    }
+    summary
+}
+
+//
+// The same link shows up once per HBA it is reachable from, so the edges are
+// reported per pair of devices rather than per pair of FMRIs.
+//
+fn cross_zone_edges(digraph: &SasDigraph) -> Vec<Finding> {
+    let zones = vertex_zones(digraph);
+    let mut links: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (from, to) in cross_zone_edges_of(digraph, &zones) {
+        let message = format!(
+            "{} (zone {}) is connected to {} (zone {})",
+            device_key(&from),
+            zones[&from],
+            device_key(&to),
+            zones[&to]
+        );
+        let fmris = links.entry(message).or_default();
+        fmris.push(from);
+        fmris.push(to);
+    }
+
+    links
+        .into_iter()
+        .map(|(message, fmris)| Finding {
+            kind: FindingKind::CrossZoneEdge,
+            message,
+            fmris,
+        })
+        .collect()
+}
+
+//
+// A link between two ports that negotiated a lower rate than the hardware at
+// both of its ends is capable of, e.g. a 12G drive running at 6G.
+//
+#[derive(Clone, Debug)]
+pub(crate) struct Downshift {
+    pub source: String,
+    pub target: String,
+    // negotiated link rate codes
+    pub negotiated: u64,
+    pub capability: u64,
+}
+
+//
+// Returns the codes of the actual rates (1.5G and up) among the per-PHY link
+// rates of the property, ignoring those of PHYs that are down.
+//
+fn rates(vtx: &SasDigraphVertex, name: &str) -> Vec<u64> {
+    prop(vtx, name)
+        .map(|value| value.split(',').filter_map(parse_count).filter(|r| *r >= 0x8).collect())
+        .unwrap_or_default()
+}
+
+//
+// Finds the links negotiating below the maximum rate supported by both of
+// their ends, which is only known where the ports report their max-link-rate.
+// A wide link is compared at the rate of its slowest PHY.
+//
+pub(crate) fn link_downshifts(digraph: &SasDigraph) -> Vec<Downshift> {
+    let mut downshifts = Vec::new();
+    for conn in connections(digraph) {
+        let source = &digraph.vertices[&conn.source.port_fmri];
+        let target = &digraph.vertices[&conn.target.port_fmri];
+        let negotiated = match rates(source, "negotiated-link-rate").into_iter().min() {
+            Some(rate) => rate,
+            None => continue,
+        };
+        let capability = [source, target]
+            .iter()
+            .filter_map(|vtx| rates(vtx, "max-link-rate").into_iter().max())
+            .min();
+        if let Some(capability) = capability {
+            if negotiated < capability {
+                downshifts.push(Downshift {
+                    source: conn.source.port_fmri,
+                    target: conn.target.port_fmri,
+                    negotiated,
+                    capability,
+                });
+            }
+        }
+    }
+    downshifts
+}
+
+//
+// Like the cross-zone edges, the downshifted links are reported per pair of
+// devices rather than per pair of FMRIs.
+//
+fn downshifted_links(digraph: &SasDigraph) -> Vec<Finding> {
+    let mut links: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for downshift in link_downshifts(digraph) {
+        let message = format!(
+            "{} is connected to {} at {}, below the {} both ends support",
+            device_key(&downshift.source),
+            device_key(&downshift.target),
+            link_rate_label(Some(downshift.negotiated)),
+            link_rate_label(Some(downshift.capability))
+        );
+        let fmris = links.entry(message).or_default();
+        fmris.push(downshift.source);
+        fmris.push(downshift.target);
+    }
+
+    links
+        .into_iter()
+        .map(|(message, fmris)| Finding {
+            kind: FindingKind::LinkDownshift,
+            message,
+            fmris,
+        })
+        .collect()
+}
+
+pub(crate) fn analyze(digraph: &SasDigraph) -> Vec<Finding> {
+    let mut findings = duplicate_hardware(digraph);
+    findings.extend(cross_zone_edges(digraph));
+    findings.extend(downshifted_links(digraph));
+    findings
+}
+
+//
+// Generate the table of findings shown on the HTML page.  Nothing is
+// generated if there aren't any.
+//
+pub(crate) fn findings_html(findings: &[Finding]) -> String {
+    if findings.is_empty() {
+        return String::new();
+    }
+
+    let mut html = String::from(
+        "<table id=\"warnings\">\n<thead><tr><th colspan=\"2\">Warnings</th></tr></thead>\n",
+    );
+    for finding in findings {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td></tr>\n",
+            finding.kind.as_str(),
+            html_escape(&finding.message)
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
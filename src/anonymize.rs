@@ -0,0 +1,106 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Anonymization of a topology before it is rendered, so that the output can
+// be shared outside of the organization.  The host name, the WWNs (in FMRIs as
+// well as in property values) and the serial numbers are replaced with
+// pseudonyms.  Each distinct value gets its own pseudonym, so the shape of the
+// fabric (including e.g. devices that are reachable over several paths) is
+// preserved.
+//
+use std::collections::BTreeMap;
+
+use crate::{SasDigraph, VertexGraph};
+
+// WWNs are 64-bit, i.e. 16 hex digits
+const WWN_DIGITS: usize = 16;
+
+#[derive(Default)]
+struct Pseudonyms {
+    wwns: BTreeMap<String, String>,
+    serials: BTreeMap<String, String>,
+}
+
+impl Pseudonyms {
+    fn wwn(&mut self, wwn: &str) -> String {
+        let next = self.wwns.len() as u64 + 1;
+        self.wwns
+            .entry(wwn.to_lowercase())
+            .or_insert_with(|| format!("{:016x}", 0xa000_0000_0000_0000 | next))
+            .clone()
+    }
+
+    fn serial(&mut self, serial: &str) -> String {
+        let next = self.serials.len() + 1;
+        self.serials
+            .entry(serial.to_string())
+            .or_insert_with(|| format!("SERIAL{:04}", next))
+            .clone()
+    }
+
+    //
+    // Replace every run of exactly 16 hex digits in s with its pseudonym.
+    //
+    fn replace_wwns(&mut self, s: &str) -> String {
+        let mut result = String::with_capacity(s.len());
+        let mut token = String::new();
+        for c in s.chars().chain(std::iter::once('\0')) {
+            if c.is_ascii_alphanumeric() {
+                token.push(c);
+                continue;
+            }
+            if token.len() == WWN_DIGITS && token.chars().all(|c| c.is_ascii_hexdigit()) {
+                result.push_str(&self.wwn(&token));
+            } else {
+                result.push_str(&token);
+            }
+            token.clear();
+            if c != '\0' {
+                result.push(c);
+            }
+        }
+        result
+    }
+}
+
+pub(crate) fn anonymize(digraph: &mut SasDigraph) {
+    let mut pseudonyms = Pseudonyms::default();
+
+    //
+    // Assign the pseudonyms in FMRI order, so that the result doesn't depend
+    // on the order of the hash map.
+    //
+    let mut fmris: Vec<String> = digraph.vertices.keys().cloned().collect();
+    fmris.sort();
+
+    let mut vertices = VertexGraph::new();
+    for fmri in fmris {
+        let mut vtx = digraph.vertices.remove(&fmri).unwrap();
+        vtx.fmri = pseudonyms.replace_wwns(&vtx.fmri);
+        if let Some(edges) = &mut vtx.outgoing_edges {
+            for edge in edges.iter_mut() {
+                *edge = pseudonyms.replace_wwns(edge);
+            }
+        }
+        for prop in &mut vtx.properties {
+            prop.value = match prop.name.as_str() {
+                "serial-number" => pseudonyms.serial(&prop.value),
+                _ => pseudonyms.replace_wwns(&prop.value),
+            };
+            prop.typed = None;
+        }
+        vertices.insert(vtx.fmri.clone(), vtx);
+    }
+    digraph.vertices = vertices;
+
+    for fmri in &mut digraph.initiators {
+        *fmri = pseudonyms.replace_wwns(fmri);
+    }
+    digraph.nodename = "anonymized".to_string();
+}
@@ -0,0 +1,267 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Archival mode.
+//
+// The JS and CSS the viewer needs are inlined into the generated SVG and HTML
+// page, and the icons and chassis images are copied into the output
+// directory, so an output directory doesn't depend on anything outside of
+// itself.  In archival mode, a manifest is written alongside the output,
+// recording the version of the generator along with an integrity hash (in the
+// Subresource Integrity "sha256-<base64>" format) of every file the run
+// wrote.  The assets are copied under the same names by every version, so
+// their hashes are listed apart as well, identifying the icons and images the
+// diagram was made with.  The manifest has a version of its own,
+// MANIFEST_VERSION, independent of that of the JSON export.  When an
+// archived diagram is opened years later, verify_archive() can be used to
+// confirm that it is still exactly what was generated.
+//
+// For storage, an archived output directory is packed into a gzip-compressed
+// tarball with pack_archive(), once it has been verified, and unpacked with
+// unpack_archive(), which verifies it again:
+//
+//     sastopo2svg --pack-archive out        writes out.tar.gz
+//     sastopo2svg --unpack-archive out.tar.gz   unpacks it to out
+//
+// The tarball only holds the files of the manifest and the manifest itself,
+// with the same timestamp and permissions, so that packing the same output
+// twice gives the same tarball.
+//
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::SimpleError;
+
+pub const MANIFEST: &str = "sastopo-manifest.json";
+
+// version of the layout of the manifest
+pub const MANIFEST_VERSION: &str = "1.0";
+
+// directory of the output the assets are copied to, see write_assets()
+pub(crate) const ASSETS_DIR: &str = "assets/";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub generator: String,
+    pub manifest_version: String,
+    // integrity hashes, keyed by path relative to the output directory
+    pub files: BTreeMap<String, String>,
+    // integrity hashes of the assets, keyed by path relative to the assets
+    // directory
+    #[serde(default)]
+    pub assets: BTreeMap<String, String>,
+}
+
+pub(crate) fn integrity(contents: &[u8]) -> String {
+    format!("sha256-{}", base64::encode(Sha256::digest(contents)))
+}
+
+//
+// Returns the paths (relative to dir) of all of the files below dir.
+//
+pub(crate) fn list_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relpath = match prefix {
+            "" => name,
+            _ => format!("{}/{}", prefix, name),
+        };
+        if entry.file_type()?.is_dir() {
+            list_files(&entry.path(), &relpath, files)?;
+        } else if relpath != MANIFEST {
+            files.push(relpath);
+        }
+    }
+    Ok(())
+}
+
+//
+// Returns the manifest covering the files with the specified integrity
+// hashes, keyed by path relative to the output directory.
+//
+pub(crate) fn manifest_json(files: &BTreeMap<String, String>) -> Result<String, Box<dyn Error>> {
+    let assets = files
+        .iter()
+        .filter_map(|(path, hash)| {
+            Some((path.strip_prefix(ASSETS_DIR)?.to_string(), hash.clone()))
+        })
+        .collect();
+    let manifest = Manifest {
+        generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        manifest_version: MANIFEST_VERSION.to_string(),
+        files: files.clone(),
+        assets,
+    };
+    Ok(serde_json::to_string_pretty(&manifest)?)
+}
+
+fn read_manifest(dir: &str) -> Result<Manifest, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(format!("{}/{}", dir, MANIFEST))?)?)
+}
+
+//
+// Check the files in an archived output directory against its manifest.
+// Returns a description of every file that is missing, has been modified or
+// wasn't part of the original output.  An empty list means the archive is
+// intact.
+//
+pub fn verify_archive(dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let manifest = read_manifest(dir)?;
+
+    let mut problems = Vec::new();
+    for (path, expected) in &manifest.files {
+        match fs::read(format!("{}/{}", dir, path)) {
+            Ok(contents) => {
+                if integrity(&contents) != *expected {
+                    problems.push(format!("{}: modified", path));
+                }
+            }
+            Err(_) => problems.push(format!("{}: missing", path)),
+        }
+    }
+
+    let mut paths = Vec::new();
+    list_files(Path::new(dir), "", &mut paths)?;
+    for path in paths {
+        if !manifest.files.contains_key(&path) {
+            problems.push(format!("{}: not part of the archive", path));
+        }
+    }
+    problems.sort();
+    Ok(problems)
+}
+
+//
+// Pack an archived output directory into a gzip-compressed tarball at path.
+// The directory is verified first, so that a tarball is only ever made of an
+// intact output.
+//
+pub fn pack_archive(dir: &str, path: &str) -> Result<(), Box<dyn Error>> {
+    let problems = verify_archive(dir)?;
+    if !problems.is_empty() {
+        return Err(Box::new(SimpleError(format!(
+            "{} doesn't match its manifest: {}",
+            dir,
+            problems.join(", ")
+        ))));
+    }
+
+    let manifest = read_manifest(dir)?;
+    let gzip = GzEncoder::new(fs::File::create(path)?, Compression::default());
+    let mut tarball = tar::Builder::new(gzip);
+    for file in manifest.files.keys().map(|f| f.as_str()).chain(std::iter::once(MANIFEST)) {
+        let contents = fs::read(format!("{}/{}", dir, file))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_cksum();
+        tarball.append_data(&mut header, file, contents.as_slice())?;
+    }
+    tarball.into_inner()?.finish()?;
+    Ok(())
+}
+
+//
+// Unpack a tarball written by pack_archive() into dir, which mustn't exist
+// yet, and check what was unpacked against the manifest, as verify_archive()
+// does.
+//
+pub fn unpack_archive(path: &str, dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    if Path::new(dir).exists() {
+        return Err(Box::new(SimpleError(format!("{} already exists", dir))));
+    }
+    fs::create_dir_all(dir)?;
+    // entries with paths leading out of dir are refused by unpack()
+    tar::Archive::new(GzDecoder::new(fs::File::open(path)?)).unpack(dir)?;
+    verify_archive(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> String {
+        let dir = std::env::temp_dir()
+            .join(format!("sastopo2svg-archive-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.to_string_lossy().to_string()
+    }
+
+    // writes an archived output directory, as run() would in archival mode
+    fn archived_output(dir: &str) {
+        let files = [("sastopo.svg", "<svg/>"), ("assets/icon.png", "PNG")];
+        let mut hashes = BTreeMap::new();
+        fs::create_dir_all(format!("{}/assets", dir)).unwrap();
+        for (path, contents) in &files {
+            fs::write(format!("{}/{}", dir, path), contents).unwrap();
+            hashes.insert(path.to_string(), integrity(contents.as_bytes()));
+        }
+        fs::write(format!("{}/{}", dir, MANIFEST), manifest_json(&hashes).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        let (dir, unpacked) = (scratch("out"), scratch("unpacked"));
+        let tarball = format!("{}.tar.gz", dir);
+        archived_output(&dir);
+        pack_archive(&dir, &tarball).unwrap();
+
+        // packing the same output again gives the same tarball
+        let again = format!("{}-again.tar.gz", dir);
+        pack_archive(&dir, &again).unwrap();
+        assert_eq!(fs::read(&tarball).unwrap(), fs::read(&again).unwrap());
+
+        assert!(unpack_archive(&tarball, &unpacked).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(format!("{}/assets/icon.png", unpacked)).unwrap(), "PNG");
+        assert!(unpack_archive(&tarball, &unpacked).is_err());
+
+        for path in [&dir, &unpacked] {
+            fs::remove_dir_all(path).unwrap();
+        }
+        for path in [&tarball, &again] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn manifest_assets() {
+        let mut files = BTreeMap::new();
+        files.insert("sastopo.svg".to_string(), integrity(b"<svg/>"));
+        files.insert("assets/icon.png".to_string(), integrity(b"PNG"));
+        let manifest: Manifest = serde_json::from_str(&manifest_json(&files).unwrap()).unwrap();
+        assert_eq!(manifest.manifest_version, MANIFEST_VERSION);
+        assert_eq!(manifest.files, files);
+        assert_eq!(manifest.assets.len(), 1);
+        assert_eq!(manifest.assets["icon.png"], integrity(b"PNG"));
+    }
+
+    #[test]
+    fn pack_modified() {
+        let dir = scratch("modified");
+        archived_output(&dir);
+        fs::write(format!("{}/sastopo.svg", dir), "<svg></svg>").unwrap();
+        let error = pack_archive(&dir, &format!("{}.tar.gz", dir)).unwrap_err().to_string();
+        assert!(error.contains("sastopo.svg: modified"), "{}", error);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
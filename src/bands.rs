@@ -0,0 +1,69 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Column banding.
+//
+// Each column of the layout holds the devices that are the same number of
+// hops away from the initiators.  On a wide fabric it's hard to follow a
+// column down the page, so each one can be drawn on a full-height background
+// band, alternating between the colors of the theme, and labeled with its
+// hop distance at the bottom.  The bands are off unless turned on with
+// --column-bands or by the theme of a configuration file.
+//
+use svg::node::element::{Group, Rectangle, Text};
+
+use crate::{Layout, Theme};
+
+//
+// Draw the bands behind the columns 1 through max_depth, from top down to
+// bottom.  Returns None if the theme has banding disabled.
+//
+pub(crate) fn render_bands(
+    theme: &Theme,
+    layout: &Layout,
+    max_depth: u32,
+    top: u32,
+    bottom: u32,
+) -> Option<Group> {
+    if theme.column_bands.is_empty() {
+        return None;
+    }
+
+    let mut layer = Group::new().set("id", "layer-columns");
+    for depth in 1..=max_depth {
+        let center = ((depth - 1) * layout.column_width)
+            + layout.x_margin
+            + (layout.vertex_width / 2);
+        let left = center.saturating_sub(layout.column_width / 2);
+        let right = center + (layout.column_width / 2);
+        let color = &theme.column_bands[(depth - 1) as usize % theme.column_bands.len()];
+
+        let band = Rectangle::new()
+            .set("class", "column-band")
+            .set("x", left)
+            .set("y", top)
+            .set("width", right - left)
+            .set("height", bottom.saturating_sub(top))
+            .set("fill", color.clone());
+        layer = layer.add(band);
+
+        if theme.column_labels {
+            let label = Text::new()
+                .set("x", center)
+                .set("y", bottom.saturating_sub(6))
+                .set("text-anchor", "middle")
+                .set("font-family", "Arial, Helvetica, sans-serif")
+                .set("font-size", 12)
+                .set("fill", "#9E9E9E")
+                .add(svg::node::Text::new(format!("hop {}", depth - 1)));
+            layer = layer.add(label);
+        }
+    }
+    Some(layer)
+}
@@ -0,0 +1,157 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Building topologies programmatically.
+//
+// Tests and fabric simulators describe topologies in Rust rather than by
+// synthesizing the XML sastopo would write.  SasDigraphBuilder collects the
+// vertices, their properties and the edges between them, and build() checks
+// that they make up a digraph, i.e. that the FMRIs are unique and that every
+// edge leads to a vertex, before handing it over to be rendered:
+//
+//     let digraph = SasDigraph::builder()
+//         .set_host_info("Joyent-M12G5", "testhost", "joyent_20200101T000000Z",
+//             "2020-01-01T00:00:00Z")
+//         .add_vertex(HBA, "initiator", 0)
+//         .add_property(HBA, "initiator", "manufacturer", "LSI")
+//         .add_vertex(DISK, "target", 0)
+//         .add_edge(HBA, DISK)
+//         .build()?;
+//     render(&config, digraph)?;
+//
+// The roots of the topology are the vertices named as such by the FMRI scheme
+// (see scheme.rs), i.e. the initiators unless set_scheme() says otherwise.
+//
+use std::error::Error;
+
+use crate::{
+    SasDigraph, SasDigraphProperty, SasDigraphVertex, SasInstance, Scheme, SimpleError, VertexGraph,
+};
+
+#[derive(Clone, Debug)]
+pub struct SasDigraphBuilder {
+    digraph: SasDigraph,
+    // the vertices in the order they were added, with their edges
+    vertices: Vec<SasDigraphVertex>,
+    // the first of the errors to report from build(), if any
+    error: Option<String>,
+}
+
+impl SasDigraphBuilder {
+    pub fn new() -> SasDigraphBuilder {
+        SasDigraphBuilder {
+            digraph: SasDigraph::new(String::new(), String::new(), String::new(), String::new()),
+            vertices: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn set_host_info(
+        mut self,
+        product_id: &str,
+        nodename: &str,
+        os_version: &str,
+        timestamp: &str,
+    ) -> SasDigraphBuilder {
+        self.digraph.product_id = product_id.to_string();
+        self.digraph.nodename = nodename.to_string();
+        self.digraph.os_version = os_version.to_string();
+        self.digraph.timestamp = timestamp.to_string();
+        self
+    }
+
+    pub fn set_scheme(mut self, scheme: Scheme) -> SasDigraphBuilder {
+        self.digraph.scheme = scheme;
+        self
+    }
+
+    pub fn add_vertex(mut self, fmri: &str, name: &str, instance: u64) -> SasDigraphBuilder {
+        if self.vertices.iter().any(|vtx| vtx.fmri == fmri) {
+            self.fail(format!("vertex {} was added twice", fmri));
+            return self;
+        }
+        self.vertices.push(SasDigraphVertex::new(
+            fmri.to_string(),
+            name.to_string(),
+            SasInstance::from_value(instance),
+            None,
+        ));
+        self
+    }
+
+    //
+    // Adds a string property, in the specified property group, to a vertex
+    // that was already added.
+    //
+    pub fn add_property(
+        mut self,
+        fmri: &str,
+        group: &str,
+        name: &str,
+        value: &str,
+    ) -> SasDigraphBuilder {
+        match self.vertices.iter_mut().find(|vtx| vtx.fmri == fmri) {
+            Some(vtx) => {
+                let mut prop = SasDigraphProperty::new(name.to_string(), value.to_string());
+                prop.proptype = Some("string".to_string());
+                prop.group = Some(group.to_string());
+                vtx.properties.push(prop);
+            }
+            None => self.fail(format!("property {} added to unknown vertex {}", name, fmri)),
+        }
+        self
+    }
+
+    //
+    // Adds an edge from a vertex that was already added.  The vertex it leads
+    // to can be added later on.
+    //
+    pub fn add_edge(mut self, from: &str, to: &str) -> SasDigraphBuilder {
+        match self.vertices.iter_mut().find(|vtx| vtx.fmri == from) {
+            Some(vtx) => vtx.outgoing_edges.get_or_insert_with(Vec::new).push(to.to_string()),
+            None => self.fail(format!("edge to {} added from unknown vertex {}", to, from)),
+        }
+        self
+    }
+
+    pub fn build(self) -> Result<SasDigraph, Box<dyn Error>> {
+        if let Some(error) = self.error {
+            return Err(Box::new(SimpleError(error)));
+        }
+
+        let mut digraph = self.digraph;
+        let mut vertices = VertexGraph::new();
+        for vtx in self.vertices {
+            if digraph.scheme.is_root(&vtx.name) {
+                digraph.initiators.push(vtx.fmri.clone());
+            }
+            vertices.insert(vtx.fmri.clone(), vtx);
+        }
+        for vtx in vertices.values() {
+            if let Some(edge) = vtx.outgoing_edges().iter().find(|e| !vertices.contains_key(e)) {
+                return Err(Box::new(SimpleError(format!(
+                    "edge from {} leads to unknown vertex {}",
+                    vtx.fmri, edge
+                ))));
+            }
+        }
+        digraph.vertices = vertices;
+        Ok(digraph)
+    }
+
+    fn fail(&mut self, error: String) {
+        self.error.get_or_insert(error);
+    }
+}
+
+impl Default for SasDigraphBuilder {
+    fn default() -> SasDigraphBuilder {
+        SasDigraphBuilder::new()
+    }
+}
@@ -0,0 +1,239 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Support bundles.
+//
+// Support bundles are tarballs (usually gzip-compressed) of the diagnostic
+// data collected from a system, among which is the output of sastopo -x.
+// Where in the bundle the snapshot is, and what it is called, depends on the
+// tool and version that collected the bundle, so rather than going by the
+// layout, the snapshot is recognized by its contents: it is the first XML
+// document (possibly compressed) in the bundle with a SAS scheme <topology>
+// element at its top.  Failing that, an hc-scheme topology is taken.
+//
+// The snapshot is extracted to a temporary file, which is read like any
+// other snapshot (see open_snapshot()), so that neither it nor the bundle has
+// to be held in memory.  The candidates are extracted into a private
+// directory that is created for the purpose and removed along with them, so
+// that nothing is left behind on errors, and other users can neither read
+// nor plant the files.  Candidates that can't be decompressed are skipped.
+//
+use log::debug;
+use tar::Archive;
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{decompress, SimpleError};
+
+// offset and value of the magic number in the header of a tar archive
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+// how much of a file is looked at to recognize a snapshot
+const HEAD_SIZE: u64 = 4096;
+
+// how many names are tried for the temporary directory
+const TEMP_DIR_ATTEMPTS: usize = 100;
+
+// tells apart the temporary directories created by the same process
+static TEMP_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+//
+// A private temporary directory, which is removed along with its contents
+// when dropped.
+//
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn create() -> io::Result<TempDir> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        let mut attempts = 0;
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "sastopo2svg-bundle-{}-{:08x}-{}",
+                std::process::id(),
+                nanos,
+                TEMP_DIR_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            //
+            // Creating the directory fails if anything exists at the path,
+            // including a symlink, so it is ours.
+            //
+            match builder.create(&path) {
+                Ok(()) => return Ok(TempDir(path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    attempts += 1;
+                    if attempts == TEMP_DIR_ATTEMPTS {
+                        return Err(e);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.0) {
+            debug!("Failed to remove {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+//
+// A snapshot extracted from a support bundle, which is removed when dropped.
+//
+pub(crate) struct ExtractedSnapshot {
+    pub(crate) path: PathBuf,
+    _dir: TempDir,
+}
+
+//
+// Whether the data starting with head is a tar archive.  Archives compressed
+// as a whole are recognized by name, as not enough of the data may have been
+// decompressed to look at its header.
+//
+pub(crate) fn is_bundle(path: &str, head: &[u8]) -> bool {
+    let name = path.to_lowercase();
+    name.ends_with(".tar.gz")
+        || name.ends_with(".tgz")
+        || name.ends_with(".tar")
+        || head.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC)
+}
+
+//
+// Whether the file is a topology snapshot, and of which scheme, going by the
+// first few KB of it, decompressed if need be.
+//
+fn topology_scheme(path: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    let mut head = Vec::new();
+    decompress(&path.to_string_lossy())?
+        .take(HEAD_SIZE)
+        .read_to_end(&mut head)?;
+    let head = String::from_utf8_lossy(&head);
+    if !head.contains("<topology") || !head.contains("<vertices") {
+        return Ok(None);
+    }
+    let scheme = head
+        .split("<fmri-scheme>")
+        .nth(1)
+        .and_then(|rest| rest.split("</fmri-scheme>").next())
+        .unwrap_or("sas");
+    Ok(Some(scheme.trim().to_string()))
+}
+
+//
+// Extracts the topology snapshot from the support bundle read from source
+// into a temporary file, which is removed when the result is dropped.
+//
+pub(crate) fn extract_snapshot(
+    bundle: &str,
+    source: Box<dyn BufRead>,
+) -> Result<ExtractedSnapshot, Box<dyn Error>> {
+    let dir = TempDir::create()?;
+    let mut archive = Archive::new(source);
+    let mut fallback: Option<PathBuf> = None;
+    for (index, entry) in archive.entries()?.enumerate() {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().to_string();
+        let base = name.trim_end_matches(".gz").trim_end_matches(".bz2");
+        if !base.ends_with(".xml") && !base.ends_with(".out") && !base.ends_with(".txt") {
+            continue;
+        }
+
+        let tmp = dir.0.join(format!("entry-{}.xml", index));
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+        io::copy(&mut entry, &mut file)?;
+        let scheme = match topology_scheme(&tmp) {
+            Ok(scheme) => scheme,
+            Err(e) => {
+                debug!("Skipping {} in {}: {}", name, bundle, e);
+                None
+            }
+        };
+        match scheme.as_deref() {
+            Some("sas") => {
+                debug!("Found the SAS topology snapshot in {} at {}", bundle, name);
+                return Ok(ExtractedSnapshot { path: tmp, _dir: dir });
+            }
+            Some(_) if fallback.is_none() => {
+                debug!("Found a topology snapshot in {} at {}", bundle, name);
+                fallback = Some(tmp);
+            }
+            _ => fs::remove_file(&tmp)?,
+        }
+    }
+    match fallback {
+        Some(tmp) => Ok(ExtractedSnapshot { path: tmp, _dir: dir }),
+        None => Err(Box::new(SimpleError(format!(
+            "no topology snapshot found in the support bundle {}",
+            bundle
+        )))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    const SNAPSHOT: &str = "<?xml version=\"1.0\"?>\n\
+        <topology>\n<fmri-scheme>sas</fmri-scheme>\n<vertices>\n</vertices>\n</topology>\n";
+
+    fn bundle(entries: &[(&str, &[u8])]) -> Box<dyn BufRead> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        Box::new(Cursor::new(builder.into_inner().unwrap()))
+    }
+
+    #[test]
+    fn skips_corrupt_entries() {
+        // gzip magic number followed by garbage
+        let corrupt: &[u8] = &[0x1f, 0x8b, 0x08, 0x00, 0xde, 0xad, 0xbe, 0xef];
+        let source = bundle(&[
+            ("sos/sastopo.xml.gz", corrupt),
+            ("sos/sastopo.out", SNAPSHOT.as_bytes()),
+        ]);
+        let extracted = extract_snapshot("bundle.tar", source).unwrap();
+        assert_eq!(fs::read_to_string(&extracted.path).unwrap(), SNAPSHOT);
+
+        let dir = extracted.path.parent().unwrap().to_path_buf();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        drop(extracted);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn no_snapshot() {
+        let source = bundle(&[("sos/uname.txt", b"SunOS".as_slice())]);
+        assert!(extract_snapshot("bundle.tar", source).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Capture of the SAS topology of the local system.
+//
+// The topology is captured by running the sastopo utility shipped with FMA,
+// which takes a snapshot of the SAS scheme topology through libtopo and writes
+// it to stdout as XML.  Going through the utility rather than linking against
+// libtopo keeps this crate buildable (and usable on snapshots) on systems
+// without libtopo, and keeps it working across changes to the private libtopo
+// interfaces.  The command can be replaced, e.g. to capture the topology of
+// another system over ssh.  It is run by the shell, like the notification
+// commands (see notify.rs), so that it can quote its arguments.
+//
+use log::debug;
+
+use std::error::Error;
+use std::fs;
+use std::process::Command;
+
+use crate::SimpleError;
+
+pub const DEFAULT_CAPTURE_COMMAND: &str = "sastopo -x";
+
+//
+// Run the capture command and return the XML it wrote to stdout.
+//
+pub(crate) fn capture_xml(command: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if command.trim().is_empty() {
+        return Err(Box::new(SimpleError("no capture command specified".to_string())));
+    }
+    debug!("Capturing the topology with {}", command);
+    let output = match Command::new("/bin/sh").arg("-c").arg(command).output() {
+        Ok(output) => output,
+        Err(e) => return Err(Box::new(SimpleError(format!("failed to run {}: {}", command, e)))),
+    };
+    if !output.status.success() {
+        let msg = format!(
+            "capture command \"{}\" failed ({}): {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        return Err(Box::new(SimpleError(msg)));
+    }
+    Ok(output.stdout)
+}
+
+//
+// Capture the topology with the specified command and save the XML to path,
+// where it can be rendered from like any other snapshot.
+//
+pub fn capture_snapshot(command: &str, path: &str) -> Result<(), Box<dyn Error>> {
+    let xml = capture_xml(command)?;
+    fs::write(path, xml)?;
+    Ok(())
+}
@@ -0,0 +1,362 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// TOML configuration file.
+//
+// Settings that are site-specific (and thus tedious to pass on every command
+// line) can be stored in a configuration file, e.g.:
+//
+//     [branding]
+//     logo = "/opt/site/logo.png"
+//     favicon = "/opt/site/favicon.ico"
+//
+//     [baseline]
+//     approved = "/opt/site/approved-topology.xml"
+//     show_diff = true
+//
+//     [theme]
+//     column_bands = ["#FFFFFF", "#F5F5F5"]
+//     column_labels = false
+//
+// Named profiles describe variants of the output for different audiences,
+// which are all generated from one snapshot in a single run (each into a
+// subdirectory of the output directory named after the profile):
+//
+//     [profiles.engineering]
+//     outputs = ["svg", "json", "cabling"]
+//
+//     [profiles.management]
+//     outputs = ["svg"]
+//     anonymize = true
+//     exclude_properties = ["devfs-path"]
+//
+//     [profiles.assets]
+//     outputs = ["cabling"]
+//
+// Presets bundle layout, theme and filter settings under a name, so that they
+// can be selected with a single --preset option:
+//
+//     [presets.compact]
+//     layout = { column_width = 180, row_height = 110 }
+//     exclude_properties = ["devfs-path"]
+//
+//     [presets.print]
+//     extends = "compact"
+//     theme = { monochrome = true, column_bands = [] }
+//
+// The settings are applied on top of a RenderOptionsBuilder, so options
+// passed explicitly on the command line take precedence.
+//
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Filter, Layout, OutputFormat, RenderOptionsBuilder, SimpleError, Theme};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Branding {
+    // image shown above the page heading
+    pub logo: Option<String>,
+    // icon shown in the browser tab
+    pub favicon: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Baseline {
+    // approved snapshot every render is compared against
+    pub approved: Option<String>,
+    // list the individual differences, not just the drift indicator
+    #[serde(default)]
+    pub show_diff: bool,
+}
+
+//
+// Overrides of the default Theme.  Settings that are left out keep their
+// default value.
+//
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeSettings {
+    pub edge_color: Option<String>,
+    pub edge_width: Option<u32>,
+    // an empty list disables the column bands
+    pub column_bands: Option<Vec<String>>,
+    pub column_labels: Option<bool>,
+    // start from the monochrome variant of the theme
+    pub monochrome: Option<bool>,
+}
+
+impl ThemeSettings {
+    fn is_empty(&self) -> bool {
+        self.edge_color.is_none()
+            && self.edge_width.is_none()
+            && self.column_bands.is_none()
+            && self.column_labels.is_none()
+            && self.monochrome.is_none()
+    }
+
+    fn apply_to(&self, theme: &mut Theme) {
+        match self.monochrome {
+            Some(true) => *theme = theme.clone().to_monochrome(),
+            Some(false) => theme.monochrome = false,
+            None => (),
+        }
+        if let Some(color) = &self.edge_color {
+            theme.edge_color = color.clone();
+        }
+        if let Some(width) = self.edge_width {
+            theme.edge_width = width;
+        }
+        if let Some(bands) = &self.column_bands {
+            theme.column_bands = bands.clone();
+        }
+        if let Some(labels) = self.column_labels {
+            theme.column_labels = labels;
+        }
+    }
+}
+
+//
+// Overrides of the default Layout.
+//
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutSettings {
+    pub column_width: Option<u32>,
+    pub row_height: Option<u32>,
+    pub vertex_width: Option<u32>,
+    pub vertex_height: Option<u32>,
+    pub x_margin: Option<u32>,
+    pub y_margin: Option<u32>,
+}
+
+impl LayoutSettings {
+    fn apply_to(&self, layout: &mut Layout) {
+        let settings = [
+            (self.column_width, &mut layout.column_width),
+            (self.row_height, &mut layout.row_height),
+            (self.vertex_width, &mut layout.vertex_width),
+            (self.vertex_height, &mut layout.vertex_height),
+            (self.x_margin, &mut layout.x_margin),
+            (self.y_margin, &mut layout.y_margin),
+        ];
+        for (setting, value) in settings {
+            if let Some(setting) = setting {
+                *value = setting;
+            }
+        }
+    }
+}
+
+//
+// A named bundle of settings, selected with --preset.  A preset can extend
+// another one, in which case its settings are applied on top of those of the
+// preset it extends.
+//
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub extends: Option<String>,
+    #[serde(default)]
+    pub theme: ThemeSettings,
+    #[serde(default)]
+    pub layout: LayoutSettings,
+    #[serde(default)]
+    pub exclude_properties: Vec<String>,
+    pub numbering: Option<bool>,
+    pub reachability: Option<bool>,
+    pub max_properties: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    // outputs to generate: svg, json, cabling, dot, graphml
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    // replace host names, WWNs and serial numbers with pseudonyms
+    #[serde(default)]
+    pub anonymize: bool,
+    pub title: Option<String>,
+    pub numbering: Option<bool>,
+    // properties left out of the rendered vertices
+    #[serde(default)]
+    pub exclude_properties: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub branding: Branding,
+    #[serde(default)]
+    pub baseline: Baseline,
+    #[serde(default)]
+    pub theme: ThemeSettings,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(default)]
+    pub presets: BTreeMap<String, Preset>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &str) -> Result<ConfigFile, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    pub fn apply(&self, mut builder: RenderOptionsBuilder) -> RenderOptionsBuilder {
+        if let Some(logo) = &self.branding.logo {
+            builder = builder.logo(logo);
+        }
+        if let Some(favicon) = &self.branding.favicon {
+            builder = builder.favicon(favicon);
+        }
+        if let Some(approved) = &self.baseline.approved {
+            builder = builder.approved_baseline(approved, self.baseline.show_diff);
+        }
+        if !self.theme.is_empty() {
+            let mut theme = Theme::default();
+            self.theme.apply_to(&mut theme);
+            builder = builder.theme(theme);
+        }
+        builder
+    }
+
+    //
+    // Returns the chain of presets the named one extends, starting with the
+    // preset that doesn't extend any other one.
+    //
+    fn preset_chain<'a>(&'a self, name: &'a str) -> Result<Vec<&'a Preset>, Box<dyn Error>> {
+        let mut chain = Vec::new();
+        let mut names = Vec::new();
+        let mut next = Some(name);
+        while let Some(name) = next {
+            if names.contains(&name) {
+                names.push(name);
+                return Err(Box::new(SimpleError(format!(
+                    "presets extend each other: {}",
+                    names.join(" -> ")
+                ))));
+            }
+            let preset = match self.presets.get(name) {
+                Some(preset) => preset,
+                None => return Err(Box::new(SimpleError(format!("unknown preset: {}", name)))),
+            };
+            names.push(name);
+            chain.push(preset);
+            next = preset.extends.as_deref();
+        }
+        chain.reverse();
+        Ok(chain)
+    }
+
+    //
+    // Apply the named preset (and the ones it extends) on top of builder.
+    // The theme of the preset is applied on top of the [theme] section.
+    //
+    pub fn apply_preset(
+        &self,
+        name: &str,
+        mut builder: RenderOptionsBuilder,
+    ) -> Result<RenderOptionsBuilder, Box<dyn Error>> {
+        let chain = self.preset_chain(name)?;
+        let mut theme = Theme::default();
+        self.theme.apply_to(&mut theme);
+        let mut layout = Layout::default();
+        for preset in chain {
+            preset.theme.apply_to(&mut theme);
+            preset.layout.apply_to(&mut layout);
+            for prop in &preset.exclude_properties {
+                builder = builder.filter(Filter::ExcludeProperty(prop.clone()));
+            }
+            if let Some(numbering) = preset.numbering {
+                builder = builder.numbering(numbering);
+            }
+            if let Some(reachability) = preset.reachability {
+                builder = builder.reachability(reachability);
+            }
+            if let Some(max) = preset.max_properties {
+                builder = builder.max_properties(max);
+            }
+        }
+        Ok(builder.theme(theme).layout(layout))
+    }
+
+    pub fn profile_names(&self) -> Vec<&str> {
+        self.profiles.keys().map(|name| name.as_str()).collect()
+    }
+
+    //
+    // The subdirectory of outdir the named profile is generated into.
+    //
+    pub fn profile_dir(outdir: &Path, name: &str) -> PathBuf {
+        outdir.join(name)
+    }
+
+    //
+    // Apply the settings of the named profile on top of builder, which
+    // normally has the rest of the configuration file applied already.
+    //
+    pub fn apply_profile(
+        &self,
+        name: &str,
+        mut builder: RenderOptionsBuilder,
+    ) -> Result<RenderOptionsBuilder, Box<dyn Error>> {
+        let profile = match self.profiles.get(name) {
+            Some(profile) => profile,
+            None => return Err(Box::new(SimpleError(format!("unknown profile: {}", name)))),
+        };
+        for output in &profile.outputs {
+            let format: OutputFormat = output.parse().map_err(SimpleError)?;
+            builder = builder.output(format);
+        }
+        for prop in &profile.exclude_properties {
+            builder = builder.filter(Filter::ExcludeProperty(prop.clone()));
+        }
+        if let Some(title) = &profile.title {
+            builder = builder.title(title);
+        }
+        if let Some(numbering) = profile.numbering {
+            builder = builder.numbering(numbering);
+        }
+        Ok(builder.anonymize(profile.anonymize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_dirs() {
+        let conffile: ConfigFile = toml::from_str(
+            "[profiles.engineering]\n\
+             outputs = [\"svg\"]\n\
+             [profiles.assets]\n\
+             outputs = [\"cabling\"]\n",
+        )
+        .unwrap();
+        let dirs: Vec<PathBuf> = conffile
+            .profile_names()
+            .into_iter()
+            .map(|name| ConfigFile::profile_dir(Path::new("/var/tmp/topo"), name))
+            .collect();
+        assert_eq!(
+            dirs,
+            vec![PathBuf::from("/var/tmp/topo/assets"), PathBuf::from("/var/tmp/topo/engineering")]
+        );
+        assert_eq!(ConfigFile::profile_dir(Path::new("topo/"), "assets"), Path::new("topo/assets"));
+    }
+}
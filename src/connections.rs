@@ -0,0 +1,297 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Cabling table.
+//
+// In the digraph, a physical connection shows up as an edge between two port
+// vertices that belong to different devices, e.g.:
+//
+//     initiator -> initiator/port -> expander/port -> expander
+//
+// Those edges are collected into a list of connections, each with the WWNs
+// and PHYs of both endpoints, which is written out as CSV and JSON so it can
+// be used as an automatically generated cabling table.  Each endpoint starts
+// with the stable id of its device (see stable_id()), which is also the id of
+// the device's node in the SVG, so that a row can be traced to the diagram.  Ports reported one PHY
+// at a time are listed as the wide port they make up (see wide_ports.rs), so
+// that there is one connection per cable.
+//
+use serde::Serialize;
+
+use std::error::Error;
+
+use crate::fmri::{device_fmri, field, shorten};
+use crate::key_case::KeyCase;
+use crate::locale::Locale;
+use crate::wide_ports;
+use crate::{stable_id, ExportHost, FmriDisplay, SasDigraph, PORT};
+
+// the columns of the CSV table, the instances added after the original ones
+const CSV_COLUMNS: &[&str] = &[
+    "source_id",
+    "source_type",
+    "source_reference",
+    "source_device",
+    "source_sas_address",
+    "source_phys",
+    "target_id",
+    "target_type",
+    "target_reference",
+    "target_device",
+    "target_sas_address",
+    "target_phys",
+    "negotiated_link_rate",
+    "source_instance",
+    "target_instance",
+];
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionEndpoint {
+    // stable id of the device, see stable_id()
+    pub device_id: String,
+    // type of the device the port belongs to (initiator, expander, target)
+    pub device_type: String,
+    pub device_fmri: String,
+    // instance of the device, as it appears in the snapshot
+    pub device_instance: String,
+    // reference number of the device, if device numbering was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_reference: Option<String>,
+    pub port_fmri: String,
+    // WWN of the port
+    pub sas_address: String,
+    // PHY (or range of PHYs for a wide port), e.g. "0-3"
+    pub phys: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Connection {
+    pub source: ConnectionEndpoint,
+    pub target: ConnectionEndpoint,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub negotiated_link_rate: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CablingTable {
+    pub generator: String,
+    pub host: ExportHost,
+    pub connections: Vec<Connection>,
+}
+
+fn port_prop(digraph: &SasDigraph, port_fmri: &str, name: &str) -> Option<String> {
+    digraph.vertices[port_fmri]
+        .properties
+        .iter()
+        .find(|p| p.name == name)
+        .map(|p| p.value.clone())
+}
+
+//
+// Returns the PHY (or range of PHYs for a wide port) of a port, e.g. "0-3".
+//
+pub(crate) fn port_phys(digraph: &SasDigraph, port_fmri: &str) -> String {
+    let start = field(port_fmri, "start-phy")
+        .map(|s| s.to_string())
+        .or_else(|| port_prop(digraph, port_fmri, "start-phy"));
+    let end = field(port_fmri, "end-phy")
+        .map(|s| s.to_string())
+        .or_else(|| port_prop(digraph, port_fmri, "end-phy"));
+    match (start, end) {
+        (Some(start), Some(end)) if start == end => start,
+        (Some(start), Some(end)) => format!("{}-{}", start, end),
+        (Some(start), None) => start,
+        _ => String::new(),
+    }
+}
+
+fn endpoint(digraph: &SasDigraph, port_fmri: &str) -> ConnectionEndpoint {
+    let dev_fmri = device_fmri(port_fmri);
+    let device = digraph.vertices.get(dev_fmri);
+
+    ConnectionEndpoint {
+        device_id: stable_id(dev_fmri),
+        device_type: device.map_or_else(String::new, |d| d.name.clone()),
+        device_fmri: dev_fmri.to_string(),
+        device_instance: device.map_or_else(String::new, |d| d.instance.to_string()),
+        device_reference: device.and_then(|d| d.reference.clone()),
+        port_fmri: port_fmri.to_string(),
+        sas_address: port_prop(digraph, port_fmri, "local-sas-address")
+            .or_else(|| field(port_fmri, "port").map(|s| s.to_string()))
+            .unwrap_or_default(),
+        phys: port_phys(digraph, port_fmri),
+    }
+}
+
+//
+// Derive the list of physical connections from the digraph, sorted by the
+// FMRIs of the source and target ports.
+//
+pub(crate) fn connections(digraph: &SasDigraph) -> Vec<Connection> {
+    let mut pairs = Vec::new();
+    for vtx in digraph.vertices.values() {
+        if vtx.name != PORT {
+            continue;
+        }
+        if let Some(edges) = &vtx.outgoing_edges {
+            for edge in edges {
+                let is_port = matches!(digraph.vertices.get(edge), Some(v) if v.name == PORT);
+                if is_port && device_fmri(&vtx.fmri) != device_fmri(edge) {
+                    pairs.push((vtx.fmri.as_str(), edge.as_str()));
+                }
+            }
+        }
+    }
+    pairs.sort();
+
+    pairs
+        .into_iter()
+        .map(|(source, target)| {
+            let negotiated_link_rate = digraph.vertices[source]
+                .properties
+                .iter()
+                .find(|p| p.name == "negotiated-link-rate")
+                .map(|p| p.value.clone());
+            Connection {
+                source: endpoint(digraph, source),
+                target: endpoint(digraph, target),
+                negotiated_link_rate,
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn csv_field(s: &str) -> String {
+    if s.contains([',', ';', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl CablingTable {
+    pub(crate) fn new(digraph: &SasDigraph) -> CablingTable {
+        let merged = wide_ports::merged(digraph);
+        let digraph = merged.as_ref().unwrap_or(digraph);
+        CablingTable {
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            host: ExportHost {
+                product_id: digraph.product_id.clone(),
+                nodename: digraph.nodename.clone(),
+                os_version: digraph.os_version.clone(),
+                timestamp: digraph.timestamp.clone(),
+                scheme: digraph.scheme.to_string(),
+            },
+            connections: connections(digraph),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    //
+    // Like to_json(), with the keys named as specified.
+    //
+    pub fn to_json_with(&self, key_case: KeyCase) -> Result<String, Box<dyn Error>> {
+        Ok(key_case.to_json(self)?)
+    }
+
+    pub fn to_csv(&self) -> String {
+        self.to_csv_with(Locale::default(), FmriDisplay::Full)
+    }
+
+    //
+    // Like to_csv(), with the fields separated as is customary in the
+    // specified locale and the device FMRIs shortened as specified.
+    //
+    pub fn to_csv_with(&self, locale: Locale, fmri_display: FmriDisplay) -> String {
+        self.render_csv(locale, fmri_display, KeyCase::Raw)
+    }
+
+    pub(crate) fn render_csv(
+        &self,
+        locale: Locale,
+        fmri_display: FmriDisplay,
+        key_case: KeyCase,
+    ) -> String {
+        let separator = locale.csv_separator();
+        let mut csv = CSV_COLUMNS
+            .iter()
+            .map(|column| key_case.convert(column))
+            .collect::<Vec<String>>()
+            .join(separator);
+        csv.push('\n');
+        for conn in &self.connections {
+            let mut fields = Vec::new();
+            for end in &[&conn.source, &conn.target] {
+                fields.push(csv_field(&end.device_id));
+                fields.push(csv_field(&end.device_type));
+                fields.push(csv_field(end.device_reference.as_deref().unwrap_or("")));
+                fields.push(csv_field(&shorten(&end.device_fmri, fmri_display)));
+                fields.push(csv_field(&end.sas_address));
+                fields.push(csv_field(&end.phys));
+            }
+            fields.push(csv_field(conn.negotiated_link_rate.as_deref().unwrap_or("")));
+            // added after the original columns, so their positions don't change
+            fields.push(csv_field(&conn.source.device_instance));
+            fields.push(csv_field(&conn.target.device_instance));
+            csv.push_str(&fields.join(separator));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HBA: &str = "sas://:hba=500605b0000272a0:/initiator=500605b0000272a0";
+    const HBA_PORT: &str = "sas://:hba=500605b0000272a0:/initiator=500605b0000272a0/\
+                            port=500605b0000272a0:start-phy=0:end-phy=3";
+    const EXP_PORT: &str = "sas://:hba=500605b0000272a0:/expander=500304801e0f1abf/\
+                            port=500304801e0f1abf:start-phy=0:end-phy=3";
+    const EXP: &str = "sas://:hba=500605b0000272a0:/expander=500304801e0f1abf";
+
+    fn table() -> CablingTable {
+        let digraph = SasDigraph::builder()
+            .add_vertex(HBA, "initiator", 0)
+            .add_vertex(HBA_PORT, "port", 0)
+            .add_vertex(EXP_PORT, "port", 1)
+            .add_vertex(EXP, "expander", 0)
+            .add_edge(HBA, HBA_PORT)
+            .add_edge(HBA_PORT, EXP_PORT)
+            .add_edge(EXP_PORT, EXP)
+            .build()
+            .unwrap();
+        CablingTable::new(&digraph)
+    }
+
+    #[test]
+    fn csv_ids() {
+        let csv = table().to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        let header: Vec<&str> = lines[0].split(',').collect();
+        assert_eq!((header[0], header[6]), ("source_id", "target_id"));
+        let fields: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(fields[0], stable_id(HBA));
+        assert_eq!(fields[1], "initiator");
+        assert_eq!(fields[6], stable_id(EXP));
+        assert_eq!(fields[7], "expander");
+    }
+
+    #[test]
+    fn json_ids() {
+        let table: serde_json::Value = serde_json::from_str(&table().to_json().unwrap()).unwrap();
+        let connection = &table["connections"][0];
+        assert_eq!(connection["source"]["device_id"], stable_id(HBA).as_str());
+        assert_eq!(connection["target"]["device_id"], stable_id(EXP).as_str());
+    }
+}
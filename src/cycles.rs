@@ -0,0 +1,156 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Cycle detection.
+//
+// The edges of a SAS topology lead away from the initiators, so a snapshot
+// whose edges form a loop is corrupted, e.g. by a back-edge from an expander
+// to the HBA port it is attached to.  The layout would place each vertex of
+// the loop wherever it happened to reach it first and silently drop the edge
+// closing the loop, so the snapshot is rejected up front, naming the vertices
+// that make up the loop:
+//
+//     the topology has a cycle: sas://.../expander=500304801e0f1abf ->
+//     sas://.../port=500304801e0f1abf:start-phy=0:end-phy=3 -> ... ->
+//     sas://.../expander=500304801e0f1abf
+//
+use petgraph::algo::tarjan_scc;
+use petgraph::stable_graph::NodeIndex;
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::error::Error;
+
+use crate::{SasDigraph, SimpleError};
+
+//
+// Returns the FMRIs along a cycle of the digraph, starting and ending with
+// the same one, or None if there is no cycle.  If there are several, the
+// cycle through the vertex with the lowest FMRI of those on any is returned,
+// so that the same snapshot always reports the same cycle.
+//
+pub(crate) fn find_cycle(digraph: &SasDigraph) -> Option<Vec<String>> {
+    let graph = digraph.graph();
+    let fmri = |node: NodeIndex| graph[node].fmri.clone();
+
+    // every cycle lies within a strongly connected component
+    let component = tarjan_scc(graph)
+        .into_iter()
+        .filter(|nodes| nodes.len() > 1 || graph.contains_edge(nodes[0], nodes[0]))
+        .min_by_key(|nodes| nodes.iter().map(|n| fmri(*n)).min())?;
+    let start = *component.iter().min_by_key(|n| fmri(**n))?;
+    let members: BTreeSet<NodeIndex> = component.into_iter().collect();
+
+    //
+    // The shortest way back to the start, staying within the component,
+    // which it is bound to find.
+    //
+    let mut previous: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+    let mut queue = VecDeque::from(vec![start]);
+    while let Some(node) = queue.pop_front() {
+        let mut next: Vec<NodeIndex> = graph.neighbors(node).collect();
+        next.sort_by_key(|n| fmri(*n));
+        for next in next {
+            if !members.contains(&next) || previous.contains_key(&next) {
+                continue;
+            }
+            previous.insert(next, node);
+            if next == start {
+                queue.clear();
+                break;
+            }
+            queue.push_back(next);
+        }
+    }
+
+    let mut cycle = vec![fmri(start)];
+    let mut node = *previous.get(&start)?;
+    while node != start {
+        cycle.push(fmri(node));
+        node = previous[&node];
+    }
+    cycle.push(fmri(start));
+    cycle.reverse();
+    Some(cycle)
+}
+
+pub(crate) fn check_acyclic(digraph: &SasDigraph) -> Result<(), Box<dyn Error>> {
+    match find_cycle(digraph) {
+        Some(cycle) => Err(Box::new(SimpleError(format!(
+            "the topology has a cycle, which a snapshot can't have unless it is corrupted: {}",
+            cycle.join(" -> ")
+        )))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SasDigraphBuilder;
+
+    const HBA: &str = "sas://:hba=500605b0000272a0:/initiator=500605b0000272a0";
+    const HBA_PORT: &str = "sas://:hba=500605b0000272a0:/initiator=500605b0000272a0/\
+                            port=500605b0000272a0:start-phy=0:end-phy=3";
+    const EXP: &str = "sas://:hba=500605b0000272a0:/expander=500304801e0f1abf";
+    const EXP_PORT: &str = "sas://:hba=500605b0000272a0:/expander=500304801e0f1abf/\
+                            port=500304801e0f1abf:start-phy=0:end-phy=3";
+    const EXP_PORT8: &str = "sas://:hba=500605b0000272a0:/expander=500304801e0f1abf/\
+                             port=500304801e0f1abf:start-phy=8:end-phy=8";
+    const EXP_PORT9: &str = "sas://:hba=500605b0000272a0:/expander=500304801e0f1abf/\
+                             port=500304801e0f1abf:start-phy=9:end-phy=9";
+    const DISK0_PORT: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000000/\
+                              port=5000c50090000000:start-phy=0:end-phy=0";
+    const DISK0: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000000";
+    const DISK1_PORT: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000001/\
+                              port=5000c50090000001:start-phy=0:end-phy=0";
+    const DISK1: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000001";
+
+    //
+    // An HBA cabled to an expander with two drives attached, laid out like
+    // the snapshots sastopo takes.
+    //
+    fn sample() -> SasDigraphBuilder {
+        SasDigraph::builder()
+            .add_vertex(HBA, "initiator", 0)
+            .add_vertex(HBA_PORT, "port", 0)
+            .add_vertex(EXP_PORT, "port", 1)
+            .add_vertex(EXP, "expander", 0)
+            .add_vertex(EXP_PORT8, "port", 2)
+            .add_vertex(DISK0_PORT, "port", 3)
+            .add_vertex(DISK0, "target", 0)
+            .add_vertex(EXP_PORT9, "port", 4)
+            .add_vertex(DISK1_PORT, "port", 5)
+            .add_vertex(DISK1, "target", 1)
+            .add_edge(HBA, HBA_PORT)
+            .add_edge(HBA_PORT, EXP_PORT)
+            .add_edge(EXP_PORT, EXP)
+            .add_edge(EXP, EXP_PORT8)
+            .add_edge(EXP, EXP_PORT9)
+            .add_edge(EXP_PORT8, DISK0_PORT)
+            .add_edge(DISK0_PORT, DISK0)
+            .add_edge(EXP_PORT9, DISK1_PORT)
+            .add_edge(DISK1_PORT, DISK1)
+    }
+
+    #[test]
+    fn acyclic() {
+        let digraph = sample().build().unwrap();
+        assert_eq!(find_cycle(&digraph), None);
+        assert!(check_acyclic(&digraph).is_ok());
+    }
+
+    #[test]
+    fn cycle() {
+        // a back-edge from a port of the expander to the port it is cabled on
+        let digraph = sample().add_edge(EXP_PORT8, EXP_PORT).build().unwrap();
+        assert_eq!(find_cycle(&digraph).unwrap(), vec![EXP, EXP_PORT8, EXP_PORT, EXP]);
+        let error = check_acyclic(&digraph).unwrap_err().to_string();
+        assert!(error.ends_with(&[EXP, EXP_PORT8, EXP_PORT, EXP].join(" -> ")), "{}", error);
+    }
+}
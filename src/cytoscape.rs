@@ -0,0 +1,118 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// JSON output for Cytoscape.js.
+//
+// The document is in the format cy.json() reads, so it can be loaded into
+// existing Cytoscape-based tooling as is:
+//
+//     { "elements": {
+//         "nodes": [ { "data": { "id": "v...", "parent": "v...", ... } } ],
+//         "edges": [ { "data": { "id": "e0", "source": "v...", "target": "v..." } } ]
+//     } }
+//
+// The ports are compound children of the device they belong to, so the
+// edges between a device and its own ports are left out; Cytoscape draws the
+// ports inside of the device instead.
+//
+use serde::Serialize;
+
+use std::error::Error;
+
+use crate::fmri::device_fmri;
+use crate::{stable_id, SasDigraph, PORT};
+
+#[derive(Debug, Serialize)]
+struct NodeData {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent: Option<String>,
+    label: String,
+    #[serde(rename = "type")]
+    vertex_type: String,
+    fmri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reference: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct EdgeData {
+    id: String,
+    source: String,
+    target: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Element<T> {
+    data: T,
+}
+
+#[derive(Debug, Serialize)]
+struct Elements {
+    nodes: Vec<Element<NodeData>>,
+    edges: Vec<Element<EdgeData>>,
+}
+
+#[derive(Debug, Serialize)]
+struct CytoscapeDocument {
+    elements: Elements,
+}
+
+//
+// Returns the FMRI of the device the port with the specified FMRI belongs to,
+// if the device is in the digraph.
+//
+fn parent<'a>(digraph: &SasDigraph, fmri: &'a str) -> Option<&'a str> {
+    let device = device_fmri(fmri);
+    let is_port = matches!(digraph.vertices.get(fmri), Some(v) if v.name == PORT);
+    match is_port && device != fmri && digraph.vertices.contains_key(device) {
+        true => Some(device),
+        false => None,
+    }
+}
+
+pub(crate) fn render_cytoscape(digraph: &SasDigraph) -> Result<String, Box<dyn Error>> {
+    let mut fmris: Vec<&String> = digraph.vertices.keys().collect();
+    fmris.sort();
+
+    let mut elements = Elements {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+    };
+    for fmri in fmris {
+        let vtx = &digraph.vertices[fmri];
+        elements.nodes.push(Element {
+            data: NodeData {
+                id: stable_id(fmri),
+                parent: parent(digraph, fmri).map(stable_id),
+                label: format!("{} {}", vtx.name, vtx.instance),
+                vertex_type: vtx.name.clone(),
+                fmri: fmri.clone(),
+                reference: vtx.reference.clone(),
+            },
+        });
+
+        for edge in vtx.outgoing_edges.iter().flatten() {
+            if !digraph.vertices.contains_key(edge)
+                || parent(digraph, edge) == Some(fmri.as_str())
+                || parent(digraph, fmri) == Some(edge.as_str())
+            {
+                continue;
+            }
+            elements.edges.push(Element {
+                data: EdgeData {
+                    id: format!("e{}", elements.edges.len()),
+                    source: stable_id(fmri),
+                    target: stable_id(edge),
+                },
+            });
+        }
+    }
+    Ok(serde_json::to_string_pretty(&CytoscapeDocument { elements })?)
+}
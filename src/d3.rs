@@ -0,0 +1,154 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// JSON output for D3.
+//
+// Two documents are generated, in the shapes D3's layouts take as input, so
+// that a front-end can animate the fabric without parsing the XML:
+//
+//  - a graph for d3-force, with the links referring to the nodes by their
+//    index in the nodes array:
+//
+//        { "nodes": [ { "id": "v...", "label": "expander 0x0", ... } ],
+//          "links": [ { "source": 0, "target": 1 } ] }
+//
+//  - a tree for d3-hierarchy, rooted at the host with the initiators as its
+//    children.  The digraph isn't a tree (e.g. a drive may be reachable from
+//    two initiators), so each vertex only appears once, under the first
+//    parent it is reached from in a breadth-first walk; the force graph has
+//    all of the edges.
+//
+use serde::Serialize;
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::error::Error;
+
+use crate::{layout_roots, stable_id, SasDigraph, SasDigraphVertex};
+
+#[derive(Debug, Serialize)]
+struct D3Node {
+    id: String,
+    label: String,
+    // vertex type, e.g. for coloring the nodes
+    #[serde(rename = "type")]
+    vertex_type: String,
+    fmri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reference: Option<String>,
+}
+
+impl D3Node {
+    fn new(vtx: &SasDigraphVertex) -> D3Node {
+        D3Node {
+            id: stable_id(&vtx.fmri),
+            label: format!("{} {}", vtx.name, vtx.instance),
+            vertex_type: vtx.name.clone(),
+            fmri: vtx.fmri.clone(),
+            reference: vtx.reference.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct D3Link {
+    source: usize,
+    target: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct D3Graph {
+    nodes: Vec<D3Node>,
+    links: Vec<D3Link>,
+}
+
+#[derive(Debug, Serialize)]
+struct D3Tree {
+    #[serde(flatten)]
+    node: D3Node,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<D3Tree>,
+}
+
+fn subtree(digraph: &SasDigraph, children: &BTreeMap<&str, Vec<&str>>, fmri: &str) -> D3Tree {
+    D3Tree {
+        node: D3Node::new(&digraph.vertices[fmri]),
+        children: children
+            .get(fmri)
+            .map(|kids| kids.iter().map(|k| subtree(digraph, children, k)).collect())
+            .unwrap_or_default(),
+    }
+}
+
+pub(crate) fn render_d3_graph(digraph: &SasDigraph) -> Result<String, Box<dyn Error>> {
+    let mut fmris: Vec<&String> = digraph.vertices.keys().collect();
+    fmris.sort();
+    let index: BTreeMap<&str, usize> = fmris
+        .iter()
+        .enumerate()
+        .map(|(i, fmri)| (fmri.as_str(), i))
+        .collect();
+
+    let mut graph = D3Graph {
+        nodes: Vec::new(),
+        links: Vec::new(),
+    };
+    for fmri in &fmris {
+        let vtx = &digraph.vertices[*fmri];
+        graph.nodes.push(D3Node::new(vtx));
+        for edge in vtx.outgoing_edges.iter().flatten() {
+            // edges to vertices missing from the snapshot can't be indexed
+            if let Some(target) = index.get(edge.as_str()) {
+                graph.links.push(D3Link {
+                    source: index[fmri.as_str()],
+                    target: *target,
+                });
+            }
+        }
+    }
+    Ok(serde_json::to_string_pretty(&graph)?)
+}
+
+pub(crate) fn render_d3_tree(digraph: &SasDigraph) -> Result<String, Box<dyn Error>> {
+    //
+    // Walk the digraph breadth-first, recording the children each vertex is
+    // the first parent of.
+    //
+    let roots = layout_roots(digraph);
+    let mut children: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut seen: HashSet<&str> = roots.iter().map(|r| r.as_str()).collect();
+    let mut queue: VecDeque<&str> = roots.iter().map(|r| r.as_str()).collect();
+    while let Some(fmri) = queue.pop_front() {
+        let mut edges: Vec<&str> = digraph.vertices[fmri]
+            .outgoing_edges
+            .iter()
+            .flatten()
+            .map(|e| e.as_str())
+            .filter(|e| digraph.vertices.contains_key(e))
+            .collect();
+        edges.sort_unstable();
+        for edge in edges {
+            if seen.insert(edge) {
+                children.entry(fmri).or_default().push(edge);
+                queue.push_back(edge);
+            }
+        }
+    }
+
+    let tree = D3Tree {
+        node: D3Node {
+            id: "host".to_string(),
+            label: digraph.nodename.clone(),
+            vertex_type: "host".to_string(),
+            fmri: String::new(),
+            reference: None,
+        },
+        children: roots.iter().map(|r| subtree(digraph, &children, r)).collect(),
+    };
+    Ok(serde_json::to_string_pretty(&tree)?)
+}
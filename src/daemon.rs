@@ -0,0 +1,278 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Daemon mode.
+//
+// Rather than rendering a single snapshot, the daemon periodically captures
+// the SAS topology of the local system and renders each capture into its own
+// subdirectory of the output directory:
+//
+//     OUTPUT_DIR/index.html
+//     OUTPUT_DIR/snapshot-1589824800/sastopo.xml
+//     OUTPUT_DIR/snapshot-1589824800/sastopo2svg.html
+//     ...
+//
+// Only the most recent snapshots are retained.  The index page is regenerated
+// after every capture and lists the retained snapshots as a timeline, along
+// with what changed since the previous one, which turns a directory served by
+// any web server into a record of the fabric's history.  Changes can also be
+// pushed out as they happen (see notify.rs), and open pages can be made to
+// follow on to new snapshots (see live_reload.rs).
+//
+use log::{info, warn};
+
+use std::error::Error;
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::capture::{capture_xml, DEFAULT_CAPTURE_COMMAND};
+use crate::diff::TopoDiff;
+use crate::live_reload::{self, LiveReload};
+use crate::notify::{notify_changes, Notifier};
+use crate::{html_escape, read_digraph, run, Config, RenderOptions, SasDigraph};
+
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+const SNAPSHOT_XML: &str = "sastopo.xml";
+
+pub struct DaemonConfig {
+    pub outdir: String,
+    // shell command writing the topology XML to stdout
+    pub capture_command: String,
+    // time between two captures
+    pub interval: Duration,
+    // number of snapshots to keep
+    pub retention: usize,
+    // options every snapshot is rendered with
+    pub options: RenderOptions,
+    // who to tell when the topology changes
+    pub notifiers: Vec<Notifier>,
+    // whether open pages poll for new snapshots
+    pub live_reload: bool,
+}
+
+impl DaemonConfig {
+    pub fn new(outdir: String, options: RenderOptions) -> DaemonConfig {
+        DaemonConfig {
+            outdir,
+            capture_command: DEFAULT_CAPTURE_COMMAND.to_string(),
+            interval: Duration::from_secs(3600),
+            retention: 48,
+            options,
+            notifiers: Vec::new(),
+            live_reload: false,
+        }
+    }
+}
+
+//
+// Capture the topology and render it into a new snapshot directory.  Returns
+// the name of the directory.
+//
+fn capture(config: &DaemonConfig) -> Result<String, Box<dyn Error>> {
+    let xml = capture_xml(&config.capture_command)?;
+
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let name = format!("{}{}", SNAPSHOT_PREFIX, secs);
+    let dir = format!("{}/{}", config.outdir, name);
+    fs::create_dir_all(&dir)?;
+    let xml_path = format!("{}/{}", dir, SNAPSHOT_XML);
+    fs::write(&xml_path, &xml)?;
+
+    //
+    // The snapshot is rendered with the rest of the output, so that it is
+    // covered by the manifest of an archived snapshot directory.
+    //
+    let mut options = config.options.clone();
+    options.set_keep_snapshot(true);
+    if config.live_reload {
+        options.set_live_reload(LiveReload {
+            state_url: format!("../{}", live_reload::STATE_FILE),
+            snapshot: Some(name.clone()),
+            poll_secs: poll_secs(config),
+        });
+    }
+    run(&Config::with_options(dir, xml_path, options))?;
+    Ok(name)
+}
+
+//
+// How often the pages poll for a new snapshot: often enough to notice it
+// soon after it has been captured, without polling needlessly often when
+// the captures are hours apart.
+//
+fn poll_secs(config: &DaemonConfig) -> u64 {
+    (config.interval.as_secs() / 10).clamp(5, 60)
+}
+
+//
+// Returns the names of the snapshot directories, oldest first.
+//
+fn snapshots(outdir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(outdir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(SNAPSHOT_PREFIX) && entry.file_type()?.is_dir() {
+            names.push(name);
+        }
+    }
+    names.sort_by_key(|name| name[SNAPSHOT_PREFIX.len()..].parse::<u64>().unwrap_or(0));
+    Ok(names)
+}
+
+//
+// Remove all but the most recent snapshots.
+//
+fn prune(config: &DaemonConfig) -> Result<(), Box<dyn Error>> {
+    let names = snapshots(&config.outdir)?;
+    if names.len() > config.retention {
+        for name in &names[..names.len() - config.retention] {
+            info!("removing expired snapshot {}", name);
+            fs::remove_dir_all(format!("{}/{}", config.outdir, name))?;
+        }
+    }
+    Ok(())
+}
+
+fn change_summary(diff: &TopoDiff) -> String {
+    if diff.is_empty() {
+        return "no changes".to_string();
+    }
+    let mut changes = Vec::new();
+    for (count, what) in &[
+        (diff.added_vertices.len(), "vertices added"),
+        (diff.removed_vertices.len(), "vertices removed"),
+        (diff.added_edges.len(), "edges added"),
+        (diff.removed_edges.len(), "edges removed"),
+    ] {
+        if *count > 0 {
+            changes.push(format!("{} {}", count, what));
+        }
+    }
+    changes.join(", ")
+}
+
+//
+// Regenerate the index page listing the retained snapshots, most recent
+// first.
+//
+fn write_index(config: &DaemonConfig) -> Result<(), Box<dyn Error>> {
+    let mut rows = Vec::new();
+    let mut previous = None;
+    for name in snapshots(&config.outdir)? {
+        let digraph = match snapshot_digraph(config, &name) {
+            Ok(digraph) => digraph,
+            Err(e) => {
+                warn!("skipping snapshot {}: {}", name, e);
+                continue;
+            }
+        };
+        let changes = match &previous {
+            Some(before) => change_summary(&TopoDiff::new(before, &digraph)),
+            None => String::new(),
+        };
+        rows.push(format!(
+            "<tr><td><a href=\"{}/sastopo2svg.html\">{}</a></td><td>{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td></tr>\n",
+            name,
+            name,
+            html_escape(&digraph.timestamp),
+            html_escape(&digraph.nodename),
+            digraph.vertices.len(),
+            changes
+        ));
+        previous = Some(digraph);
+    }
+    rows.reverse();
+
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>SAS topology history</title>\n\
+         <style>\n\
+         body { font-family: Arial, Helvetica, sans-serif; }\n\
+         td, th { padding: 2px 12px; text-align: left; }\n\
+         </style>\n</head>\n<body>\n<h1>SAS topology history</h1>\n\
+         <table id=\"timeline\">\n\
+         <tr><th>Snapshot</th><th>Captured</th><th>Host</th><th>Vertices</th>\
+         <th>Changes</th></tr>\n",
+    );
+    for row in rows {
+        html.push_str(&row);
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    if config.live_reload {
+        let live_reload = LiveReload {
+            state_url: live_reload::STATE_FILE.to_string(),
+            snapshot: None,
+            poll_secs: poll_secs(config),
+        };
+        html = live_reload.inject(&html);
+    }
+    fs::write(format!("{}/index.html", config.outdir), html)?;
+    Ok(())
+}
+
+//
+// Point the pages polling for new snapshots at the latest one.  This is
+// written last, once the index page already lists the snapshot.
+//
+fn write_state(config: &DaemonConfig, name: &str) -> Result<(), Box<dyn Error>> {
+    let state = live_reload::State::new(name).to_json()?;
+    fs::write(format!("{}/{}", config.outdir, live_reload::STATE_FILE), state)?;
+    Ok(())
+}
+
+fn snapshot_digraph(config: &DaemonConfig, name: &str) -> Result<SasDigraph, Box<dyn Error>> {
+    read_digraph(&format!("{}/{}/{}", config.outdir, name, SNAPSHOT_XML), &config.options)
+}
+
+//
+// Notify about the changes between the two snapshots, if anybody is
+// interested.
+//
+fn notify(config: &DaemonConfig, previous: &str, current: &str) -> Result<(), Box<dyn Error>> {
+    if config.notifiers.is_empty() {
+        return Ok(());
+    }
+    let before = snapshot_digraph(config, previous)?;
+    let after = snapshot_digraph(config, current)?;
+    notify_changes(&config.notifiers, &before, &after, current)
+}
+
+//
+// Capture, render and prune snapshots until the process is killed.  A failed
+// capture or notification is logged and retried at the next interval rather
+// than terminating the daemon.
+//
+pub fn run_daemon(config: &DaemonConfig) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(&config.outdir)?;
+    loop {
+        let previous = snapshots(&config.outdir)?.pop();
+        match capture(config) {
+            Ok(name) => {
+                info!("captured snapshot {}", name);
+                if let Some(previous) = previous {
+                    if let Err(e) = notify(config, &previous, &name) {
+                        warn!("notification failed: {}", e);
+                    }
+                }
+            }
+            Err(e) => warn!("capture failed: {}", e),
+        }
+        prune(config)?;
+        write_index(config)?;
+        if config.live_reload {
+            if let Some(latest) = snapshots(&config.outdir)?.pop() {
+                write_state(config, &latest)?;
+            }
+        }
+        thread::sleep(config.interval);
+    }
+}
@@ -0,0 +1,265 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Comparison of two snapshots of a SAS topology.
+//
+// Vertices are matched by FMRI, and so are the properties of the vertices
+// present in both snapshots by name.  Library consumers get at the diff
+// through SasDigraph::diff().
+//
+// Comparing the properties means going through every property of every
+// vertex, and the daemon, the notifications and the drift indicator only look
+// at the structure, so TopoDiff::new() leaves them out and only
+// TopoDiff::with_properties() (which SasDigraph::diff() uses) compares them.
+//
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::locale::Locale;
+use crate::{html_escape, SasDigraph};
+
+//
+// A property of a vertex present in both snapshots that was added, removed or
+// changed its value.
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyChange {
+    pub fmri: String,
+    pub name: String,
+    // the value in the older snapshot, if the property was there
+    pub before: Option<String>,
+    // the value in the newer snapshot, if the property is there
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct TopoDiff {
+    // FMRIs of the vertices only present in the newer snapshot
+    pub added_vertices: Vec<String>,
+    // FMRIs of the vertices only present in the older snapshot
+    pub removed_vertices: Vec<String>,
+    // (from, to) FMRI pairs of the edges only present in the newer snapshot
+    pub added_edges: Vec<(String, String)>,
+    // (from, to) FMRI pairs of the edges only present in the older snapshot
+    pub removed_edges: Vec<(String, String)>,
+    // in FMRI and property name order, if the properties were compared
+    pub changed_properties: Vec<PropertyChange>,
+}
+
+fn vertex_set(digraph: &SasDigraph) -> BTreeSet<String> {
+    digraph.vertices.keys().cloned().collect()
+}
+
+fn edge_set(digraph: &SasDigraph) -> BTreeSet<(String, String)> {
+    let mut edges = BTreeSet::new();
+    for vtx in digraph.vertices.values() {
+        if let Some(outgoing) = &vtx.outgoing_edges {
+            for edge in outgoing {
+                edges.insert((vtx.fmri.clone(), edge.clone()));
+            }
+        }
+    }
+    edges
+}
+
+fn property_changes(before: &SasDigraph, after: &SasDigraph) -> Vec<PropertyChange> {
+    let mut fmris: Vec<&String> =
+        before.vertices.keys().filter(|fmri| after.vertices.contains_key(fmri)).collect();
+    fmris.sort();
+
+    let mut changes = Vec::new();
+    for fmri in fmris {
+        let old: BTreeMap<&str, &str> = before.vertices[fmri].properties().collect();
+        let new: BTreeMap<&str, &str> = after.vertices[fmri].properties().collect();
+        let names: BTreeSet<&str> = old.keys().chain(new.keys()).copied().collect();
+        for name in names {
+            let (before, after) = (old.get(name), new.get(name));
+            if before != after {
+                changes.push(PropertyChange {
+                    fmri: fmri.clone(),
+                    name: name.to_string(),
+                    before: before.map(|value| value.to_string()),
+                    after: after.map(|value| value.to_string()),
+                });
+            }
+        }
+    }
+    changes
+}
+
+impl TopoDiff {
+    //
+    // Compares the vertices and edges of the snapshots, but not the
+    // properties of the vertices.
+    //
+    pub fn new(before: &SasDigraph, after: &SasDigraph) -> TopoDiff {
+        let before_vertices = vertex_set(before);
+        let after_vertices = vertex_set(after);
+        let before_edges = edge_set(before);
+        let after_edges = edge_set(after);
+
+        TopoDiff {
+            added_vertices: after_vertices.difference(&before_vertices).cloned().collect(),
+            removed_vertices: before_vertices.difference(&after_vertices).cloned().collect(),
+            added_edges: after_edges.difference(&before_edges).cloned().collect(),
+            removed_edges: before_edges.difference(&after_edges).cloned().collect(),
+            changed_properties: Vec::new(),
+        }
+    }
+
+    //
+    // Compares the properties of the vertices present in both snapshots as
+    // well.
+    //
+    pub fn with_properties(before: &SasDigraph, after: &SasDigraph) -> TopoDiff {
+        TopoDiff {
+            changed_properties: property_changes(before, after),
+            ..TopoDiff::new(before, after)
+        }
+    }
+
+    //
+    // Whether the structure of the topology is unchanged, i.e. no vertex or
+    // edge was added or removed.  Changed property values don't count, as
+    // counters and sensor readings change all the time.
+    //
+    pub fn is_empty(&self) -> bool {
+        self.added_vertices.is_empty()
+            && self.removed_vertices.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+}
+
+//
+// Generate the drift indicator shown on the HTML page when the topology is
+// compared against an approved baseline snapshot.  If show_diff is set, the
+// individual differences are listed in a collapsible section below it.
+//
+pub(crate) fn drift_html(
+    diff: &TopoDiff,
+    approved: &SasDigraph,
+    show_diff: bool,
+    locale: Locale,
+) -> String {
+    let approved_at = locale.timestamp(&approved.timestamp);
+    if diff.is_empty() {
+        return format!(
+            "<div id=\"drift\" class=\"drift-ok\">Matches the approved topology ({})</div>\n",
+            html_escape(&approved_at)
+        );
+    }
+
+    let mut html = format!(
+        "<div id=\"drift\" class=\"drift-changed\">Drift from the approved topology ({}): \
+         {} vertices added, {} removed, {} edges added, {} removed</div>\n",
+        html_escape(&approved_at),
+        locale.count(diff.added_vertices.len()),
+        locale.count(diff.removed_vertices.len()),
+        locale.count(diff.added_edges.len()),
+        locale.count(diff.removed_edges.len())
+    );
+
+    if show_diff {
+        html.push_str("<details id=\"drift-details\"><summary>Differences</summary><table>\n");
+        let rows = diff
+            .added_vertices
+            .iter()
+            .map(|fmri| ("added vertex", fmri.clone()))
+            .chain(diff.removed_vertices.iter().map(|fmri| ("removed vertex", fmri.clone())))
+            .chain(
+                diff.added_edges
+                    .iter()
+                    .map(|(from, to)| ("added edge", format!("{} \u{2192} {}", from, to))),
+            )
+            .chain(
+                diff.removed_edges
+                    .iter()
+                    .map(|(from, to)| ("removed edge", format!("{} \u{2192} {}", from, to))),
+            );
+        for (change, what) in rows {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td></tr>\n",
+                change,
+                html_escape(&what)
+            ));
+        }
+        html.push_str("</table></details>\n");
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HBA: &str = "sas:///initiator=500605b00f1e0000";
+    const DISK: &str = "sas:///target=5000c50090000001";
+    const GONE: &str = "sas:///target=5000c50090000002";
+
+    fn snapshot(props: &[(&str, &str)], gone: bool) -> SasDigraph {
+        let mut builder = SasDigraph::builder()
+            .add_vertex(HBA, "initiator", 0)
+            .add_vertex(DISK, "target", 0)
+            .add_edge(HBA, DISK);
+        for (name, value) in props {
+            builder = builder.add_property(DISK, "target", name, value);
+        }
+        if gone {
+            builder = builder.add_vertex(GONE, "target", 1).add_edge(HBA, GONE);
+        }
+        builder.build().unwrap()
+    }
+
+    fn change(name: &str, before: Option<&str>, after: Option<&str>) -> PropertyChange {
+        PropertyChange {
+            fmri: DISK.to_string(),
+            name: name.to_string(),
+            before: before.map(|value| value.to_string()),
+            after: after.map(|value| value.to_string()),
+        }
+    }
+
+    #[test]
+    fn structure() {
+        let before = snapshot(&[], true);
+        let after = snapshot(&[], false);
+        let diff = before.diff(&after);
+        assert_eq!(diff.removed_vertices, vec![GONE.to_string()]);
+        assert_eq!(diff.removed_edges, vec![(HBA.to_string(), GONE.to_string())]);
+        assert!(diff.added_vertices.is_empty() && diff.added_edges.is_empty());
+        assert!(!diff.is_empty());
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn properties() {
+        let before = snapshot(&[("temperature", "30"), ("fw-rev", "A001"), ("led", "off")], false);
+        let after = snapshot(&[("temperature", "35"), ("fw-rev", "A001"), ("serial", "Z1")], false);
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.changed_properties,
+            vec![
+                change("led", Some("off"), None),
+                change("serial", None, Some("Z1")),
+                change("temperature", Some("30"), Some("35")),
+            ]
+        );
+        // property changes alone leave the structure as it was
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn properties_opt_in() {
+        let before = snapshot(&[("temperature", "30")], true);
+        let after = snapshot(&[("temperature", "35")], false);
+        let diff = TopoDiff::new(&before, &after);
+        assert!(diff.changed_properties.is_empty());
+        assert_eq!(diff.removed_vertices, vec![GONE.to_string()]);
+    }
+}
@@ -0,0 +1,77 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Graphviz DOT output.
+//
+// Our own layout places the vertices on a grid by their distance from the
+// initiators, which gets unwieldy for very large fabrics.  The DOT output
+// carries the same digraph without any layout, so that it can be fed to
+// dot(1), sfdp(1) and friends instead.  Each node is labeled with the name
+// and instance of the vertex, and carries its FMRI and properties as node
+// attributes.
+//
+use crate::{stable_id, SasDigraph, EXPANDER, INITIATOR, PORT, TARGET};
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", dot_escape(s))
+}
+
+fn shape(name: &str) -> &'static str {
+    match name {
+        INITIATOR => "box",
+        EXPANDER => "hexagon",
+        TARGET => "cylinder",
+        PORT => "point",
+        _ => "ellipse",
+    }
+}
+
+pub(crate) fn render_dot(digraph: &SasDigraph) -> String {
+    let mut dot = format!("digraph {} {{\n", dot_quote(&digraph.nodename));
+    dot.push_str("    rankdir=LR;\n");
+
+    //
+    // Sort the vertices by FMRI, so that the output of two runs on the same
+    // snapshot can be compared.
+    //
+    let mut fmris: Vec<&String> = digraph.vertices.keys().collect();
+    fmris.sort();
+
+    for fmri in &fmris {
+        let vtx = &digraph.vertices[*fmri];
+        let mut lines = vec![format!("{} {}", vtx.name, vtx.instance)];
+        if let Some(reference) = &vtx.reference {
+            lines.push(reference.clone());
+        }
+        let label: Vec<String> = lines.iter().map(|l| dot_escape(l)).collect();
+        let mut attrs = vec![
+            format!("label=\"{}\"", label.join("\\n")),
+            format!("shape={}", shape(&vtx.name)),
+            format!("fmri={}", dot_quote(&vtx.fmri)),
+        ];
+        for prop in &vtx.properties {
+            attrs.push(format!("{}={}", dot_quote(&prop.name), dot_quote(&prop.value)));
+        }
+        dot.push_str(&format!("    {} [{}];\n", stable_id(fmri), attrs.join(", ")));
+    }
+
+    for fmri in &fmris {
+        if let Some(edges) = &digraph.vertices[*fmri].outgoing_edges {
+            for edge in edges {
+                dot.push_str(&format!("    {} -> {};\n", stable_id(fmri), stable_id(edge)));
+            }
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
@@ -0,0 +1,110 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// diagrams.net (draw.io) output.
+//
+// The digraph is written as an uncompressed mxGraph model, with every vertex
+// placed where it is in the SVG, so that admins can open the rendering in
+// diagrams.net and annotate it by hand without having to lay it out again.
+// The vertices are drawn as plain shapes colored by type rather than with the
+// icons, so the file doesn't depend on the assets.  The FMRI of each vertex
+// is kept as a "fmri" attribute of its object.
+//
+use std::error::Error;
+
+use crate::{html_escape as xml_escape, plan_layout, stable_id, Layout, SasDigraph};
+use crate::{EXPANDER, INITIATOR, PORT, TARGET};
+
+//
+// Style of the shape representing a vertex of the specified type.
+//
+fn vertex_style(name: &str) -> &'static str {
+    match name {
+        INITIATOR => "rounded=1;whiteSpace=wrap;fillColor=#dae8fc;strokeColor=#6c8ebf;",
+        EXPANDER => "rounded=1;whiteSpace=wrap;fillColor=#d5e8d4;strokeColor=#82b366;",
+        TARGET => "shape=cylinder3;whiteSpace=wrap;fillColor=#fff2cc;strokeColor=#d6b656;",
+        PORT => "ellipse;whiteSpace=wrap;fillColor=#f5f5f5;strokeColor=#666666;",
+        _ => "whiteSpace=wrap;",
+    }
+}
+
+const EDGE_STYLE: &str = "edgeStyle=orthogonalEdgeStyle;rounded=0;endArrow=none;";
+
+pub(crate) fn render_drawio(
+    digraph: &SasDigraph,
+    layout: &Layout,
+    depth_limit: u32,
+) -> Result<String, Box<dyn Error>> {
+    let plan = plan_layout(digraph, depth_limit)?;
+    let positions = plan.positions(layout, 0);
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <mxfile host=\"sastopo2svg\">\n",
+    );
+    xml.push_str(&format!(
+        "  <diagram id=\"sastopo\" name=\"{}\">\n\
+         \x20   <mxGraphModel grid=\"1\" gridSize=\"10\">\n\
+         \x20     <root>\n\
+         \x20       <mxCell id=\"0\"/>\n\
+         \x20       <mxCell id=\"1\" parent=\"0\"/>\n",
+        xml_escape(&digraph.nodename)
+    ));
+
+    let mut fmris: Vec<&String> = positions.keys().collect();
+    fmris.sort();
+    for fmri in &fmris {
+        let vtx = &digraph.vertices[*fmri];
+        let (x, y) = positions[*fmri];
+        let mut label = format!("{} {}", vtx.name, vtx.instance);
+        if let Some(reference) = &vtx.reference {
+            label = format!("{}\n{}", reference, label);
+        }
+        xml.push_str(&format!(
+            "        <object id=\"{}\" label=\"{}\" fmri=\"{}\">\n\
+             \x20         <mxCell style=\"{}\" vertex=\"1\" parent=\"1\">\n\
+             \x20           <mxGeometry x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+             as=\"geometry\"/>\n\
+             \x20         </mxCell>\n\
+             \x20       </object>\n",
+            stable_id(fmri),
+            xml_escape(&label).replace('\n', "&#xa;"),
+            xml_escape(fmri),
+            vertex_style(&vtx.name),
+            x,
+            y,
+            layout.vertex_width,
+            layout.vertex_height
+        ));
+    }
+
+    let mut edge_id = 0;
+    for fmri in &fmris {
+        for edge in digraph.vertices[*fmri].outgoing_edges.iter().flatten() {
+            // only the vertices that were laid out can be connected
+            if !positions.contains_key(edge) {
+                continue;
+            }
+            xml.push_str(&format!(
+                "        <mxCell id=\"e{}\" style=\"{}\" edge=\"1\" parent=\"1\" source=\"{}\" \
+                 target=\"{}\">\n\
+                 \x20         <mxGeometry relative=\"1\" as=\"geometry\"/>\n\
+                 \x20       </mxCell>\n",
+                edge_id,
+                EDGE_STYLE,
+                stable_id(fmri),
+                stable_id(edge)
+            ));
+            edge_id += 1;
+        }
+    }
+
+    xml.push_str("      </root>\n    </mxGraphModel>\n  </diagram>\n</mxfile>\n");
+    Ok(xml)
+}
@@ -0,0 +1,203 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Dual-ported drives.
+//
+// Each of the two ports of a dual-ported SAS drive has a SAS address of its
+// own, which shows up as a target vertex of its own, e.g.:
+//
+//     sas://:hba=500605b0000272a0:/target=5000c50090000001
+//     sas://:hba=500605b0000272a0:/target=5000c50090000002
+//
+// The two are recognized as the same drive by reporting the same serial
+// number, and by their SAS addresses being a few apart (drives derive the
+// addresses of their ports from the WWN of the drive, by adding 1 and 2).
+// The latter tells them apart from two different drives that report the same
+// serial number, which is flagged as a duplicate (see analysis.rs).
+//
+// For the rendering, the two target vertices are merged into one, so that
+// the drive is drawn once with both of its ports attached to it, which is
+// what technicians expect to see.  The other outputs keep both vertices.
+//
+use std::collections::{BTreeMap, HashMap};
+
+use crate::analysis::prop;
+use crate::fmri::{device_key, wwn};
+use crate::{SasDigraph, SasDigraphProperty, SasDigraphVertex, TARGET};
+
+// how far apart the SAS addresses of the ports of a drive can be
+const MAX_PORT_DISTANCE: u64 = 3;
+
+//
+// Name of the property a merged target carries the FMRI of the target
+// vertex of its other port in.
+//
+const OTHER_PORT_PROPERTY: &str = "dual-port-target";
+
+fn address(vtx: &SasDigraphVertex) -> Option<u64> {
+    u64::from_str_radix(wwn(&vtx.fmri)?, 16).ok()
+}
+
+//
+// Whether the two vertices are the targets of the two ports of the same
+// drive.
+//
+pub(crate) fn same_drive(a: &SasDigraphVertex, b: &SasDigraphVertex) -> bool {
+    if a.name != TARGET || b.name != TARGET {
+        return false;
+    }
+    match (prop(a, "serial-number"), prop(b, "serial-number")) {
+        (Some(x), Some(y)) if !x.is_empty() && x == y => (),
+        _ => return false,
+    }
+    match (address(a), address(b)) {
+        (Some(x), Some(y)) => x != y && x.max(y) - x.min(y) <= MAX_PORT_DISTANCE,
+        _ => false,
+    }
+}
+
+//
+// Returns the pairs of target vertices that are the two ports of the same
+// drive, as seen from the same HBA, lower SAS address first.
+//
+fn dual_ported(digraph: &SasDigraph) -> Vec<(String, String)> {
+    let mut by_serial: BTreeMap<(&str, &str), Vec<&SasDigraphVertex>> = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        if vtx.name != TARGET {
+            continue;
+        }
+        if let Some(serial) = prop(vtx, "serial-number") {
+            let authority = &vtx.fmri[..vtx.fmri.len() - device_key(&vtx.fmri).len()];
+            by_serial.entry((authority, serial)).or_default().push(vtx);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    for mut targets in by_serial.into_values() {
+        if targets.len() == 2 && same_drive(targets[0], targets[1]) {
+            targets.sort_by_key(|vtx| address(vtx));
+            pairs.push((targets[0].fmri.clone(), targets[1].fmri.clone()));
+        }
+    }
+    pairs
+}
+
+//
+// Returns the device key (see fmri::device_key()) of the second port of each
+// dual-ported drive, mapped to that of its first port, which stands for the
+// drive.  Unlike dual_ported(), this goes across HBAs, as one HBA may only be
+// cabled to the first port of a drive and another one to the second.
+//
+pub(crate) fn drive_keys(digraph: &SasDigraph) -> HashMap<String, String> {
+    let mut by_serial: BTreeMap<&str, BTreeMap<u64, &SasDigraphVertex>> = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        if vtx.name != TARGET {
+            continue;
+        }
+        if let (Some(serial), Some(address)) = (prop(vtx, "serial-number"), address(vtx)) {
+            by_serial.entry(serial).or_default().entry(address).or_insert(vtx);
+        }
+    }
+
+    let mut keys = HashMap::new();
+    for ports in by_serial.values() {
+        // in SAS address order
+        let ports: Vec<&SasDigraphVertex> = ports.values().copied().collect();
+        if ports.len() == 2 && same_drive(ports[0], ports[1]) {
+            keys.insert(
+                device_key(&ports[1].fmri).to_string(),
+                device_key(&ports[0].fmri).to_string(),
+            );
+        }
+    }
+    keys
+}
+
+//
+// Returns a copy of the digraph with the target vertices of each dual-ported
+// drive merged into the one of its first port, or None if there aren't any
+// dual-ported drives.  The edges into the target of the second port are
+// redirected to the merged one.
+//
+pub(crate) fn merged(digraph: &SasDigraph) -> Option<SasDigraph> {
+    let pairs = dual_ported(digraph);
+    if pairs.is_empty() {
+        return None;
+    }
+
+    let mut merged = digraph.clone();
+    for (keep, drop) in pairs {
+        merged.vertices.remove(&drop);
+        merged.vertices.map_edges(|vtx| {
+            let edges = vtx.outgoing_edges.as_ref()?;
+            Some(
+                edges
+                    .iter()
+                    .map(|edge| if *edge == drop { keep.clone() } else { edge.clone() })
+                    .collect(),
+            )
+        });
+        if let Some(vtx) = merged.vertices.get_mut(&keep) {
+            let mut other = SasDigraphProperty::new(OTHER_PORT_PROPERTY.to_string(), drop);
+            other.group = Some(TARGET.to_string());
+            vtx.properties.push(other);
+        }
+    }
+    Some(merged)
+}
+
+//
+// Returns the FMRI of the target vertex that was merged into the given one
+// by merged(), if any.
+//
+pub(crate) fn absorbed(vtx: &SasDigraphVertex) -> Option<&str> {
+    prop(vtx, OTHER_PORT_PROPERTY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HBA: &str = "sas://:hba=500605b0000272a0:/initiator=500605b0000272a0";
+    const PORT_A: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000001";
+    const PORT_B: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000002";
+
+    fn topology() -> SasDigraph {
+        SasDigraph::builder()
+            .add_vertex(HBA, "initiator", 0)
+            .add_vertex(PORT_B, TARGET, 0)
+            .add_vertex(PORT_A, TARGET, 0)
+            .add_property(PORT_A, TARGET, "serial-number", "ZA1234")
+            .add_property(PORT_B, TARGET, "serial-number", "ZA1234")
+            .add_edge(HBA, PORT_A)
+            .add_edge(HBA, PORT_B)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn merged_keeps_first_port() {
+        let merged = merged(&topology()).unwrap();
+        assert!(!merged.vertices.contains_key(PORT_B));
+        assert!(merged.vertices[HBA].outgoing_edges().iter().all(|e| e == PORT_A));
+        assert_eq!(absorbed(&merged.vertices[PORT_A]), Some(PORT_B));
+        assert_eq!(absorbed(&merged.vertices[HBA]), None);
+    }
+
+    #[test]
+    fn merged_none() {
+        let digraph = SasDigraph::builder()
+            .add_vertex(HBA, "initiator", 0)
+            .add_vertex(PORT_A, TARGET, 0)
+            .add_edge(HBA, PORT_A)
+            .build()
+            .unwrap();
+        assert!(merged(&digraph).is_none());
+    }
+}
@@ -0,0 +1,329 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Enclosures.
+//
+// If the snapshot carries SES data, each expander and target names the
+// enclosure it is in, and the vertices in each column are grouped by
+// enclosure, like they are by zone (see zones.rs).  Without SES data, the
+// enclosures are inferred instead:
+//
+//   - expanders attached to the same drive (i.e. the two I/O modules of a
+//     JBOD, each reaching one port of every drive) are in the same enclosure
+//
+//   - expanders whose SAS addresses are close together are in the same
+//     enclosure, as the addresses of the expanders of an enclosure are
+//     assigned from one block
+//
+//   - a target is in the enclosure of the expander it is attached to
+//
+// The inferred enclosures are labeled as such, and can be named, confirmed
+// and corrected with an alias file listing the SAS addresses of the expanders
+// in each enclosure:
+//
+//     [[enclosure]]
+//     name = "JBOD 3, rack B"
+//     expanders = ["500304801e0f1abf", "500304801e0f1aff"]
+//
+// An enclosure all of whose expanders are listed in the alias file is no
+// longer labeled as inferred.  Expanders that aren't listed stay in the
+// enclosure that was inferred for them, which takes the name of the listed
+// expanders in it.
+//
+use serde::Deserialize;
+use svg::node::element::{Group, Rectangle, Text};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+
+use crate::fmri::{device_fmri, wwn};
+use crate::{ColumnHash, Layout, SasDigraph, SasDigraphProperty, EXPANDER, PORT, TARGET};
+
+// properties naming the enclosure a vertex is in
+const ENCLOSURE_PROPS: &[&str] = &["enclosure", "enclosure-id", "ses-enclosure"];
+
+// property telling how the enclosure of a vertex was determined
+const SOURCE_PROP: &str = "enclosure-source";
+
+const INFERRED: &str = "inferred";
+const ALIAS: &str = "alias";
+
+//
+// How far apart the SAS addresses of two expanders in the same enclosure can
+// be.  Expanders use a block of addresses for their virtual PHYs (e.g. the
+// SES device), so this is the size of the block the expanders of an enclosure
+// are assigned from.
+//
+const MAX_WWN_DISTANCE: u64 = 0x40;
+
+#[derive(Deserialize)]
+struct AliasFile {
+    #[serde(default)]
+    enclosure: Vec<Alias>,
+}
+
+#[derive(Deserialize)]
+struct Alias {
+    name: String,
+    expanders: Vec<String>,
+}
+
+//
+// Reads an alias file, returning the name of the enclosure of each expander,
+// hashed by its SAS address.
+//
+pub(crate) fn load_aliases(path: &str) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    let file: AliasFile = toml::from_str(&fs::read_to_string(path)?)?;
+    let mut aliases = BTreeMap::new();
+    for alias in file.enclosure {
+        for expander in alias.expanders {
+            aliases.insert(expander.to_lowercase(), alias.name.clone());
+        }
+    }
+    Ok(aliases)
+}
+
+fn address(wwn: &str) -> Option<u64> {
+    u64::from_str_radix(wwn, 16).ok()
+}
+
+fn find(parent: &mut BTreeMap<String, String>, wwn: &str) -> String {
+    let mut root = wwn.to_string();
+    while parent[&root] != root {
+        root = parent[&root].clone();
+    }
+    parent.insert(wwn.to_string(), root.clone());
+    root
+}
+
+fn union(parent: &mut BTreeMap<String, String>, a: &str, b: &str) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    // the group is named after its lowest address
+    if a < b {
+        parent.insert(b, a);
+    } else {
+        parent.insert(a, b);
+    }
+}
+
+//
+// Infers the enclosures of the expanders and targets, and adds them to the
+// properties of those vertices and of their ports.  Nothing is done if the
+// snapshot names the enclosures itself.
+//
+pub(crate) fn infer_enclosures(digraph: &mut SasDigraph, aliases: &BTreeMap<String, String>) {
+    let explicit = digraph.vertices.values().any(|vtx| {
+        vtx.properties.iter().any(|p| ENCLOSURE_PROPS.contains(&p.name.as_str()))
+    });
+    if explicit {
+        return;
+    }
+
+    let device_wwn = |fmri: &str, name: &str| match digraph.vertices.get(device_fmri(fmri)) {
+        Some(vtx) if vtx.name == name => wwn(&vtx.fmri).map(|w| w.to_lowercase()),
+        _ => None,
+    };
+
+    //
+    // The expanders are identified by SAS address rather than by FMRI, as the
+    // same expander shows up once per HBA it's reachable from.
+    //
+    let mut parent: BTreeMap<String, String> = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        if vtx.name == EXPANDER {
+            if let Some(wwn) = wwn(&vtx.fmri) {
+                parent.insert(wwn.to_lowercase(), wwn.to_lowercase());
+            }
+        }
+    }
+    if parent.is_empty() {
+        return;
+    }
+
+    // the expanders each target is attached to
+    let mut attached: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        if vtx.name != PORT {
+            continue;
+        }
+        let expander = match device_wwn(&vtx.fmri, EXPANDER) {
+            Some(expander) => expander,
+            None => continue,
+        };
+        for edge in vtx.outgoing_edges.iter().flatten() {
+            if let Some(target) = device_wwn(edge, TARGET) {
+                attached.entry(target).or_default().insert(expander.clone());
+            }
+        }
+    }
+
+    let expanders: Vec<String> = parent.keys().cloned().collect();
+    for pair in expanders.windows(2) {
+        if let (Some(a), Some(b)) = (address(&pair[0]), address(&pair[1])) {
+            if b - a <= MAX_WWN_DISTANCE {
+                union(&mut parent, &pair[0], &pair[1]);
+            }
+        }
+    }
+    for shared in attached.values() {
+        let mut shared = shared.iter();
+        if let Some(first) = shared.next() {
+            for other in shared {
+                union(&mut parent, first, other);
+            }
+        }
+    }
+
+    //
+    // Name the enclosure of each expander.  An inferred enclosure takes the
+    // name of the first of its expanders that is listed in the alias file.
+    //
+    let mut group_alias: BTreeMap<String, &String> = BTreeMap::new();
+    for expander in &expanders {
+        if let Some(alias) = aliases.get(expander) {
+            group_alias.entry(find(&mut parent, expander)).or_insert(alias);
+        }
+    }
+    let mut names: BTreeMap<&str, String> = BTreeMap::new();
+    for expander in &expanders {
+        let root = find(&mut parent, expander);
+        let name = match (aliases.get(expander), group_alias.get(&root).copied()) {
+            (Some(alias), _) | (None, Some(alias)) => alias.to_string(),
+            (None, None) => format!("enclosure {}", root),
+        };
+        names.insert(expander, name);
+    }
+    let mut confirmed: BTreeMap<&str, bool> = BTreeMap::new();
+    for (expander, name) in &names {
+        let listed = aliases.contains_key(*expander);
+        *confirmed.entry(name.as_str()).or_insert(true) &= listed;
+    }
+
+    let mut enclosures: BTreeMap<String, String> = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        let wwn = match wwn(&vtx.fmri) {
+            Some(wwn) => wwn.to_lowercase(),
+            None => continue,
+        };
+        let name = match vtx.name.as_str() {
+            EXPANDER => names.get(wwn.as_str()),
+            TARGET => attached
+                .get(&wwn)
+                .and_then(|expanders| expanders.iter().next())
+                .and_then(|expander| names.get(expander.as_str())),
+            _ => None,
+        };
+        if let Some(name) = name {
+            enclosures.insert(vtx.fmri.clone(), name.clone());
+        }
+    }
+
+    for vtx in digraph.vertices.values_mut() {
+        let name = match enclosures.get(device_fmri(&vtx.fmri)) {
+            Some(name) => name,
+            None => continue,
+        };
+        let source = if confirmed[name.as_str()] { ALIAS } else { INFERRED };
+        for (prop, value) in &[(ENCLOSURE_PROPS[0], name.as_str()), (SOURCE_PROP, source)] {
+            let mut prop = SasDigraphProperty::new(prop.to_string(), value.to_string());
+            prop.group = Some("enclosure".to_string());
+            vtx.properties.push(prop);
+        }
+    }
+}
+
+//
+// Returns the enclosure of each vertex, hashed by FMRI, with the inferred
+// ones labeled as such.  The result is empty if the enclosures are neither
+// known nor inferred.
+//
+pub(crate) fn vertex_enclosures(digraph: &SasDigraph) -> BTreeMap<String, String> {
+    let mut enclosures = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        let prop = vtx.properties.iter().find(|p| ENCLOSURE_PROPS.contains(&p.name.as_str()));
+        if let Some(prop) = prop {
+            let inferred = vtx
+                .properties
+                .iter()
+                .any(|p| p.name == SOURCE_PROP && p.value == INFERRED);
+            let label = if inferred {
+                format!("{} ({})", prop.value, INFERRED)
+            } else {
+                prop.value.clone()
+            };
+            enclosures.insert(vtx.fmri.clone(), label);
+        }
+    }
+    enclosures
+}
+
+//
+// Order the vertices within each column by enclosure, so that the members of
+// an enclosure are placed next to each other.  The sort is stable, so the
+// order within an enclosure is preserved.
+//
+pub(crate) fn group_columns(column_hash: &mut ColumnHash, enclosures: &BTreeMap<String, String>) {
+    for fmris in column_hash.values_mut() {
+        fmris.sort_by_key(|fmri| enclosures.get(fmri).cloned().unwrap_or_default());
+    }
+}
+
+//
+// Draw a labeled outline around each run of vertices in a column that are in
+// the same enclosure.  The label goes on the right, so that it doesn't
+// collide with that of a zone.  This has to be called after the vertices have
+// been placed.
+//
+pub(crate) fn render_enclosures(
+    digraph: &SasDigraph,
+    column_hash: &ColumnHash,
+    enclosures: &BTreeMap<String, String>,
+    layout: &Layout,
+) -> Group {
+    let mut layer = Group::new().set("id", "layer-enclosures");
+
+    let mut depths: Vec<&u32> = column_hash.keys().collect();
+    depths.sort();
+    for depth in depths {
+        let mut runs: Vec<(&str, u32, u32, u32)> = Vec::new();
+        for fmri in &column_hash[depth] {
+            let (enclosure, geom) = match (enclosures.get(fmri), digraph.vertices.get(fmri)) {
+                (Some(enclosure), Some(vtx)) => (enclosure.as_str(), &vtx.geometry),
+                _ => continue,
+            };
+            match runs.last_mut() {
+                Some(run) if run.0 == enclosure => run.3 = geom.y + geom.height,
+                _ => runs.push((enclosure, geom.x, geom.y, geom.y + geom.height)),
+            }
+        }
+
+        for (enclosure, x, top, bottom) in runs {
+            let outline = Rectangle::new()
+                .set("x", x.saturating_sub(14))
+                .set("y", top.saturating_sub(28))
+                .set("width", layout.vertex_width + 28)
+                .set("height", bottom - top + 42)
+                .set("rx", 4)
+                .set("fill", "none")
+                .set("stroke", "#555555")
+                .set("stroke-dasharray", "6,4");
+            let label = Text::new()
+                .set("x", x + layout.vertex_width + 10)
+                .set("y", top.saturating_sub(14))
+                .set("text-anchor", "end")
+                .set("font-family", "Arial, Helvetica, sans-serif")
+                .set("font-size", 11)
+                .set("fill", "#555555")
+                .add(svg::node::Text::new(enclosure));
+            layer = layer.add(outline).add(label);
+        }
+    }
+    layer
+}
@@ -0,0 +1,73 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Snapshot encodings.
+//
+// sastopo writes UTF-8, but some older firmware reports property strings
+// (e.g. enclosure labels) with high-bit characters in latin-1, and the
+// snapshots taken on such systems declare it in their prolog:
+//
+//     <?xml version="1.0" encoding="ISO-8859-1"?>
+//
+// The XML parser only reads UTF-8, so a snapshot declaring any other encoding
+// is transcoded to UTF-8 as it is read.  The encoding names are looked up as
+// the WHATWG Encoding Standard specifies, under which ISO-8859-1 and latin1
+// are labels of windows-1252, a superset of it.
+//
+use encoding_rs::{Encoding, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use log::debug;
+
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+
+use crate::SimpleError;
+
+//
+// Returns the encoding declared in the XML declaration at the start of the
+// data, if any.
+//
+fn declared_encoding(head: &[u8]) -> Option<String> {
+    if !head.starts_with(b"<?xml") {
+        return None;
+    }
+    let end = head.windows(2).position(|w| w == b"?>")?;
+    let decl = String::from_utf8_lossy(&head[..end]);
+    let rest = decl.split("encoding").nth(1)?.trim_start().strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = rest[1..].split(quote).next()?;
+    Some(value.trim().to_string())
+}
+
+//
+// Returns the source, transcoded to UTF-8 as it is read if it starts with an
+// XML declaration of another encoding.
+//
+pub(crate) fn transcode(
+    mut source: Box<dyn BufRead>,
+    path: &str,
+) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let label = match declared_encoding(source.fill_buf()?) {
+        Some(label) => label,
+        None => return Ok(source),
+    };
+    let encoding = match Encoding::for_label(label.as_bytes()) {
+        Some(encoding) if encoding == UTF_8 => return Ok(source),
+        Some(encoding) => encoding,
+        None => {
+            return Err(Box::new(SimpleError(format!(
+                "{} is in an unsupported encoding: {}",
+                path, label
+            ))))
+        }
+    };
+    debug!("Transcoding {} from {} ({})", path, label, encoding.name());
+    let decoder = DecodeReaderBytesBuilder::new().encoding(Some(encoding)).build(source);
+    Ok(Box::new(BufReader::new(decoder)))
+}
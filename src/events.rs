@@ -0,0 +1,127 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// JSON Lines event stream.
+//
+// Rather than rendering the topology, it can be written out as a stream of
+// JSON objects, one per line, for consumption by jq pipelines and log
+// ingestion systems.  The first event describes the host, and is followed by
+// an event for each vertex and one for each of its outgoing edges, written as
+// soon as the vertex has been parsed rather than once the whole graph has
+// been built:
+//
+//     {"type":"host","product_id":"Joyent-M12G5","nodename":"testhost",...}
+//     {"type":"vertex","id":"v1e9ac0bfb971d22d","fmri":"sas://...",...}
+//     {"type":"edge","source":"sas://...","target":"sas://..."}
+//
+// The properties of a vertex are given as strings, except for nested nvlists
+// which are given as structured JSON.
+//
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::Write;
+
+use crate::{stable_id, SasDigraph, SasDigraphVertex};
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Event<'a> {
+    Host {
+        product_id: &'a str,
+        nodename: &'a str,
+        os_version: &'a str,
+        timestamp: &'a str,
+    },
+    Vertex {
+        id: String,
+        fmri: &'a str,
+        name: &'a str,
+        instance: &'a str,
+        properties: BTreeMap<&'a str, serde_json::Value>,
+    },
+    Edge {
+        source: &'a str,
+        target: &'a str,
+    },
+}
+
+pub(crate) struct EventStream<'a> {
+    out: &'a mut dyn Write,
+    count: usize,
+}
+
+impl<'a> EventStream<'a> {
+    pub(crate) fn new(out: &'a mut dyn Write) -> EventStream<'a> {
+        EventStream { out, count: 0 }
+    }
+
+    fn emit(&mut self, event: &Event) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut *self.out, event)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+        self.count += 1;
+        Ok(())
+    }
+
+    //
+    // Writes the host event, unless it has been written already.
+    //
+    pub(crate) fn host(&mut self, digraph: &SasDigraph) -> Result<(), Box<dyn Error>> {
+        if self.count > 0 {
+            return Ok(());
+        }
+        self.emit(&Event::Host {
+            product_id: &digraph.product_id,
+            nodename: &digraph.nodename,
+            os_version: &digraph.os_version,
+            timestamp: &digraph.timestamp,
+        })
+    }
+
+    pub(crate) fn vertex(
+        &mut self,
+        digraph: &SasDigraph,
+        vtx: &SasDigraphVertex,
+    ) -> Result<(), Box<dyn Error>> {
+        self.host(digraph)?;
+
+        let properties = vtx
+            .properties
+            .iter()
+            .map(|prop| {
+                let value = match &prop.structured {
+                    Some(json) => json.clone(),
+                    None => serde_json::Value::String(prop.value.clone()),
+                };
+                (prop.name.as_str(), value)
+            })
+            .collect();
+        self.emit(&Event::Vertex {
+            id: stable_id(&vtx.fmri),
+            fmri: &vtx.fmri,
+            name: &vtx.name,
+            instance: &vtx.instance.raw,
+            properties,
+        })?;
+
+        for edge in vtx.outgoing_edges.iter().flatten() {
+            self.emit(&Event::Edge {
+                source: &vtx.fmri,
+                target: edge,
+            })?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+}
@@ -0,0 +1,137 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Acceptance checks.
+//
+// When a newly racked system is burned in, its snapshot can be checked
+// against a file of expectations rather than by eyeballing the diagram, e.g.:
+//
+//     initiators = 2
+//     expanders = 2
+//     targets = 24
+//     enclosure_serials = ["EXP0", "EXP1"]
+//     serials = ["ZA1234", "ZA1235"]
+//     no_warnings = true
+//
+// Devices are counted once, no matter how many HBAs they are reachable from.
+// Expectations that are left out aren't checked.
+//
+use serde::Deserialize;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+
+use crate::analysis::analyze;
+use crate::fmri::device_key;
+use crate::{read_digraph, RenderOptions, SasDigraph, EXPANDER, INITIATOR, TARGET};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectations {
+    pub initiators: Option<usize>,
+    pub expanders: Option<usize>,
+    pub targets: Option<usize>,
+    // serial numbers of the expanders, which identify the enclosures
+    #[serde(default)]
+    pub enclosure_serials: Vec<String>,
+    // serial numbers of devices of any type that must be present
+    #[serde(default)]
+    pub serials: Vec<String>,
+    // fail if the analysis reports any problems (duplicate WWNs, ...)
+    #[serde(default)]
+    pub no_warnings: bool,
+}
+
+impl Expectations {
+    pub fn load(path: &str) -> Result<Expectations, Box<dyn Error>> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+//
+// Returns the number of distinct devices of each type, and the serial numbers
+// of the devices of each type.
+//
+pub(crate) fn inventory(
+    digraph: &SasDigraph,
+) -> (BTreeMap<&str, usize>, BTreeMap<&str, BTreeSet<&str>>) {
+    let mut devices: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    let mut serials: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        devices
+            .entry(vtx.name.as_str())
+            .or_default()
+            .insert(device_key(&vtx.fmri));
+        if let Some(prop) = vtx.properties.iter().find(|p| p.name == "serial-number") {
+            serials
+                .entry(vtx.name.as_str())
+                .or_default()
+                .insert(prop.value.as_str());
+        }
+    }
+    let counts = devices.into_iter().map(|(name, keys)| (name, keys.len())).collect();
+    (counts, serials)
+}
+
+//
+// Check the snapshot at xml_path, read as specified by the options, against
+// the expectations.  Returns a description of every expectation that isn't
+// met; an empty list means the snapshot passed.
+//
+pub fn check_expectations(
+    xml_path: &str,
+    expect: &Expectations,
+    options: &RenderOptions,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let digraph = read_digraph(xml_path, options)?;
+    Ok(check_digraph(&digraph, expect))
+}
+
+//
+// Like check_expectations(), for a snapshot that has been read in.
+//
+pub(crate) fn check_digraph(digraph: &SasDigraph, expect: &Expectations) -> Vec<String> {
+    let (counts, serials) = inventory(digraph);
+    let mut failures = Vec::new();
+
+    for (name, expected) in &[
+        (INITIATOR, expect.initiators),
+        (EXPANDER, expect.expanders),
+        (TARGET, expect.targets),
+    ] {
+        if let Some(expected) = expected {
+            let found = counts.get(name).cloned().unwrap_or(0);
+            if found != *expected {
+                failures.push(format!("expected {} {}s, found {}", expected, name, found));
+            }
+        }
+    }
+
+    let empty = BTreeSet::new();
+    let enclosures = serials.get(EXPANDER).unwrap_or(&empty);
+    for serial in &expect.enclosure_serials {
+        if !enclosures.contains(serial.as_str()) {
+            failures.push(format!("enclosure with serial number {} not found", serial));
+        }
+    }
+    let all_serials: BTreeSet<&str> = serials.values().flatten().cloned().collect();
+    for serial in &expect.serials {
+        if !all_serials.contains(serial.as_str()) {
+            failures.push(format!("device with serial number {} not found", serial));
+        }
+    }
+
+    if expect.no_warnings {
+        for finding in analyze(digraph) {
+            failures.push(format!("{}: {}", finding.kind.as_str(), finding.message));
+        }
+    }
+    failures
+}
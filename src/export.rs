@@ -0,0 +1,262 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Versioned JSON representation of a SAS topology.
+//
+// The same document is written out as the JSON export and embedded in the
+// generated HTML page as a data island (a <script type="application/json">
+// element with the id "sastopo-data").
+//
+// Compatibility policy for FORMAT_VERSION (MAJOR.MINOR):
+//
+//  - The MINOR version is bumped when fields are added.  Consumers must
+//    ignore fields they don't recognize.
+//  - The MAJOR version is bumped when a field is removed or renamed, or
+//    when the meaning or type of an existing field changes.
+//
+// A consumer that understands version X.Y can safely read any document
+// whose major version is X, regardless of the minor version.
+//
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use crate::identifiers::identifiers;
+use crate::key_case::KeyCase;
+use crate::lenient::ParseWarning;
+use crate::properties::categorized_properties;
+use crate::wide_ports::{wide_ports, WidePort};
+use crate::{
+    stable_id, SasDigraph, SasDigraphProperty, SasDigraphVertex, SasInstance, SimpleError,
+};
+
+pub const FORMAT_VERSION: &str = "1.10";
+
+//
+// Id of the HTML element holding the embedded data island.
+//
+pub const DATA_ISLAND_ID: &str = "sastopo-data";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportHost {
+    pub product_id: String,
+    pub nodename: String,
+    pub os_version: String,
+    pub timestamp: String,
+    // since 1.8, the FMRI scheme of the nodes ("sas" or "hc")
+    #[serde(default)]
+    pub scheme: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportNode {
+    // since 1.2, see stable_id()
+    #[serde(default)]
+    pub id: String,
+    pub fmri: String,
+    pub name: String,
+    // 0 if the instance isn't numeric, see instance_raw
+    #[serde(default)]
+    pub instance: u64,
+    // since 1.4, the instance as it appears in the snapshot, numeric or not
+    #[serde(default)]
+    pub instance_raw: String,
+    // since 1.1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    pub properties: BTreeMap<String, String>,
+    // since 1.3, property names grouped by category in display order
+    #[serde(default)]
+    pub property_categories: BTreeMap<String, Vec<String>>,
+    // since 1.5, the property group each property came from, where known
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub property_groups: BTreeMap<String, String>,
+    // since 1.6, the structured values of the properties that are nested
+    // nvlists (their entries in properties hold the same value as a string)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub structured_properties: BTreeMap<String, serde_json::Value>,
+    // since 1.7, the identifiers to paste into commands (see identifiers.rs)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub identifiers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportLink {
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportDocument {
+    pub format_version: String,
+    pub generator: String,
+    pub host: ExportHost,
+    pub nodes: Vec<ExportNode>,
+    pub links: Vec<ExportLink>,
+    // since 1.9, the logical wide ports made up of several of the port nodes
+    // (see wide_ports.rs)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wide_ports: Vec<WidePort>,
+    // since 1.10, the vertices skipped in lenient mode (see lenient.rs)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parse_warnings: Vec<ParseWarning>,
+}
+
+impl ExportDocument {
+    pub(crate) fn new(digraph: &SasDigraph) -> ExportDocument {
+        let mut fmris: Vec<&String> = digraph.vertices.keys().collect();
+        fmris.sort();
+
+        let mut nodes = Vec::new();
+        let mut links = Vec::new();
+        for fmri in fmris {
+            let vtx = &digraph.vertices[fmri];
+            let mut properties = BTreeMap::new();
+            let mut property_groups = BTreeMap::new();
+            let mut structured_properties = BTreeMap::new();
+            for prop in &vtx.properties {
+                properties.insert(prop.name.clone(), prop.value.clone());
+                if let Some(group) = &prop.group {
+                    property_groups.insert(prop.name.clone(), group.clone());
+                }
+                if let Some(structured) = &prop.structured {
+                    structured_properties.insert(prop.name.clone(), structured.clone());
+                }
+            }
+            let property_categories = categorized_properties(vtx)
+                .into_iter()
+                .map(|(category, names)| (category.as_str().to_string(), names))
+                .collect();
+            nodes.push(ExportNode {
+                id: stable_id(&vtx.fmri),
+                fmri: vtx.fmri.clone(),
+                name: vtx.name.clone(),
+                instance: vtx.instance.value.unwrap_or(0),
+                instance_raw: vtx.instance.raw.clone(),
+                reference: vtx.reference.clone(),
+                properties,
+                property_categories,
+                property_groups,
+                structured_properties,
+                identifiers: identifiers(vtx),
+            });
+
+            if let Some(edges) = &vtx.outgoing_edges {
+                for edge in edges {
+                    links.push(ExportLink {
+                        source: vtx.fmri.clone(),
+                        target: edge.clone(),
+                    });
+                }
+            }
+        }
+
+        ExportDocument {
+            format_version: FORMAT_VERSION.to_string(),
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            host: ExportHost {
+                product_id: digraph.product_id.clone(),
+                nodename: digraph.nodename.clone(),
+                os_version: digraph.os_version.clone(),
+                timestamp: digraph.timestamp.clone(),
+                scheme: digraph.scheme.to_string(),
+            },
+            nodes,
+            links,
+            wide_ports: wide_ports(digraph),
+            parse_warnings: digraph.parse_warnings.clone(),
+        }
+    }
+
+    //
+    // Recreate a SasDigraph from a (possibly enriched) exported document.
+    //
+    pub(crate) fn into_digraph(self) -> Result<SasDigraph, Box<dyn Error>> {
+        let mut digraph = SasDigraph::new(
+            self.host.product_id,
+            self.host.nodename,
+            self.host.os_version,
+            self.host.timestamp,
+        );
+        if !self.host.scheme.is_empty() {
+            digraph.scheme = self.host.scheme.parse()?;
+        }
+        digraph.parse_warnings = self.parse_warnings;
+
+        let mut edges: HashMap<String, Vec<String>> = HashMap::new();
+        for link in self.links {
+            edges.entry(link.source).or_default().push(link.target);
+        }
+
+        for node in self.nodes {
+            let outgoing_edges = edges.remove(&node.fmri);
+            let instance = match node.instance_raw.is_empty() {
+                false => SasInstance::parse(&node.instance_raw),
+                true => SasInstance::from_value(node.instance),
+            };
+            let mut vtx = SasDigraphVertex::new(node.fmri, node.name, instance, outgoing_edges);
+            vtx.reference = node.reference;
+            for (name, value) in node.properties {
+                let mut prop = SasDigraphProperty::new(name, value);
+                prop.group = node.property_groups.get(&prop.name).cloned();
+                prop.structured = node.structured_properties.get(&prop.name).cloned();
+                vtx.properties.push(prop);
+            }
+
+            if digraph.scheme.is_root(&vtx.name) {
+                digraph.initiators.push(vtx.fmri.clone());
+            }
+            digraph.vertices.insert(vtx.fmri.clone(), vtx);
+        }
+        Ok(digraph)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    //
+    // Like to_json(), with the keys named as specified.  Only a document
+    // with the raw keys can be read back in.
+    //
+    pub fn to_json_with(&self, key_case: KeyCase) -> Result<String, Box<dyn Error>> {
+        Ok(key_case.to_json(self)?)
+    }
+
+    //
+    // Serialize the document so that it can be safely embedded inside of an
+    // HTML <script> element.
+    //
+    pub fn to_data_island(&self) -> Result<String, Box<dyn Error>> {
+        let json = serde_json::to_string(self)?;
+        Ok(format!(
+            "<script type=\"application/json\" id=\"{}\">{}</script>\n",
+            DATA_ISLAND_ID,
+            json.replace("</", "<\\/")
+        ))
+    }
+}
+
+//
+// Check whether a document with the specified format version can be read by
+// this version of the crate, per the compatibility policy described above.
+//
+pub fn check_format_version(version: &str) -> Result<(), Box<dyn Error>> {
+    let major = |v: &str| v.split('.').next().unwrap_or("").to_string();
+
+    if major(version) != major(FORMAT_VERSION) {
+        return Err(Box::new(SimpleError(format!(
+            "unsupported format version {} (expected {}.x)",
+            version,
+            major(FORMAT_VERSION)
+        ))));
+    }
+    Ok(())
+}
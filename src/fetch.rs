@@ -0,0 +1,62 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Fetching snapshots over HTTP(S).
+//
+// Snapshots collected across a fleet usually end up on an artifact server
+// rather than on the machine they're rendered on, so anywhere a snapshot is
+// read from a path, an http:// or https:// URL can be given instead.  The
+// response body is read as it is received, just like a file would be, so it
+// can be compressed, a support bundle or a JSON export as well.
+//
+// Servers requiring authentication are sent the bearer token in the
+// SASTOPO2SVG_HTTP_TOKEN environment variable, if it is set.
+//
+use log::debug;
+
+use std::env;
+use std::error::Error;
+use std::io::Read;
+use std::time::Duration;
+
+use crate::SimpleError;
+
+const TOKEN_VAR: &str = "SASTOPO2SVG_HTTP_TOKEN";
+
+//
+// How long to wait for the server to accept the connection, and then for each
+// read of the response, so that a server that stalls mid-transfer fails the
+// run rather than hanging it.
+//
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub(crate) fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+//
+// Requests the URL, returning a reader of the response body.
+//
+pub(crate) fn fetch(url: &str) -> Result<Box<dyn Read + Send>, Box<dyn Error>> {
+    debug!("Fetching {}", url);
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+    let mut request = agent.get(url);
+    if let Ok(token) = env::var(TOKEN_VAR) {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    match request.call() {
+        Ok(response) => Ok(response.into_reader()),
+        // the error names the URL
+        Err(e) => Err(Box::new(SimpleError(format!("failed to fetch {}", e)))),
+    }
+}
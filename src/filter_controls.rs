@@ -0,0 +1,113 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Filter controls shown above the diagram.
+//
+// The viewer can dim the vertices that don't match a choice of vertex type,
+// status, enclosure and pool, e.g. to pick out the faulted targets in one
+// enclosure.  The choices offered are collected while the vertices are
+// rendered, like the legend counts are, so only values that some drawn vertex
+// has are offered, along with how many vertices have them.  A dimension with
+// fewer than two values doesn't narrow anything down, so it gets no control.
+//
+// The values are matched against attributes of the vertex groups in the SVG,
+// the name and status that they have anyway and the filter-enclosure and
+// filter-pool attributes set for the purpose (see filterVertices() in the HTML
+// page).
+//
+use std::collections::BTreeMap;
+
+use crate::analysis::VertexStatus;
+use crate::{html_escape, SasDigraphVertex};
+
+// properties naming the ZFS pool a drive is part of
+const POOL_PROPS: &[&str] = &["zpool", "pool", "zpool-name"];
+
+//
+// The filter dimensions, in the order they are shown: the attribute of the
+// vertex groups they are matched against, and their label.
+//
+const DIMENSIONS: &[(&str, &str)] = &[
+    ("name", "Type"),
+    ("status", "Status"),
+    (ENCLOSURE_ATTRIBUTE, "Enclosure"),
+    (POOL_ATTRIBUTE, "Pool"),
+];
+
+pub(crate) const ENCLOSURE_ATTRIBUTE: &str = "filter-enclosure";
+pub(crate) const POOL_ATTRIBUTE: &str = "filter-pool";
+
+//
+// Returns the pool the vertex is in, if any.
+//
+pub(crate) fn vertex_pool(vtx: &SasDigraphVertex) -> Option<&str> {
+    vtx.properties
+        .iter()
+        .find(|p| POOL_PROPS.contains(&p.name.as_str()) && !p.value.is_empty())
+        .map(|p| p.value.as_str())
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct FilterControls {
+    // number of vertices having each value, by attribute
+    counts: BTreeMap<&'static str, BTreeMap<String, usize>>,
+}
+
+impl FilterControls {
+    pub(crate) fn count(
+        &mut self,
+        vtx: &SasDigraphVertex,
+        status: VertexStatus,
+        enclosure: Option<&str>,
+    ) {
+        let values = [
+            ("name", Some(vtx.name.as_str())),
+            ("status", Some(status.as_str())),
+            (ENCLOSURE_ATTRIBUTE, enclosure),
+            (POOL_ATTRIBUTE, vertex_pool(vtx)),
+        ];
+        for (attribute, value) in values.iter() {
+            if let Some(value) = value {
+                *self
+                    .counts
+                    .entry(attribute)
+                    .or_default()
+                    .entry(value.to_string())
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    pub(crate) fn to_html(&self) -> String {
+        let mut controls = String::new();
+        for (attribute, label) in DIMENSIONS {
+            let values = match self.counts.get(attribute) {
+                Some(values) if values.len() > 1 => values,
+                _ => continue,
+            };
+            controls.push_str(&format!(
+                "<label>{} <select data-attribute=\"{}\" onchange=\"filterVertices()\">\n\
+                 <option value=\"\">all</option>\n",
+                label, attribute
+            ));
+            for (value, count) in values {
+                let value = html_escape(value);
+                controls.push_str(&format!(
+                    "<option value=\"{}\">{} ({})</option>\n",
+                    value, value, count
+                ));
+            }
+            controls.push_str("</select></label>\n");
+        }
+        if controls.is_empty() {
+            return String::new();
+        }
+        format!("<div id=\"vertex-filters\">\n{}</div>\n", controls)
+    }
+}
@@ -0,0 +1,128 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Helpers for picking apart sas-scheme FMRIs, which look like:
+//
+//     sas://:hba=500605b0000272a0:/expander=500304801e0f1abf
+//     sas://:hba=500605b0000272a0:/expander=500304801e0f1abf/port=500304801e0f1abf:start-phy=0:end-phy=3
+//
+// i.e. an authority identifying the HBA the device was discovered through,
+// followed by a path of name=WWN components.  Ports carry their PHY range as
+// additional fields of the last component.
+//
+use crate::{FmriDisplay, SasDigraphProperty, PORT};
+
+//
+// Returns the FMRI of the device a port belongs to.
+//
+pub(crate) fn device_fmri(port_fmri: &str) -> &str {
+    match port_fmri.rfind("/port=") {
+        Some(pos) => &port_fmri[..pos],
+        None => port_fmri,
+    }
+}
+
+//
+// Returns the value of the named field of the last component of the FMRI,
+// e.g. "start-phy" or "port".
+//
+pub(crate) fn field<'a>(fmri: &'a str, key: &str) -> Option<&'a str> {
+    let last = fmri.rsplit('/').next()?;
+    last.split(':')
+        .filter_map(|kv| kv.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+//
+// The same device shows up once per HBA it is reachable from, under FMRIs that
+// only differ in the authority.  The path following the authority identifies
+// the physical device, e.g. "target=5000c50090000000".
+//
+pub(crate) fn device_key(fmri: &str) -> &str {
+    let path = fmri.splitn(2, "://").last().unwrap_or(fmri);
+    match path.find(":/") {
+        Some(pos) => path[pos + 2..].trim_start_matches('/'),
+        None => path,
+    }
+}
+
+//
+// Shortens the FMRI for display as specified by the rule, e.g. with
+// FmriDisplay::LastComponents(2):
+//
+//     .../expander=500304801e0f1abf/port=500304801e0f1abf:start-phy=0:end-phy=3
+//
+pub(crate) fn shorten(fmri: &str, rule: FmriDisplay) -> String {
+    match rule {
+        FmriDisplay::Full => fmri.to_string(),
+        FmriDisplay::Ellipsis(max) => {
+            let chars: Vec<char> = fmri.chars().collect();
+            if chars.len() <= max {
+                return fmri.to_string();
+            }
+            // the ellipsis counts towards the maximum
+            let tail = (max - 1) / 2;
+            let head = max - 1 - tail;
+            let mut short: String = chars[..head].iter().collect();
+            short.push('\u{2026}');
+            short.extend(&chars[chars.len() - tail..]);
+            short
+        }
+        FmriDisplay::LastComponents(n) => {
+            let components: Vec<&str> = device_key(fmri).split('/').collect();
+            if components.len() <= n && device_key(fmri) == fmri {
+                return fmri.to_string();
+            }
+            let last = &components[components.len().saturating_sub(n)..];
+            format!("\u{2026}/{}", last.join("/"))
+        }
+    }
+}
+
+//
+// Returns the WWN the last component of the FMRI is named by.  The components
+// of hc-scheme FMRIs are named by instance numbers instead, so those don't
+// have one.
+//
+pub(crate) fn wwn(fmri: &str) -> Option<&str> {
+    if fmri.starts_with("hc:") {
+        return None;
+    }
+    device_key(fmri)
+        .rsplit('/')
+        .next()?
+        .split(':')
+        .next()?
+        .split_once('=')
+        .map(|(_, wwn)| wwn)
+}
+
+//
+// Some old snapshots don't contain any property groups for a vertex.  The
+// FMRI still identifies it though, so we derive the basic identity properties
+// from it, so that those vertices can be told apart in the info panel.
+//
+pub(crate) fn identity_properties(name: &str, fmri: &str) -> Vec<SasDigraphProperty> {
+    let mut props = Vec::new();
+    let mut push = |name: &str, value: Option<&str>| {
+        if let Some(value) = value {
+            props.push(SasDigraphProperty::new(name.to_string(), value.to_string()));
+        }
+    };
+
+    if name == PORT {
+        push("local-sas-address", wwn(fmri));
+        push("start-phy", field(fmri, "start-phy"));
+        push("end-phy", field(fmri, "end-phy"));
+    } else {
+        push("wwn", wwn(fmri));
+    }
+    props
+}
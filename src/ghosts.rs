@@ -0,0 +1,152 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// "Last seen" ghosts.
+//
+// Given the previous snapshots of a host, devices that were present in any of
+// them but are missing now are rendered as ghosted outlines below the column
+// they used to be in, along with the time since which they have been missing.
+// This helps to tell a drive that was pulled from a slot that never had a
+// drive in it.
+//
+use svg::node::element::{Group, Image, Rectangle, Text, Title};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::{plan_layout, vertex_icon, Layout, SasDigraph, PORT};
+
+pub(crate) struct Ghost {
+    pub fmri: String,
+    pub name: String,
+    // time of the first snapshot the device was missing from
+    pub missing_since: String,
+    // column the device was placed in when it was last seen
+    pub depth: u32,
+}
+
+//
+// Find the devices that are present in any of the snapshots in history but
+// missing from the current one.  Ports aren't included, as they go missing
+// along with the device they belong to.
+//
+pub(crate) fn find_ghosts(
+    history: &[SasDigraph],
+    current: &SasDigraph,
+    depth_limit: u32,
+) -> Result<Vec<Ghost>, Box<dyn Error>> {
+    let mut snapshots: Vec<&SasDigraph> = history.iter().collect();
+    snapshots.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+
+    // index of the last snapshot each missing device was seen in
+    let mut last_seen: BTreeMap<&str, usize> = BTreeMap::new();
+    for (index, snapshot) in snapshots.iter().enumerate() {
+        for vtx in snapshot.vertices.values() {
+            if vtx.name != PORT && !current.vertices.contains_key(&vtx.fmri) {
+                last_seen.insert(&vtx.fmri, index);
+            }
+        }
+    }
+
+    let mut ghosts = Vec::new();
+    for (fmri, index) in last_seen {
+        let snapshot = snapshots[index];
+        let missing_since = match snapshots.get(index + 1) {
+            Some(next) => next.timestamp.clone(),
+            None => current.timestamp.clone(),
+        };
+        let plan = plan_layout(snapshot, depth_limit)?;
+        let depth = plan
+            .column_hash
+            .iter()
+            .find(|(_, fmris)| fmris.iter().any(|f| f == fmri))
+            .map_or(1, |(depth, _)| *depth);
+        ghosts.push(Ghost {
+            fmri: fmri.to_string(),
+            name: snapshot.vertices[fmri].name.clone(),
+            missing_since,
+            depth,
+        });
+    }
+    Ok(ghosts)
+}
+
+//
+// Returns the number of rows needed below the graph to place the ghosts, i.e.
+// the maximum number of ghosts in any one column.
+//
+pub(crate) fn ghost_rows(ghosts: &[Ghost]) -> u32 {
+    let mut per_column: BTreeMap<u32, u32> = BTreeMap::new();
+    for ghost in ghosts {
+        *per_column.entry(ghost.depth).or_insert(0) += 1;
+    }
+    per_column.values().cloned().max().unwrap_or(0)
+}
+
+//
+// Render the ghosts below the graph, which ends at y_start.
+//
+pub(crate) fn render_ghosts(
+    ghosts: &[Ghost],
+    layout: &Layout,
+    y_start: u32,
+) -> Result<Group, Box<dyn Error>> {
+    let mut layer = Group::new().set("id", "layer-ghosts");
+    let mut rows: BTreeMap<u32, u32> = BTreeMap::new();
+
+    for ghost in ghosts {
+        let row = rows.entry(ghost.depth).or_insert(0);
+        let x = ((ghost.depth - 1) * layout.column_width) + layout.x_margin;
+        let y = y_start + (*row * layout.row_height);
+        *row += 1;
+
+        let outline = Rectangle::new()
+            .set("x", x)
+            .set("y", y)
+            .set("width", layout.vertex_width)
+            .set("height", layout.vertex_height)
+            .set("fill", "none")
+            .set("stroke", "#9E9E9E")
+            .set("stroke-width", 2)
+            .set("stroke-dasharray", "6,4");
+        let img = Image::new()
+            .set("href", vertex_icon(&ghost.name)?)
+            .set("x", x)
+            .set("y", y)
+            .set("width", layout.vertex_width)
+            .set("height", layout.vertex_height)
+            .set("opacity", 0.25);
+        let note = Text::new()
+            .set("x", x + (layout.vertex_width / 2))
+            .set("y", y + layout.vertex_height + 14)
+            .set("text-anchor", "middle")
+            .set("font-family", "Arial, Helvetica, sans-serif")
+            .set("font-size", 12)
+            .set("fill", "#757575")
+            .add(svg::node::Text::new(format!(
+                "missing since {}",
+                ghost.missing_since
+            )));
+
+        let group = Group::new()
+            .set("class", "ghost")
+            .set("name", ghost.name.clone())
+            .set("fmri", ghost.fmri.clone())
+            .set("missing-since", ghost.missing_since.clone())
+            .add(Title::new().add(svg::node::Text::new(format!(
+                "{} (missing since {})",
+                ghost.fmri, ghost.missing_since
+            ))))
+            .add(outline)
+            .add(img)
+            .add(note);
+        layer = layer.add(group);
+    }
+    Ok(layer)
+}
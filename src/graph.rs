@@ -0,0 +1,306 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// The vertices of a topology and the edges between them.
+//
+// The vertices are the nodes of a petgraph StableDiGraph, found by FMRI
+// through an index of their NodeIndex, so that the standard graph algorithms
+// (toposort, SCC, shortest paths and so on) apply to the topology as is.  The
+// indices are stable, i.e. removing a vertex doesn't renumber the others.
+//
+// Each vertex keeps the FMRIs its edges lead to, in the order the snapshot
+// lists them, which is the order they are laid out in.  These can name
+// vertices that aren't (or aren't yet) in the graph: a snapshot names the
+// vertices an edge leads to before it describes them, and a truncated one may
+// not describe them at all.  The graph has an edge for each of the FMRIs that
+// names a vertex it has, and is kept in step as vertices come and go.  The
+// edges of a vertex are changed through set_edges() rather than by editing
+// the FMRIs of a vertex handed out by get_mut(), which would leave the graph
+// behind.
+//
+// Each edge of the graph is weighted by the position of the FMRI it stems from
+// in the list of the vertex, so that traversals can follow the edges in the
+// order they are laid out in off the graph alone (see successors()).
+//
+use petgraph::stable_graph::{NodeIndex, StableDiGraph};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+
+use std::collections::{HashMap, HashSet};
+use std::iter::FromIterator;
+use std::ops::Index;
+
+use crate::SasDigraphVertex;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct VertexGraph {
+    graph: StableDiGraph<SasDigraphVertex, usize>,
+    // the node of each vertex, by FMRI
+    index: HashMap<String, NodeIndex>,
+    // the FMRIs of the vertices with edges to each FMRI that isn't in the graph
+    dangling: HashMap<String, HashSet<String>>,
+}
+
+impl VertexGraph {
+    pub(crate) fn new() -> VertexGraph {
+        VertexGraph::default()
+    }
+
+    //
+    // The underlying petgraph, whose node weights are the vertices.
+    //
+    pub fn graph(&self) -> &StableDiGraph<SasDigraphVertex, usize> {
+        &self.graph
+    }
+
+    pub fn node_index(&self, fmri: &str) -> Option<NodeIndex> {
+        self.index.get(fmri).copied()
+    }
+
+    //
+    // The nodes the edges of the node lead to, in the order they are listed.
+    //
+    pub(crate) fn successors(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut edges: Vec<_> = self.graph.edges(node).collect();
+        edges.sort_by_key(|edge| *edge.weight());
+        edges.into_iter().map(|edge| edge.target()).collect()
+    }
+
+    //
+    // Returns the first edge, in FMRI order, leading to an FMRI that isn't in
+    // the graph, if any, as a (from, to) pair.
+    //
+    pub(crate) fn dangling_edge(&self) -> Option<(&str, &str)> {
+        let mut fmris: Vec<&String> = self.index.keys().collect();
+        fmris.sort();
+        fmris.into_iter().find_map(|fmri| {
+            let edge = self[fmri].outgoing_edges().iter().find(|e| !self.contains_key(e))?;
+            Some((fmri.as_str(), edge.as_str()))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn contains_key(&self, fmri: &str) -> bool {
+        self.index.contains_key(fmri)
+    }
+
+    pub fn get(&self, fmri: &str) -> Option<&SasDigraphVertex> {
+        self.index.get(fmri).map(|node| &self.graph[*node])
+    }
+
+    pub(crate) fn get_mut(&mut self, fmri: &str) -> Option<&mut SasDigraphVertex> {
+        let node = *self.index.get(fmri)?;
+        Some(&mut self.graph[node])
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.index.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &SasDigraphVertex> {
+        self.index.values().map(move |node| &self.graph[*node])
+    }
+
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut SasDigraphVertex> {
+        self.graph.node_weights_mut()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &SasDigraphVertex)> {
+        self.index.iter().map(move |(fmri, node)| (fmri, &self.graph[*node]))
+    }
+
+    //
+    // Adds the vertex under the specified FMRI, replacing (and returning) the
+    // one that was there, if any.
+    //
+    pub(crate) fn insert(
+        &mut self,
+        fmri: String,
+        vtx: SasDigraphVertex,
+    ) -> Option<SasDigraphVertex> {
+        let old = self.remove(&fmri);
+        let edges = vtx.outgoing_edges.clone();
+        let node = self.graph.add_node(vtx);
+        self.index.insert(fmri.clone(), node);
+
+        // the edges to the vertex from those that were added before it
+        for from in self.dangling.remove(&fmri).into_iter().flatten() {
+            if let Some(from) = self.node_index(&from) {
+                let edges = self.graph[from].outgoing_edges();
+                if let Some(position) = edges.iter().position(|edge| *edge == fmri) {
+                    self.graph.update_edge(from, node, position);
+                }
+            }
+        }
+        self.link(node, edges);
+        old
+    }
+
+    pub(crate) fn remove(&mut self, fmri: &str) -> Option<SasDigraphVertex> {
+        let node = self.index.remove(fmri)?;
+        let sources: HashSet<String> = self
+            .graph
+            .neighbors_directed(node, Direction::Incoming)
+            .filter(|from| *from != node)
+            .map(|from| self.graph[from].fmri.clone())
+            .collect();
+        if !sources.is_empty() {
+            self.dangling.entry(fmri.to_string()).or_default().extend(sources);
+        }
+        self.graph.remove_node(node)
+    }
+
+    pub(crate) fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&String, &SasDigraphVertex) -> bool,
+    {
+        let removed: Vec<String> =
+            self.iter().filter(|(fmri, vtx)| !keep(fmri, vtx)).map(|(f, _)| f.clone()).collect();
+        for fmri in removed {
+            self.remove(&fmri);
+        }
+    }
+
+    //
+    // Replaces the edges of a vertex with edges to the specified FMRIs.
+    //
+    pub(crate) fn set_edges(&mut self, fmri: &str, edges: Option<Vec<String>>) {
+        let node = match self.node_index(fmri) {
+            Some(node) => node,
+            None => return,
+        };
+        let old: Vec<_> = self.graph.edges(node).map(|edge| edge.id()).collect();
+        for edge in old {
+            self.graph.remove_edge(edge);
+        }
+        self.graph[node].outgoing_edges = edges.clone();
+        self.link(node, edges);
+    }
+
+    //
+    // Passes the edges of each vertex through the function, which returns
+    // the edges to replace them with.
+    //
+    pub(crate) fn map_edges<F>(&mut self, mut map: F)
+    where
+        F: FnMut(&SasDigraphVertex) -> Option<Vec<String>>,
+    {
+        let mut fmris: Vec<String> = self.index.keys().cloned().collect();
+        fmris.sort();
+        for fmri in fmris {
+            let edges = map(&self[&fmri]);
+            if edges.as_deref() != self[&fmri].outgoing_edges.as_deref() {
+                self.set_edges(&fmri, edges);
+            }
+        }
+    }
+
+    fn link(&mut self, node: NodeIndex, edges: Option<Vec<String>>) {
+        for (position, edge) in edges.into_iter().flatten().enumerate() {
+            match self.node_index(&edge) {
+                // an FMRI listed twice is laid out where it is listed first
+                Some(to) => {
+                    if self.graph.find_edge(node, to).is_none() {
+                        self.graph.add_edge(node, to, position);
+                    }
+                }
+                None => {
+                    let from = self.graph[node].fmri.clone();
+                    self.dangling.entry(edge).or_default().insert(from);
+                }
+            }
+        }
+    }
+}
+
+impl Index<&str> for VertexGraph {
+    type Output = SasDigraphVertex;
+
+    fn index(&self, fmri: &str) -> &SasDigraphVertex {
+        match self.get(fmri) {
+            Some(vtx) => vtx,
+            None => panic!("no vertex {}", fmri),
+        }
+    }
+}
+
+impl Index<&String> for VertexGraph {
+    type Output = SasDigraphVertex;
+
+    fn index(&self, fmri: &String) -> &SasDigraphVertex {
+        &self[fmri.as_str()]
+    }
+}
+
+impl FromIterator<(String, SasDigraphVertex)> for VertexGraph {
+    fn from_iter<I: IntoIterator<Item = (String, SasDigraphVertex)>>(iter: I) -> VertexGraph {
+        let mut vertices = VertexGraph::new();
+        for (fmri, vtx) in iter {
+            vertices.insert(fmri, vtx);
+        }
+        vertices
+    }
+}
+
+impl IntoIterator for VertexGraph {
+    type Item = (String, SasDigraphVertex);
+    type IntoIter = std::vec::IntoIter<(String, SasDigraphVertex)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let VertexGraph { mut graph, index, .. } = self;
+        index
+            .into_iter()
+            .filter_map(|(fmri, node)| graph.remove_node(node).map(|vtx| (fmri, vtx)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SasInstance;
+
+    fn vertex(fmri: &str, edges: &[&str]) -> SasDigraphVertex {
+        let mut vtx = SasDigraphVertex::new(
+            fmri.to_string(),
+            "port".to_string(),
+            SasInstance::from_value(0),
+            None,
+        );
+        vtx.outgoing_edges = Some(edges.iter().map(|edge| edge.to_string()).collect());
+        vtx
+    }
+
+    #[test]
+    fn successors() {
+        // the vertices the edges lead to come before and after the vertex
+        let mut vertices = VertexGraph::new();
+        vertices.insert("b".to_string(), vertex("b", &[]));
+        vertices.insert("a".to_string(), vertex("a", &["c", "b", "d", "c"]));
+        vertices.insert("d".to_string(), vertex("d", &[]));
+        vertices.insert("c".to_string(), vertex("c", &[]));
+
+        let a = vertices.node_index("a").unwrap();
+        let fmris: Vec<&str> =
+            vertices.successors(a).into_iter().map(|n| vertices.graph()[n].fmri()).collect();
+        assert_eq!(fmris, vec!["c", "b", "d"]);
+        assert_eq!(vertices.dangling_edge(), None);
+
+        vertices.remove("d");
+        assert_eq!(vertices.dangling_edge(), Some(("a", "d")));
+    }
+}
@@ -0,0 +1,131 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// GraphML output, for loading a topology into yEd, Gephi and the like.
+//
+// Every property found on any vertex is declared as a GraphML key, typed
+// after the nvpair type of its value where one maps onto a GraphML type.
+// Arrays are flattened to comma-separated strings, as they are everywhere
+// else.  Node ids are the stable ids of the vertices.
+//
+use std::collections::BTreeMap;
+
+use crate::{html_escape as xml_escape, stable_id, SasDigraph};
+
+// keys every node has, besides its properties
+const NODE_KEYS: &[&str] = &["name", "instance", "fmri", "reference"];
+
+//
+// Map an nvpair type onto a GraphML attribute type.
+//
+fn graphml_type(proptype: Option<&str>) -> &'static str {
+    match proptype {
+        Some("boolean") | Some("boolean_value") => "boolean",
+        Some("int8") | Some("uint8") | Some("int16") | Some("uint16") | Some("int32") => "int",
+        Some("uint32") | Some("int64") | Some("uint64") => "long",
+        Some("double") => "double",
+        _ => "string",
+    }
+}
+
+pub(crate) fn render_graphml(digraph: &SasDigraph) -> String {
+    let mut fmris: Vec<&String> = digraph.vertices.keys().collect();
+    fmris.sort();
+
+    //
+    // Collect the property keys along with their types.  A property that
+    // doesn't have the same type on all vertices is declared as a string.
+    //
+    let mut prop_types: BTreeMap<&str, &str> = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        for prop in &vtx.properties {
+            let ty = graphml_type(prop.proptype.as_deref());
+            let entry = prop_types.entry(&prop.name).or_insert(ty);
+            if *entry != ty {
+                *entry = "string";
+            }
+        }
+    }
+    let prop_ids: BTreeMap<&str, String> = prop_types
+        .keys()
+        .enumerate()
+        .map(|(i, name)| (*name, format!("p{}", i)))
+        .collect();
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\" \
+         xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:schemaLocation=\"http://graphml.graphdrawing.org/xmlns \
+         http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd\">\n",
+    );
+    for key in NODE_KEYS {
+        xml.push_str(&format!(
+            "  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"string\"/>\n",
+            key, key
+        ));
+    }
+    for (name, ty) in &prop_types {
+        xml.push_str(&format!(
+            "  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>\n",
+            prop_ids[name],
+            xml_escape(name),
+            ty
+        ));
+    }
+
+    xml.push_str(&format!(
+        "  <graph id=\"{}\" edgedefault=\"directed\">\n",
+        xml_escape(&digraph.nodename)
+    ));
+    for fmri in &fmris {
+        let vtx = &digraph.vertices[*fmri];
+        let mut data = vec![
+            ("name", vtx.name.clone()),
+            ("instance", vtx.instance.to_string()),
+            ("fmri", vtx.fmri.clone()),
+        ];
+        if let Some(reference) = &vtx.reference {
+            data.push(("reference", reference.clone()));
+        }
+        xml.push_str(&format!("    <node id=\"{}\">\n", stable_id(fmri)));
+        for (key, value) in data {
+            xml.push_str(&format!(
+                "      <data key=\"{}\">{}</data>\n",
+                key,
+                xml_escape(&value)
+            ));
+        }
+        for prop in &vtx.properties {
+            xml.push_str(&format!(
+                "      <data key=\"{}\">{}</data>\n",
+                prop_ids[prop.name.as_str()],
+                xml_escape(&prop.value)
+            ));
+        }
+        xml.push_str("    </node>\n");
+    }
+
+    let mut n = 0;
+    for fmri in &fmris {
+        if let Some(edges) = &digraph.vertices[*fmri].outgoing_edges {
+            for edge in edges {
+                xml.push_str(&format!(
+                    "    <edge id=\"e{}\" source=\"{}\" target=\"{}\"/>\n",
+                    n,
+                    stable_id(fmri),
+                    stable_id(edge)
+                ));
+                n += 1;
+            }
+        }
+    }
+    xml.push_str("  </graph>\n</graphml>\n");
+    xml
+}
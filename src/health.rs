@@ -0,0 +1,114 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Fabric health summary.
+//
+// A bar across the top of the diagram with one segment per class of link
+// (negotiated rate and status), sized by the number of links in that class.
+// A healthy fabric is a single green bar; slow or degraded links show up as
+// segments in a different color, without having to inspect every edge.
+//
+use svg::node::element::{Group, Rectangle, Text, Title};
+
+use std::collections::BTreeMap;
+
+use crate::analysis::{link_rate_label, VertexStatus};
+use crate::monochrome::segment_fill;
+use crate::{Layout, Theme};
+
+// total width of the bar
+const SUMMARY_WIDTH: u32 = 600;
+const SUMMARY_HEIGHT: u32 = 20;
+
+fn segment_color(rate: Option<u64>, status: VertexStatus) -> &'static str {
+    if status != VertexStatus::Ok {
+        return "#E53935";
+    }
+    match rate {
+        Some(rate) if rate >= 0xb => "#43A047",
+        Some(0xa) => "#9CCC65",
+        Some(0x9) => "#FDD835",
+        Some(_) => "#FB8C00",
+        None => "#BDBDBD",
+    }
+}
+
+//
+// Render the summary of the link classes returned by analysis::link_summary,
+// with the top of the bar at y.  On a monochrome theme the segments are gray
+// or hatched, and outlined so that they can be told apart.
+//
+pub(crate) fn render_summary(
+    links: &BTreeMap<(Option<u64>, VertexStatus), usize>,
+    theme: &Theme,
+    layout: &Layout,
+    y: u32,
+) -> Group {
+    let total: usize = links.values().sum();
+    let mut group = Group::new().set("id", "health-summary");
+
+    //
+    // The fastest links come first.  Every class gets a segment wide enough
+    // to be seen, even if it's only a single link.
+    //
+    let mut x = layout.x_margin;
+    for ((rate, status), count) in links.iter().rev() {
+        let width = std::cmp::max(8, (*count as u32 * SUMMARY_WIDTH) / total as u32);
+        let (fill, stroke, label_color) = match theme.monochrome {
+            true => (segment_fill(*rate, *status), "black", "black"),
+            false => (segment_color(*rate, *status), "none", "white"),
+        };
+        let label = match status {
+            VertexStatus::Ok => link_rate_label(*rate),
+            _ => format!("{} {}", link_rate_label(*rate), status.as_str()),
+        };
+        let segment = Group::new()
+            .set("class", "health-segment")
+            .set("link-rate", link_rate_label(*rate))
+            .set("status", status.as_str())
+            .set("count", *count)
+            .add(Title::new().add(svg::node::Text::new(format!(
+                "{}: {} of {} links",
+                label, count, total
+            ))))
+            .add(
+                Rectangle::new()
+                    .set("x", x)
+                    .set("y", y)
+                    .set("width", width)
+                    .set("height", SUMMARY_HEIGHT)
+                    .set("fill", fill)
+                    .set("stroke", stroke),
+            );
+        let segment = match width >= 60 {
+            true => segment.add(
+                Text::new()
+                    .set("x", x + 4)
+                    .set("y", y + SUMMARY_HEIGHT - 6)
+                    .set("font-family", "Arial, Helvetica, sans-serif")
+                    .set("font-size", 12)
+                    .set("fill", label_color)
+                    .add(svg::node::Text::new(format!("{} × {}", count, label))),
+            ),
+            false => segment,
+        };
+        group = group.add(segment);
+        x += width;
+    }
+
+    group.add(
+        Text::new()
+            .set("x", x + 8)
+            .set("y", y + SUMMARY_HEIGHT - 6)
+            .set("font-family", "Arial, Helvetica, sans-serif")
+            .set("font-size", 12)
+            .set("fill", "#616161")
+            .add(svg::node::Text::new(format!("{} links", total))),
+    )
+}
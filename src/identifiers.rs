@@ -0,0 +1,61 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Machine-usable identifiers of the vertices.
+//
+// Operators look a device up in the viewer and then go on to run format(8),
+// diskinfo(8) or fmadm(8) against it, which takes the exact FMRI, SAS address,
+// serial number, device path or disk name.  Rather than have them retype those
+// from the info panel, each vertex carries its identifiers as a JSON object
+// (in the "identifiers" attribute of its group, and in the JSON export), from
+// which the viewer generates a copy-to-clipboard control per identifier.
+//
+// Unlike the properties, the identifiers are never shortened or moved to the
+// sidecar property store, so what is copied is always what the commands
+// expect.
+//
+use std::collections::BTreeMap;
+
+use crate::analysis::prop;
+use crate::fmri::wwn;
+use crate::SasDigraphVertex;
+
+//
+// The properties holding identifiers, in the order they're offered in.
+//
+const IDENTIFIER_PROPS: &[&str] = &["serial-number", "devfs-path", "logical-disk"];
+
+//
+// Returns the identifiers of the vertex, hashed by name.  The FMRI is always
+// there, the others only if the vertex has them.
+//
+pub(crate) fn identifiers(vtx: &SasDigraphVertex) -> BTreeMap<String, String> {
+    let mut ids = BTreeMap::new();
+    ids.insert("fmri".to_string(), vtx.fmri.clone());
+    if let Some(wwn) = wwn(&vtx.fmri) {
+        ids.insert("wwn".to_string(), wwn.to_string());
+    }
+    for name in IDENTIFIER_PROPS {
+        match prop(vtx, name) {
+            Some(value) if !value.is_empty() => {
+                ids.insert(name.to_string(), value.to_string());
+            }
+            _ => (),
+        }
+    }
+    ids
+}
+
+//
+// The identifiers of the vertex as the JSON object the viewer reads.
+//
+pub(crate) fn encode(vtx: &SasDigraphVertex) -> String {
+    // a map of strings always serializes
+    serde_json::to_string(&identifiers(vtx)).unwrap_or_default()
+}
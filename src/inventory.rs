@@ -0,0 +1,239 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// CSV and JSON inventory.
+//
+// Every vertex is flattened into a row, for asset tracking in a spreadsheet.
+// Each row starts with the stable id of the vertex (see stable_id()), which
+// is also the id of its node in the SVG and in the JSON export, so that a row
+// can be traced back to the diagram.  Besides the FMRI, name and instance
+// (and the reference, if numbering is enabled) each row carries the WWN and
+// a selection of properties, by default DEFAULT_INVENTORY_PROPERTIES.
+// Vertices lacking a property get an empty field.
+//
+// If a reference snapshot is given, only the rows that differ from it are
+// included, with a change column saying whether the vertex was added,
+// removed or changed, and for the latter, which of the selected properties
+// changed.  Removed vertices are listed as they were in the reference.
+//
+use serde::Serialize;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+
+use crate::connections::csv_field;
+use crate::fmri::{shorten, wwn};
+use crate::key_case::KeyCase;
+use crate::locale::Locale;
+use crate::{stable_id, FmriDisplay, SasDigraph, SasDigraphVertex};
+
+pub const DEFAULT_INVENTORY_PROPERTIES: &[&str] = &[
+    "manufacturer",
+    "model",
+    "serial-number",
+    "firmware-revision",
+    "location",
+];
+
+#[derive(Debug, Serialize)]
+struct InventoryRow {
+    // see stable_id()
+    id: String,
+    fmri: String,
+    name: String,
+    instance: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reference: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wwn: Option<String>,
+    // the selected properties, None if the vertex lacks them
+    properties: BTreeMap<String, Option<String>>,
+    // "added", "removed" or "changed", if compared against a reference
+    #[serde(skip_serializing_if = "Option::is_none")]
+    change: Option<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    changed_properties: Vec<String>,
+}
+
+impl InventoryRow {
+    fn new(vtx: &SasDigraphVertex, properties: &[String]) -> InventoryRow {
+        InventoryRow {
+            id: stable_id(&vtx.fmri),
+            fmri: vtx.fmri.clone(),
+            name: vtx.name.clone(),
+            instance: vtx.instance.to_string(),
+            reference: vtx.reference.clone(),
+            wwn: wwn(&vtx.fmri).map(|w| w.to_string()),
+            properties: properties
+                .iter()
+                .map(|name| {
+                    let value = vtx.properties.iter().find(|p| &p.name == name);
+                    (name.clone(), value.map(|p| p.value.clone()))
+                })
+                .collect(),
+            change: None,
+            changed_properties: Vec::new(),
+        }
+    }
+}
+
+fn inventory_rows(
+    digraph: &SasDigraph,
+    properties: &[String],
+    reference: Option<&SasDigraph>,
+) -> Vec<InventoryRow> {
+    let reference = match reference {
+        Some(reference) => reference,
+        None => {
+            let mut rows: Vec<InventoryRow> = digraph
+                .vertices
+                .values()
+                .map(|vtx| InventoryRow::new(vtx, properties))
+                .collect();
+            rows.sort_by(|a, b| a.fmri.cmp(&b.fmri));
+            return rows;
+        }
+    };
+
+    let fmris: BTreeSet<&String> = digraph
+        .vertices
+        .keys()
+        .chain(reference.vertices.keys())
+        .collect();
+    let mut rows = Vec::new();
+    for fmri in fmris {
+        let row = match (reference.vertices.get(fmri), digraph.vertices.get(fmri)) {
+            (None, Some(vtx)) => InventoryRow {
+                change: Some("added"),
+                ..InventoryRow::new(vtx, properties)
+            },
+            (Some(vtx), None) => InventoryRow {
+                change: Some("removed"),
+                ..InventoryRow::new(vtx, properties)
+            },
+            (Some(before), Some(after)) => {
+                let before = InventoryRow::new(before, properties);
+                let after = InventoryRow::new(after, properties);
+                let changed: Vec<String> = properties
+                    .iter()
+                    .filter(|p| before.properties[*p] != after.properties[*p])
+                    .cloned()
+                    .collect();
+                if changed.is_empty() {
+                    continue;
+                }
+                InventoryRow {
+                    change: Some("changed"),
+                    changed_properties: changed,
+                    ..after
+                }
+            }
+            (None, None) => continue,
+        };
+        rows.push(row);
+    }
+    rows
+}
+
+pub(crate) fn render_inventory_csv(
+    digraph: &SasDigraph,
+    properties: &[String],
+    reference: Option<&SasDigraph>,
+    locale: Locale,
+    fmri_display: FmriDisplay,
+    key_case: KeyCase,
+) -> String {
+    let separator = locale.csv_separator();
+    let mut header = vec!["id", "fmri", "name", "instance", "reference", "wwn"];
+    header.extend(properties.iter().map(|p| p.as_str()));
+    if reference.is_some() {
+        header.extend(&["change", "changed-properties"]);
+    }
+    let mut csv = header
+        .iter()
+        .map(|h| csv_field(&key_case.convert(h)))
+        .collect::<Vec<String>>()
+        .join(separator);
+    csv.push('\n');
+
+    for row in inventory_rows(digraph, properties, reference) {
+        let mut fields = vec![
+            csv_field(&row.id),
+            csv_field(&shorten(&row.fmri, fmri_display)),
+            csv_field(&row.name),
+            csv_field(&row.instance),
+            csv_field(row.reference.as_deref().unwrap_or("")),
+            csv_field(row.wwn.as_deref().unwrap_or("")),
+        ];
+        for name in properties {
+            fields.push(csv_field(row.properties[name].as_deref().unwrap_or("")));
+        }
+        if reference.is_some() {
+            fields.push(csv_field(row.change.unwrap_or("")));
+            fields.push(csv_field(&row.changed_properties.join(";")));
+        }
+        csv.push_str(&fields.join(separator));
+        csv.push('\n');
+    }
+    csv
+}
+
+pub(crate) fn render_inventory_json(
+    digraph: &SasDigraph,
+    properties: &[String],
+    reference: Option<&SasDigraph>,
+    key_case: KeyCase,
+) -> Result<String, Box<dyn Error>> {
+    Ok(key_case.to_json(&inventory_rows(digraph, properties, reference))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HBA: &str = "sas://:hba=500605b0000272a0:/initiator=500605b0000272a0";
+    const DISK: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000000";
+
+    fn topology() -> SasDigraph {
+        SasDigraph::builder()
+            .add_vertex(HBA, "initiator", 0)
+            .add_vertex(DISK, "target", 0)
+            .add_property(DISK, "target", "serial-number", "ZA1234")
+            .add_edge(HBA, DISK)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn csv_ids() {
+        let properties = vec!["serial-number".to_string()];
+        let csv = render_inventory_csv(
+            &topology(),
+            &properties,
+            None,
+            Locale::default(),
+            FmriDisplay::Full,
+            KeyCase::Raw,
+        );
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "id,fmri,name,instance,reference,wwn,serial-number");
+        assert_eq!(
+            lines[2],
+            format!("{},{},target,0x0,,5000c50090000000,ZA1234", stable_id(DISK), DISK)
+        );
+    }
+
+    #[test]
+    fn json_ids() {
+        let json = render_inventory_json(&topology(), &[], None, KeyCase::Raw).unwrap();
+        let rows: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        let ids: Vec<&str> = rows.iter().map(|row| row["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec![stable_id(HBA), stable_id(DISK)]);
+    }
+}
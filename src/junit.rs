@@ -0,0 +1,265 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Health check of a snapshot, reported as JUnit XML.
+//
+// Deployment pipelines verify the fabric of each system they bring up, and
+// their dashboards already know how to show JUnit results, so the checks are
+// reported in that format, one test case per check:
+//
+//   - integrity: whether the snapshot could be read and hangs together (the
+//     edges lead to vertices in the snapshot, the ports to their devices, and
+//     there are initiators to lay the graph out from)
+//
+//   - policy: that none of the problems the analysis looks for (duplicate
+//     hardware, cross-zone edges, downshifted links) are found, and that no
+//     devices are faulted or degraded
+//
+//   - expectations: if a file of expectations is given (see expect.rs), that
+//     the snapshot meets them
+//
+// A snapshot that can't be read still gets a report, with the read error as
+// the only (failing) test case.
+//
+use crate::analysis::{analyze, vertex_status, FindingKind, VertexStatus};
+use crate::expect::{check_digraph, Expectations};
+use crate::fmri::device_fmri;
+use crate::{
+    html_escape, plan_layout, read_digraph, RenderOptions, SasDigraph, DEFAULT_MAX_DEPTH, PORT,
+};
+
+// the findings each of the policy test cases fails on
+const POLICY_FINDINGS: &[(&str, FindingKind)] = &[
+    ("no duplicate serial numbers", FindingKind::DuplicateSerial),
+    ("no duplicate WWNs", FindingKind::DuplicateWwn),
+    ("no cross-zone edges", FindingKind::CrossZoneEdge),
+    ("no link downshifts", FindingKind::LinkDownshift),
+];
+
+// failure messages listed in the body of a failing test case at most
+const MAX_LISTED: usize = 50;
+
+struct TestCase {
+    name: String,
+    failures: Vec<String>,
+    // the test case couldn't be run, as opposed to failing
+    error: bool,
+}
+
+impl TestCase {
+    fn new(name: &str, failures: Vec<String>) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            failures,
+            error: false,
+        }
+    }
+}
+
+struct TestSuite {
+    name: &'static str,
+    cases: Vec<TestCase>,
+}
+
+//
+// Results of the health check of a snapshot.
+//
+pub struct HealthReport {
+    snapshot: String,
+    hostname: String,
+    timestamp: String,
+    suites: Vec<TestSuite>,
+}
+
+fn integrity(digraph: &SasDigraph) -> TestSuite {
+    let mut dangling = Vec::new();
+    let mut orphans = Vec::new();
+    for vtx in digraph.vertices.values() {
+        for edge in vtx.outgoing_edges.iter().flatten() {
+            if !digraph.vertices.contains_key(edge) {
+                dangling.push(format!("{} has an edge to missing vertex {}", vtx.fmri, edge));
+            }
+        }
+        if vtx.name == PORT && !digraph.vertices.contains_key(device_fmri(&vtx.fmri)) {
+            orphans.push(format!("port {} belongs to a missing device", vtx.fmri));
+        }
+    }
+    dangling.sort();
+    orphans.sort();
+
+    let mut roots = Vec::new();
+    if digraph.initiators.is_empty() {
+        roots.push("no initiators found".to_string());
+    }
+    let layout = match plan_layout(digraph, DEFAULT_MAX_DEPTH) {
+        Ok(_) => Vec::new(),
+        Err(e) => vec![e.to_string()],
+    };
+
+    TestSuite {
+        name: "integrity",
+        cases: vec![
+            TestCase::new("snapshot is readable", Vec::new()),
+            TestCase::new("edges lead to vertices in the snapshot", dangling),
+            TestCase::new("ports belong to devices in the snapshot", orphans),
+            TestCase::new("initiators present", roots),
+            TestCase::new("graph can be laid out", layout),
+        ],
+    }
+}
+
+fn policy(digraph: &SasDigraph) -> TestSuite {
+    let findings = analyze(digraph);
+    let mut cases: Vec<TestCase> = POLICY_FINDINGS
+        .iter()
+        .map(|(name, kind)| {
+            let failures = findings
+                .iter()
+                .filter(|f| f.kind == *kind)
+                .map(|f| f.message.clone())
+                .collect();
+            TestCase::new(name, failures)
+        })
+        .collect();
+
+    let mut faulted = Vec::new();
+    let mut degraded = Vec::new();
+    for vtx in digraph.vertices.values() {
+        match vertex_status(vtx, &findings) {
+            VertexStatus::Faulted => faulted.push(format!("{} is faulted", vtx.fmri)),
+            VertexStatus::Degraded => degraded.push(format!("{} is degraded", vtx.fmri)),
+            _ => (),
+        }
+    }
+    faulted.sort();
+    degraded.sort();
+    cases.push(TestCase::new("no faulted devices", faulted));
+    cases.push(TestCase::new("no degraded devices", degraded));
+
+    TestSuite {
+        name: "policy",
+        cases,
+    }
+}
+
+//
+// Check the snapshot at xml_path, read as specified by the options, and
+// against the expectations, if any.
+//
+pub fn health_check(
+    xml_path: &str,
+    expect: Option<&Expectations>,
+    options: &RenderOptions,
+) -> HealthReport {
+    let digraph = match read_digraph(xml_path, options) {
+        Ok(digraph) => digraph,
+        Err(e) => {
+            let mut case = TestCase::new("snapshot is readable", vec![e.to_string()]);
+            case.error = true;
+            return HealthReport {
+                snapshot: xml_path.to_string(),
+                hostname: String::new(),
+                timestamp: String::new(),
+                suites: vec![TestSuite {
+                    name: "integrity",
+                    cases: vec![case],
+                }],
+            };
+        }
+    };
+
+    let mut suites = vec![integrity(&digraph), policy(&digraph)];
+    if let Some(expect) = expect {
+        suites.push(TestSuite {
+            name: "expectations",
+            cases: vec![TestCase::new("expectations met", check_digraph(&digraph, expect))],
+        });
+    }
+    HealthReport {
+        snapshot: xml_path.to_string(),
+        hostname: digraph.nodename.clone(),
+        timestamp: digraph.timestamp.clone(),
+        suites,
+    }
+}
+
+impl HealthReport {
+    pub fn passed(&self) -> bool {
+        self.suites.iter().flat_map(|s| &s.cases).all(|c| c.failures.is_empty())
+    }
+
+    pub fn to_junit(&self) -> String {
+        let count = |suite: &TestSuite, error: bool| {
+            suite
+                .cases
+                .iter()
+                .filter(|c| !c.failures.is_empty() && c.error == error)
+                .count()
+        };
+        let tests: usize = self.suites.iter().map(|s| s.cases.len()).sum();
+        let failures: usize = self.suites.iter().map(|s| count(s, false)).sum();
+        let errors: usize = self.suites.iter().map(|s| count(s, true)).sum();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">\n",
+            html_escape(&self.snapshot),
+            tests,
+            failures,
+            errors
+        ));
+        for suite in &self.suites {
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\"",
+                suite.name,
+                suite.cases.len(),
+                count(suite, false),
+                count(suite, true)
+            ));
+            if !self.hostname.is_empty() {
+                xml.push_str(&format!(" hostname=\"{}\"", html_escape(&self.hostname)));
+            }
+            if !self.timestamp.is_empty() {
+                xml.push_str(&format!(" timestamp=\"{}\"", html_escape(&self.timestamp)));
+            }
+            xml.push_str(">\n");
+            for case in &suite.cases {
+                xml.push_str(&format!(
+                    "    <testcase classname=\"sastopo2svg.{}\" name=\"{}\"",
+                    suite.name,
+                    html_escape(&case.name)
+                ));
+                if case.failures.is_empty() {
+                    xml.push_str("/>\n");
+                    continue;
+                }
+                let element = if case.error { "error" } else { "failure" };
+                let message = match case.failures.len() {
+                    1 => case.failures[0].clone(),
+                    n => format!("{} problems, the first: {}", n, case.failures[0]),
+                };
+                let mut listed: Vec<String> =
+                    case.failures.iter().take(MAX_LISTED).map(|f| html_escape(f)).collect();
+                if case.failures.len() > MAX_LISTED {
+                    listed.push(format!("... and {} more", case.failures.len() - MAX_LISTED));
+                }
+                xml.push_str(&format!(
+                    ">\n      <{} message=\"{}\">{}</{}>\n    </testcase>\n",
+                    element,
+                    html_escape(&message),
+                    listed.join("\n"),
+                    element
+                ));
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
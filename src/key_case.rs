@@ -0,0 +1,108 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Naming of the keys in the data exports.
+//
+// The JSON export, the inventory and the cabling table mix the naming
+// conventions of their sources: their own fields are in snake_case (e.g.
+// "product_id"), while the properties go by the names the topo modules gave
+// them, which are mostly kebab-case (e.g. "serial-number") and qualified with
+// their group where they clash (e.g. "storage:model").  Databases the exports
+// are loaded into usually expect one convention throughout, so the keys can
+// be converted to either one:
+//
+//   - the object keys of the JSON documents, at any depth, including those of
+//     the structured property values
+//
+//   - the column headers of the CSV tables
+//
+// Values are never touched.  A JSON export written with converted keys is
+// meant for other consumers, and can't be read back in.
+//
+use serde_json::{Map, Value};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum KeyCase {
+    // the keys as they come, mixing snake_case and property names
+    #[default]
+    Raw,
+    // e.g. "serial_number"
+    Snake,
+    // e.g. "serial-number"
+    Kebab,
+}
+
+impl std::str::FromStr for KeyCase {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<KeyCase, String> {
+        match name {
+            "raw" => Ok(KeyCase::Raw),
+            "snake" | "snake_case" => Ok(KeyCase::Snake),
+            "kebab" | "kebab-case" => Ok(KeyCase::Kebab),
+            _ => Err(format!("unknown key case: {}", name)),
+        }
+    }
+}
+
+impl KeyCase {
+    //
+    // Converts a key, lowercasing it and replacing anything but letters and
+    // digits (i.e. the separators of both conventions, and the colon of
+    // qualified property names) with the separator of this case.
+    //
+    pub(crate) fn convert(self, key: &str) -> String {
+        let separator = match self {
+            KeyCase::Raw => return key.to_string(),
+            KeyCase::Snake => '_',
+            KeyCase::Kebab => '-',
+        };
+        key.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { separator })
+            .collect()
+    }
+
+    //
+    // Converts the keys of all of the objects in the JSON value.
+    //
+    pub(crate) fn convert_json(self, value: Value) -> Value {
+        if self == KeyCase::Raw {
+            return value;
+        }
+        match value {
+            Value::Object(object) => {
+                let mut converted = Map::new();
+                for (key, value) in object {
+                    converted.insert(self.convert(&key), self.convert_json(value));
+                }
+                Value::Object(converted)
+            }
+            Value::Array(values) => {
+                Value::Array(values.into_iter().map(|v| self.convert_json(v)).collect())
+            }
+            value => value,
+        }
+    }
+
+    //
+    // Serializes the document as pretty-printed JSON with its keys converted.
+    // The keys of the converted objects come out sorted, so the raw keys are
+    // written directly to keep them in the order of the fields.
+    //
+    pub(crate) fn to_json<T: serde::Serialize>(
+        self,
+        document: &T,
+    ) -> Result<String, serde_json::Error> {
+        if self == KeyCase::Raw {
+            return serde_json::to_string_pretty(document);
+        }
+        let value = self.convert_json(serde_json::to_value(document)?);
+        serde_json::to_string_pretty(&value)
+    }
+}
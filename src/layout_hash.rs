@@ -0,0 +1,69 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Layout hashing.
+//
+// A wrapper re-rendering a topology periodically mostly wants to know whether
+// anything changed since the last rendering, which the diff engine (see
+// diff.rs) answers at the cost of keeping and parsing the previous snapshot.
+// The layout hash answers it from one string: a SHA-256 of the structure of
+// the laid-out graph, i.e. of each vertex (by FMRI) with the cell of the grid
+// it is placed in and the vertices its edges lead to, in a canonical order.
+//
+// The hash doesn't depend on when the snapshot was taken or on the property
+// values (which include counters and sensor readings that change all the
+// time), nor on the options that only affect the drawing (theme, spacing and
+// so on), so two renderings have the same hash exactly when the same devices
+// are connected the same way and placed in the same spots.  It is that of the
+// graph as drawn, with dual-ported drives and the PHYs of wide ports merged
+// (see dual_port.rs and wide_ports.rs), so a snapshot describing ports one PHY
+// at a time hashes the same as one describing the wide ports.
+//
+use sha2::{Digest, Sha256};
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::{dual_port, plan_layout, wide_ports, SasDigraph};
+
+//
+// Returns the hash of the layout of the digraph, as a hex string.
+//
+pub(crate) fn layout_hash(
+    digraph: &SasDigraph,
+    depth_limit: u32,
+) -> Result<String, Box<dyn Error>> {
+    let dual = dual_port::merged(digraph);
+    let digraph = dual.as_ref().unwrap_or(digraph);
+    let wide = wide_ports::merged(digraph);
+    let digraph = wide.as_ref().unwrap_or(digraph);
+    let plan = plan_layout(digraph, depth_limit)?;
+
+    let mut cells: BTreeMap<&str, (u32, usize)> = BTreeMap::new();
+    for (depth, fmris) in &plan.column_hash {
+        for (row, fmri) in fmris.iter().enumerate() {
+            cells.insert(fmri, (*depth, row));
+        }
+    }
+
+    let mut canonical = String::new();
+    for (fmri, (depth, row)) in &cells {
+        canonical.push_str(&format!("{} {} {}\n", fmri, depth, row));
+        let mut edges: Vec<&String> = digraph
+            .vertices
+            .get(fmri)
+            .map(|vtx| vtx.outgoing_edges().iter().collect())
+            .unwrap_or_default();
+        edges.sort();
+        for edge in edges {
+            canonical.push_str(&format!("  -> {}\n", edge));
+        }
+    }
+    Ok(format!("{:x}", Sha256::digest(canonical.as_bytes())))
+}
@@ -0,0 +1,76 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Legend shown above the diagram.
+//
+// Besides explaining the icons, the legend gives a quantitative summary of
+// the rendered topology: the number of vertices of each type, and how many of
+// those aren't healthy, e.g. "targets: 184 (3 degraded)".  The counts are
+// collected while the vertices are rendered, so they match what's drawn.
+//
+use std::collections::{BTreeMap, HashMap};
+
+use crate::analysis::VertexStatus;
+use crate::locale::Locale;
+use crate::{vertex_icon, EXPANDER, INITIATOR, PORT, TARGET};
+
+// vertex types in the order they are listed, along with their plural label
+const LEGEND_TYPES: &[(&str, &str)] = &[
+    (INITIATOR, "initiators"),
+    (EXPANDER, "expanders"),
+    (TARGET, "targets"),
+    (PORT, "ports"),
+];
+
+#[derive(Debug, Default)]
+pub(crate) struct Legend {
+    counts: HashMap<String, BTreeMap<VertexStatus, usize>>,
+}
+
+impl Legend {
+    pub(crate) fn count(&mut self, name: &str, status: VertexStatus) {
+        *self
+            .counts
+            .entry(name.to_string())
+            .or_default()
+            .entry(status)
+            .or_insert(0) += 1;
+    }
+
+    pub(crate) fn to_html(&self, locale: Locale) -> String {
+        let mut html = String::from("<div id=\"legend\">\n");
+        for (name, label) in LEGEND_TYPES {
+            let statuses = match self.counts.get(*name) {
+                Some(statuses) => statuses,
+                None => continue,
+            };
+            let total: usize = statuses.values().sum();
+            let unhealthy: Vec<String> = statuses
+                .iter()
+                .filter(|(status, _)| **status != VertexStatus::Ok)
+                .map(|(status, count)| format!("{} {}", locale.count(*count), status.as_str()))
+                .collect();
+
+            html.push_str("<span class=\"legend-entry\">");
+            if let Ok(icon) = vertex_icon(name) {
+                html.push_str(&format!("<img src=\"{}\">", icon));
+            }
+            html.push_str(&format!("{}: {}", label, locale.count(total)));
+            if !unhealthy.is_empty() {
+                html.push_str(&format!(
+                    " <span class=\"legend-degraded\">({})</span>",
+                    unhealthy.join(", ")
+                ));
+            }
+            html.push_str("</span>\n");
+        }
+        html.push_str("</div>\n");
+        html
+    }
+}
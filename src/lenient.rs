@@ -0,0 +1,79 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Lenient parsing.
+//
+// A vertex whose property groups are malformed (e.g. a property group without
+// a name, as some firmware reports for a drive it can't talk to) normally
+// fails the whole conversion.  In lenient mode such vertices are skipped
+// instead, along with the edges leading to them, and the rest of the topology
+// is rendered.  Each vertex skipped is logged, and reported as a parse warning
+// in the run report, on the HTML page and in the JSON export, so that the gap
+// in the picture is accounted for.
+//
+// Only vertices that were read completely but can't be made sense of are
+// skipped; a snapshot that isn't well-formed XML still fails, as there is no
+// telling where the next vertex starts.
+//
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::{html_escape, SasDigraph};
+
+//
+// A vertex that was skipped, and why.
+//
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ParseWarning {
+    pub fmri: String,
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.fmri, self.message)
+    }
+}
+
+//
+// Removes the edges leading to the vertices that were skipped, which the
+// layout would otherwise fail to follow.
+//
+pub(crate) fn drop_skipped_edges(digraph: &mut SasDigraph) {
+    let skipped: HashSet<&str> = digraph.parse_warnings.iter().map(|w| w.fmri.as_str()).collect();
+    if skipped.is_empty() {
+        return;
+    }
+    digraph.vertices.map_edges(|vtx| {
+        let edges = vtx.outgoing_edges.as_ref()?;
+        Some(edges.iter().filter(|edge| !skipped.contains(edge.as_str())).cloned().collect())
+    });
+}
+
+pub(crate) fn parse_warnings_html(warnings: &[ParseWarning]) -> String {
+    if warnings.is_empty() {
+        return String::new();
+    }
+
+    let mut html = String::from(
+        "<table id=\"parse-warnings\">\n<thead><tr><th colspan=\"2\">Parse warnings \
+         (vertices skipped)</th></tr></thead>\n",
+    );
+    for warning in warnings {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td></tr>\n",
+            html_escape(&warning.fmri),
+            html_escape(&warning.message)
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
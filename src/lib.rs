@@ -8,31 +8,170 @@
 extern crate env_logger;
 extern crate log;
 
-use log::debug;
+use log::{debug, warn};
 
-extern crate fs_extra;
+extern crate base64;
+extern crate bzip2;
+extern crate encoding_rs;
+extern crate encoding_rs_io;
+
+extern crate flate2;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 
 extern crate serde;
 extern crate serde_derive;
-extern crate serde_xml_rs;
+extern crate serde_json;
+extern crate petgraph;
+use petgraph::stable_graph::{NodeIndex, StableDiGraph};
+use petgraph::Direction;
+
+extern crate sha2;
+extern crate quick_xml;
+extern crate ureq;
 
 extern crate topo_digraph_xml;
 use topo_digraph_xml::{
-    NvlistXmlArrayElement, TopoDigraphXML, PG_NAME, PG_VALS, PROP_NAME, PROP_VALUE,
+    NvlistXmlArrayElement, PG_NAME, PG_VALS, PROP_NAME, PROP_TYPE, PROP_VALUE,
 };
 
+extern crate tar;
+extern crate toml;
+
 extern crate svg;
 use svg::node::element::{
-    Filter, Group, Image, Line, Rectangle, Script};
+    Filter as SvgFilter, Group, Image, Line, Rectangle, Script, Text, Title};
 use svg::Document;
 
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
 use std::error::Error;
 use std::fmt;
 use std::fs;
-use std::io::Write;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+mod analysis;
+mod anonymize;
+mod bands;
+mod archive;
+mod builder;
+pub use builder::SasDigraphBuilder;
+mod bundle;
+pub use archive::{pack_archive, unpack_archive, verify_archive, Manifest, MANIFEST_VERSION};
+
+pub use analysis::{Finding, FindingKind, VertexStatus};
+
+mod capture;
+pub use capture::{capture_snapshot, DEFAULT_CAPTURE_COMMAND};
+mod cytoscape;
+mod d3;
+mod daemon;
+pub use daemon::{run_daemon, DaemonConfig};
+
+mod diff;
+pub use diff::{PropertyChange, TopoDiff};
+mod dot;
+mod drawio;
+mod dual_port;
+mod enclosures;
+mod encoding;
+mod events;
+mod expect;
+pub use expect::{check_expectations, Expectations};
+mod export;
+mod fetch;
+mod filter_controls;
+mod fmri;
+mod ghosts;
+mod graph;
+use graph::VertexGraph;
+mod graphml;
+mod health;
+mod identifiers;
+mod inventory;
+mod junit;
+pub use junit::{health_check, HealthReport};
+pub use inventory::DEFAULT_INVENTORY_PROPERTIES;
+mod key_case;
+mod layout_hash;
+pub use key_case::KeyCase;
+mod legend;
+mod lenient;
+pub use lenient::ParseWarning;
+mod live_reload;
+mod locale;
+pub use locale::Locale;
+mod merge;
+mod mermaid;
+mod metrics;
+mod monochrome;
+pub use metrics::LayoutMetrics;
+mod notify;
+pub use notify::Notifier;
+mod nvlist;
+mod odg;
+mod xml;
+pub use export::{
+    check_format_version, ExportDocument, ExportHost, ExportLink, ExportNode, FORMAT_VERSION,
+};
+
+mod connections;
+mod cycles;
+pub use connections::{CablingTable, Connection, ConnectionEndpoint};
+
+mod config_file;
+pub use config_file::{
+    Baseline, Branding, ConfigFile, LayoutSettings, Preset, Profile, ThemeSettings,
+};
+
+mod options;
+mod packed_nvlist;
+mod paths;
+pub use paths::PathReport;
+mod pdf;
+mod pipeline;
+pub use pipeline::{Condition, Pipeline, Selector, Stage};
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(feature = "s3")]
+pub use s3::{S3Credentials, S3Sink};
+mod properties;
+mod prop_value;
+pub use prop_value::PropValue;
+mod reader;
+pub use reader::TopoXmlReader;
+mod scheme;
+pub use scheme::Scheme;
+mod schema_version;
+pub use schema_version::{SchemaVersion, LATEST_SCHEMA_VERSION};
+mod raster;
+mod reachability;
+mod redundancy;
+pub use redundancy::{RedundancyReport, TargetRedundancy};
+mod report;
+mod sidecar;
+mod sink;
+mod split;
+pub use sink::{DirectorySink, MemorySink, OutputSink, ZipSink};
+use sink::Artifacts;
+mod standalone;
+mod text;
+mod tikz;
+mod zones;
+mod transition;
+mod validate;
+pub use validate::SchemaViolation;
+mod vertex_pages;
+mod wide_ports;
+pub use wide_ports::WidePort;
+pub use properties::PropCategory;
+use properties::{categorized_properties, encode_categories, encode_groups};
+pub use options::{
+    EmptyPropGroups, Filter, FmriDisplay, InputFormat, Layout, OutputFormat, Overlay,
+    RenderOptions, RenderOptionsBuilder, Theme,
+};
 
 //
 // Constants for topo node names in SAS scheme topology
@@ -53,7 +192,7 @@ impl fmt::Display for SimpleError {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct SasGeometry {
     x: u32,
     y: u32,
@@ -72,33 +211,134 @@ impl SasGeometry {
     }
 }
 
-#[derive(Debug)]
-struct SasDigraphProperty {
+//
+// A property of a vertex, flattened out of its property group.
+//
+#[derive(Clone, Debug)]
+pub struct SasDigraphProperty {
     name: String,
     value: String,
+    // nvpair type of the value (e.g. "uint32-array"), if known
+    proptype: Option<String>,
+    // property group the property came from, if known
+    group: Option<String>,
+    // the value as structured JSON, if it is a nested nvlist (in which case
+    // value holds its compact serialization)
+    structured: Option<serde_json::Value>,
+    // the value as the type of its nvpair, if it was read from one and is to
+    // be taken as something other than a string (see prop_value.rs)
+    typed: Option<PropValue>,
 }
 
 impl SasDigraphProperty {
     fn new(name: String, value: String) -> SasDigraphProperty {
-        SasDigraphProperty { name, value }
+        SasDigraphProperty {
+            name,
+            value,
+            proptype: None,
+            group: None,
+            structured: None,
+            typed: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // the value as displayed, with array values joined by commas
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    // nvpair type of the value (e.g. "uint32-array"), if known
+    pub fn proptype(&self) -> Option<&str> {
+        self.proptype.as_deref()
+    }
+
+    // property group the property came from, if known
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    //
+    // The value as the type of the nvpair it was read from, for numeric
+    // comparisons and the like.
+    //
+    pub fn typed_value(&self) -> PropValue {
+        self.typed.clone().unwrap_or_else(|| PropValue::String(self.value.clone()))
     }
 }
 
-#[derive(Debug)]
-struct SasDigraphVertex {
+//
+// Topo node instance.  Instances are normally hex numbers (e.g. "0x1f"), but
+// some snapshots contain instances that don't fit into a u64 or that aren't
+// numeric at all.  The instance is stored as it appears in the snapshot, so it
+// can be displayed faithfully, along with its numeric value, if it has one.
+//
+#[derive(Clone, Debug, PartialEq)]
+struct SasInstance {
+    raw: String,
+    value: Option<u64>,
+}
+
+impl SasInstance {
+    fn parse(raw: &str) -> SasInstance {
+        let trimmed = raw.trim();
+        let value = match trimmed
+            .strip_prefix("0x")
+            .or_else(|| trimmed.strip_prefix("0X"))
+        {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => trimmed.parse::<u64>().ok(),
+        };
+        if value.is_none() {
+            debug!("non-numeric or oversized instance: {}", raw);
+        }
+        SasInstance {
+            raw: raw.to_string(),
+            value,
+        }
+    }
+
+    fn from_value(value: u64) -> SasInstance {
+        SasInstance {
+            raw: format!("0x{:x}", value),
+            value: Some(value),
+        }
+    }
+}
+
+impl fmt::Display for SasInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+//
+// A vertex of the topology, i.e. a topo node.  Vertices are handed to library
+// consumers by TopoXmlReader, so they can be inspected through accessors.
+//
+#[derive(Clone, Debug)]
+pub struct SasDigraphVertex {
     fmri: String,
     name: String,
-    instance: u64,
+    instance: SasInstance,
     properties: Vec<SasDigraphProperty>,
     geometry: SasGeometry,
     outgoing_edges: Option<Vec<String>>,
+    // reference number (e.g. T047), if device numbering was requested
+    reference: Option<String>,
+    // the property groups as they appear in the snapshot, pretty-printed, if
+    // requested (see nvlist.rs)
+    raw_nvlist: Option<String>,
 }
 
 impl SasDigraphVertex {
     fn new(
         fmri: String,
         name: String,
-        instance: u64,
+        instance: SasInstance,
         outgoing_edges: Option<Vec<String>>,
     ) -> SasDigraphVertex {
         let properties = Vec::new();
@@ -110,12 +350,79 @@ impl SasDigraphVertex {
             properties,
             geometry,
             outgoing_edges,
+            reference: None,
+            raw_nvlist: None,
         }
     }
+
+    pub fn fmri(&self) -> &str {
+        &self.fmri
+    }
+
+    // the node name, e.g. INITIATOR or TARGET
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // the instance as it appears in the snapshot
+    pub fn instance(&self) -> &str {
+        &self.instance.raw
+    }
+
+    // FMRIs of the vertices this one has an edge to
+    pub fn outgoing_edges(&self) -> &[String] {
+        self.outgoing_edges.as_deref().unwrap_or(&[])
+    }
+
+    //
+    // The properties as (name, value) pairs, with array values joined by
+    // commas.
+    //
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.properties.iter().map(|p| (p.name.as_str(), p.value.as_str()))
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties().find(|(n, _)| *n == name).map(|(_, value)| value)
+    }
+
+    //
+    // The properties along with their type and property group, in the order
+    // they appear in the snapshot.
+    //
+    pub fn property_entries(&self) -> &[SasDigraphProperty] {
+        &self.properties
+    }
+
+    // reference number (e.g. T047), if device numbering was requested
+    pub fn reference(&self) -> Option<&str> {
+        self.reference.as_deref()
+    }
 }
 
-#[derive(Debug)]
-struct SasDigraph {
+//
+// Returns a stable identifier for the vertex with the specified FMRI.  The id
+// is a 64-bit FNV-1a hash of the FMRI, so it is the same across all of the
+// outputs generated by a run (SVG, JSON, CSV, ...) as well as across runs,
+// which allows rows in a spreadsheet to be traced back to nodes in the
+// diagram.  It is also safe to use as an XML/HTML id or a file name.
+//
+pub fn stable_id(fmri: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in fmri.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("v{:016x}", hash)
+}
+
+//
+// A topology snapshot: the host it was taken on and its vertices, keyed by
+// FMRI.  It is what read_topology() returns and render() draws, and can be
+// inspected in between through its accessors.
+//
+#[derive(Clone, Debug)]
+pub struct SasDigraph {
     // server product ID
     product_id: String,
     // machine nodename
@@ -124,10 +431,14 @@ struct SasDigraph {
     os_version: String,
     // time of snapshot in ISO-8601 format
     timestamp: String,
-    // hashmap of vertices, hashed by FMRI
-    vertices: HashMap<String, SasDigraphVertex>,
-    // array of initiator FMRIs
+    // the vertices and the edges between them, see graph.rs
+    vertices: VertexGraph,
+    // array of root FMRIs, i.e. initiators (or chassis on hc-scheme)
     initiators: Vec<String>,
+    // FMRI scheme of the vertices
+    scheme: Scheme,
+    // vertices skipped in lenient mode, see lenient.rs
+    parse_warnings: Vec<ParseWarning>,
 }
 
 impl SasDigraph {
@@ -137,7 +448,7 @@ impl SasDigraph {
         os_version: String,
         timestamp: String,
     ) -> SasDigraph {
-        let vertices = HashMap::new();
+        let vertices = VertexGraph::new();
         let initiators = Vec::new();
 
         SasDigraph {
@@ -147,25 +458,158 @@ impl SasDigraph {
             timestamp,
             vertices,
             initiators,
+            scheme: Scheme::Sas,
+            parse_warnings: Vec::new(),
         }
     }
+
+    // see builder.rs
+    pub fn builder() -> SasDigraphBuilder {
+        SasDigraphBuilder::new()
+    }
+
+    // server product ID
+    pub fn product_id(&self) -> &str {
+        &self.product_id
+    }
+
+    pub fn nodename(&self) -> &str {
+        &self.nodename
+    }
+
+    pub fn os_version(&self) -> &str {
+        &self.os_version
+    }
+
+    // time of the snapshot in ISO-8601 format
+    pub fn timestamp(&self) -> &str {
+        &self.timestamp
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        self.scheme
+    }
+
+    //
+    // The vertices, in no particular order.  Use get() to follow the edges
+    // from one vertex to the next.
+    //
+    pub fn vertices(&self) -> impl Iterator<Item = &SasDigraphVertex> {
+        self.vertices.values()
+    }
+
+    // FMRIs of the root vertices, i.e. initiators (or chassis on hc-scheme)
+    pub fn initiators(&self) -> &[String] {
+        &self.initiators
+    }
+
+    pub fn get(&self, fmri: &str) -> Option<&SasDigraphVertex> {
+        self.vertices.get(fmri)
+    }
+
+    //
+    // The topology as a petgraph, whose node weights are the vertices, for
+    // use with its algorithms.  The nodes are found with node_index().
+    //
+    pub fn graph(&self) -> &StableDiGraph<SasDigraphVertex, usize> {
+        self.vertices.graph()
+    }
+
+    pub fn node_index(&self, fmri: &str) -> Option<NodeIndex> {
+        self.vertices.node_index(fmri)
+    }
+
+    //
+    // The distinct paths from one vertex to another, e.g. from an initiator
+    // to a target through the expanders, as the vertices along them.  See
+    // paths.rs.  There can be exponentially many of them in a topology with
+    // many redundant links, which paths_limited() guards against.
+    //
+    pub fn paths(&self, from_fmri: &str, to_fmri: &str) -> Vec<Vec<&SasDigraphVertex>> {
+        paths::all_paths(self, from_fmri, to_fmri, usize::MAX)
+    }
+
+    //
+    // Like paths(), but at most limit of the paths are returned.
+    //
+    pub fn paths_limited(
+        &self,
+        from_fmri: &str,
+        to_fmri: &str,
+        limit: usize,
+    ) -> Vec<Vec<&SasDigraphVertex>> {
+        paths::all_paths(self, from_fmri, to_fmri, limit)
+    }
+
+    //
+    // What changed from this snapshot to the other, newer one, property
+    // values included, see diff.rs.
+    //
+    pub fn diff(&self, other: &SasDigraph) -> TopoDiff {
+        TopoDiff::with_properties(self, other)
+    }
+
+    // the vertices skipped in lenient mode, see lenient.rs
+    pub fn parse_warnings(&self) -> &[ParseWarning] {
+        &self.parse_warnings
+    }
 }
 
 #[derive(Debug)]
 pub struct Config {
     pub outdir: String,
     pub xml_path: String,
+    pub options: RenderOptions,
 }
 
 impl Config {
     pub fn new(outdir: String, xml_path: String) -> Config {
+        Config::with_options(outdir, xml_path, RenderOptions::default())
+    }
+
+    pub fn with_options(outdir: String, xml_path: String, options: RenderOptions) -> Config {
         Config {
             outdir,
             xml_path,
+            options,
         }
     }
 }
 
+//
+// Escape a string for inclusion in HTML text or attribute values.
+//
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+//
+// Read in an image file and return it encoded as a data URI, so that it can be
+// embedded directly into a generated document.
+//
+fn data_uri(path: &str) -> Result<String, Box<dyn Error>> {
+    let ext = path.rsplit('.').next().unwrap_or("").to_lowercase();
+    let mime = match ext.as_ref() {
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        _ => {
+            return Err(Box::new(SimpleError(format!(
+                "unsupported image type: {}",
+                path
+            ))))
+        }
+    };
+    let contents = fs::read(path)?;
+    Ok(format!("data:{};base64,{}", mime, base64::encode(&contents)))
+}
+
 //
 // Parse an NvlistXmlArrayElement representing a topo property, extract the
 // prop name and value (as a string) and return a SasDigraphProperty.
@@ -173,15 +617,32 @@ impl Config {
 fn parse_prop(nvl: &NvlistXmlArrayElement) -> Result<SasDigraphProperty, Box<dyn Error>> {
     let mut propname: Option<String> = None;
     let mut propval: Option<String> = None;
-
-    if nvl.nvpairs.is_some() {
-        for nvpair in nvl.nvpairs.as_ref().unwrap() {
-            match nvpair.name.as_ref().unwrap().as_ref() {
-                PROP_NAME => {
-                    propname = Some(nvpair.value.as_ref().unwrap().clone());
+    let mut proptype: Option<String> = None;
+    let mut structured: Option<serde_json::Value> = None;
+    let mut typed: Option<PropValue> = None;
+
+    if let Some(nvpairs) = &nvl.nvpairs {
+        for nvpair in nvpairs {
+            match nvpair.name.as_deref() {
+                Some(PROP_NAME) => {
+                    propname = nvpair.value.clone();
+                }
+                Some(PROP_TYPE) => {
+                    proptype = nvpair.value.clone();
                 }
-                PROP_VALUE => {
-                    if nvpair.nvpair_elements.is_some() {
+                Some(PROP_VALUE) => {
+                    if nvpair.nvlist_elements.is_some() {
+                        //
+                        // The value is a nested nvlist (or an array of them),
+                        // which is kept as structured JSON.  Its compact
+                        // serialization serves as the flat value.
+                        //
+                        let json = nvlist::nvpair_to_json(nvpair);
+                        propval = Some(json.to_string());
+                        typed = Some(PropValue::Nvlist(json.clone()));
+                        structured = Some(json);
+                        proptype = proptype.or_else(|| Some(nvpair.nvpair_type.clone()));
+                    } else if nvpair.nvpair_elements.is_some() {
                         //
                         // If nvpair_elements is something then this is an array
                         // type in which case we iterate through the child nvpairs
@@ -190,11 +651,15 @@ fn parse_prop(nvl: &NvlistXmlArrayElement) -> Result<SasDigraphProperty, Box<dyn
                         //
                         let mut valarr = Vec::new();
                         for elem in nvpair.nvpair_elements.as_ref().unwrap() {
-                            valarr.push(elem.value.as_ref().unwrap().clone());
+                            valarr.push(elem.value.clone().unwrap_or_default());
                         }
                         propval = Some(valarr.join(","));
+                        typed = PropValue::parse(&nvpair.nvpair_type, &valarr);
                     } else {
-                        propval = Some(nvpair.value.as_ref().unwrap().clone());
+                        propval = nvpair.value.clone();
+                        typed = propval.as_ref().and_then(|v| {
+                            PropValue::parse(&nvpair.nvpair_type, std::slice::from_ref(v))
+                        });
                     }
                 }
                 _ => {}
@@ -203,7 +668,12 @@ fn parse_prop(nvl: &NvlistXmlArrayElement) -> Result<SasDigraphProperty, Box<dyn
     }
 
     if let (Some(name), Some(val)) = (propname, propval) {
-        Ok(SasDigraphProperty::new(name, val))
+        let mut prop = SasDigraphProperty::new(name, val);
+        prop.proptype = proptype;
+        prop.structured = structured;
+        // strings are kept as such by typed_value()
+        prop.typed = typed.filter(|typed| typed.as_str().is_none());
+        Ok(prop)
     } else {
         Err(Box::new(SimpleError(format!(
             "malformed property value nvlist: {:?}",
@@ -212,65 +682,241 @@ fn parse_prop(nvl: &NvlistXmlArrayElement) -> Result<SasDigraphProperty, Box<dyn
     }
 }
 
+// vertex FMRIs, hashed by their depth (column) in the layout
+type ColumnHash = HashMap<u32, Vec<String>>;
+
+//
+// The deepest a layout may go, unless configured otherwise.  Real fabrics are
+// a few tiers of expanders deep at most, so anything beyond this is almost
+// certainly a malformed or malicious snapshot.
+//
+pub const DEFAULT_MAX_DEPTH: u32 = 64;
+
+//
+// Place the vertices reachable from the root node in the columns of the
+// layout, in depth-first order, and return the maximum depth reached.  The
+// traversal uses an explicit stack rather than recursion so that a
+// pathologically deep graph can't overflow the call stack; graphs deeper than
+// depth_limit are rejected.
+//
+// A vertex that is reachable over more than one path (e.g. an expander that is
+// cabled to two initiators) is only placed in the column where it was first
+// reached, so that each vertex appears exactly once in the diagram.
+//
 fn visit_vertex(
-    vertices: &HashMap<String, SasDigraphVertex>,
-    vtx: &SasDigraphVertex,
-    column_hash: &mut HashMap<u32, Vec<String>>,
-    depth: u32,
+    vertices: &VertexGraph,
+    root: NodeIndex,
+    column_hash: &mut ColumnHash,
+    placed: &mut HashSet<NodeIndex>,
+    depth_limit: u32,
 ) -> Result<u32, Box<dyn Error>> {
-    let mut max_depth = depth + 1;
+    let mut deepest = 0;
+    let mut stack: Vec<(NodeIndex, u32)> = vec![(root, 1)];
 
-    column_hash
-        .entry(max_depth)
-        .or_insert_with(Vec::new)
-        .push(vtx.fmri.clone());
+    while let Some((node, depth)) = stack.pop() {
+        if !placed.insert(node) {
+            continue;
+        }
+        let vtx = &vertices.graph()[node];
+        if depth > depth_limit {
+            return Err(Box::new(SimpleError(format!(
+                "topology is deeper than the maximum of {} vertices at {}",
+                depth_limit, vtx.fmri
+            ))));
+        }
+        deepest = cmp::max(deepest, depth);
 
-    if vtx.outgoing_edges.is_some() {
-        for edge in vtx.outgoing_edges.as_ref().unwrap() {
-            let next_vtx = match vertices.get(&edge.to_string()) {
-                Some(entry) => entry,
-                None => {
-                    return Err(Box::new(SimpleError("failed to lookup vertex".to_string())));
-                }
-            };
-            let rc = visit_vertex(vertices, next_vtx, column_hash, depth + 1)?;
-            if rc > max_depth {
-                max_depth = rc;
-            }
+        column_hash
+            .entry(depth)
+            .or_default()
+            .push(vtx.fmri.clone());
+
+        //
+        // The successors are pushed in reverse, so that they are visited in
+        // the same order as the edges are listed.
+        //
+        for next in vertices.successors(node).into_iter().rev() {
+            stack.push((next, depth + 1));
         }
     }
-    Ok(max_depth)
+    Ok(deepest)
+}
+
+//
+// Returns the FMRIs of the vertices that the layout is rooted at.  Normally
+// these are the initiators.  A snapshot may not contain any initiators though
+// (e.g. if the HBAs failed to enumerate), in which case we fall back to the
+// vertices that have no incoming edges, so that the unattached components of
+// the graph are still rendered.
+//
+fn layout_roots(digraph: &SasDigraph) -> Vec<String> {
+    if !digraph.initiators.is_empty() {
+        return digraph.initiators.clone();
+    }
+
+    let graph = digraph.graph();
+    let mut roots: Vec<String> = graph
+        .externals(Direction::Incoming)
+        .map(|node| graph[node].fmri.clone())
+        .collect();
+    roots.sort();
+    roots
 }
 
 //
-// Generates an SVG representation of the directed graph and save it to a file.
+// Walk all of the paths through the digraph starting from the specified root
+// vertices and return a hash map of vertex FMRIs, hashed by their depth, along
+// with the maximum depth of the graph.
 //
-fn build_svg(config: &Config, digraph: &mut SasDigraph) -> Result<(), Box<dyn Error>> {
+fn build_columns(
+    digraph: &SasDigraph,
+    roots: &[String],
+    depth_limit: u32,
+) -> Result<(ColumnHash, u32), Box<dyn Error>> {
+    // a loop can't be laid out, see cycles.rs
+    cycles::check_acyclic(digraph)?;
+    if let Some((from, to)) = digraph.vertices.dangling_edge() {
+        return Err(Box::new(SimpleError(format!(
+            "failed to lookup vertex {}, which {} has an edge to",
+            to, from
+        ))));
+    }
+
     let mut max_depth: u32 = 0;
-    let mut max_height: usize = 0;
-    let mut column_hash: HashMap<u32, Vec<String>> = HashMap::new();
-    let depth: u32 = 0;
+    let mut column_hash: ColumnHash = HashMap::new();
+    let mut placed: HashSet<NodeIndex> = HashSet::new();
+
+    for fmri in roots {
+        debug!("root: {}", fmri);
+        let node = match digraph.node_index(fmri) {
+            Some(entry) => entry,
+            None => {
+                return Err(Box::new(SimpleError("failed to lookup vertex".to_string())));
+            }
+        };
+
+        let rc = visit_vertex(&digraph.vertices, node, &mut column_hash, &mut placed, depth_limit)?;
+        if rc > max_depth {
+            max_depth = rc;
+        }
+    }
+
+    // The members of each enclosure are placed together, and so are those
+    // of each zone on a zoned fabric
+    let enclosures = enclosures::vertex_enclosures(digraph);
+    if !enclosures.is_empty() {
+        enclosures::group_columns(&mut column_hash, &enclosures);
+    }
+    let zones = zones::vertex_zones(digraph);
+    if !zones.is_empty() {
+        zones::group_columns(&mut column_hash, &zones);
+    }
+    Ok((column_hash, max_depth))
+}
+
+//
+// Assign sequential reference numbers (I1, E1, T001, ...) to the initiator,
+// expander and target vertices.  The numbers follow the order in which the
+// vertices appear in the rendered diagram (left to right, top to bottom), so
+// that people looking at the same picture can refer to a device by its number
+// rather than by reading out WWNs.  Ports are not numbered.
+//
+fn assign_references(digraph: &mut SasDigraph, depth_limit: u32) -> Result<(), Box<dyn Error>> {
+    let roots = layout_roots(digraph);
+    let (column_hash, max_depth) = build_columns(digraph, &roots, depth_limit)?;
+    let mut counters: HashMap<&str, u32> = HashMap::new();
+
+    for depth in 1..=max_depth {
+        let fmris = match column_hash.get(&depth) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        for fmri in fmris {
+            let vtx = digraph.vertices.get_mut(fmri).unwrap();
+            if vtx.reference.is_some() {
+                continue;
+            }
+            let (prefix, width) = match vtx.name.as_ref() {
+                INITIATOR => ("I", 1),
+                EXPANDER => ("E", 1),
+                TARGET => ("T", 3),
+                _ => continue,
+            };
+            let count = counters.entry(prefix).or_insert(0);
+            *count += 1;
+            vtx.reference = Some(format!("{}{:0width$}", prefix, count, width = width));
+        }
+    }
+    Ok(())
+}
+
+//
+// The grid that the vertices are placed on, as computed by plan_layout().
+//
+struct LayoutPlan {
+    column_hash: ColumnHash,
+    max_depth: u32,
+    max_height: usize,
+    // explanation shown above the graph if the layout is degenerate
+    notice: Option<String>,
+}
 
+impl LayoutPlan {
     //
-    // First we create a hidden element that we can attach the host information
-    // properties to.  The JS code will reference those to populate the Host
-    // Information table,
+    // Returns the coordinates of the vertex at the specified (1-based) depth
+    // and height in a column containing column_len vertices.
     //
-    let hostinfo = Rectangle::new()
-        .set("x", 1)
-        .set("y", 1)
-        .set("width", 1)
-        .set("height", 1)
-        .set("visibility", "hidden")
-        .set("id", "hostprops")
-        .set("product-id", digraph.product_id.clone())
-        .set("nodename", digraph.nodename.clone())
-        .set("os-version", digraph.os_version.clone())
-        .set("timestamp", digraph.timestamp.clone());
+    fn position(
+        &self,
+        layout: &Layout,
+        depth: u32,
+        height: u32,
+        column_len: usize,
+        y_offset: u32,
+    ) -> (u32, u32) {
+        let x = ((depth - 1) * layout.column_width) + layout.x_margin;
+
+        let y_factor: u32 = match height {
+            1 => 1,
+            _ => (self.max_height / column_len).try_into().unwrap(),
+        };
+        let y = ((height - 1) * layout.row_height * y_factor) + layout.y_margin + y_offset;
+        (x, y)
+    }
+
+    //
+    // Returns the coordinates of all of the vertices in the layout, hashed by
+    // FMRI.
+    //
+    fn positions(&self, layout: &Layout, y_offset: u32) -> HashMap<String, (u32, u32)> {
+        let mut positions = HashMap::new();
+        for (depth, fmris) in &self.column_hash {
+            for (index, fmri) in fmris.iter().enumerate() {
+                let height: u32 = (index + 1).try_into().unwrap();
+                positions.insert(
+                    fmri.clone(),
+                    self.position(layout, *depth, height, fmris.len(), y_offset),
+                );
+            }
+        }
+        positions
+    }
+
+    //
+    // Size of the viewport the SVG is displayed in on the HTML page.
+    //
+    fn canvas_size(&self, layout: &Layout) -> (u32, u32) {
+        (
+            cmp::max(1200, self.max_depth * layout.column_width),
+            cmp::max(1100, self.max_height as u32 * layout.row_height),
+        )
+    }
+}
 
+fn plan_layout(digraph: &SasDigraph, depth_limit: u32) -> Result<LayoutPlan, Box<dyn Error>> {
     //
     // Next we iterate over all of the paths through the digraph starting from
-    // the initiator vertices.  There are two purposes here:
+    // the root (normally initiator) vertices.  There are two purposes here:
     //
     // The first is to calculate the maximum depth (width) of the graph.
     // The second is to create a hash map of vertex FMRIs, hashed by their
@@ -283,20 +929,24 @@ fn build_svg(config: &Config, digraph: &mut SasDigraph) -> Result<(), Box<dyn Er
     // grid and use that to determine the size and placement of the various SVG
     // elements.
     //
-    for fmri in &digraph.initiators {
-        debug!("initiator: {}", fmri);
-        let vtx = match digraph.vertices.get(&fmri.to_string()) {
-            Some(entry) => entry,
-            None => {
-                return Err(Box::new(SimpleError("failed to lookup vertex".to_string())));
-            }
-        };
-
-        let rc = visit_vertex(&digraph.vertices, vtx, &mut column_hash, depth)?;
-        if rc > max_depth {
-            max_depth = rc;
-        }
+    let roots = layout_roots(digraph);
+    let notice = if digraph.vertices.is_empty() {
+        Some("This snapshot does not contain any vertices.".to_string())
+    } else if digraph.initiators.is_empty() {
+        Some(format!(
+            "No initiators were found in this snapshot. Showing {} unattached \
+             component(s).",
+            roots.len()
+        ))
+    } else {
+        None
+    };
+    if let Some(msg) = &notice {
+        warn!("{}", msg);
     }
+    let mut max_height: usize = 0;
+
+    let (column_hash, max_depth) = build_columns(digraph, &roots, depth_limit)?;
 
     for i in 1..=max_depth {
         let height = match column_hash.get(&i) {
@@ -311,92 +961,625 @@ fn build_svg(config: &Config, digraph: &mut SasDigraph) -> Result<(), Box<dyn Er
     debug!("max_depth: {}", max_depth);
     debug!("max_height: {}", max_height);
 
-    let mut script = String::new();
-    script.push_str("<![CDATA[");
-    let js_code = include_str!("sastopo2svg.js");
-    script.push_str(js_code);
-    script.push_str("]]>");
+    Ok(LayoutPlan {
+        column_hash,
+        max_depth,
+        max_height,
+        notice,
+    })
+}
 
-    let on_click = Script::new(script).set("type", "application/ecmascript");
+// name of the animated transition SVG, see transition.rs
+const TRANSITION_SVG: &str = "sastopo-transition.svg";
 
-    let filter_matrix = svg::node::Text::new(" <feColorMatrix type=\"matrix\" values=\"1 0 0 1.9 -2.2 0 1 0 0.0 0.3 0 0 1 0 0.5 0 0 0 1 0.2\" />");
-    let filter = Filter::new()
-        .set("id", "linear")
-        .add(filter_matrix);
+// name of the copy of the snapshot, see RenderOptions::keep_snapshot()
+const SNAPSHOT_XML: &str = "sastopo.xml";
 
-    let mut document = Document::new()
-        .set("overflow", "scroll")
-        .set("viewbox", (0, 0, (100 * max_depth), (250 * max_height)))
-        .add(on_click)
-        .add(filter)
-        .add(hostinfo);
+//
+// Returns the path of the icon representing a vertex of the specified type.
+// Any other than the SAS scheme node types is taken to be an hc-scheme node.
+//
+fn vertex_icon(name: &str) -> Result<&'static str, Box<dyn Error>> {
+    match name {
+        INITIATOR => Ok("assets/icons/initiator.png"),
+        PORT => Ok("assets/icons/port.png"),
+        EXPANDER => Ok("assets/icons/expander.png"),
+        TARGET => Ok("assets/icons/target.png"),
+        _ => Ok(scheme::hc_icon(name)),
+    }
+}
 
-    let vtx_width = 120;
-    let vtx_height = 120;
+//
+// The layers the SVG elements are organized into, along with the label of the
+// corresponding toggle control on the HTML page.
+//
+const LAYERS: &[(&str, &str)] = &[
+    ("layer-vertices", "Devices"),
+    ("layer-ports", "Ports"),
+    ("layer-edges", "Edges"),
+    ("layer-labels", "Labels"),
+    ("layer-overlays", "Overlays"),
+    ("layer-reachability", "Reachability"),
+    ("layer-ghosts", "Missing devices"),
+    ("layer-zones", "Zones"),
+    ("layer-enclosures", "Enclosures"),
+    ("layer-columns", "Column bands"),
+];
 
-    //
-    // Generate the SVG elements for all the vertices.
-    //
-    for depth in 1..=max_depth {
-        let vertices = column_hash.get(&depth).unwrap();
-        for index in 0..vertices.len() {
-            let height: u32 = (index + 1).try_into().unwrap();
+//
+// Generate the HTML controls for hiding and showing the layers of the SVG.
+// Controls are only generated for the layers that contain any elements.
+//
+fn layer_toggles(present: &HashSet<&str>) -> String {
+    let mut html = String::from("<div id=\"layer-toggles\">\n");
+    for (id, label) in LAYERS {
+        if !present.contains(id) {
+            continue;
+        }
+        html.push_str(&format!(
+            "<label><input type=\"checkbox\" checked onchange=\"toggleLayer('{}', \
+             this.checked)\"> {}</label>\n",
+            id, label
+        ));
+    }
+    html.push_str("</div>\n");
+    html
+}
+
+//
+// Returns the directory the assets are installed in, next to the executable.
+//
+fn asset_root() -> Result<String, Box<dyn Error>> {
+    let src_dir_path = std::env::current_exe()?;
+    let src_dir = match src_dir_path.parent() {
+        Some (path) => path.to_str().unwrap(),
+        None => "/"
+    };
+    Ok(src_dir.to_string())
+}
+
+//
+// Copy the icons and chassis images the SVG and HTML page refer to into the
+// assets directory of the output.
+//
+fn write_assets(out: &mut Artifacts) -> Result<(), Box<dyn Error>> {
+    let asset_src_dir = format!("{}/assets", asset_root()?);
+    debug!("Copying image assets from {}", asset_src_dir);
+    let mut assets = Vec::new();
+    archive::list_files(Path::new(&asset_src_dir), "", &mut assets)?;
+    for asset in assets {
+        let contents = fs::read(format!("{}/{}", asset_src_dir, asset))?;
+        out.write(&format!("{}{}", archive::ASSETS_DIR, asset), &contents)?;
+    }
+    Ok(())
+}
+
+fn page_title(config: &Config, digraph: &SasDigraph) -> String {
+    match config.options.title() {
+        Some(title) => title.to_string(),
+        None => format!(
+            "SAS Topology: {} ({})",
+            digraph.nodename, digraph.timestamp
+        ),
+    }
+}
+
+//
+// Whether digraph is rendered on one of the per-initiator pages.  A snapshot
+// without any initiators can't be split, so it is rendered on a single page
+// regardless.
+//
+fn split_pages(config: &Config, digraph: &SasDigraph) -> bool {
+    config.options.split_initiators() && !digraph.initiators.is_empty()
+}
+
+//
+// Read in an older snapshot to compare digraph against.  If the initiators are
+// rendered on pages of their own, it is cut down to the initiators of digraph.
+//
+fn read_comparison(
+    config: &Config,
+    digraph: &SasDigraph,
+    path: &str,
+) -> Result<SasDigraph, Box<dyn Error>> {
+    let snapshot = read_digraph(path, &config.options)?;
+    match split_pages(config, digraph) {
+        true => Ok(split::initiator_subtree(&snapshot, &digraph.initiators)),
+        false => Ok(snapshot),
+    }
+}
+
+//
+// Render the SVG to svg_file along with the HTML page that displays it to
+// html_file, and any outputs that are derived from the SVG.  The properties
+// that go into the sidecar file are added to prop_store.  Returns the size of
+// the canvas.
+//
+fn build_svg(
+    config: &Config,
+    digraph: &mut SasDigraph,
+    (svg_file, html_file): (&str, &str),
+    prop_store: &mut sidecar::PropertyStore,
+    out: &mut Artifacts,
+) -> Result<(u32, u32), Box<dyn Error>> {
+    //
+    // Dual-ported drives are drawn as one drive with both of its ports
+    // attached to it, see dual_port.rs.
+    //
+    let mut merged;
+    let digraph = match dual_port::merged(digraph) {
+        Some(digraph) => {
+            merged = digraph;
+            &mut merged
+        }
+        None => digraph,
+    };
+
+    //
+    // Likewise, ports reported one PHY at a time are drawn as the wide port
+    // they make up, see wide_ports.rs.
+    //
+    let mut wide;
+    let digraph = match wide_ports::merged(digraph) {
+        Some(digraph) => {
+            wide = digraph;
+            &mut wide
+        }
+        None => digraph,
+    };
+
+    //
+    // First we create a hidden element that we can attach the host information
+    // properties to.  The JS code will reference those to populate the Host
+    // Information table,
+    //
+    let hostinfo = Rectangle::new()
+        .set("x", 1)
+        .set("y", 1)
+        .set("width", 1)
+        .set("height", 1)
+        .set("visibility", "hidden")
+        .set("id", "hostprops")
+        .set("product-id", digraph.product_id.clone())
+        .set("nodename", digraph.nodename.clone())
+        .set("os-version", digraph.os_version.clone())
+        .set("timestamp", digraph.timestamp.clone())
+        .set("display-timestamp", config.options.locale().timestamp(&digraph.timestamp));
+
+    let plan = plan_layout(digraph, config.options.max_depth())?;
+
+    let mut script = String::new();
+    script.push_str("<![CDATA[");
+    let js_code = include_str!("sastopo2svg.js");
+    script.push_str(js_code);
+    script.push_str("]]>");
+
+    let on_click = Script::new(script).set("type", "application/ecmascript");
+
+    let filter_matrix = svg::node::Text::new(" <feColorMatrix type=\"matrix\" values=\"1 0 0 1.9 -2.2 0 1 0 0.0 0.3 0 0 1 0 0.5 0 0 0 1 0.2\" />");
+    let filter = SvgFilter::new()
+        .set("id", "linear")
+        .add(filter_matrix);
+
+    let mut document = Document::new()
+        .set("overflow", "scroll")
+        .set(
+            "viewbox",
+            (0, 0, (100 * cmp::max(1, plan.max_depth)), (250 * cmp::max(1, plan.max_height))),
+        )
+        .add(on_click)
+        .add(filter)
+        .add(hostinfo);
+    if sidecar::needed(&config.options) {
+        document = document.add(
+            Script::new("")
+                .set("href", sidecar::SIDECAR_FILE)
+                .set("type", "application/ecmascript"),
+        );
+    }
+
+    let theme = config.options.theme();
+    if theme.monochrome {
+        document = document.add(monochrome::patterns());
+    }
+    let layout = config.options.layout();
+    let vtx_width = layout.vertex_width;
+    let vtx_height = layout.vertex_height;
+
+    //
+    // If the graph is degenerate, explain why at the top of the document and
+    // shift the grid down to make room for the notice.
+    //
+    let mut y_offset = 0;
+    if let Some(msg) = &plan.notice {
+        let text = Text::new()
+            .set("id", "notice")
+            .set("x", layout.x_margin)
+            .set("y", layout.y_margin + 20)
+            .set("font-family", "Arial, Helvetica, sans-serif")
+            .set("font-size", 18)
+            .set("fill", "#B00000")
+            .add(svg::node::Text::new(msg.clone()));
+        document = document.add(text);
+        y_offset = 40;
+    }
+
+    //
+    // The elements are organized into layers, which the viewer can hide and
+    // show individually.
+    //
+    let mut vertex_layer = Group::new().set("id", "layer-vertices");
+    let mut port_layer = Group::new().set("id", "layer-ports");
+    let mut edge_layer = Group::new().set("id", "layer-edges");
+    let mut label_layer = Group::new().set("id", "layer-labels");
+    let mut overlay_layer = Group::new().set("id", "layer-overlays");
+    let mut reachability_layer = Group::new().set("id", "layer-reachability");
+    let mut present: HashSet<&str> = HashSet::new();
+
+    let reachability = match config.options.reachability() {
+        true => Some(reachability::Reachability::new(digraph)),
+        false => None,
+    };
+    let redundancy = match config.options.mark_single_path() {
+        true => Some(redundancy::analyze(digraph)),
+        false => None,
+    };
+
+    let mut legend = legend::Legend::default();
+    let mut filter_controls = filter_controls::FilterControls::default();
+    let enclosures = enclosures::vertex_enclosures(digraph);
+    let max_props = config.options.max_properties();
+    let lazy_props = config.options.lazy_properties();
+    if (lazy_props || config.options.raw_nvlists()) && config.options.standalone() {
+        return Err(Box::new(SimpleError(
+            "lazily loaded properties and raw nvlists require the sidecar file, so \
+             they can't be combined with standalone HTML"
+                .to_string(),
+        )));
+    }
+
+    let findings = analysis::analyze(digraph);
+    let mut statuses: HashMap<String, VertexStatus> = HashMap::new();
+    let mut vtx_warnings: HashMap<&str, Vec<&str>> = HashMap::new();
+    for finding in &findings {
+        warn!("{}: {}", finding.kind.as_str(), finding.message);
+        for fmri in &finding.fmris {
+            vtx_warnings
+                .entry(fmri.as_str())
+                .or_default()
+                .push(finding.message.as_str());
+        }
+    }
+
+    //
+    // Summarize the condition of the links in a strip above the graph.
+    //
+    let links = analysis::link_summary(digraph, &findings);
+    if !links.is_empty() {
+        let y = layout.y_margin + y_offset;
+        document = document.add(health::render_summary(&links, theme, layout, y));
+        y_offset += 40;
+    }
+
+    //
+    // Generate the SVG elements for all the vertices.
+    //
+    for depth in 1..=plan.max_depth {
+        let vertices = match plan.column_hash.get(&depth) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        for index in 0..vertices.len() {
+            let height: u32 = (index + 1).try_into().unwrap();
             let vtx_fmri: String = vertices[index].to_string();
             let vtx = digraph.vertices.get_mut(&vtx_fmri).unwrap();
 
-            let x_margin = 50;
-            let y_margin = 10;
-            let x = ((depth - 1) * 250) + x_margin;
-
-            let y_factor: u32 = match height {
-                1 => 1,
-                _ => (max_height / vertices.len()).try_into().unwrap(),
-            };
-            let y = ((height - 1) * 150 * y_factor) + y_margin;
+            let (x, y) = plan.position(layout, depth, height, vertices.len(), y_offset);
 
             debug!(
                 "VERTEX: fmri: {}, depth: {}, height: {}, x: {}, y: {}",
                 vtx_fmri, depth, height, x, y
             );
 
-            let imguri = match vtx.name.as_ref() {
-                INITIATOR => "assets/icons/initiator.png",
-                PORT => "assets/icons/port.png",
-                EXPANDER => "assets/icons/expander.png",
-                TARGET => "assets/icons/target.png",
-                &_ => return Err(Box::new(SimpleError("unexpected vertex name".to_string()))),
-            };
-            let img = Image::new()
+            let imguri = vertex_icon(&vtx.name)?;
+            let mut img = Image::new()
                 .set("href", imguri)
                 .set("x", x)
                 .set("y", y)
                 .set("width", vtx_width)
                 .set("height", vtx_height);
 
+            if config.options.is_highlighted(&vtx_fmri) {
+                img = img.set("filter", "url(#linear)");
+            }
+
             vtx.geometry.x = x;
-            vtx.geometry.y = y.try_into().unwrap();
+            vtx.geometry.y = y;
             vtx.geometry.width = vtx_width;
             vtx.geometry.height = vtx_height;
 
             let mut vtx_group = Group::new()
+                .set("id", stable_id(&vtx_fmri))
                 .set("onclick", "showInfo(evt)")
                 .set("name", vtx.name.clone())
+                .set("instance", vtx.instance.to_string())
                 .set("fmri", vtx_fmri)
                 .add(img);
 
-            for prop in &vtx.properties {
-                vtx_group = vtx_group.set(prop.name.clone(), prop.value.clone());
+            //
+            // The vertices that were merged into this one aren't drawn, so
+            // their stable ids go on it, for looking them up by the ids the
+            // JSON export and the inventory list them under.
+            //
+            let merged_ids: Vec<String> = dual_port::absorbed(vtx)
+                .into_iter()
+                .chain(wide_ports::absorbed(vtx))
+                .map(stable_id)
+                .collect();
+            if !merged_ids.is_empty() {
+                vtx_group = vtx_group.set("data-merged-ids", merged_ids.join(" "));
+            }
+
+            //
+            // Attach the properties, along with the categorized property
+            // names, which the info panel uses to render the properties in
+            // collapsible sections.  If the number of properties per vertex
+            // is capped, the properties are embedded in display order and the
+            // rest go to the sidecar property store.  With lazy loading, all
+            // of them go to the sidecar store, and the viewer looks them up
+            // there when the vertex is clicked.
+            //
+            let values: HashMap<&str, &str> = vtx
+                .properties
+                .iter()
+                .map(|p| (p.name.as_str(), p.value.as_str()))
+                .collect();
+            let mut categories: BTreeMap<PropCategory, Vec<String>> = BTreeMap::new();
+            let max_embedded = max_props.unwrap_or(usize::MAX);
+            let mut embedded = 0;
+            let mut more = 0;
+            for (category, names) in categorized_properties(vtx) {
+                for name in names {
+                    if !config.options.includes_property(&name) {
+                        continue;
+                    }
+                    let value = values[name.as_str()];
+                    if lazy_props {
+                        prop_store.insert(&stable_id(&vtx.fmri), &name, value);
+                    } else if embedded < max_embedded {
+                        vtx_group = vtx_group.set(name.clone(), value);
+                        embedded += 1;
+                    } else {
+                        prop_store.insert(&stable_id(&vtx.fmri), &name, value);
+                        more += 1;
+                        continue;
+                    }
+                    categories.entry(category).or_default().push(name);
+                }
+            }
+            if more > 0 {
+                vtx_group = vtx_group.set("more-props", more);
+            }
+            if lazy_props {
+                prop_store.set_categories(&stable_id(&vtx.fmri), encode_categories(&categories));
+                vtx_group = vtx_group.set("lazy-props", "true");
+            } else {
+                vtx_group = vtx_group.set("prop-categories", encode_categories(&categories));
+            }
+            if let Some(dump) = &vtx.raw_nvlist {
+                prop_store.set_raw(&stable_id(&vtx.fmri), dump);
+                vtx_group = vtx_group.set("raw-nvlist", "true");
+            }
+            let prop_groups = encode_groups(vtx);
+            if !prop_groups.is_empty() {
+                vtx_group = vtx_group.set("prop-groups", prop_groups);
+            }
+            if config.options.vertex_pages() {
+                vtx_group = vtx_group.set("property-page", vertex_pages::page_file(&vtx.fmri));
+            }
+            vtx_group = vtx_group.set("identifiers", identifiers::encode(vtx));
+
+            //
+            // If FMRIs are to be shortened, the info panel shows the short
+            // forms given as "<name>-display" attributes, and the vertex gets
+            // a tooltip with its short FMRI.
+            //
+            let fmri_display = config.options.fmri_display();
+            if fmri_display != FmriDisplay::Full {
+                for name in &["fmri", "hc-fmri", "dev-fmri"] {
+                    let value = match *name {
+                        "fmri" => Some(vtx.fmri.as_str()),
+                        _ => values.get(name).copied(),
+                    };
+                    if let Some(value) = value {
+                        let short = fmri::shorten(value, fmri_display);
+                        if short != value {
+                            vtx_group = vtx_group.set(format!("{}-display", name), short);
+                        }
+                    }
+                }
+                vtx_group = vtx_group.add(
+                    Title::new().add(svg::node::Text::new(fmri::shorten(&vtx.fmri, fmri_display))),
+                );
+            }
+
+            if let Some(reference) = &vtx.reference {
+                let label = Text::new()
+                    .set("x", x + (vtx_width / 2))
+                    .set("y", y + vtx_height + 14)
+                    .set("text-anchor", "middle")
+                    .set("font-family", "Arial, Helvetica, sans-serif")
+                    .set("font-size", 14)
+                    .set("font-weight", "bold")
+                    .set("pointer-events", "none")
+                    .add(svg::node::Text::new(reference.clone()));
+                vtx_group = vtx_group.set("reference", reference.clone());
+                label_layer = label_layer.add(label);
+                present.insert("layer-labels");
+            }
+
+            //
+            // The instance is the quickest way to correlate a vertex with
+            // driver messages, so it can be shown as a caption in the
+            // bottom-right corner of the icon.
+            //
+            if config.options.instances() {
+                let caption = Text::new()
+                    .set("x", x + vtx_width - 4)
+                    .set("y", y + vtx_height - 4)
+                    .set("text-anchor", "end")
+                    .set("font-family", "Arial, Helvetica, sans-serif")
+                    .set("font-size", 10)
+                    .set("fill", "#555555")
+                    .set("class", "instance")
+                    .set("pointer-events", "none")
+                    .add(svg::node::Text::new(vtx.instance.to_string()));
+                label_layer = label_layer.add(caption);
+                present.insert("layer-labels");
+            }
+
+            //
+            // Flag vertices involved in any of the findings with a warning
+            // badge in their top-right corner.
+            //
+            if let Some(warnings) = vtx_warnings.get(vtx.fmri.as_str()) {
+                let badge = Text::new()
+                    .set("x", x + vtx_width - 16)
+                    .set("y", y + 20)
+                    .set("font-size", 20)
+                    .set("fill", "#E65100")
+                    .add(svg::node::Text::new("\u{26a0}"))
+                    .add(Title::new().add(svg::node::Text::new(warnings.join("\n"))));
+                vtx_group = vtx_group.set("warnings", warnings.join("; "));
+                overlay_layer = overlay_layer.add(badge);
+                present.insert("layer-overlays");
             }
 
-            document = document.add(vtx_group);
+            if let Some(redundancy) = &redundancy {
+                if vtx.name == TARGET {
+                    if let Some(outline) =
+                        redundancy.outline(&vtx.fmri, x, y, vtx_width, vtx_height)
+                    {
+                        overlay_layer = overlay_layer.add(outline);
+                        present.insert("layer-overlays");
+                    }
+                }
+            }
+
+            if let Some(reach) = &reachability {
+                if vtx.name != PORT {
+                    if let Some(strip) = reach.strip(&vtx.fmri, x, y, vtx_width) {
+                        reachability_layer = reachability_layer.add(strip);
+                        present.insert("layer-reachability");
+                    }
+                }
+            }
+
+            //
+            // Unhealthy vertices are outlined in the color of their status,
+            // or with its dash pattern and hatched on a monochrome theme.
+            //
+            let status = analysis::vertex_status(vtx, &findings);
+            legend.count(&vtx.name, status);
+            let enclosure = enclosures.get(&vtx.fmri).map(|e| e.as_str());
+            filter_controls.count(vtx, status, enclosure);
+            vtx_group = vtx_group.set("status", status.as_str());
+            if let Some(enclosure) = enclosure {
+                vtx_group = vtx_group.set(filter_controls::ENCLOSURE_ATTRIBUTE, enclosure);
+            }
+            if let Some(pool) = filter_controls::vertex_pool(vtx) {
+                vtx_group = vtx_group.set(filter_controls::POOL_ATTRIBUTE, pool);
+            }
+            let patterns = (monochrome::dash(status), monochrome::hatching(status));
+            if let (true, (Some(dash), Some(hatching))) = (theme.monochrome, patterns) {
+                let outline = Rectangle::new()
+                    .set("x", x)
+                    .set("y", y)
+                    .set("width", vtx_width)
+                    .set("height", vtx_height)
+                    .set("fill", hatching)
+                    .set("stroke", "black")
+                    .set("stroke-width", 3)
+                    .set("stroke-dasharray", dash)
+                    .set("pointer-events", "none");
+                vtx_group = vtx_group.add(outline);
+            } else if let Some(color) = status.color() {
+                let outline = Rectangle::new()
+                    .set("x", x)
+                    .set("y", y)
+                    .set("width", vtx_width)
+                    .set("height", vtx_height)
+                    .set("fill", "none")
+                    .set("stroke", color)
+                    .set("stroke-width", 3)
+                    .set("pointer-events", "none");
+                vtx_group = vtx_group.add(outline);
+            }
+            statuses.insert(vtx.fmri.clone(), status);
+
+            if vtx.name == PORT {
+                port_layer = port_layer.add(vtx_group);
+                present.insert("layer-ports");
+            } else {
+                vertex_layer = vertex_layer.add(vtx_group);
+                present.insert("layer-vertices");
+            }
         }
     }
 
+    //
+    // Targets attached through more than one port (i.e. dual-ported drives)
+    // get an attachment point per port, spread along their left side, rather
+    // than having all of the edges end in the middle.
+    //
+    let mut attached: HashMap<&str, Vec<&str>> = HashMap::new();
+    for vtx in digraph.vertices.values() {
+        if vtx.name != PORT {
+            continue;
+        }
+        for edge in vtx.outgoing_edges.iter().flatten() {
+            if matches!(digraph.vertices.get(edge), Some(v) if v.name == TARGET) {
+                attached.entry(edge.as_str()).or_default().push(&vtx.fmri);
+            }
+        }
+    }
+    let mut attach_y: HashMap<(String, String), u32> = HashMap::new();
+    for (target, mut ports) in attached {
+        if ports.len() < 2 {
+            continue;
+        }
+        ports.sort();
+        let count = ports.len() as u32;
+        for (i, port) in ports.into_iter().enumerate() {
+            let offset = vtx_height * (i as u32 + 1) / (count + 1);
+            attach_y.insert((port.to_string(), target.to_string()), offset);
+        }
+    }
+
+    //
+    // Links negotiating below what their ends support get a badge in the
+    // middle of their edge.
+    //
+    let downshifts: HashMap<(String, String), String> = analysis::link_downshifts(digraph)
+        .into_iter()
+        .map(|d| {
+            let label = format!(
+                "negotiated {}, both ends support {}",
+                analysis::link_rate_label(Some(d.negotiated)),
+                analysis::link_rate_label(Some(d.capability))
+            );
+            ((d.source, d.target), label)
+        })
+        .collect();
+
     //
     // Generate the SVG elements for all of the edges
     //
-    for depth in 1..=max_depth {
-        let vertices = column_hash.get(&depth).unwrap();
+    for depth in 1..=plan.max_depth {
+        let vertices = match plan.column_hash.get(&depth) {
+            Some(entry) => entry,
+            None => continue,
+        };
         for v in vertices {
             let vtx_fmri: String = v.to_string();
             let vtx = digraph.vertices.get(&vtx_fmri).unwrap();
@@ -405,6 +1588,21 @@ fn build_svg(config: &Config, digraph: &mut SasDigraph) -> Result<(), Box<dyn Er
                 continue;
             }
 
+            //
+            // Edges are drawn in the color of the least healthy vertex they
+            // connect, if either isn't healthy, or dashed on a monochrome theme.
+            //
+            let status_of = |fmri: &str| statuses.get(fmri).copied().unwrap_or(VertexStatus::Ok);
+            let edge_color = |status: VertexStatus| match status.color() {
+                Some(color) if !theme.monochrome => color.to_string(),
+                _ => theme.edge_color.clone(),
+            };
+            let dashed = |line: Line, status: VertexStatus| match monochrome::dash(status) {
+                Some(dash) if theme.monochrome => line.set("stroke-dasharray", dash),
+                _ => line,
+            };
+            let vtx_status = status_of(&vtx_fmri);
+
             let start_x1 = vtx.geometry.x + vtx_width;
             let start_y1: u32 = vtx.geometry.y + (vtx_height / 2);
             let start_x2 = start_x1 + 50;
@@ -414,30 +1612,34 @@ fn build_svg(config: &Config, digraph: &mut SasDigraph) -> Result<(), Box<dyn Er
                 .set("y1", start_y1)
                 .set("x2", start_x2)
                 .set("y2", start_y2)
-                .set("stroke", "black")
-                .set("stroke-width", "2");
+                .set("stroke", edge_color(vtx_status))
+                .set("stroke-width", theme.edge_width);
 
-            document = document.add(line);
+            edge_layer = edge_layer.add(dashed(line, vtx_status));
+            present.insert("layer-edges");
 
             for edge_fmri in vtx.outgoing_edges.as_ref().unwrap() {
                 let edge_vtx = digraph.vertices.get(edge_fmri).unwrap();
+                let status = cmp::max(vtx_status, status_of(edge_fmri));
+                let color = edge_color(status);
                 let mid_x1 = start_x2;
                 let mid_y1 = start_y2;
+                let attach = attach_y.get(&(vtx_fmri.clone(), edge_fmri.clone())).copied();
                 let mid_x2 = start_x2;
-                let mid_y2 = edge_vtx.geometry.y + (vtx_height / 2);
+                let mid_y2 = edge_vtx.geometry.y + attach.unwrap_or(vtx_height / 2);
 
                 let line = Line::new()
                     .set("x1", mid_x1)
                     .set("y1", mid_y1)
                     .set("x2", mid_x2)
                     .set("y2", mid_y2)
-                    .set("stroke", "black")
-                    .set("stroke-width", "2");
+                    .set("stroke", color.clone())
+                    .set("stroke-width", theme.edge_width);
 
-                document = document.add(line);
+                edge_layer = edge_layer.add(dashed(line, status));
 
                 let end_x1 = start_x2;
-                let end_y1 = edge_vtx.geometry.y + (vtx_height / 2);
+                let end_y1 = mid_y2;
                 let end_x2 = edge_vtx.geometry.x;
                 let end_y2 = end_y1;
 
@@ -446,161 +1648,1205 @@ fn build_svg(config: &Config, digraph: &mut SasDigraph) -> Result<(), Box<dyn Er
                     .set("y1", end_y1)
                     .set("x2", end_x2)
                     .set("y2", end_y2)
-                    .set("stroke", "black")
-                    .set("stroke-width", "2");
+                    .set("stroke", color.clone())
+                    .set("stroke-width", theme.edge_width);
+
+                edge_layer = edge_layer.add(dashed(line, status));
+
+                if attach.is_some() {
+                    let point = Rectangle::new()
+                        .set("x", end_x2 - 4)
+                        .set("y", end_y2 - 4)
+                        .set("width", 8)
+                        .set("height", 8)
+                        .set("fill", color.clone())
+                        .set("class", "port-attachment");
+                    edge_layer = edge_layer.add(point);
+                }
 
-                document = document.add(line);
+                if let Some(label) = downshifts.get(&(vtx_fmri.clone(), edge_fmri.clone())) {
+                    let badge = Text::new()
+                        .set("x", (end_x1 + end_x2) / 2)
+                        .set("y", end_y1 - 4)
+                        .set("text-anchor", "middle")
+                        .set("font-size", 14)
+                        .set("fill", "#E65100")
+                        .set("class", "downshift")
+                        .add(svg::node::Text::new("\u{2193}"))
+                        .add(Title::new().add(svg::node::Text::new(label.clone())));
+                    overlay_layer = overlay_layer.add(badge);
+                    present.insert("layer-overlays");
+                }
             }
         }
     }
 
-    fs::create_dir_all(&config.outdir)?;
+    //
+    // If previous snapshots were specified, render the devices that have gone
+    // missing since as ghosts below the graph.
+    //
+    let mut ghost_rows = 0;
+    let mut ghost_layer = None;
+    if !config.options.history().is_empty() {
+        let mut history = Vec::new();
+        for path in config.options.history() {
+            history.push(read_comparison(config, digraph, path)?);
+        }
+        let ghosts = ghosts::find_ghosts(&history, digraph, config.options.max_depth())?;
+        if !ghosts.is_empty() {
+            ghost_rows = ghosts::ghost_rows(&ghosts);
+            let y_start = layout.y_margin
+                + y_offset
+                + (cmp::max(1, plan.max_height as u32) * layout.row_height);
+            ghost_layer = Some(ghosts::render_ghosts(&ghosts, layout, y_start)?);
+        }
+    }
 
-    let src_dir_path = std::env::current_exe()?;
-    let src_dir = match src_dir_path.parent() {
-        Some (path) => path.to_str().unwrap(),
-        None => "/"
+    //
+    // The column bands and zones go behind everything else.  The bands start
+    // below the notice and the health summary, if any.
+    //
+    let graph_height = layout.y_margin
+        + y_offset
+        + ((cmp::max(1, plan.max_height as u32) + ghost_rows) * layout.row_height);
+    let bands = bands::render_bands(theme, layout, plan.max_depth, y_offset, graph_height);
+    if let Some(bands) = bands {
+        document = document.add(bands);
+        present.insert("layer-columns");
+    }
+
+    if !enclosures.is_empty() {
+        document = document.add(enclosures::render_enclosures(
+            digraph,
+            &plan.column_hash,
+            &enclosures,
+            layout,
+        ));
+        present.insert("layer-enclosures");
+    }
+
+    let zones = zones::vertex_zones(digraph);
+    if !zones.is_empty() {
+        document = document.add(zones::render_zones(digraph, &plan.column_hash, &zones, layout));
+        present.insert("layer-zones");
+    }
+
+    if let Some(layer) = ghost_layer {
+        document = document.add(layer);
+        present.insert("layer-ghosts");
+    }
+
+    document = document
+        .add(vertex_layer)
+        .add(port_layer)
+        .add(edge_layer)
+        .add(label_layer)
+        .add(overlay_layer)
+        .add(reachability_layer);
+
+    let src_dir = asset_root()?;
+    let svg_data = document.to_string();
+
+    //
+    // SVG compresses very well, which makes a big difference when copying the
+    // rendering of a large fabric off an appliance.  Browsers only inflate an
+    // SVGZ served with Content-Encoding: gzip though, so it's optional.
+    //
+    let svg_file = match config.options.svgz() {
+        true => {
+            let svgz_file = format!("{}.svgz", svg_file.trim_end_matches(".svg"));
+            debug!("Saving compressed SVG to {}", svgz_file);
+            let mut gz = GzEncoder::new(Vec::new(), Compression::best());
+            gz.write_all(svg_data.as_bytes())?;
+            out.write(&svgz_file, &gz.finish()?)?;
+            svgz_file
+        }
+        false => {
+            debug!("Saving SVG to {}", svg_file);
+            out.write(svg_file, svg_data.as_bytes())?;
+            svg_file.to_string()
+        }
     };
 
-    let asset_src_dir = format!("{}/assets", src_dir);
-    debug!("Copying image assets: {} to {}", asset_src_dir, config.outdir);
-    let mut options = fs_extra::dir::CopyOptions::new();
-    options.overwrite = true;
-    fs_extra::dir::copy(&asset_src_dir, &config.outdir, &options)?;
+    let (svg_width, svg_height) = plan.canvas_size(layout);
+    let svg_height = svg_height + y_offset + (ghost_rows * layout.row_height);
+
+    if config.options.has_output(OutputFormat::Png) {
+        let png_file = format!("{}.png", svg_file.trim_end_matches(".svg"));
+        debug!("Saving PNG to {}", png_file);
+        let png = raster::render_png(
+            &svg_data,
+            &src_dir,
+            (svg_width, svg_height),
+            config.options.png_dpi(),
+        )?;
+        out.write(&png_file, &png)?;
+    }
 
-    let svg_file = "sastopo.svg".to_string();
-    let svg_path = format!("{}/{}", config.outdir, svg_file);
-    debug!("Saving SVG to {}", svg_file);
-    svg::save(&svg_path, &document)?;
+    if config.options.has_output(OutputFormat::Pdf) {
+        let pdf_file = format!("{}.pdf", svg_file.trim_end_matches(".svg"));
+        debug!("Saving PDF report to {}", pdf_file);
+        let size = (svg_width, svg_height);
+        let pdf = pdf::render_pdf(digraph, &svg_data, &src_dir, size, config.options.locale())?;
+        out.write(&pdf_file, &pdf)?;
+    }
 
     //
     // The SVG can be quite large depending on the size of the SAS fabric.
     // So to allow it to be more easily viewable in a browser, we embed the
     // SVG in a scrollable HTML iframe.
     //
-    let html_code = include_str!("sastopo2svg.html");
-    let html_path = format!("{}/sastopo2svg.html", config.outdir);
-    let svg_width = cmp::max(1200, max_depth * 250);
-    let svg_height = cmp::max(1100, max_height * 150);
+    let title = page_title(config, digraph);
+    let favicon = match config.options.favicon() {
+        Some(path) => format!("<link rel=\"icon\" href=\"{}\">", data_uri(path)?),
+        None => String::new(),
+    };
+    let logo = match config.options.logo() {
+        Some(path) => format!("<img id=\"site-logo\" src=\"{}\">", data_uri(path)?),
+        None => String::new(),
+    };
+    let html_code = include_str!("sastopo2svg.html")
+        .replace("{{title}}", &html_escape(&title))
+        .replace("{{favicon}}", &favicon)
+        .replace("{{logo}}", &logo);
 
-    let mut htmlfile = fs::File::create(&html_path)?;
+    let mut htmlfile: Vec<u8> = Vec::new();
     htmlfile.write_fmt(format_args!("{}", html_code))?;
+    //
+    // The data island carries all of the properties too, so it is left out
+    // when they are loaded lazily.  The JSON export has the same content.
+    //
+    if !lazy_props {
+        htmlfile.write_fmt(format_args!(
+            "{}",
+            ExportDocument::new(digraph).to_data_island()?
+        ))?;
+    }
+    htmlfile.write_fmt(format_args!("{}", analysis::findings_html(&findings)))?;
     htmlfile.write_fmt(format_args!(
-        "<iframe src=\"{}\" width={} height={} scrollable=\"yes\" frameborder=\"no\" />",
-        svg_file, svg_width, svg_height
+        "{}",
+        lenient::parse_warnings_html(&digraph.parse_warnings)
     ))?;
-    htmlfile.write_fmt(format_args!("</div></div></body></html>\n"))?;
-    Ok(())
+    if let Some(path) = config.options.approved_baseline() {
+        let approved = read_comparison(config, digraph, path)?;
+        let diff = diff::TopoDiff::new(&approved, digraph);
+        htmlfile.write_fmt(format_args!(
+            "{}",
+            diff::drift_html(&diff, &approved, config.options.baseline_diff(), config.options.locale())
+        ))?;
+    }
+    let legend_html = match config.options.standalone() {
+        true => standalone::embed_assets(&legend.to_html(config.options.locale()), &src_dir)?,
+        false => legend.to_html(config.options.locale()),
+    };
+    htmlfile.write_fmt(format_args!("{}", legend_html))?;
+    htmlfile.write_fmt(format_args!("{}", layer_toggles(&present)))?;
+    htmlfile.write_fmt(format_args!("{}", filter_controls.to_html()))?;
+    if let Some(reach) = &reachability {
+        htmlfile.write_fmt(format_args!("{}", reach.legend_html()))?;
+    }
+    if let Some(redundancy) = &redundancy {
+        htmlfile.write_fmt(format_args!("{}", redundancy.to_html()))?;
+    }
+    if config.options.transition_from().is_some() {
+        htmlfile.write_fmt(format_args!(
+            "<p><a href=\"{}\">Animated transition from the previous snapshot</a></p>\n",
+            TRANSITION_SVG
+        ))?;
+    }
+    if split_pages(config, digraph) {
+        htmlfile.write_fmt(format_args!(
+            "<p><a href=\"sastopo2svg.html\">All initiators</a></p>\n"
+        ))?;
+    }
+    if config.options.standalone() {
+        htmlfile.write_fmt(format_args!(
+            "{}<div id=\"sastopo-frame\">{}</div>",
+            standalone::chassis_images_script(&src_dir, digraph)?,
+            standalone::inline_svg(&svg_data, &src_dir, (svg_width, svg_height))?
+        ))?;
+    } else {
+        htmlfile.write_fmt(format_args!(
+            "<iframe id=\"sastopo-frame\" src=\"{}\" width={} height={} scrollable=\"yes\" \
+             frameborder=\"no\" />",
+            svg_file, svg_width, svg_height
+        ))?;
+    }
+    htmlfile.write_fmt(format_args!("</div></div>"))?;
+    if let Some(live_reload) = config.options.live_reload() {
+        htmlfile.write_fmt(format_args!("{}", live_reload.script()))?;
+    }
+    htmlfile.write_fmt(format_args!("</body></html>\n"))?;
+    out.write(html_file, &htmlfile)?;
+    Ok((svg_width, svg_height))
 }
 
-pub fn run(config: &Config) -> Result<(), Box<dyn Error>> {
-    //
-    // Read in the serialized (XML) representation of a SAS topology and
-    // deserialize it into a TopoDigraphXML structure.
-    //
-    let xml_contents = fs::read_to_string(&config.xml_path)?;
-    let sasxml: TopoDigraphXML = serde_xml_rs::from_str(&xml_contents)?;
-
-    let mut digraph = SasDigraph::new(
-        sasxml.product_id,
-        sasxml.nodename,
-        sasxml.os_version,
-        sasxml.timestamp,
-    );
+// callback handed each vertex as it is parsed, see parse_xml()
+type VertexCallback<'a> = &'a mut dyn FnMut(&SasDigraph, &SasDigraphVertex) -> Result<(), Box<dyn Error>>;
 
-    //
-    // Iterate through the TopoDigraphXML and recreate the SAS topology in the
-    // form of a SasDigraph structure.
-    //
-    for vtxxml in sasxml.vertices.vertex {
-        // Convert hex string to a u64, skipping the leading '0x'
-        let instance = u64::from_str_radix(&vtxxml.instance[2..], 16)?;
+//
+// Recreate a vertex from its serialized (XML) representation.
+//
+// the name of a property group, and its property values if it has any
+type PropGroup = (String, Option<Vec<NvlistXmlArrayElement>>);
 
-        let mut vtx = match vtxxml.outgoing_edges {
-            Some(outgoing_edges) => {
-                let mut edges = Vec::new();
-                for edgexml in outgoing_edges.edges {
-                    edges.push(edgexml.fmri);
-                }
-                SasDigraphVertex::new(vtxxml.fmri, vtxxml.name, instance, Some(edges))
+//
+// Returns the name of a property group and its property values, if it has
+// any.  Each group is parsed on its own, so that a group lacking either can't
+// pick up those of the group before it.  A group naming itself or listing its
+// values twice is malformed, as its properties could go under either name.  A
+// name without a value counts as not set.
+//
+fn parse_propgroup(fmri: &str, pg: NvlistXmlArrayElement) -> Result<PropGroup, Box<dyn Error>> {
+    let mut pgname: Option<String> = None;
+    let mut props: Option<Option<Vec<NvlistXmlArrayElement>>> = None;
+    for pgnvp in pg.nvpairs.unwrap_or_default() {
+        let name = match pgnvp.name.as_deref() {
+            Some(name) => name,
+            None => {
+                return Err(Box::new(SimpleError(format!(
+                    "malformed propgroup on {}, nvpair without a name",
+                    fmri
+                ))))
             }
-            None => SasDigraphVertex::new(vtxxml.fmri, vtxxml.name, instance, None),
         };
+        let duplicate = match name {
+            PG_NAME => pgname.replace(pgnvp.value.unwrap_or_default()).is_some(),
+            PG_VALS => props.replace(pgnvp.nvlist_elements).is_some(),
+            _ => return Err(Box::new(SimpleError("Unexpected nvpair name".to_string()))),
+        };
+        if duplicate {
+            return Err(Box::new(SimpleError(format!(
+                "malformed propgroup on {}, {} set twice",
+                fmri, name
+            ))));
+        }
+    }
 
-        //
-        // The XML contains a set of nested NvpairXML structures representing
-        // the node property groups and their contained properties.  We descend
-        // through these to build an array of SasDigraphProperty structs which
-        // will contains a subset of properties that we want to display when
-        // the vertex is clicked on.
-        //
-        for pgnvl in vtxxml.propgroups {
-            let pgarr = pgnvl.nvlist_elements.unwrap();
-            for pg in pgarr {
-                let mut owned1;
-                let mut owned2;
-
-                let mut props: Option<&Vec<NvlistXmlArrayElement>> = None;
-                let mut pgname: &str = "";
-                if pg.nvpairs.is_some() {
-                    for pgnvp in pg.nvpairs.unwrap() {
-                        match pgnvp.name.unwrap().as_ref() {
-                            PG_NAME => {
-                                owned1 = pgnvp.value.unwrap();
-                                pgname = owned1.as_ref();
-                            }
-                            PG_VALS => {
-                                if pgnvp.nvlist_elements.is_some() {
-                                    owned2 = pgnvp.nvlist_elements.unwrap();
-                                    props = Some(owned2.as_ref());
-                                }
-                            }
-                            _ => {
-                                return Err(Box::new(SimpleError("Unexpected nvpair name".to_string())))
-                            }
+    // Sanity check against malformed XML
+    match pgname {
+        Some(pgname) if !pgname.is_empty() => Ok((pgname, props.flatten())),
+        _ => Err(Box::new(SimpleError(format!(
+            "malformed propgroup, {} not set",
+            PG_NAME
+        )))),
+    }
+}
+
+fn build_vertex(
+    vtxxml: xml::VertexXml,
+    scheme: Scheme,
+    options: &RenderOptions,
+) -> Result<SasDigraphVertex, Box<dyn Error>> {
+    let instance = SasInstance::parse(&vtxxml.instance);
+
+    let mut vtx = match vtxxml.outgoing_edges {
+        Some(outgoing_edges) => {
+            let mut edges = Vec::new();
+            for edgexml in outgoing_edges.edges {
+                edges.push(edgexml.fmri);
+            }
+            SasDigraphVertex::new(vtxxml.fmri, vtxxml.name, instance, Some(edges))
+        }
+        None => SasDigraphVertex::new(vtxxml.fmri, vtxxml.name, instance, None),
+    };
+
+    if options.raw_nvlists() {
+        vtx.raw_nvlist = Some(nvlist::format_nvpairs(&vtxxml.propgroups));
+    }
+
+    //
+    // The XML contains a set of nested NvpairXML structures representing
+    // the node property groups and their contained properties.  We descend
+    // through these to build an array of SasDigraphProperty structs which
+    // will contains a subset of properties that we want to display when
+    // the vertex is clicked on.
+    //
+    for pgnvl in vtxxml.propgroups {
+        let pgarr = match pgnvl.nvlist_elements {
+            Some(pgarr) => pgarr,
+            None => continue,
+        };
+        for pg in pgarr {
+            let (pgname, props) = parse_propgroup(&vtx.fmri, pg)?;
+            let props = match props {
+                Some(props) => props,
+                None => {
+                    match options.empty_propgroups() {
+                        EmptyPropGroups::Skip => (),
+                        EmptyPropGroups::Warn => warn!(
+                            "{}: propgroup {} has no values ({} not set)",
+                            vtx.fmri, pgname, PG_VALS
+                        ),
+                        EmptyPropGroups::Error => {
+                            return Err(Box::new(SimpleError(format!(
+                                "malformed propgroup {} on {}, {} not set",
+                                pgname, vtx.fmri, PG_VALS
+                            ))));
                         }
                     }
-                }
-
-                // Sanity check against malformed XML
-                if pgname == "" {
-                    return Err(Box::new(SimpleError(format!(
-                        "malformed propgroup, {} not set",
-                        PG_NAME
-                    ))));
-                } else if props.is_none() {
-                    /*return Err(Box::new(SimpleError(
-                    format!("malformed propgroup, {} not set", PG_VALS))));*/
                     continue;
                 }
+            };
 
-                //
-                // The only things in the protocol property group is an nvlist
-                // representation of the FMRI, which we don't need as we
-                // already have the FMRI as a string in a separate field.
-                //
-                if pgname == "protocol" {
-                    continue;
-                }
+            //
+            // The only things in the protocol property group is an nvlist
+            // representation of the FMRI, which we don't need as we
+            // already have the FMRI as a string in a separate field.  The
+            // hc-scheme adds the label, which the scheme maps (see
+            // scheme.rs) like the properties that go by different names
+            // there.
+            //
+            if pgname == "protocol" && scheme == Scheme::Sas {
+                continue;
+            }
 
-                for propnvl in props.unwrap() {
-                    let prop = parse_prop(&propnvl)?;
-                    vtx.properties.push(prop);
+            //
+            // Different property groups may define properties with the
+            // same name.  The first definition keeps the plain name, and
+            // any later ones are qualified with the name of their group,
+            // so that neither value is lost.
+            //
+            for propnvl in &props {
+                let mut prop = parse_prop(propnvl)?;
+                match scheme.property_name(&pgname, &prop.name) {
+                    Some(name) => prop.name = name.to_string(),
+                    None => continue,
+                }
+                if vtx.properties.iter().any(|p| p.name == prop.name) {
+                    debug!(
+                        "{}: property {} is also defined in propgroup {}",
+                        vtx.fmri, prop.name, pgname
+                    );
+                    prop.name = format!("{}:{}", pgname, prop.name);
                 }
+                prop.group = Some(pgname.to_string());
+                vtx.properties.push(prop);
             }
         }
+    }
+
+    if vtx.properties.is_empty() {
+        debug!("no properties for {}, deriving identity from FMRI", vtx.fmri);
+        vtx.properties = fmri::identity_properties(&vtx.name, &vtx.fmri);
+    }
+    Ok(vtx)
+}
 
-        if vtx.name == INITIATOR {
+//
+// Recreate a SAS topology from the XML output of sastopo -x.
+//
+// If a callback is specified, each vertex is handed to it as soon as it has
+// been parsed instead of being added to the digraph.  The digraph returned
+// then only describes the host.
+//
+fn parse_xml(
+    source: &mut dyn BufRead,
+    options: &RenderOptions,
+    mut on_vertex: Option<VertexCallback>,
+) -> Result<SasDigraph, Box<dyn Error>> {
+    //
+    // The snapshot is parsed as it is read, one vertex at a time (see
+    // reader.rs), so that neither the XML nor its deserialized form is ever
+    // held in memory as a whole, only the SasDigraph being built.
+    //
+    let mut reader = TopoXmlReader::with_options(source, options)?;
+    let mut digraph = reader.host().clone();
+
+    for vtx in &mut reader {
+        let vtx = vtx?;
+
+        if let Some(callback) = on_vertex.as_mut() {
+            callback(&digraph, &vtx)?;
+            continue;
+        }
+
+        if digraph.scheme.is_root(&vtx.name) {
             digraph.initiators.push(vtx.fmri.clone());
         }
         digraph.vertices.insert(vtx.fmri.clone(), vtx);
     }
+    digraph.parse_warnings = reader.parse_warnings().to_vec();
+    lenient::drop_skipped_edges(&mut digraph);
+    Ok(digraph)
+}
+
+//
+// Read in a SAS topology from the specified file.  The file can either contain
+// the XML output of sastopo -x or a JSON document previously exported by this
+// crate.  The latter allows pipelines to enrich the JSON export and feed it
+// back in for the final rendering.  The XML is parsed as specified by the
+// options (e.g. how to handle property groups without values).  The
+// snapshots to merge in (see merge.rs) only go into the topology that is
+// rendered, see read_topology(), rather than into those it is compared with.
+//
+fn read_digraph(path: &str, options: &RenderOptions) -> Result<SasDigraph, Box<dyn Error>> {
+    read_snapshot(path, options, None)
+}
+
+//
+// Open a snapshot (or JSON export) for reading, decompressing it as it is
+// read if it is compressed with gzip or bzip2, as the snapshots in support
+// bundles are.  The compression is recognized by the magic number rather than
+// the file extension, so that e.g. sastopo.xml.gz and sastopo.xml.bz2 both
+// work, as does a compressed file that was renamed.  A support bundle is read
+// from instead, see bundle.rs.  A snapshot in an encoding other than UTF-8 is
+// transcoded as it is read, see encoding.rs.
+//
+fn open_snapshot(path: &str) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let mut source = decompress(path)?;
+    if bundle::is_bundle(path, source.fill_buf()?) {
+        debug!("Looking for the topology snapshot in the support bundle {}", path);
+        let extracted = bundle::extract_snapshot(path, source)?;
+        let snapshot = decompress(&extracted.path.to_string_lossy());
+        // the file stays readable for as long as it is open
+        drop(extracted);
+        return encoding::transcode(snapshot?, path);
+    }
+    encoding::transcode(source, path)
+}
+
+//
+// Opens the file (or URL), decompressing it if it is gzip- or
+// bzip2-compressed.
+//
+fn decompress(path: &str) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let file: Box<dyn Read> = match fetch::is_url(path) {
+        true => fetch::fetch(path)?,
+        false => Box::new(fs::File::open(path)?),
+    };
+    let mut file = BufReader::new(file);
+    let magic = file.fill_buf()?;
+    if magic.starts_with(&[0x1f, 0x8b]) {
+        debug!("Decompressing gzip-compressed {}", path);
+        Ok(Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(file))))
+    } else if magic.starts_with(b"BZh") {
+        debug!("Decompressing bzip2-compressed {}", path);
+        Ok(Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(file))))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+//
+// Whether the document read from source is JSON (rather than XML), going by
+// its first character, which is looked at without consuming it.
+//
+fn looks_like_json(source: &mut dyn BufRead) -> Result<bool, Box<dyn Error>> {
+    let start = source.fill_buf()?;
+    Ok(start.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{'))
+}
+
+//
+// Like read_digraph(), merging in the snapshots the options name (see
+// merge.rs), and handing each vertex to the callback, if any, as described for
+// parse_xml().  The snapshots to merge in have to be read as a whole first,
+// so their vertices are handed over once merged.
+//
+fn read_digraph_each(
+    path: &str,
+    options: &RenderOptions,
+    on_vertex: Option<VertexCallback>,
+) -> Result<SasDigraph, Box<dyn Error>> {
+    if options.merged_snapshots().is_empty() {
+        return read_snapshot(path, options, on_vertex);
+    }
+    let mut digraph = read_snapshot(path, options, None)?;
+    for other in options.merged_snapshots() {
+        debug!("Merging in the topology from {}", other);
+        merge::merge(&mut digraph, read_snapshot(other, options, None)?, other)?;
+    }
+    if let Some(callback) = on_vertex {
+        hand_over(&mut digraph, callback)?;
+    }
+    Ok(digraph)
+}
+
+//
+// Hands the vertices of a digraph that was read as a whole to the callback,
+// in the order of their FMRIs, leaving only the host information.
+//
+fn hand_over(digraph: &mut SasDigraph, callback: VertexCallback) -> Result<(), Box<dyn Error>> {
+    let mut fmris: Vec<String> = digraph.vertices.keys().cloned().collect();
+    fmris.sort();
+    for fmri in fmris {
+        let vtx = digraph.vertices.remove(&fmri).unwrap();
+        callback(digraph, &vtx)?;
+    }
+    digraph.initiators.clear();
+    Ok(())
+}
+
+fn read_snapshot(
+    path: &str,
+    options: &RenderOptions,
+    on_vertex: Option<VertexCallback>,
+) -> Result<SasDigraph, Box<dyn Error>> {
+    let mut source = open_snapshot(path)?;
+    let name = path.trim_end_matches(".gz").trim_end_matches(".bz2");
+    let format = match options.input_format() {
+        InputFormat::Auto if name.ends_with(".json") || looks_like_json(&mut source)? => {
+            InputFormat::Json
+        }
+        InputFormat::Auto if packed_nvlist::is_packed_nvlist(source.fill_buf()?) => {
+            InputFormat::Nvlist
+        }
+        InputFormat::Auto => InputFormat::Xml,
+        format => format,
+    };
+
+    if format == InputFormat::Nvlist {
+        debug!("Reading packed nvlist topology from {}", path);
+        let mut packed = Vec::new();
+        source.read_to_end(&mut packed)?;
+        let mut digraph = packed_nvlist::read_digraph(&packed, options)?;
+        if let Some(callback) = on_vertex {
+            hand_over(&mut digraph, callback)?;
+        }
+        Ok(digraph)
+    } else if format == InputFormat::Json {
+        debug!("Reading JSON topology from {}", path);
+        let doc: ExportDocument = serde_json::from_reader(source)?;
+        check_format_version(&doc.format_version)?;
+        let mut digraph = doc.into_digraph()?;
+        if let Some(callback) = on_vertex {
+            hand_over(&mut digraph, callback)?;
+        }
+        Ok(digraph)
+    } else {
+        if options.validate() {
+            check_schema(path)?;
+        }
+        debug!("Reading XML topology from {}", path);
+        parse_xml(&mut source, options, on_vertex)
+    }
+}
+
+//
+// Check the XML snapshot (which may be compressed, or in a support bundle)
+// against the topo digraph schema, returning the violations found.
+//
+pub fn validate_snapshot(path: &str) -> Result<Vec<SchemaViolation>, Box<dyn Error>> {
+    debug!("Validating {}", path);
+    Ok(validate::validate(&mut open_snapshot(path)?))
+}
+
+//
+// Fails with the violations of the schema listed if the snapshot doesn't
+// match it.
+//
+fn check_schema(path: &str) -> Result<(), Box<dyn Error>> {
+    let violations = validate_snapshot(path)?;
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let listed: Vec<String> = violations.iter().map(|v| format!("  {}", v)).collect();
+    Err(Box::new(SimpleError(format!(
+        "{} doesn't match the topology schema:\n{}",
+        path,
+        listed.join("\n")
+    ))))
+}
+
+//
+// Summary of the layout a run would produce, as returned by dry_run().
+//
+#[derive(Debug)]
+pub struct DryRunReport {
+    // number of vertices in the snapshot
+    pub vertices: usize,
+    // number of vertices placed in each column of the layout
+    pub column_heights: Vec<usize>,
+    // number of rows in the tallest column
+    pub rows: usize,
+    // size of the viewport the SVG is displayed in
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+    // approximate size of the SVG document in bytes
+    pub estimated_svg_bytes: usize,
+    // quality of the layout
+    pub layout_metrics: LayoutMetrics,
+    // hash of the layout, if requested (see layout_hash.rs)
+    pub layout_hash: Option<String>,
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "vertices:            {}", self.vertices)?;
+        writeln!(f, "columns:             {}", self.column_heights.len())?;
+        writeln!(f, "rows:                {}", self.rows)?;
+        for (i, height) in self.column_heights.iter().enumerate() {
+            writeln!(f, "  column {:<3}        {} vertices", i + 1, height)?;
+        }
+        writeln!(
+            f,
+            "canvas:              {} x {}",
+            self.canvas_width, self.canvas_height
+        )?;
+        writeln!(f, "edge crossings:      {}", self.layout_metrics.edge_crossings)?;
+        writeln!(f, "edge length:         {}", self.layout_metrics.edge_length)?;
+        writeln!(f, "overlaps:            {}", self.layout_metrics.overlaps)?;
+        if let Some(hash) = &self.layout_hash {
+            writeln!(f, "layout hash:         {}", hash)?;
+        }
+        write!(
+            f,
+            "estimated SVG size:  {:.1} KiB",
+            self.estimated_svg_bytes as f64 / 1024.0
+        )
+    }
+}
+
+//
+// Measure the quality of the layout of the specified digraph, see metrics.rs.
+//
+fn measure_layout(config: &Config, digraph: &SasDigraph) -> Result<LayoutMetrics, Box<dyn Error>> {
+    let plan = plan_layout(digraph, config.options.max_depth())?;
+    let layout = config.options.layout();
+    Ok(metrics::measure(digraph, &plan.positions(layout, 0), layout))
+}
+
+//
+// Summary of a completed run, as returned by run().
+//
+#[derive(Debug, Default)]
+pub struct RunReport {
+    // number of distinct devices of each type
+    pub devices: BTreeMap<String, usize>,
+    // number of problems reported by the analysis
+    pub warnings: usize,
+    // paths of the files that were written, not counting the assets
+    pub outputs: Vec<String>,
+    // size of the viewport the SVG is displayed in, if one was rendered
+    pub canvas: Option<(u32, u32)>,
+    // quality of the layout, if an SVG was rendered (summed over the pages
+    // if split by initiator)
+    pub layout_metrics: Option<LayoutMetrics>,
+    // hash of the layout, if requested (see layout_hash.rs)
+    pub layout_hash: Option<String>,
+    // vertices skipped in lenient mode
+    pub parse_warnings: Vec<ParseWarning>,
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rows: Vec<(String, String)> = self
+            .devices
+            .iter()
+            .map(|(name, count)| (format!("{}s", name), count.to_string()))
+            .collect();
+        rows.push(("warnings".to_string(), self.warnings.to_string()));
+        if !self.parse_warnings.is_empty() {
+            rows.push(("vertices skipped".to_string(), self.parse_warnings.len().to_string()));
+        }
+        if let Some((width, height)) = self.canvas {
+            rows.push(("canvas".to_string(), format!("{} x {}", width, height)));
+        }
+        if let Some(metrics) = &self.layout_metrics {
+            rows.push(("edge crossings".to_string(), metrics.edge_crossings.to_string()));
+            rows.push(("edge length".to_string(), metrics.edge_length.to_string()));
+            rows.push(("overlaps".to_string(), metrics.overlaps.to_string()));
+        }
+        if let Some(hash) = &self.layout_hash {
+            rows.push(("layout hash".to_string(), hash.clone()));
+        }
+        for (i, path) in self.outputs.iter().enumerate() {
+            let label = if i == 0 { "outputs" } else { "" };
+            rows.push((label.to_string(), path.clone()));
+        }
+
+        let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        for (i, (label, value)) in rows.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:<width$}  {}", label, value, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+//
+// Estimate the size of the SVG document generated for the specified layout,
+// without actually generating it.  The per-element sizes are averages taken
+// from the output of build_svg().
+//
+fn estimate_svg_size(config: &Config, digraph: &SasDigraph, plan: &LayoutPlan) -> usize {
+    const DOC_OVERHEAD: usize = 1024;
+    const VERTEX_OVERHEAD: usize = 250;
+    const LABEL_OVERHEAD: usize = 180;
+    const LINE_SIZE: usize = 100;
+
+    let mut size = DOC_OVERHEAD + include_str!("sastopo2svg.js").len();
+    for fmris in plan.column_hash.values() {
+        for fmri in fmris {
+            let vtx = &digraph.vertices[fmri];
+            size += VERTEX_OVERHEAD + (2 * vtx.fmri.len());
+            let embedded = vtx
+                .properties
+                .iter()
+                .filter(|prop| config.options.includes_property(&prop.name))
+                .take(match config.options.lazy_properties() {
+                    true => 0,
+                    false => config.options.max_properties().unwrap_or(usize::MAX),
+                });
+            for prop in embedded {
+                size += prop.name.len() + prop.value.len() + 4;
+            }
+            if vtx.reference.is_some() {
+                size += LABEL_OVERHEAD;
+            }
+            if let Some(edges) = &vtx.outgoing_edges {
+                size += LINE_SIZE * (1 + (2 * edges.len()));
+            }
+        }
+    }
+    size
+}
+
+//
+// Parse and lay out the topology and report the dimensions of the resulting
+// document, without writing anything.  This is useful to check what rendering
+// a very large fabric would produce before committing to it.
+//
+pub fn dry_run(config: &Config) -> Result<DryRunReport, Box<dyn Error>> {
+    let mut digraph = read_topology(config)?;
+
+    if config.options.numbering() {
+        assign_references(&mut digraph, config.options.max_depth())?;
+    }
+
+    let plan = plan_layout(&digraph, config.options.max_depth())?;
+    let (canvas_width, canvas_height) = plan.canvas_size(config.options.layout());
+    let column_heights = (1..=plan.max_depth)
+        .map(|depth| plan.column_hash.get(&depth).map_or(0, |c| c.len()))
+        .collect();
+    let layout = config.options.layout();
+    let layout_metrics = metrics::measure(&digraph, &plan.positions(layout, 0), layout);
+    let layout_hash = match config.options.layout_hash() {
+        true => Some(layout_hash::layout_hash(&digraph, config.options.max_depth())?),
+        false => None,
+    };
+
+    Ok(DryRunReport {
+        vertices: digraph.vertices.len(),
+        column_heights,
+        rows: plan.max_height,
+        canvas_width,
+        canvas_height,
+        estimated_svg_bytes: estimate_svg_size(config, &digraph, &plan),
+        layout_metrics,
+        layout_hash,
+    })
+}
+
+//
+// Render the topology as an indented text tree, see text.rs.  Unlike the
+// other formats, this is meant to be printed rather than written to the
+// output directory.
+//
+pub fn text_tree(config: &Config) -> Result<String, Box<dyn Error>> {
+    let mut digraph = read_topology(config)?;
+
+    if config.options.numbering() {
+        assign_references(&mut digraph, config.options.max_depth())?;
+    }
+
+    if config.options.anonymize() {
+        anonymize::anonymize(&mut digraph);
+    }
+
+    Ok(text::render_text(&digraph, config.options.inventory_properties()))
+}
+
+//
+// List the paths from each initiator to the target with the specified FMRI,
+// WWN or reference number, see paths.rs.
+//
+pub fn compare_paths(config: &Config, target: &str) -> Result<PathReport, Box<dyn Error>> {
+    let mut digraph = read_topology(config)?;
+
+    if config.options.numbering() {
+        assign_references(&mut digraph, config.options.max_depth())?;
+    }
+
+    paths::compare_paths(&digraph, target)
+}
+
+//
+// Count the paths to each target that don't share a link, see redundancy.rs.
+//
+pub fn redundancy_report(config: &Config) -> Result<RedundancyReport, Box<dyn Error>> {
+    let mut digraph = read_topology(config)?;
+
+    if config.options.numbering() {
+        assign_references(&mut digraph, config.options.max_depth())?;
+    }
+
+    Ok(redundancy::analyze(&digraph))
+}
+
+//
+// Write the topology to out as a stream of JSON Lines events, one per vertex
+// and per edge, as they are parsed (see events.rs).  Returns the number of
+// events written.
+//
+pub fn stream_events(config: &Config, out: &mut dyn Write) -> Result<usize, Box<dyn Error>> {
+    let mut stream = events::EventStream::new(out);
+    let digraph = read_digraph_each(
+        &config.xml_path,
+        &config.options,
+        Some(&mut |digraph, vtx| stream.vertex(digraph, vtx)),
+    )?;
+    // a topology without any vertices still gets its host event
+    stream.host(&digraph)?;
+    Ok(stream.count())
+}
+
+pub fn run(config: &Config) -> Result<RunReport, Box<dyn Error>> {
+    run_with_sink(config, &mut DirectorySink::new(&config.outdir))
+}
+
+//
+// Read in the topology from config.xml_path, as run() does, without
+// rendering it.  This allows the topology to be analyzed (see SasDigraph),
+// and possibly rendered later on with render().
+//
+pub fn read_topology(config: &Config) -> Result<SasDigraph, Box<dyn Error>> {
+    read_digraph_each(&config.xml_path, &config.options, None)
+}
+
+//
+// Render a topology previously read with read_topology() to config.outdir.
+//
+pub fn render(config: &Config, digraph: SasDigraph) -> Result<RunReport, Box<dyn Error>> {
+    render_with_sink(config, digraph, &mut DirectorySink::new(&config.outdir))
+}
+
+//
+// Like run(), but the generated files are handed to sink rather than written
+// to config.outdir.
+//
+pub fn run_with_sink(
+    config: &Config,
+    sink: &mut dyn OutputSink,
+) -> Result<RunReport, Box<dyn Error>> {
+    let digraph = read_topology(config)?;
+    render_with_sink(config, digraph, sink)
+}
+
+//
+// Run the stages of the pipeline (see pipeline.rs) on the snapshot, writing
+// the output of its render stages to config.outdir.  Returns the report of
+// each of them.
+//
+pub fn run_pipeline(
+    config: &Config,
+    pipeline: &Pipeline,
+) -> Result<Vec<RunReport>, Box<dyn Error>> {
+    let mut digraph = read_topology(config)?;
+    let mut selection: Vec<String> = digraph.vertices.keys().cloned().collect();
+    selection.sort();
+
+    let mut reports = Vec::new();
+    for stage in pipeline.stages() {
+        match stage {
+            Stage::Select(selector) => {
+                debug!("Selecting {}", selector);
+                let (selected, matched) = pipeline::select(&digraph, selector);
+                if matched.is_empty() {
+                    return Err(Box::new(SimpleError(format!(
+                        "select {} matched none of the vertices",
+                        selector
+                    ))));
+                }
+                digraph = selected;
+                selection = matched;
+            }
+            Stage::Annotate(annotations) => {
+                pipeline::annotate(&mut digraph, &selection, annotations);
+            }
+            Stage::Render(formats) => {
+                let mut config = Config::with_options(
+                    config.outdir.clone(),
+                    config.xml_path.clone(),
+                    config.options.clone(),
+                );
+                if !formats.is_empty() {
+                    config.options.set_outputs(formats.clone());
+                }
+                let mut sink = DirectorySink::new(&config.outdir);
+                reports.push(render_with_sink(&config, digraph.clone(), &mut sink)?);
+            }
+        }
+    }
+    Ok(reports)
+}
+
+//
+// Like render(), but the generated files are handed to sink.
+//
+pub fn render_with_sink(
+    config: &Config,
+    mut digraph: SasDigraph,
+    sink: &mut dyn OutputSink,
+) -> Result<RunReport, Box<dyn Error>> {
+    let mut report = RunReport::default();
+    let mut out = Artifacts::new(sink);
+
+    if config.options.numbering() {
+        assign_references(&mut digraph, config.options.max_depth())?;
+    }
+
+    // Without SES data, the enclosures are inferred, see enclosures.rs
+    let aliases = match config.options.enclosure_aliases() {
+        Some(path) => enclosures::load_aliases(path)?,
+        None => BTreeMap::new(),
+    };
+    enclosures::infer_enclosures(&mut digraph, &aliases);
+
+    if config.options.anonymize() {
+        anonymize::anonymize(&mut digraph);
+    }
+
+    if config.options.layout_hash() {
+        let hash = layout_hash::layout_hash(&digraph, config.options.max_depth())?;
+        report.layout_hash = Some(hash);
+    }
 
     //
     // Generate an SVG from the SasDigraph structure and save it to the
-    // specified file.
+    // specified file.  The PNG and PDF are rendered from the SVG, so they
+    // imply the latter.
     //
-    build_svg(config, &mut digraph)?;
+    let options = &config.options;
+    let mut files: Vec<String> = Vec::new();
+    if [OutputFormat::Svg, OutputFormat::Png, OutputFormat::Pdf]
+        .iter()
+        .any(|format| options.has_output(*format))
+    {
+        let mut prop_store = sidecar::PropertyStore::new();
+        let mut pages = vec![("sastopo.svg".to_string(), "sastopo2svg.html".to_string())];
+        if split_pages(config, &digraph) {
+            //
+            // Each initiator gets a page of its own, with the usual page
+            // replaced by an index linking to them.  The canvas reported is
+            // that of the largest page.
+            //
+            let mut index = Vec::new();
+            let mut canvas = (0, 0);
+            let mut layout_metrics = LayoutMetrics::default();
+            for (i, initiator) in layout_roots(&digraph).iter().enumerate() {
+                let initiators = std::slice::from_ref(initiator);
+                let mut subtree = split::initiator_subtree(&digraph, initiators);
+                let page = split::InitiatorPage::new(i + 1, &subtree);
+                let names = (page.svg_file.as_str(), page.html_file.as_str());
+                let (width, height) =
+                    build_svg(config, &mut subtree, names, &mut prop_store, &mut out)?;
+                canvas = (cmp::max(canvas.0, width), cmp::max(canvas.1, height));
+                layout_metrics += measure_layout(config, &subtree)?;
+                index.push(page);
+            }
+            let title = page_title(config, &digraph);
+            let mut index_html = split::index_html(&title, &index, config.options.locale());
+            if let Some(live_reload) = config.options.live_reload() {
+                index_html = live_reload.inject(&index_html);
+            }
+            out.write("sastopo2svg.html", index_html.as_bytes())?;
+            report.canvas = Some(canvas);
+            report.layout_metrics = Some(layout_metrics);
+            pages = index
+                .into_iter()
+                .map(|page| (page.svg_file, page.html_file))
+                .collect();
+            files.push("sastopo2svg.html".to_string());
+        } else {
+            if config.options.split_initiators() {
+                warn!("no initiators to split the rendering by");
+            }
+            let names = ("sastopo.svg", "sastopo2svg.html");
+            report.canvas =
+                Some(build_svg(config, &mut digraph, names, &mut prop_store, &mut out)?);
+            report.layout_metrics = Some(measure_layout(config, &digraph)?);
+        }
 
-    Ok(())
+        write_assets(&mut out)?;
+        if sidecar::needed(options) {
+            debug!("Saving sidecar properties to {}", sidecar::SIDECAR_FILE);
+            out.write(sidecar::SIDECAR_FILE, prop_store.to_script()?.as_bytes())?;
+        }
+        if options.vertex_pages() {
+            debug!("Saving vertex property pages");
+            for vtx in digraph.vertices.values() {
+                let page = vertex_pages::render_page(vtx, options);
+                out.write(&vertex_pages::page_file(&vtx.fmri), page.as_bytes())?;
+            }
+        }
+
+        for (svg_file, html_file) in pages {
+            let stem = svg_file.trim_end_matches(".svg").to_string();
+            match options.svgz() {
+                true => files.push(format!("{}.svgz", stem)),
+                false => files.push(svg_file),
+            }
+            files.push(html_file);
+            if options.has_output(OutputFormat::Png) {
+                files.push(format!("{}.png", stem));
+            }
+            if options.has_output(OutputFormat::Pdf) {
+                files.push(format!("{}.pdf", stem));
+            }
+        }
+    }
+
+    if let Some(path) = config.options.transition_from() {
+        let before = read_digraph(path, &config.options)?;
+        let document = transition::build_transition_svg(config, &before, &digraph)?;
+        debug!("Saving transition SVG to {}", TRANSITION_SVG);
+        out.write(TRANSITION_SVG, document.to_string().as_bytes())?;
+        files.push(TRANSITION_SVG.to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Json) {
+        debug!("Saving JSON to sastopo.json");
+        let json = ExportDocument::new(&digraph).to_json_with(config.options.key_case())?;
+        out.write("sastopo.json", json.as_bytes())?;
+        files.push("sastopo.json".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Dot) {
+        debug!("Saving DOT to sastopo.dot");
+        out.write("sastopo.dot", dot::render_dot(&digraph).as_bytes())?;
+        files.push("sastopo.dot".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::GraphMl) {
+        debug!("Saving GraphML to sastopo.graphml");
+        out.write("sastopo.graphml", graphml::render_graphml(&digraph).as_bytes())?;
+        files.push("sastopo.graphml".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Mermaid) {
+        debug!("Saving Mermaid flowchart to sastopo.mmd");
+        out.write("sastopo.mmd", mermaid::render_mermaid(&digraph).as_bytes())?;
+        files.push("sastopo.mmd".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::D3) {
+        debug!("Saving D3 documents");
+        out.write("sastopo-d3-graph.json", d3::render_d3_graph(&digraph)?.as_bytes())?;
+        out.write("sastopo-d3-tree.json", d3::render_d3_tree(&digraph)?.as_bytes())?;
+        files.push("sastopo-d3-graph.json".to_string());
+        files.push("sastopo-d3-tree.json".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Cytoscape) {
+        debug!("Saving Cytoscape.js elements to sastopo-cytoscape.json");
+        let json = cytoscape::render_cytoscape(&digraph)?;
+        out.write("sastopo-cytoscape.json", json.as_bytes())?;
+        files.push("sastopo-cytoscape.json".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Drawio) {
+        debug!("Saving diagrams.net diagram to sastopo.drawio");
+        let xml = drawio::render_drawio(&digraph, config.options.layout(), config.options.max_depth())?;
+        out.write("sastopo.drawio", xml.as_bytes())?;
+        files.push("sastopo.drawio".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Tikz) {
+        debug!("Saving TikZ picture to sastopo.tex");
+        let layout = config.options.layout();
+        let tex = tikz::render_tikz(&digraph, layout, config.options.max_depth())?;
+        out.write("sastopo.tex", tex.as_bytes())?;
+        files.push("sastopo.tex".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Odg) {
+        debug!("Saving LibreOffice Draw drawing to sastopo.odg");
+        let odg = odg::render_odg(&digraph, config.options.layout(), config.options.max_depth())?;
+        out.write("sastopo.odg", &odg)?;
+        files.push("sastopo.odg".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Markdown) {
+        debug!("Saving Markdown report to sastopo-report.md");
+        let md = report::render_report(&digraph, config.options.locale());
+        out.write("sastopo-report.md", md.as_bytes())?;
+        files.push("sastopo-report.md".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Inventory) {
+        let reference = match config.options.inventory_reference() {
+            Some(path) => Some(read_digraph(path, &config.options)?),
+            None => None,
+        };
+        let properties = config.options.inventory_properties();
+        debug!("Saving inventory to sastopo-inventory.csv");
+        let csv = inventory::render_inventory_csv(
+            &digraph,
+            properties,
+            reference.as_ref(),
+            config.options.locale(),
+            config.options.fmri_display(),
+            config.options.key_case(),
+        );
+        out.write("sastopo-inventory.csv", csv.as_bytes())?;
+        let json = inventory::render_inventory_json(
+            &digraph,
+            properties,
+            reference.as_ref(),
+            config.options.key_case(),
+        )?;
+        out.write("sastopo-inventory.json", json.as_bytes())?;
+        files.push("sastopo-inventory.csv".to_string());
+        files.push("sastopo-inventory.json".to_string());
+    }
+
+    if config.options.has_output(OutputFormat::Cabling) {
+        let table = CablingTable::new(&digraph);
+        debug!("Saving cabling table to sastopo-cabling.csv");
+        let csv = table.render_csv(
+            config.options.locale(),
+            config.options.fmri_display(),
+            config.options.key_case(),
+        );
+        out.write("sastopo-cabling.csv", csv.as_bytes())?;
+        let json = table.to_json_with(config.options.key_case())?;
+        out.write("sastopo-cabling.json", json.as_bytes())?;
+        files.push("sastopo-cabling.csv".to_string());
+        files.push("sastopo-cabling.json".to_string());
+    }
+
+    //
+    // The snapshot goes through the sink like the rest of the output, so that
+    // it ends up in the zip file and in the manifest.
+    //
+    if config.options.keep_snapshot() {
+        debug!("Saving the snapshot to {}", SNAPSHOT_XML);
+        out.write(SNAPSHOT_XML, &fs::read(&config.xml_path)?)?;
+        files.push(SNAPSHOT_XML.to_string());
+    }
+
+    //
+    // The manifest covers all of the files written by this run, so it has to
+    // be written last.
+    //
+    if config.options.archival() {
+        debug!("Writing archive manifest");
+        let manifest = archive::manifest_json(out.hashes())?;
+        out.write(archive::MANIFEST, manifest.as_bytes())?;
+        files.push(archive::MANIFEST.to_string());
+    }
+    out.finish()?;
+    report.outputs = files.iter().map(|file| out.location(file)).collect();
+
+    let (counts, _) = expect::inventory(&digraph);
+    report.devices = counts
+        .into_iter()
+        .map(|(name, count)| (name.to_string(), count))
+        .collect();
+    report.warnings = analysis::analyze(&digraph).len();
+    report.parse_warnings = digraph.parse_warnings.clone();
+    Ok(report)
 }
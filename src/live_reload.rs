@@ -0,0 +1,115 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Live reload of the pages rendered in daemon mode.
+//
+// The daemon only writes static files, so rather than pushing anything to the
+// browser, the pages poll a small state file the daemon rewrites after every
+// capture:
+//
+//     OUTPUT_DIR/latest.json
+//
+//     {"snapshot":"snapshot-1589824800","page":"snapshot-1589824800/sastopo2svg.html"}
+//
+// When a new snapshot shows up there, the page of the previous one navigates
+// to the page of the new one, and the index page reloads itself.  Polling
+// works with any web server (and with none, in browsers allowing fetch() on
+// file URLs), which is all the daemon can rely on.
+//
+use serde::Serialize;
+
+use std::error::Error;
+
+pub(crate) const STATE_FILE: &str = "latest.json";
+
+#[derive(Serialize)]
+pub(crate) struct State<'a> {
+    pub snapshot: &'a str,
+    // page of the snapshot, relative to the state file
+    pub page: String,
+}
+
+impl<'a> State<'a> {
+    pub(crate) fn new(snapshot: &'a str) -> State<'a> {
+        State {
+            snapshot,
+            page: format!("{}/sastopo2svg.html", snapshot),
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct LiveReload {
+    // URL of the state file, relative to the page
+    pub state_url: String,
+    // the snapshot shown by the page, or None for a page that is rewritten
+    // in place (i.e. the index page) and only needs reloading
+    pub snapshot: Option<String>,
+    pub poll_secs: u64,
+}
+
+impl LiveReload {
+    //
+    // Only a snapshot page showing the latest snapshot follows on to the
+    // next one, so that older snapshots can still be looked at.
+    //
+    pub(crate) fn script(&self) -> String {
+        let snapshot = match &self.snapshot {
+            Some(snapshot) => serde_json::Value::from(snapshot.as_str()),
+            None => serde_json::Value::Null,
+        };
+        format!(
+            "<script>\n\
+             (function () {{\n\
+             \x20   var stateUrl = {};\n\
+             \x20   var snapshot = {};\n\
+             \x20   var seen = null;\n\
+             \x20   function poll() {{\n\
+             \x20       fetch(stateUrl, {{ cache: 'no-store' }})\n\
+             \x20           .then(function (response) {{\n\
+             \x20               return response.ok ? response.json() : null;\n\
+             \x20           }})\n\
+             \x20           .then(function (state) {{\n\
+             \x20               if (state === null || state.snapshot === seen) {{\n\
+             \x20                   return;\n\
+             \x20               }}\n\
+             \x20               if (seen !== null && snapshot === null) {{\n\
+             \x20                   window.location.reload();\n\
+             \x20               }} else if (seen !== null && seen === snapshot) {{\n\
+             \x20                   var base = new URL(stateUrl, window.location.href);\n\
+             \x20                   window.location.href = new URL(state.page, base).href;\n\
+             \x20               }}\n\
+             \x20               seen = state.snapshot;\n\
+             \x20           }})\n\
+             \x20           .catch(function () {{}});\n\
+             \x20   }}\n\
+             \x20   poll();\n\
+             \x20   setInterval(poll, {});\n\
+             }})();\n\
+             </script>\n",
+            serde_json::Value::from(self.state_url.as_str()),
+            snapshot,
+            self.poll_secs * 1000
+        )
+    }
+
+    //
+    // Inserts the script right before the end of the body of the page.
+    //
+    pub(crate) fn inject(&self, html: &str) -> String {
+        match html.rfind("</body>") {
+            Some(end) => format!("{}{}{}", &html[..end], self.script(), &html[end..]),
+            None => format!("{}{}", html, self.script()),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Regional formatting of the numbers and dates in the reports.
+//
+// The outputs are read by teams in different regions, who expect e.g.
+// "1.234" rather than "1,234" for a count, or "31.12.2020" rather than
+// "2020-12-31" for a date.  Only a handful of locales are supported, as the
+// conventions are built in rather than taken from the system's locale
+// database.  The default (C) keeps the machine-readable forms, i.e. counts
+// without grouping and timestamps in ISO-8601 as reported by the snapshot.
+//
+// This only applies to what's meant for people to read: the HTML page, the
+// Markdown and PDF reports and the CSV files, which are mostly opened in
+// spreadsheets.  The JSON outputs are left alone.
+//
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Locale {
+    #[default]
+    C,
+    EnUs,
+    EnGb,
+    DeDe,
+    FrFr,
+}
+
+impl std::str::FromStr for Locale {
+    type Err = String;
+
+    //
+    // Accepts the spellings of the LANG environment variable as well as BCP 47
+    // language tags, e.g. "de_DE.UTF-8" and "de-DE".  A bare language picks
+    // the region it is most commonly used with.
+    //
+    fn from_str(name: &str) -> Result<Locale, String> {
+        let tag = name.split('.').next().unwrap_or(name).replace('_', "-");
+        match tag.to_ascii_lowercase().as_str() {
+            "c" | "posix" => Ok(Locale::C),
+            "en" | "en-us" => Ok(Locale::EnUs),
+            "en-gb" => Ok(Locale::EnGb),
+            "de" | "de-de" | "de-at" | "de-ch" => Ok(Locale::DeDe),
+            "fr" | "fr-fr" => Ok(Locale::FrFr),
+            _ => Err(format!("unsupported locale: {}", name)),
+        }
+    }
+}
+
+impl Locale {
+    fn group_separator(self) -> Option<&'static str> {
+        match self {
+            Locale::C => None,
+            Locale::EnUs | Locale::EnGb => Some(","),
+            Locale::DeDe => Some("."),
+            // a no-break space, which is in Latin-1 unlike the narrow one
+            Locale::FrFr => Some("\u{a0}"),
+        }
+    }
+
+    //
+    // Spreadsheets in locales where the comma is the decimal separator expect
+    // the fields of CSV files to be separated by semicolons.
+    //
+    pub(crate) fn csv_separator(self) -> &'static str {
+        match self {
+            Locale::DeDe | Locale::FrFr => ";",
+            _ => ",",
+        }
+    }
+
+    pub(crate) fn count(self, n: usize) -> String {
+        let digits = n.to_string();
+        let separator = match self.group_separator() {
+            Some(separator) => separator,
+            None => return digits,
+        };
+        let mut end = match digits.len() % 3 {
+            0 => 3,
+            n => n,
+        };
+        let mut grouped = digits[..end].to_string();
+        while end < digits.len() {
+            grouped.push_str(separator);
+            grouped.push_str(&digits[end..end + 3]);
+            end += 3;
+        }
+        grouped
+    }
+
+    //
+    // Reformats an ISO-8601 timestamp as found in the snapshots, e.g.
+    // "2020-05-18T18:00:00Z".  Anything that doesn't parse as one is returned
+    // as is.
+    //
+    pub(crate) fn timestamp(self, iso: &str) -> String {
+        if self == Locale::C {
+            return iso.to_string();
+        }
+        let (date, time) = match iso.split_once('T') {
+            Some(parts) => parts,
+            None => return iso.to_string(),
+        };
+        let ymd: Vec<&str> = date.split('-').collect();
+        if ymd.len() != 3 || time.len() < 8 || !time.is_ascii() {
+            return iso.to_string();
+        }
+        let (year, month, day) = (ymd[0], ymd[1], ymd[2]);
+        let (hms, zone) = time.split_at(8);
+        let zone = match zone.trim_start_matches(|c: char| c == '.' || c.is_ascii_digit()) {
+            "" => String::new(),
+            "Z" => " UTC".to_string(),
+            offset => format!(" UTC{}", offset),
+        };
+
+        match self {
+            Locale::EnUs => {
+                let hour: u32 = match hms[..2].parse() {
+                    Ok(hour) => hour,
+                    Err(_) => return iso.to_string(),
+                };
+                let (hour, meridiem) = match hour {
+                    0 => (12, "AM"),
+                    1..=11 => (hour, "AM"),
+                    12 => (12, "PM"),
+                    _ => (hour - 12, "PM"),
+                };
+                format!(
+                    "{}/{}/{} {}:{} {}{}",
+                    month, day, year, hour, &hms[3..], meridiem, zone
+                )
+            }
+            Locale::DeDe => format!("{}.{}.{} {}{}", day, month, year, hms, zone),
+            _ => format!("{}/{}/{} {}{}", day, month, year, hms, zone),
+        }
+    }
+}
@@ -13,6 +13,8 @@ extern crate getopts;
 use getopts::Options;
 
 use std::env;
+use std::io::{self, IsTerminal};
+use std::path::Path;
 use std::process;
 
 extern crate sastopo2svg;
@@ -22,6 +24,42 @@ fn usage(progname: &str, opts: &Options) {
     print!("{}", opts.usage(&msg));
 }
 
+//
+// The flags that each add an output to those of -f, see add_output().
+//
+const OUTPUT_FLAGS: &[&str] = &[
+    "cabling",
+    "dot",
+    "graphml",
+    "mermaid",
+    "d3",
+    "cytoscape",
+    "drawio",
+    "tikz",
+    "odg",
+    "markdown",
+    "inventory",
+    "inventory-since",
+    "png",
+    "pdf",
+];
+
+//
+// Adds the output of one of the output flags, along with the SVG that is
+// generated by default unless with_svg is false, i.e. formats were given
+// with -f.
+//
+fn add_output(
+    builder: sastopo2svg::RenderOptionsBuilder,
+    format: sastopo2svg::OutputFormat,
+    with_svg: bool,
+) -> sastopo2svg::RenderOptionsBuilder {
+    match with_svg {
+        true => builder.output(sastopo2svg::OutputFormat::Svg).output(format),
+        false => builder.output(format),
+    }
+}
+
 fn main() {
     env_logger::init();
 
@@ -31,11 +69,335 @@ fn main() {
     let mut opts = Options::new();
     opts.optflag("h", "help", "print this usage message");
     opts.optopt("d", "OUTPUT_DIR", "Directory to output webpage to", "OUTPUT_DIR");
-    opts.optopt("x", "XML", "Output of sastopo -x", "XML");
+    opts.optmulti(
+        "x",
+        "XML",
+        "Output of sastopo -x (or a JSON export, or a support bundle containing one), \
+         or its http(s) URL (may be repeated to merge the fabrics seen from several hosts \
+         or HBAs into one picture)",
+        "XML",
+    );
+    opts.optopt(
+        "",
+        "zip",
+        "bundle all of the generated files into a zip archive instead of OUTPUT_DIR",
+        "FILE",
+    );
+    opts.optopt("c", "config", "TOML configuration file", "CONFIG");
+    opts.optopt("p", "preset", "apply a preset defined in the config file", "PRESET");
+    opts.optopt(
+        "",
+        "transition-from",
+        "also generate an animated transition from an older snapshot",
+        "XML",
+    );
+    opts.optmulti(
+        "",
+        "history",
+        "previous snapshot, devices missing since are shown as ghosts (may be repeated)",
+        "XML",
+    );
+    opts.optopt("t", "title", "title of the generated HTML page", "TITLE");
+    opts.optflag("n", "number", "assign reference numbers (I1, E1, T001...) to devices");
+    opts.optflag(
+        "",
+        "cabling",
+        "also write a table of the physical connections (CSV and JSON)",
+    );
+    opts.optmulti(
+        "f",
+        "format",
+        "output format instead of SVG: svg, json, cabling, dot, graphml, mermaid, d3, \
+         cytoscape, drawio, tikz, odg, inventory, markdown, png, pdf or text (may be repeated)",
+        "FORMAT",
+    );
+    opts.optflag(
+        "",
+        "profiles",
+        "generate a variant of the output for each profile in the config file",
+    );
+    opts.optflag("", "dot", "also write the digraph as a Graphviz DOT file");
+    opts.optflag("", "graphml", "also write the digraph as a GraphML file");
+    opts.optflag("", "mermaid", "also write the digraph as a Mermaid flowchart");
+    opts.optflag("", "d3", "also write the digraph as JSON for d3-force and d3-hierarchy");
+    opts.optflag("", "cytoscape", "also write the digraph as JSON for Cytoscape.js");
+    opts.optflag("", "drawio", "also write the topology as a diagrams.net (draw.io) diagram");
+    opts.optflag("", "tikz", "also write the topology as a TikZ picture for LaTeX");
+    opts.optflag("", "odg", "also write the topology as a LibreOffice Draw (ODG) drawing");
+    opts.optflag(
+        "",
+        "inventory",
+        "also write an inventory of all of the vertices (CSV and JSON)",
+    );
+    opts.optflag(
+        "",
+        "markdown",
+        "also write a Markdown report summarizing the fabric in tables",
+    );
+    opts.optopt(
+        "",
+        "inventory-props",
+        "comma-separated properties listed in the inventory (default: manufacturer, \
+         model, serial-number, firmware-revision, location)",
+        "LIST",
+    );
+    opts.optopt(
+        "",
+        "inventory-since",
+        "only list the vertices added, removed or changed since an older snapshot in \
+         the inventory",
+        "XML",
+    );
+    opts.optopt(
+        "",
+        "enclosure-aliases",
+        "name, confirm and correct the enclosures inferred without SES data, from a TOML \
+         file listing the expanders in each",
+        "FILE",
+    );
+    opts.optflag("", "png", "also write a PNG of the topology (requires the png feature)");
+    opts.optopt("", "dpi", "resolution of the PNG (default: 96)", "DPI");
+    opts.optflag("", "pdf", "also write a printable PDF report (requires the pdf feature)");
+    opts.optflag(
+        "",
+        "standalone",
+        "inline the SVG and images into the HTML page, making it a single portable file",
+    );
+    opts.optflag(
+        "",
+        "raw-nvlists",
+        "also dump the raw property groups of each vertex, for debugging",
+    );
+    opts.optflag(
+        "",
+        "split-initiators",
+        "render each initiator on a page of its own, with an index page linking them",
+    );
+    opts.optopt(
+        "",
+        "fmri-display",
+        "shorten the FMRIs shown in the info panel, tooltips and CSV tables: full, \
+         ellipsis:N (at most N characters) or last:N (last N components)",
+        "RULE",
+    );
+    opts.optopt(
+        "",
+        "key-case",
+        "naming of the keys in the JSON and CSV data exports: raw (default), snake or \
+         kebab",
+        "CASE",
+    );
+    opts.optflag(
+        "",
+        "column-bands",
+        "draw alternating bands behind the columns, labeled with their hop distance",
+    );
+    opts.optflag(
+        "",
+        "monochrome",
+        "show the status of devices and links with patterns rather than colors, for \
+         printing and e-ink displays",
+    );
+    opts.optflag(
+        "",
+        "instances",
+        "show the instance of each vertex in the corner of its icon",
+    );
+    opts.optflag(
+        "",
+        "svgz",
+        "write the SVG gzip-compressed (.svgz), which web servers have to serve with \
+         Content-Encoding: gzip",
+    );
+    opts.optflag(
+        "",
+        "vertex-pages",
+        "also write a page listing all of the properties of each vertex, linked from the \
+         info panel",
+    );
+    opts.optopt(
+        "",
+        "input-format",
+        "format of the snapshot: auto (default, by extension and contents), xml, json or \
+         nvlist (packed)",
+        "FORMAT",
+    );
+    opts.optflag(
+        "",
+        "validate",
+        "check the XML snapshot against the topology schema before parsing it, listing \
+         where it doesn't match",
+    );
+    opts.optflag(
+        "",
+        "layout-hash",
+        "print a hash of the structure of the layout, which only changes when the devices or \
+         how they're connected and placed do",
+    );
+    opts.optflag(
+        "",
+        "lenient",
+        "skip the vertices with malformed property groups, rendering the rest of the topology \
+         and listing what was skipped",
+    );
+    opts.optopt(
+        "",
+        "empty-propgroups",
+        "how to handle property groups without values: skip (default), warn or error",
+        "POLICY",
+    );
+    opts.optopt(
+        "",
+        "locale",
+        "format the numbers and dates in the reports for a locale: C (default), en-US, \
+         en-GB, de-DE or fr-FR",
+        "LOCALE",
+    );
+    opts.optflag(
+        "",
+        "reachability",
+        "color devices by the initiators they can be reached from",
+    );
+    opts.optflag(
+        "",
+        "mark-single-path",
+        "outline the targets without a redundant path to them, and list the paths to each \
+         target",
+    );
+    opts.optflag(
+        "",
+        "redundancy",
+        "print the number of paths to each target that don't share a link instead of rendering",
+    );
+    opts.optopt(
+        "",
+        "max-props",
+        "maximum number of properties embedded per vertex, the rest are \
+         written to a separate JSON file",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "max-depth",
+        "maximum depth of the topology in vertices, deeper snapshots are rejected \
+         (default: 64)",
+        "N",
+    );
+    opts.optflag(
+        "",
+        "lazy-props",
+        "write all properties to a separate JSON file that is loaded on demand",
+    );
+    opts.optflag(
+        "",
+        "archive",
+        "write a manifest with integrity hashes of the generated files",
+    );
+    opts.optopt(
+        "",
+        "verify-archive",
+        "check an archived output directory against its manifest",
+        "DIR",
+    );
+    opts.optopt(
+        "",
+        "pack-archive",
+        "pack a verified archived output directory into DIR.tar.gz",
+        "DIR",
+    );
+    opts.optopt(
+        "",
+        "unpack-archive",
+        "unpack an archive written by --pack-archive next to it, and verify it",
+        "FILE",
+    );
+    opts.optflag(
+        "",
+        "capture",
+        "capture the topology of the local system and render it, instead of rendering -x \
+         (the XML is saved to OUTPUT_DIR/sastopo.xml)",
+    );
+    opts.optflag(
+        "",
+        "daemon",
+        "periodically capture and render the local topology into OUTPUT_DIR",
+    );
+    opts.optopt(
+        "",
+        "interval",
+        "seconds between two captures in daemon mode (default: 3600)",
+        "SECS",
+    );
+    opts.optopt(
+        "",
+        "retain",
+        "number of snapshots kept in daemon mode (default: 48)",
+        "N",
+    );
+    opts.optopt(
+        "",
+        "capture-cmd",
+        "shell command writing the topology XML to stdout, for --capture and daemon mode \
+         (default: sastopo -x)",
+        "CMD",
+    );
+    opts.optflag(
+        "",
+        "live-reload",
+        "in daemon mode, make open pages follow on to new snapshots as they are rendered",
+    );
+    opts.optmulti(
+        "",
+        "notify-url",
+        "in daemon mode, POST a summary of topology changes to URL (may be repeated)",
+        "URL",
+    );
+    opts.optmulti(
+        "",
+        "notify-cmd",
+        "in daemon mode, run CMD with a summary of topology changes on stdin (may be repeated)",
+        "CMD",
+    );
+    opts.optopt(
+        "",
+        "assert",
+        "check the snapshot against a file of expectations instead of rendering it",
+        "EXPECT",
+    );
+    opts.optflag(
+        "",
+        "check",
+        "run the integrity and policy checks (and those of --assert) and print the results \
+         as JUnit XML instead of rendering",
+    );
+    opts.optflag(
+        "",
+        "dry-run",
+        "lay out the graph and print its dimensions without writing anything",
+    );
+    opts.optopt(
+        "",
+        "paths",
+        "print the paths from each initiator to a target (FMRI, WWN or reference) side \
+         by side instead of rendering",
+        "TARGET",
+    );
+    opts.optflag(
+        "",
+        "events",
+        "stream the vertices and edges to stdout as JSON Lines instead of rendering",
+    );
+    opts.optopt(
+        "",
+        "pipeline",
+        "run a pipeline of select, annotate and render stages instead of rendering, e.g. \
+         'select name=target | annotate tier=cold | render svg json'",
+        "SCRIPT",
+    );
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
-        Err(e) => panic!(e.to_string()),
+        Err(e) => panic!("{}", e),
     };
 
     if matches.opt_present("h") {
@@ -43,8 +405,82 @@ fn main() {
         process::exit(2);
     }
 
+    if let Some(dir) = matches.opt_str("verify-archive") {
+        match sastopo2svg::verify_archive(&dir) {
+            Ok(problems) if problems.is_empty() => {
+                println!("{}: OK", dir);
+                process::exit(0);
+            }
+            Ok(problems) => {
+                for problem in problems {
+                    println!("{}", problem);
+                }
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("failed to verify {}: {}", dir, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Some(dir) = matches.opt_str("pack-archive") {
+        let dir = dir.trim_end_matches('/');
+        let path = format!("{}.tar.gz", dir);
+        if let Err(e) = sastopo2svg::pack_archive(dir, &path) {
+            eprintln!("failed to pack {}: {}", dir, e);
+            process::exit(1);
+        }
+        println!("{}", path);
+        process::exit(0);
+    }
+
+    if let Some(path) = matches.opt_str("unpack-archive") {
+        let dir = match path.strip_suffix(".tar.gz") {
+            Some(dir) => dir.to_string(),
+            None => {
+                eprintln!("{} isn't a .tar.gz file written by --pack-archive", path);
+                process::exit(2);
+            }
+        };
+        match sastopo2svg::unpack_archive(&path, &dir) {
+            Ok(problems) if problems.is_empty() => {
+                println!("{}: OK", dir);
+                process::exit(0);
+            }
+            Ok(problems) => {
+                for problem in problems {
+                    println!("{}", problem);
+                }
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("failed to unpack {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    let dry_run = matches.opt_present("dry-run");
+    // the text tree is printed, so it doesn't need an output directory
+    let formats = matches.opt_strs("f");
+    let text_only = !formats.is_empty()
+        && formats.iter().all(|f| f == "text")
+        && !OUTPUT_FLAGS.iter().any(|flag| matches.opt_present(flag));
+
     let outdir = match matches.opt_str("d") {
         Some(path) => path,
+        None if dry_run
+            || text_only
+            || matches.opt_present("paths")
+            || matches.opt_present("redundancy")
+            || matches.opt_present("events")
+            || matches.opt_present("assert")
+            || matches.opt_present("check")
+            || matches.opt_present("zip") =>
+        {
+            String::new()
+        }
         None => {
             eprintln!("-d argument is required");
             usage(&progname, &opts);
@@ -52,8 +488,20 @@ fn main() {
         }
     };
 
-    let xml_path = match matches.opt_str("x") {
+    let daemon = matches.opt_present("daemon");
+    let capture = matches.opt_present("capture");
+    let capture_command = matches
+        .opt_str("capture-cmd")
+        .unwrap_or_else(|| sastopo2svg::DEFAULT_CAPTURE_COMMAND.to_string());
+
+    let mut xml_path = match matches.opt_str("x") {
+        Some(_) if capture => {
+            eprintln!("-x and --capture are mutually exclusive");
+            usage(&progname, &opts);
+            process::exit(2);
+        }
         Some(path) => path,
+        None if daemon || capture => String::new(),
         None => {
             eprintln!("-x argument is required");
             usage(&progname, &opts);
@@ -61,14 +509,523 @@ fn main() {
         }
     };
 
-    let config = sastopo2svg::Config::new(outdir, xml_path);
+    // the snapshots following the first one are merged into it
+    let merged: Vec<String> = matches.opt_strs("x").into_iter().skip(1).collect();
+    if !merged.is_empty() && (matches.opt_present("check") || matches.opt_present("assert")) {
+        eprintln!("--check and --assert take a single -x argument");
+        usage(&progname, &opts);
+        process::exit(2);
+    }
+
+    //
+    // The captured XML is kept along with the rendering, like the daemon does
+    // for its snapshots.  Without an output directory (e.g. for --dry-run) it
+    // goes to the temporary directory.  The run writes it to the output again
+    // (see keep_snapshot()), so that it is recorded in the manifest and goes
+    // into the zip file.
+    //
+    if capture {
+        let dir = match outdir.as_str() {
+            "" => std::env::temp_dir().to_string_lossy().to_string(),
+            dir => dir.to_string(),
+        };
+        xml_path = format!("{}/sastopo.xml", dir);
+        let result = std::fs::create_dir_all(&dir)
+            .map_err(|e| e.into())
+            .and_then(|_| sastopo2svg::capture_snapshot(&capture_command, &xml_path));
+        if let Err(e) = result {
+            eprintln!("failed to capture the topology: {}", e);
+            process::exit(1);
+        }
+    }
+
+    let conffile = match matches.opt_str("c") {
+        Some(path) => match sastopo2svg::ConfigFile::load(&path) {
+            Ok(conffile) => Some(conffile),
+            Err(e) => {
+                eprintln!("failed to load config file {}: {}", path, e);
+                process::exit(2);
+            }
+        },
+        None => None,
+    };
+
+    let mut builder = sastopo2svg::RenderOptions::builder();
+    if let Some(conffile) = &conffile {
+        builder = conffile.apply(builder);
+    }
+    if let Some(preset) = matches.opt_str("preset") {
+        let applied = match &conffile {
+            Some(conffile) => conffile.apply_preset(&preset, builder),
+            None => {
+                eprintln!("--preset requires a configuration file");
+                process::exit(2);
+            }
+        };
+        builder = match applied {
+            Ok(builder) => builder,
+            Err(e) => {
+                eprintln!("failed to apply preset {}: {}", preset, e);
+                process::exit(2);
+            }
+        };
+    }
+    //
+    // The flags only turn things on, so as not to undo what the
+    // configuration file or the preset turned on.
+    //
+    if matches.opt_present("n") {
+        builder = builder.numbering(true);
+    }
+    if matches.opt_present("archive") {
+        builder = builder.archival(true);
+    }
+    if capture {
+        builder = builder.keep_snapshot(true);
+    }
+    if matches.opt_present("reachability") {
+        builder = builder.reachability(true);
+    }
+    if matches.opt_present("mark-single-path") {
+        builder = builder.mark_single_path(true);
+    }
+    if matches.opt_present("lazy-props") {
+        builder = builder.lazy_properties(true);
+    }
+    if let Some(path) = matches.opt_str("transition-from") {
+        builder = builder.transition_from(&path);
+    }
+    for path in matches.opt_strs("history") {
+        builder = builder.history(&path);
+    }
+    for path in &merged {
+        builder = builder.merge(path);
+    }
+    //
+    // The output flags add to the SVG that is generated by default, or, if
+    // formats were given with -f, to those.
+    //
+    let with_svg = formats.is_empty();
+    if matches.opt_present("cabling") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Cabling, with_svg);
+    }
+    if matches.opt_present("dot") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Dot, with_svg);
+    }
+    if matches.opt_present("graphml") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::GraphMl, with_svg);
+    }
+    if matches.opt_present("mermaid") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Mermaid, with_svg);
+    }
+    if matches.opt_present("d3") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::D3, with_svg);
+    }
+    if matches.opt_present("cytoscape") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Cytoscape, with_svg);
+    }
+    if matches.opt_present("drawio") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Drawio, with_svg);
+    }
+    if matches.opt_present("tikz") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Tikz, with_svg);
+    }
+    if matches.opt_present("odg") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Odg, with_svg);
+    }
+    if let Some(path) = matches.opt_str("inventory-since") {
+        builder = builder
+            .inventory_reference(&path)
+            .output(sastopo2svg::OutputFormat::Inventory);
+    }
+    if let Some(path) = matches.opt_str("enclosure-aliases") {
+        builder = builder.enclosure_aliases(&path);
+    }
+    if matches.opt_present("markdown") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Markdown, with_svg);
+    }
+    if matches.opt_present("inventory") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Inventory, with_svg);
+    }
+    if let Some(list) = matches.opt_str("inventory-props") {
+        builder = builder.inventory_properties(
+            list.split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        );
+    }
+    if matches.opt_present("png") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Png, with_svg);
+    }
+    if matches.opt_present("standalone") {
+        builder = builder.standalone(true);
+    }
+    if matches.opt_present("raw-nvlists") {
+        builder = builder.raw_nvlists(true);
+    }
+    if matches.opt_present("svgz") {
+        builder = builder.svgz(true);
+    }
+    if matches.opt_present("monochrome") {
+        builder = builder.monochrome(true);
+    }
+    if matches.opt_present("column-bands") {
+        builder = builder.column_bands(true);
+    }
+    if matches.opt_present("instances") {
+        builder = builder.instances(true);
+    }
+    if let Some(rule) = matches.opt_str("fmri-display") {
+        match rule.parse::<sastopo2svg::FmriDisplay>() {
+            Ok(rule) => builder = builder.fmri_display(rule),
+            Err(e) => {
+                eprintln!("{}", e);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        }
+    }
+    if let Some(case) = matches.opt_str("key-case") {
+        match case.parse::<sastopo2svg::KeyCase>() {
+            Ok(case) => builder = builder.key_case(case),
+            Err(e) => {
+                eprintln!("{}", e);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        }
+    }
+    if matches.opt_present("vertex-pages") {
+        builder = builder.vertex_pages(true);
+    }
+    if matches.opt_present("split-initiators") {
+        builder = builder.split_initiators(true);
+    }
+    if matches.opt_present("pdf") {
+        builder = add_output(builder, sastopo2svg::OutputFormat::Pdf, with_svg);
+    }
+    if let Some(dpi) = matches.opt_str("dpi") {
+        match dpi.parse::<u32>() {
+            Ok(dpi) if dpi > 0 => builder = builder.png_dpi(dpi),
+            _ => {
+                eprintln!("invalid value for --dpi: {}", dpi);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        }
+    }
+    if let Some(locale) = matches.opt_str("locale") {
+        match locale.parse::<sastopo2svg::Locale>() {
+            Ok(locale) => builder = builder.locale(locale),
+            Err(e) => {
+                eprintln!("{}", e);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        }
+    }
+    if matches.opt_present("validate") {
+        builder = builder.validate(true);
+    }
+    if matches.opt_present("layout-hash") {
+        builder = builder.layout_hash(true);
+    }
+    if matches.opt_present("lenient") {
+        builder = builder.lenient(true);
+    }
+    if let Some(format) = matches.opt_str("input-format") {
+        match format.parse::<sastopo2svg::InputFormat>() {
+            Ok(format) => builder = builder.input_format(format),
+            Err(e) => {
+                eprintln!("{}", e);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        }
+    }
+    if let Some(policy) = matches.opt_str("empty-propgroups") {
+        match policy.parse::<sastopo2svg::EmptyPropGroups>() {
+            Ok(policy) => builder = builder.empty_propgroups(policy),
+            Err(e) => {
+                eprintln!("{}", e);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        }
+    }
+    for format in matches.opt_strs("f") {
+        match format.parse::<sastopo2svg::OutputFormat>() {
+            Ok(format) => builder = builder.output(format),
+            Err(e) => {
+                eprintln!("{}", e);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        }
+    }
+    if let Some(max) = matches.opt_str("max-props") {
+        match max.parse::<usize>() {
+            Ok(max) => builder = builder.max_properties(max),
+            Err(_) => {
+                eprintln!("invalid value for --max-props: {}", max);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        }
+    }
+    if let Some(max) = matches.opt_str("max-depth") {
+        match max.parse::<u32>() {
+            Ok(max) => builder = builder.max_depth(max),
+            Err(_) => {
+                eprintln!("invalid value for --max-depth: {}", max);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        }
+    }
+    if let Some(title) = matches.opt_str("t") {
+        builder = builder.title(&title);
+    }
+    //
+    // The snapshot is read as the options say (e.g. as to property groups
+    // without values) for the checks as well.
+    //
+    let options = builder.clone().build();
+
+    //
+    // The health check exits with 1 if any of the checks failed, but only
+    // once it has printed the results of all of them.
+    //
+    if matches.opt_present("check") {
+        let expect = match matches.opt_str("assert") {
+            Some(path) => match sastopo2svg::Expectations::load(&path) {
+                Ok(expect) => Some(expect),
+                Err(e) => {
+                    eprintln!("failed to load expectations {}: {}", path, e);
+                    process::exit(2);
+                }
+            },
+            None => None,
+        };
+        let report = sastopo2svg::health_check(&xml_path, expect.as_ref(), &options);
+        print!("{}", report.to_junit());
+        process::exit(if report.passed() { 0 } else { 1 });
+    }
+
+    if let Some(path) = matches.opt_str("assert") {
+        let failures = sastopo2svg::Expectations::load(&path)
+            .and_then(|expect| sastopo2svg::check_expectations(&xml_path, &expect, &options));
+        match failures {
+            Ok(failures) if failures.is_empty() => {
+                println!("{}: OK", xml_path);
+                process::exit(0);
+            }
+            Ok(failures) => {
+                for failure in failures {
+                    println!("FAIL: {}", failure);
+                }
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("failed to check {}: {}", xml_path, e);
+                process::exit(2);
+            }
+        }
+    }
+
+    //
+    // Generate one variant of the output per profile in the configuration
+    // file, each in its own subdirectory.
+    //
+    if matches.opt_present("profiles") {
+        let conffile = match &conffile {
+            Some(conffile) if !conffile.profiles.is_empty() => conffile,
+            _ => {
+                eprintln!("--profiles requires a configuration file defining profiles");
+                process::exit(2);
+            }
+        };
+        //
+        // The profiles go into subdirectories of the output directory, so one
+        // has to be given explicitly, and they can't share a single zip file.
+        //
+        if matches.opt_present("zip") {
+            eprintln!("--profiles and --zip are mutually exclusive");
+            usage(&progname, &opts);
+            process::exit(2);
+        }
+        if !matches.opt_present("d") {
+            eprintln!("--profiles requires the -d argument");
+            usage(&progname, &opts);
+            process::exit(2);
+        }
+        for name in conffile.profile_names() {
+            let dir = sastopo2svg::ConfigFile::profile_dir(Path::new(&outdir), name);
+            let result = conffile
+                .apply_profile(name, builder.clone())
+                .and_then(|profile| {
+                    let config = sastopo2svg::Config::with_options(
+                        dir.to_string_lossy().to_string(),
+                        xml_path.clone(),
+                        profile.build(),
+                    );
+                    sastopo2svg::run(&config)
+                });
+            if let Err(e) = result {
+                eprintln!("An error occurred generating profile {}: {}", name, e);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
+
+    if daemon {
+        let mut config = sastopo2svg::DaemonConfig::new(outdir, options);
+        config.capture_command = capture_command;
+        if let Some(secs) = matches.opt_str("interval") {
+            match secs.parse::<u64>() {
+                Ok(secs) if secs > 0 => config.interval = std::time::Duration::from_secs(secs),
+                _ => {
+                    eprintln!("invalid value for --interval: {}", secs);
+                    usage(&progname, &opts);
+                    process::exit(2);
+                }
+            }
+        }
+        if let Some(count) = matches.opt_str("retain") {
+            match count.parse::<usize>() {
+                Ok(count) if count > 0 => config.retention = count,
+                _ => {
+                    eprintln!("invalid value for --retain: {}", count);
+                    usage(&progname, &opts);
+                    process::exit(2);
+                }
+            }
+        }
+        if matches.opt_present("live-reload") {
+            config.live_reload = true;
+        }
+        for url in matches.opt_strs("notify-url") {
+            config.notifiers.push(sastopo2svg::Notifier::Webhook(url));
+        }
+        for cmd in matches.opt_strs("notify-cmd") {
+            config.notifiers.push(sastopo2svg::Notifier::Command(cmd));
+        }
+        if let Err(e) = sastopo2svg::run_daemon(&config) {
+            eprintln!("An error occurred: {}", e);
+        }
+        process::exit(1);
+    }
+
+    let config = sastopo2svg::Config::with_options(outdir, xml_path, options);
+
+    if let Some(target) = matches.opt_str("paths") {
+        match sastopo2svg::compare_paths(&config, &target) {
+            Ok(report) => {
+                println!("{}", report);
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if matches.opt_present("redundancy") {
+        match sastopo2svg::redundancy_report(&config) {
+            Ok(report) => {
+                println!("{}", report);
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Some(script) = matches.opt_str("pipeline") {
+        let pipeline: sastopo2svg::Pipeline = match script.parse() {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                eprintln!("invalid pipeline: {}", e);
+                usage(&progname, &opts);
+                process::exit(2);
+            }
+        };
+        match sastopo2svg::run_pipeline(&config, &pipeline) {
+            Ok(reports) => {
+                if io::stdout().is_terminal() {
+                    for report in reports {
+                        println!("{}", report);
+                    }
+                }
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                process::exit(1);
+            }
+        }
+    }
 
-    match sastopo2svg::run(&config) {
-        Ok(_r) => {
+    if matches.opt_present("events") {
+        if let Err(e) = sastopo2svg::stream_events(&config, &mut io::stdout().lock()) {
+            eprintln!("An error occurred: {}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    if dry_run {
+        match sastopo2svg::dry_run(&config) {
+            Ok(report) => {
+                println!("{}", report);
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if config.options.has_output(sastopo2svg::OutputFormat::Text) {
+        match sastopo2svg::text_tree(&config) {
+            Ok(tree) => print!("{}", tree),
+            Err(e) => {
+                eprintln!("An error occurred: {}", e);
+                process::exit(1);
+            }
+        }
+        if config.options.outputs() == [sastopo2svg::OutputFormat::Text] {
+            process::exit(0);
+        }
+    }
+
+    let result = match matches.opt_str("zip") {
+        Some(path) => sastopo2svg::ZipSink::create(&path)
+            .and_then(|mut sink| sastopo2svg::run_with_sink(&config, &mut sink)),
+        None => sastopo2svg::run(&config),
+    };
+    match result {
+        Ok(report) => {
+            //
+            // Give interactive users an idea of what was rendered, without
+            // cluttering the output of scripts.
+            //
+            if io::stdout().is_terminal() {
+                println!("{}", report);
+            } else if let Some(hash) = &report.layout_hash {
+                // for scripts comparing it with that of the previous run
+                println!("{}", hash);
+            }
             process::exit(0);
         }
         Err(e) => {
-            eprintln!("An error occurred: {}", e.to_string());
+            eprintln!("An error occurred: {}", e);
             process::exit(1);
         }
     }
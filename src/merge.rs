@@ -0,0 +1,101 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Merging of the snapshots of a multi-fabric topology.
+//
+// A snapshot only shows the fabric as seen from the HBAs of the host it was
+// taken on.  Where several hosts (or the HBAs of different domains) share
+// enclosures, e.g. in a dual-domain setup, the snapshots taken from each of
+// them can be merged into one picture of the whole fabric.
+//
+// The same device shows up in each snapshot it is reachable from, under
+// FMRIs that only differ in the authority (the HBA it was discovered
+// through).  The vertices of a snapshot being merged in are therefore matched
+// with those already in the digraph by SAS address, i.e. by the path of their
+// FMRI following the authority (see device_key()), so that a shared expander,
+// along with its ports and the drives behind it, is drawn once, with the
+// edges from both snapshots.  The vertices already in the digraph keep their
+// FMRIs and properties.
+//
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::fmri::device_key;
+use crate::{SasDigraph, SimpleError};
+
+//
+// Merge the digraph read from path into the digraph.
+//
+pub(crate) fn merge(
+    digraph: &mut SasDigraph,
+    other: SasDigraph,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    if other.scheme != digraph.scheme {
+        return Err(Box::new(SimpleError(format!(
+            "can't merge the {}-scheme snapshot {} into a {}-scheme topology",
+            other.scheme, path, digraph.scheme
+        ))));
+    }
+
+    // the page is titled with the names of all of the hosts
+    if !digraph.nodename.split(", ").any(|name| name == other.nodename) {
+        digraph.nodename = format!("{}, {}", digraph.nodename, other.nodename);
+    }
+
+    //
+    // The same device can already show up more than once (once per HBA of
+    // the first snapshot), in which case the vertices merged in are matched
+    // with the first of them.
+    //
+    let mut fmris: Vec<&String> = digraph.vertices.keys().collect();
+    fmris.sort();
+    let mut by_address: HashMap<&str, &String> = HashMap::new();
+    for fmri in fmris {
+        by_address.entry(device_key(fmri)).or_insert(fmri);
+    }
+    let matched: HashMap<String, String> = other
+        .vertices
+        .keys()
+        .filter_map(|fmri| {
+            by_address.get(device_key(fmri)).map(|to| (fmri.clone(), to.to_string()))
+        })
+        .collect();
+
+    for (fmri, mut vtx) in other.vertices {
+        for edge in vtx.outgoing_edges.iter_mut().flatten() {
+            if let Some(to) = matched.get(edge) {
+                *edge = to.clone();
+            }
+        }
+        let to = match matched.get(&fmri) {
+            Some(to) => to,
+            None => {
+                digraph.vertices.insert(fmri, vtx);
+                continue;
+            }
+        };
+        let mut existing = digraph.vertices[to].outgoing_edges.clone();
+        for edge in vtx.outgoing_edges.into_iter().flatten() {
+            let edges = existing.get_or_insert_with(Vec::new);
+            if !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+        digraph.vertices.set_edges(to, existing);
+    }
+
+    for fmri in other.initiators {
+        if !matched.contains_key(&fmri) {
+            digraph.initiators.push(fmri);
+        }
+    }
+    digraph.parse_warnings.extend(other.parse_warnings);
+    Ok(())
+}
@@ -0,0 +1,76 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Mermaid flowchart output.
+//
+// GitHub and GitLab render Mermaid code blocks in Markdown, so this makes it
+// possible to paste a topology straight into a wiki page or an issue.  Each
+// type of vertex gets its own node shape, and nodes are labeled with the name
+// and instance of the vertex (and its reference, if numbering is enabled).
+// Unlike the DOT output, properties are left out to keep the diagram legible.
+//
+use crate::{stable_id, SasDigraph, EXPANDER, INITIATOR, PORT, TARGET};
+
+//
+// Mermaid labels are quoted, and quotes within them are written as entity
+// codes, which Mermaid spells with a leading '#' rather than '&'.
+//
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+//
+// Returns the brackets delimiting the label of a node, which determine its
+// shape.
+//
+fn shape(name: &str) -> (&'static str, &'static str) {
+    match name {
+        INITIATOR => ("[[", "]]"),
+        PORT => ("((", "))"),
+        EXPANDER => ("{{", "}}"),
+        TARGET => ("[(", ")]"),
+        _ => ("[", "]"),
+    }
+}
+
+pub(crate) fn render_mermaid(digraph: &SasDigraph) -> String {
+    let mut mermaid = String::from("graph LR\n");
+
+    //
+    // Sort the vertices by FMRI, so that the output of two runs on the same
+    // snapshot can be compared.
+    //
+    let mut fmris: Vec<&String> = digraph.vertices.keys().collect();
+    fmris.sort();
+
+    for fmri in &fmris {
+        let vtx = &digraph.vertices[*fmri];
+        let mut label = format!("{} {}", vtx.name, vtx.instance);
+        if let Some(reference) = &vtx.reference {
+            label.push_str(&format!("<br/>{}", reference));
+        }
+        let (open, close) = shape(&vtx.name);
+        mermaid.push_str(&format!(
+            "    {}{}\"{}\"{}\n",
+            stable_id(fmri),
+            open,
+            mermaid_escape(&label),
+            close
+        ));
+    }
+
+    for fmri in &fmris {
+        if let Some(edges) = &digraph.vertices[*fmri].outgoing_edges {
+            for edge in edges {
+                mermaid.push_str(&format!("    {} --> {}\n", stable_id(fmri), stable_id(edge)));
+            }
+        }
+    }
+    mermaid
+}
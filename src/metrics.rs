@@ -0,0 +1,177 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Layout quality metrics.
+//
+// Objective numbers for comparing layouts, e.g. between two sets of options
+// or two versions of the layout code:
+//
+//   - edge crossings: the number of places where two edges cross
+//   - edge length: the total length of the edges as drawn
+//   - overlaps: the number of pairs of overlapping vertices, plus the number
+//     of edges passing through a vertex they don't connect
+//
+// The edges are measured the way build_svg() draws them: a stub leaving the
+// right side of the source vertex, a vertical segment and a horizontal
+// segment ending on the left side of the destination vertex.  Edges sharing
+// the stub of their source (or running along the same line) aren't counted as
+// crossing.
+//
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::AddAssign;
+
+use crate::{Layout, SasDigraph};
+
+// length of the stub leaving the source vertex, see build_svg()
+const EDGE_STUB: i64 = 50;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LayoutMetrics {
+    pub edge_crossings: usize,
+    pub edge_length: u64,
+    pub overlaps: usize,
+}
+
+impl AddAssign for LayoutMetrics {
+    fn add_assign(&mut self, other: LayoutMetrics) {
+        self.edge_crossings += other.edge_crossings;
+        self.edge_length += other.edge_length;
+        self.overlaps += other.overlaps;
+    }
+}
+
+impl fmt::Display for LayoutMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} edge crossings, edge length {}, {} overlaps",
+            self.edge_crossings, self.edge_length, self.overlaps
+        )
+    }
+}
+
+// a horizontal or vertical segment, from (x1, y1) to (x2, y2)
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    x1: i64,
+    y1: i64,
+    x2: i64,
+    y2: i64,
+}
+
+impl Segment {
+    fn new(x1: i64, y1: i64, x2: i64, y2: i64) -> Segment {
+        Segment {
+            x1: x1.min(x2),
+            y1: y1.min(y2),
+            x2: x1.max(x2),
+            y2: y1.max(y2),
+        }
+    }
+
+    fn len(&self) -> u64 {
+        ((self.x2 - self.x1) + (self.y2 - self.y1)) as u64
+    }
+
+    fn is_vertical(&self) -> bool {
+        self.x1 == self.x2
+    }
+
+    //
+    // Whether a horizontal and a vertical segment cross in the interior of
+    // both, i.e. not where one of them ends.
+    //
+    fn crosses(&self, other: &Segment) -> bool {
+        let (h, v) = match (self.is_vertical(), other.is_vertical()) {
+            (false, true) => (self, other),
+            (true, false) => (other, self),
+            _ => return false,
+        };
+        h.x1 < v.x1 && v.x1 < h.x2 && v.y1 < h.y1 && h.y1 < v.y2
+    }
+
+    //
+    // Whether the segment passes through the interior of a rectangle.
+    //
+    fn enters(&self, (x, y, width, height): (i64, i64, i64, i64)) -> bool {
+        self.x1 < x + width && x < self.x2.max(self.x1 + 1)
+            && self.y1 < y + height && y < self.y2.max(self.y1 + 1)
+    }
+}
+
+pub(crate) fn measure(
+    digraph: &SasDigraph,
+    positions: &HashMap<String, (u32, u32)>,
+    layout: &Layout,
+) -> LayoutMetrics {
+    let width = layout.vertex_width as i64;
+    let height = layout.vertex_height as i64;
+    let rect = |fmri: &str| {
+        let (x, y) = positions[fmri];
+        (x as i64, y as i64, width, height)
+    };
+
+    let mut fmris: Vec<&String> = positions.keys().collect();
+    fmris.sort();
+
+    let mut metrics = LayoutMetrics::default();
+    for (i, a) in fmris.iter().enumerate() {
+        let (ax, ay, _, _) = rect(a);
+        for b in &fmris[i + 1..] {
+            let (bx, by, _, _) = rect(b);
+            if (ax - bx).abs() < width && (ay - by).abs() < height {
+                metrics.overlaps += 1;
+            }
+        }
+    }
+
+    // the segments of each edge, along with the vertices it connects
+    let mut edges: Vec<(&str, &str, Vec<Segment>)> = Vec::new();
+    for from in &fmris {
+        let (sx, sy, _, _) = rect(from);
+        for to in digraph.vertices[*from].outgoing_edges.iter().flatten() {
+            if !positions.contains_key(to) {
+                continue;
+            }
+            let (tx, ty, _, _) = rect(to);
+            let (start_x, start_y) = (sx + width, sy + (height / 2));
+            let mid_x = start_x + EDGE_STUB;
+            let end_y = ty + (height / 2);
+            edges.push((
+                from,
+                to,
+                vec![
+                    Segment::new(start_x, start_y, mid_x, start_y),
+                    Segment::new(mid_x, start_y, mid_x, end_y),
+                    Segment::new(mid_x, end_y, tx, end_y),
+                ],
+            ));
+        }
+    }
+
+    for (i, (from, to, segments)) in edges.iter().enumerate() {
+        metrics.edge_length += segments.iter().map(|s| s.len()).sum::<u64>();
+        for (_, _, other) in &edges[i + 1..] {
+            metrics.edge_crossings += segments
+                .iter()
+                .map(|s| other.iter().filter(|o| s.crosses(o)).count())
+                .sum::<usize>();
+        }
+        for fmri in &fmris {
+            if fmri.as_str() != *from
+                && fmri.as_str() != *to
+                && segments.iter().any(|s| s.enters(rect(fmri)))
+            {
+                metrics.overlaps += 1;
+            }
+        }
+    }
+    metrics
+}
@@ -0,0 +1,83 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Monochrome rendering.
+//
+// Printed runbooks and e-ink displays lose the colors the status of the
+// vertices and edges is normally shown in.  On a monochrome theme the same
+// statuses are instead encoded in patterns: unhealthy vertices are outlined
+// with a dashed line and hatched (sparsely if degraded, cross-hatched if
+// faulted), and their edges are dashed.  The hatching patterns are defined
+// once per document and referenced by id.
+//
+use svg::node::element::{Definitions, Path, Pattern};
+
+use crate::analysis::VertexStatus;
+
+//
+// Dash pattern the outline and the edges of a vertex with the specified
+// status are drawn with.
+//
+pub(crate) fn dash(status: VertexStatus) -> Option<&'static str> {
+    match status {
+        VertexStatus::Ok => None,
+        VertexStatus::Degraded => Some("10,5"),
+        VertexStatus::Faulted => Some("3,3"),
+    }
+}
+
+//
+// Fill referencing the hatching pattern of the specified status.
+//
+pub(crate) fn hatching(status: VertexStatus) -> Option<&'static str> {
+    match status {
+        VertexStatus::Ok => None,
+        VertexStatus::Degraded => Some("url(#hatch-degraded)"),
+        VertexStatus::Faulted => Some("url(#hatch-faulted)"),
+    }
+}
+
+fn pattern(id: &str, size: u32, path: &str) -> Pattern {
+    Pattern::new()
+        .set("id", id)
+        .set("patternUnits", "userSpaceOnUse")
+        .set("width", size)
+        .set("height", size)
+        .add(
+            Path::new()
+                .set("d", path)
+                .set("stroke", "black")
+                .set("stroke-width", 1.5)
+                .set("stroke-opacity", 0.6),
+        )
+}
+
+pub(crate) fn patterns() -> Definitions {
+    Definitions::new()
+        .add(pattern("hatch-degraded", 10, "M0,10 L10,0"))
+        .add(pattern("hatch-faulted", 6, "M0,6 L6,0 M0,0 L6,6"))
+}
+
+//
+// Gray the segments of the health summary are filled with, darker for the
+// faster links but light enough for black labels.  The unhealthy links are
+// hatched instead.
+//
+pub(crate) fn segment_fill(rate: Option<u64>, status: VertexStatus) -> &'static str {
+    if let Some(fill) = hatching(status) {
+        return fill;
+    }
+    match rate {
+        Some(rate) if rate >= 0xb => "#909090",
+        Some(0xa) => "#B0B0B0",
+        Some(0x9) => "#D0D0D0",
+        Some(_) => "#E8E8E8",
+        None => "#FFFFFF",
+    }
+}
@@ -0,0 +1,169 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Change notifications.
+//
+// In daemon mode, every new snapshot is compared against the previous one.
+// If the topology changed, or the analysis reports a problem that it didn't
+// report before, a summary of the changes is sent to each of the configured
+// notifiers, as a JSON document like:
+//
+//     {
+//       "host": "headnode",
+//       "timestamp": "2020-05-18T18:00:00Z",
+//       "snapshot": "snapshot-1589824800",
+//       "added_vertices": [],
+//       "removed_vertices": [ "sas://:hba=.../target=5000c500a0b1c2d3" ],
+//       "added_edges": [],
+//       "removed_edges": [ [ "sas://...", "sas://..." ] ],
+//       "new_findings": [ { "kind": "duplicate WWN", "message": "..." } ]
+//     }
+//
+// The document is POSTed to webhooks, and passed to commands on stdin.
+//
+use log::info;
+use serde::Serialize;
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::analysis::analyze;
+use crate::diff::TopoDiff;
+use crate::{SasDigraph, SimpleError};
+
+//
+// How long to wait for a webhook to accept the connection, and then for each
+// read of its response, so that one that doesn't answer can't hold up the
+// daemon until the next capture.
+//
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum Notifier {
+    // URL the summary is POSTed to
+    Webhook(String),
+    // shell command run with the summary on stdin
+    Command(String),
+}
+
+#[derive(Debug, Serialize)]
+struct NewFinding {
+    kind: String,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ChangeSummary {
+    host: String,
+    timestamp: String,
+    snapshot: String,
+    added_vertices: Vec<String>,
+    removed_vertices: Vec<String>,
+    added_edges: Vec<(String, String)>,
+    removed_edges: Vec<(String, String)>,
+    new_findings: Vec<NewFinding>,
+}
+
+//
+// Returns the summary of the changes from before to after, or None if
+// nothing worth notifying about changed.
+//
+fn summarize(before: &SasDigraph, after: &SasDigraph, snapshot: &str) -> Option<ChangeSummary> {
+    let diff = TopoDiff::new(before, after);
+    let known: BTreeSet<String> = analyze(before).into_iter().map(|f| f.message).collect();
+    let new_findings: Vec<NewFinding> = analyze(after)
+        .into_iter()
+        .filter(|f| !known.contains(&f.message))
+        .map(|f| NewFinding {
+            kind: f.kind.as_str().to_string(),
+            message: f.message,
+        })
+        .collect();
+
+    if diff.is_empty() && new_findings.is_empty() {
+        return None;
+    }
+    Some(ChangeSummary {
+        host: after.nodename.clone(),
+        timestamp: after.timestamp.clone(),
+        snapshot: snapshot.to_string(),
+        added_vertices: diff.added_vertices,
+        removed_vertices: diff.removed_vertices,
+        added_edges: diff.added_edges,
+        removed_edges: diff.removed_edges,
+        new_findings,
+    })
+}
+
+fn run_command(cmd: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    let mut child = Command::new("/bin/sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        let msg = format!("notification command \"{}\" failed ({})", cmd, status);
+        return Err(Box::new(SimpleError(msg)));
+    }
+    Ok(())
+}
+
+fn post(url: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+    agent
+        .post(url)
+        .set("Content-Type", "application/json")
+        .send_string(body)?;
+    Ok(())
+}
+
+//
+// Compare the snapshot named `snapshot` against the previous one, and notify
+// all of the notifiers if anything changed.  Every notifier is tried, even if
+// an earlier one fails; the first error is returned.
+//
+pub(crate) fn notify_changes(
+    notifiers: &[Notifier],
+    before: &SasDigraph,
+    after: &SasDigraph,
+    snapshot: &str,
+) -> Result<(), Box<dyn Error>> {
+    let summary = match summarize(before, after, snapshot) {
+        Some(summary) => summary,
+        None => return Ok(()),
+    };
+    let body = serde_json::to_string_pretty(&summary)?;
+
+    let mut result = Ok(());
+    for notifier in notifiers {
+        info!("notifying {:?} of changes in {}", notifier, snapshot);
+        let sent = match notifier {
+            Notifier::Webhook(url) => post(url, &body),
+            Notifier::Command(cmd) => run_command(cmd, &body),
+        };
+        if let Err(e) = sent {
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+    result
+}
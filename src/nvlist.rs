@@ -0,0 +1,137 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Pretty-printing of raw nvlists.
+//
+// Only a subset of the properties of a vertex end up in the info panel, and
+// their values are flattened.  For debugging, the property groups can also be
+// dumped as they appear in the snapshot, one nvpair per line with nested
+// nvlists indented, e.g.:
+//
+//     property-groups (nvlist-array)
+//       [0]
+//         property-group-name (string): protocol
+//         property-values (nvlist-array)
+//           [0]
+//             property-name (string): resource
+//
+// Property values that are themselves nvlists are kept as structured JSON,
+// see nvlist_to_json().
+//
+use serde_json::{Map, Number, Value};
+use topo_digraph_xml::{NvlistXmlArrayElement, NvpairXML};
+
+const INDENT: &str = "  ";
+
+fn format_nvpair(nvpair: &NvpairXML, depth: usize, out: &mut String) {
+    let indent = INDENT.repeat(depth);
+    out.push_str(&format!(
+        "{}{} ({})",
+        indent,
+        nvpair.name.as_deref().unwrap_or("?"),
+        nvpair.nvpair_type
+    ));
+    if let Some(value) = &nvpair.value {
+        out.push_str(&format!(": {}", value));
+    }
+    out.push('\n');
+
+    if let Some(elements) = &nvpair.nvpair_elements {
+        for (i, elem) in elements.iter().enumerate() {
+            out.push_str(&format!(
+                "{}{}[{}] {}\n",
+                indent,
+                INDENT,
+                i,
+                elem.value.as_deref().unwrap_or("")
+            ));
+        }
+    }
+    if let Some(nvlists) = &nvpair.nvlist_elements {
+        for (i, nvlist) in nvlists.iter().enumerate() {
+            out.push_str(&format!("{}{}[{}]\n", indent, INDENT, i));
+            for child in nvlist.nvpairs.iter().flatten() {
+                format_nvpair(child, depth + 2, out);
+            }
+        }
+    }
+}
+
+pub(crate) fn format_nvpairs(nvpairs: &[NvpairXML]) -> String {
+    let mut out = String::new();
+    for nvpair in nvpairs {
+        format_nvpair(nvpair, 0, &mut out);
+    }
+    out
+}
+
+//
+// Converts a scalar nvpair value to JSON.  Integers and booleans become JSON
+// numbers and booleans where they parse as such, anything else (including
+// integers too large for JSON, or in hex) is kept as a string.
+//
+fn scalar_to_json(nvpair_type: &str, value: &str) -> Value {
+    let numeric = nvpair_type.trim_end_matches("-array");
+    if numeric.starts_with("uint") || numeric == "byte" {
+        if let Ok(n) = value.parse::<u64>() {
+            return Value::Number(Number::from(n));
+        }
+    } else if numeric.starts_with("int") {
+        if let Ok(n) = value.parse::<i64>() {
+            return Value::Number(Number::from(n));
+        }
+    } else if numeric.starts_with("boolean") {
+        if let Ok(b) = value.parse::<bool>() {
+            return Value::Bool(b);
+        }
+    }
+    Value::String(value.to_string())
+}
+
+//
+// Converts the value of an nvpair to JSON, recursing into nested nvlists: an
+// nvlist becomes an object, keyed by the names of its nvpairs, and the array
+// types become arrays.
+//
+pub(crate) fn nvpair_to_json(nvpair: &NvpairXML) -> Value {
+    if let Some(nvlists) = &nvpair.nvlist_elements {
+        let objects: Vec<Value> = nvlists.iter().map(nvlist_to_json).collect();
+        if nvpair.nvpair_type.ends_with("-array") {
+            return Value::Array(objects);
+        }
+        return objects.into_iter().next().unwrap_or(Value::Null);
+    }
+    if let Some(elements) = &nvpair.nvpair_elements {
+        return Value::Array(
+            elements
+                .iter()
+                .map(|elem| match &elem.value {
+                    Some(value) => scalar_to_json(&nvpair.nvpair_type, value),
+                    None => Value::Null,
+                })
+                .collect(),
+        );
+    }
+    match &nvpair.value {
+        Some(value) => scalar_to_json(&nvpair.nvpair_type, value),
+        None => Value::Null,
+    }
+}
+
+pub(crate) fn nvlist_to_json(nvlist: &NvlistXmlArrayElement) -> Value {
+    let mut object = Map::new();
+    for (i, nvpair) in nvlist.nvpairs.iter().flatten().enumerate() {
+        let name = match &nvpair.name {
+            Some(name) => name.clone(),
+            None => format!("[{}]", i),
+        };
+        object.insert(name, nvpair_to_json(nvpair));
+    }
+    Value::Object(object)
+}
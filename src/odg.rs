@@ -0,0 +1,213 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// OpenDocument drawing (ODG) output, for LibreOffice Draw.
+//
+// An ODG file is a zip archive (an ODF package) holding the drawing as XML.
+// The package written here is the minimal one: the mimetype, which has to
+// come first and be stored uncompressed, the manifest, the styles (one
+// graphic style per type of vertex, and a page sized to fit the layout) and
+// the content.  Like the draw.io output, the vertices are plain shapes placed
+// where they are in the SVG, and the edges are connectors glued to them, so
+// that the shapes can be moved around in Draw without losing the edges.  As
+// in the TikZ output, 100 units of the layout are a centimeter.
+//
+use std::error::Error;
+use std::io::{Cursor, Write};
+
+use crate::{html_escape as xml_escape, plan_layout, stable_id, Layout, SasDigraph};
+use crate::{EXPANDER, INITIATOR, PORT, TARGET};
+
+const MIMETYPE: &str = "application/vnd.oasis.opendocument.graphics";
+
+const NAMESPACES: &str = "xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\" \
+     xmlns:style=\"urn:oasis:names:tc:opendocument:xmlns:style:1.0\" \
+     xmlns:text=\"urn:oasis:names:tc:opendocument:xmlns:text:1.0\" \
+     xmlns:draw=\"urn:oasis:names:tc:opendocument:xmlns:drawing:1.0\" \
+     xmlns:fo=\"urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0\" \
+     xmlns:svg=\"urn:oasis:names:tc:opendocument:xmlns:svg-compatible:1.0\" \
+     office:version=\"1.2\"";
+
+const MANIFEST: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+     <manifest:manifest xmlns:manifest=\"urn:oasis:names:tc:opendocument:xmlns:manifest:1.0\" \
+     manifest:version=\"1.2\">\n\
+     \x20<manifest:file-entry manifest:full-path=\"/\" \
+     manifest:media-type=\"application/vnd.oasis.opendocument.graphics\"/>\n\
+     \x20<manifest:file-entry manifest:full-path=\"content.xml\" manifest:media-type=\"text/xml\"/>\n\
+     \x20<manifest:file-entry manifest:full-path=\"styles.xml\" manifest:media-type=\"text/xml\"/>\n\
+     </manifest:manifest>\n";
+
+// glue points of the standard shapes
+const GLUE_RIGHT: u32 = 1;
+const GLUE_LEFT: u32 = 3;
+
+//
+// Graphic style (name, fill color and stroke color) of the shape representing
+// a vertex of the specified type.
+//
+fn vertex_style(name: &str) -> (&'static str, &'static str, &'static str) {
+    match name {
+        INITIATOR => ("initiator", "#dae8fc", "#6c8ebf"),
+        EXPANDER => ("expander", "#d5e8d4", "#82b366"),
+        TARGET => ("target", "#fff2cc", "#d6b656"),
+        PORT => ("port", "#f5f5f5", "#666666"),
+        _ => ("vertex", "#ffffff", "#000000"),
+    }
+}
+
+fn cm(units: u32) -> String {
+    format!("{}cm", units as f64 / 100.0)
+}
+
+fn render_styles(width: u32, height: u32) -> String {
+    let mut styles = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <office:document-styles {}>\n\
+         <office:styles>\n\
+         \x20<style:style style:name=\"edge\" style:family=\"graphic\">\n\
+         \x20 <style:graphic-properties draw:stroke=\"solid\" svg:stroke-color=\"#000000\" \
+         draw:fill=\"none\"/>\n\
+         \x20</style:style>\n",
+        NAMESPACES
+    );
+    for name in &[INITIATOR, EXPANDER, TARGET, PORT, ""] {
+        let (style, fill, stroke) = vertex_style(name);
+        styles.push_str(&format!(
+            "\x20<style:style style:name=\"{}\" style:family=\"graphic\">\n\
+             \x20 <style:graphic-properties draw:fill=\"solid\" draw:fill-color=\"{}\" \
+             draw:stroke=\"solid\" svg:stroke-color=\"{}\" draw:textarea-vertical-align=\"middle\"/>\n\
+             \x20 <style:paragraph-properties fo:text-align=\"center\"/>\n\
+             \x20 <style:text-properties fo:font-size=\"8pt\"/>\n\
+             \x20</style:style>\n",
+            style, fill, stroke
+        ));
+    }
+    styles.push_str(&format!(
+        "</office:styles>\n\
+         <office:automatic-styles>\n\
+         \x20<style:page-layout style:name=\"page\">\n\
+         \x20 <style:page-layout-properties fo:page-width=\"{}\" fo:page-height=\"{}\" \
+         fo:margin-top=\"0cm\" fo:margin-bottom=\"0cm\" fo:margin-left=\"0cm\" \
+         fo:margin-right=\"0cm\"/>\n\
+         \x20</style:page-layout>\n\
+         </office:automatic-styles>\n\
+         <office:master-styles>\n\
+         \x20<style:master-page style:name=\"Default\" style:page-layout-name=\"page\"/>\n\
+         </office:master-styles>\n\
+         </office:document-styles>\n",
+        cm(width),
+        cm(height)
+    ));
+    styles
+}
+
+fn render_content(
+    digraph: &SasDigraph,
+    layout: &Layout,
+    depth_limit: u32,
+) -> Result<(String, (u32, u32)), Box<dyn Error>> {
+    let plan = plan_layout(digraph, depth_limit)?;
+    let positions = plan.positions(layout, 0);
+    let (width, height) = (layout.vertex_width, layout.vertex_height);
+
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <office:document-content {}>\n\
+         <office:body>\n\
+         <office:drawing>\n\
+         <draw:page draw:name=\"{}\" draw:master-page-name=\"Default\">\n",
+        NAMESPACES,
+        xml_escape(&digraph.nodename)
+    );
+
+    let mut fmris: Vec<&String> = positions.keys().collect();
+    fmris.sort();
+    for fmri in &fmris {
+        let vtx = &digraph.vertices[*fmri];
+        let (x, y) = positions[*fmri];
+        let (style, _, _) = vertex_style(&vtx.name);
+        let element = if vtx.name == PORT { "draw:ellipse" } else { "draw:rect" };
+        let mut text = String::new();
+        if let Some(reference) = &vtx.reference {
+            text.push_str(&format!("<text:p>{}</text:p>", xml_escape(reference)));
+        }
+        text.push_str(&format!(
+            "<text:p>{} {}</text:p>",
+            xml_escape(&vtx.name),
+            xml_escape(&vtx.instance.to_string())
+        ));
+        xml.push_str(&format!(
+            " <{} draw:id=\"{}\" draw:name=\"{}\" draw:style-name=\"{}\" \
+             svg:x=\"{}\" svg:y=\"{}\" svg:width=\"{}\" svg:height=\"{}\">\
+             <svg:title>{}</svg:title>{}</{}>\n",
+            element,
+            stable_id(fmri),
+            xml_escape(fmri),
+            style,
+            cm(x),
+            cm(y),
+            cm(width),
+            cm(height),
+            xml_escape(fmri),
+            text,
+            element
+        ));
+    }
+
+    for fmri in &fmris {
+        let (sx, sy) = positions[*fmri];
+        for edge in digraph.vertices[*fmri].outgoing_edges.iter().flatten() {
+            // only the vertices that were laid out can be connected
+            let (tx, ty) = match positions.get(edge) {
+                Some(position) => *position,
+                None => continue,
+            };
+            xml.push_str(&format!(
+                " <draw:connector draw:style-name=\"edge\" draw:type=\"standard\" \
+                 draw:start-shape=\"{}\" draw:start-glue-point=\"{}\" \
+                 draw:end-shape=\"{}\" draw:end-glue-point=\"{}\" \
+                 svg:x1=\"{}\" svg:y1=\"{}\" svg:x2=\"{}\" svg:y2=\"{}\"/>\n",
+                stable_id(fmri),
+                GLUE_RIGHT,
+                stable_id(edge),
+                GLUE_LEFT,
+                cm(sx + width),
+                cm(sy + (height / 2)),
+                cm(tx),
+                cm(ty + (height / 2))
+            ));
+        }
+    }
+
+    xml.push_str("</draw:page>\n</office:drawing>\n</office:body>\n</office:document-content>\n");
+    Ok((xml, plan.canvas_size(layout)))
+}
+
+pub(crate) fn render_odg(
+    digraph: &SasDigraph,
+    layout: &Layout,
+    depth_limit: u32,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (content, (width, height)) = render_content(digraph, layout, depth_limit)?;
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let stored =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+    zip.start_file("META-INF/manifest.xml", deflated)?;
+    zip.write_all(MANIFEST.as_bytes())?;
+    zip.start_file("styles.xml", deflated)?;
+    zip.write_all(render_styles(width, height).as_bytes())?;
+    zip.start_file("content.xml", deflated)?;
+    zip.write_all(content.as_bytes())?;
+    Ok(zip.finish()?.into_inner())
+}
@@ -0,0 +1,988 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Rendering options.
+//
+// Everything that controls how a topology is rendered lives in RenderOptions
+// rather than as public fields on Config.  RenderOptions is constructed via
+// RenderOptionsBuilder and only exposes accessors, so new options can be
+// added without breaking library consumers.
+//
+use crate::inventory::DEFAULT_INVENTORY_PROPERTIES;
+use crate::key_case::KeyCase;
+use crate::live_reload::LiveReload;
+use crate::locale::Locale;
+use crate::DEFAULT_MAX_DEPTH;
+
+//
+// Colors and stroke settings used when drawing the topology.
+//
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Theme {
+    // stroke color of the edges between vertices
+    pub edge_color: String,
+    // stroke width of the edges between vertices
+    pub edge_width: u32,
+    // background colors the columns alternate between; empty, the default,
+    // disables banding
+    pub column_bands: Vec<String>,
+    // label each column band with its hop distance from the initiators
+    pub column_labels: bool,
+    // show the status of vertices and edges with patterns rather than colors,
+    // for printing and e-ink displays (see monochrome.rs)
+    pub monochrome: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            edge_color: "black".to_string(),
+            edge_width: 2,
+            column_bands: Vec::new(),
+            column_labels: true,
+            monochrome: false,
+        }
+    }
+}
+
+// the colors of the column bands, when turned on without specifying any
+const COLUMN_BANDS: [&str; 2] = ["#FAFAFA", "#F0F0F0"];
+const MONOCHROME_COLUMN_BANDS: [&str; 2] = ["#FFFFFF", "#EEEEEE"];
+
+impl Theme {
+    //
+    // The monochrome variant of the theme: black edges, gray column bands (if
+    // banding is on) and patterns for the status.
+    //
+    pub fn to_monochrome(self) -> Theme {
+        let column_bands = match self.column_bands.is_empty() {
+            true => Vec::new(),
+            false => MONOCHROME_COLUMN_BANDS.iter().map(|c| c.to_string()).collect(),
+        };
+        Theme {
+            edge_color: "black".to_string(),
+            column_bands,
+            monochrome: true,
+            ..self
+        }
+    }
+
+    //
+    // The theme with the column bands (see bands.rs) turned on, in the
+    // default colors unless it already has colors for them.
+    //
+    pub fn with_column_bands(self) -> Theme {
+        if !self.column_bands.is_empty() {
+            return self;
+        }
+        let colors = match self.monochrome {
+            true => MONOCHROME_COLUMN_BANDS,
+            false => COLUMN_BANDS,
+        };
+        Theme {
+            column_bands: colors.iter().map(|c| c.to_string()).collect(),
+            ..self
+        }
+    }
+}
+
+//
+// Dimensions (in SVG user units) of the grid the vertices are placed on.
+//
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Layout {
+    // horizontal distance between two adjacent columns
+    pub column_width: u32,
+    // vertical distance between two adjacent rows
+    pub row_height: u32,
+    // size of the image representing a vertex
+    pub vertex_width: u32,
+    pub vertex_height: u32,
+    // offset of the grid from the top-left corner of the document
+    pub x_margin: u32,
+    pub y_margin: u32,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            column_width: 250,
+            row_height: 150,
+            vertex_width: 120,
+            vertex_height: 120,
+            x_margin: 50,
+            y_margin: 10,
+        }
+    }
+}
+
+//
+// Filters restrict what ends up in the rendered output.
+//
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Filter {
+    // Don't embed the named property in the rendered vertices
+    ExcludeProperty(String),
+}
+
+//
+// The set of artifacts generated by a run.
+//
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum OutputFormat {
+    // SVG document plus the HTML page that embeds it
+    Svg,
+    // Versioned JSON document (see export::FORMAT_VERSION)
+    Json,
+    // Table of the physical connections, as CSV and JSON
+    Cabling,
+    // Graphviz DOT file, without any layout
+    Dot,
+    // GraphML file, with the properties as typed node attributes
+    GraphMl,
+    // Mermaid flowchart, for pasting into Markdown documents
+    Mermaid,
+    // Force graph and hierarchy for D3, as JSON
+    D3,
+    // Elements for Cytoscape.js, as JSON
+    Cytoscape,
+    // diagrams.net (draw.io) diagram, laid out like the SVG
+    Drawio,
+    // TikZ picture for LaTeX documents, laid out like the SVG
+    Tikz,
+    // LibreOffice Draw (ODG) drawing, laid out like the SVG
+    Odg,
+    // Flat CSV listing of every vertex, for asset tracking
+    Inventory,
+    // Summary tables of the hosts, initiators, expanders and targets, in
+    // Markdown
+    Markdown,
+    // PNG rendering of the SVG (requires the "png" feature)
+    Png,
+    // Printable report with a cover page (requires the "pdf" feature)
+    Pdf,
+    // Indented text tree, printed to stdout rather than written to a file
+    Text,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<OutputFormat, String> {
+        match name {
+            "svg" => Ok(OutputFormat::Svg),
+            "json" => Ok(OutputFormat::Json),
+            "cabling" => Ok(OutputFormat::Cabling),
+            "dot" => Ok(OutputFormat::Dot),
+            "graphml" => Ok(OutputFormat::GraphMl),
+            "mermaid" => Ok(OutputFormat::Mermaid),
+            "d3" => Ok(OutputFormat::D3),
+            "cytoscape" => Ok(OutputFormat::Cytoscape),
+            "drawio" => Ok(OutputFormat::Drawio),
+            "tikz" => Ok(OutputFormat::Tikz),
+            "odg" => Ok(OutputFormat::Odg),
+            "inventory" => Ok(OutputFormat::Inventory),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "png" => Ok(OutputFormat::Png),
+            "pdf" => Ok(OutputFormat::Pdf),
+            "text" => Ok(OutputFormat::Text),
+            _ => Err(format!("unknown output format: {}", name)),
+        }
+    }
+}
+
+//
+// What to do with property groups that don't have any values, i.e. that are
+// missing their PG_VALS nvpair.  Some topo modules create such groups as
+// placeholders, so they are skipped by default.
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EmptyPropGroups {
+    // ignore the group
+    #[default]
+    Skip,
+    // ignore the group, but log a warning naming it and the vertex
+    Warn,
+    // treat the snapshot as malformed
+    Error,
+}
+
+impl std::str::FromStr for EmptyPropGroups {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<EmptyPropGroups, String> {
+        match name {
+            "skip" => Ok(EmptyPropGroups::Skip),
+            "warn" => Ok(EmptyPropGroups::Warn),
+            "error" => Ok(EmptyPropGroups::Error),
+            _ => Err(format!("unknown empty property group policy: {}", name)),
+        }
+    }
+}
+
+//
+// Format of the snapshot read in.  By default it is detected: files named
+// *.json (possibly compressed) and documents starting with '{' are read as
+// JSON exports, those starting with a packed nvlist header as packed nvlists
+// (see packed_nvlist.rs), anything else as the XML output of sastopo -x.
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum InputFormat {
+    #[default]
+    Auto,
+    Xml,
+    Json,
+    Nvlist,
+}
+
+impl std::str::FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<InputFormat, String> {
+        match name {
+            "auto" => Ok(InputFormat::Auto),
+            "xml" => Ok(InputFormat::Xml),
+            "json" => Ok(InputFormat::Json),
+            "nvlist" => Ok(InputFormat::Nvlist),
+            _ => Err(format!("unknown input format: {}", name)),
+        }
+    }
+}
+
+//
+// How FMRIs are shown where space is tight, i.e. in the info panel, in the
+// tooltips of the vertices and in the CSV tables.  Full SAS FMRIs overflow
+// all of these.  The data island and the JSON exports always carry the full
+// FMRIs.
+//
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FmriDisplay {
+    // the FMRI as is
+    #[default]
+    Full,
+    // at most this many characters, with the middle replaced by an ellipsis
+    Ellipsis(usize),
+    // only the last this many components of the path, without the authority
+    LastComponents(usize),
+}
+
+impl std::str::FromStr for FmriDisplay {
+    type Err = String;
+
+    //
+    // Accepts "full", "ellipsis:N" (or "middle:N") and "last:N".
+    //
+    fn from_str(rule: &str) -> Result<FmriDisplay, String> {
+        let (name, arg) = match rule.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (rule, None),
+        };
+        let count = || match arg.map(|arg| arg.parse::<usize>()) {
+            Some(Ok(n)) if n > 0 => Ok(n),
+            _ => Err(format!("invalid FMRI display rule: {}", rule)),
+        };
+        match name {
+            "full" if arg.is_none() => Ok(FmriDisplay::Full),
+            "ellipsis" | "middle" => Ok(FmriDisplay::Ellipsis(count()?)),
+            "last" => Ok(FmriDisplay::LastComponents(count()?)),
+            _ => Err(format!("invalid FMRI display rule: {}", rule)),
+        }
+    }
+}
+
+//
+// Overlays add decorations on top of the rendered topology.
+//
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Overlay {
+    // Highlight the vertex with the specified FMRI
+    Highlight(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    theme: Theme,
+    layout: Layout,
+    filters: Vec<Filter>,
+    outputs: Vec<OutputFormat>,
+    overlays: Vec<Overlay>,
+    numbering: bool,
+    title: Option<String>,
+    logo: Option<String>,
+    favicon: Option<String>,
+    transition_from: Option<String>,
+    approved_baseline: Option<String>,
+    baseline_diff: bool,
+    archival: bool,
+    keep_snapshot: bool,
+    reachability: bool,
+    mark_single_path: bool,
+    max_properties: Option<usize>,
+    lazy_properties: bool,
+    history: Vec<String>,
+    merged_snapshots: Vec<String>,
+    anonymize: bool,
+    png_dpi: u32,
+    empty_propgroups: EmptyPropGroups,
+    input_format: InputFormat,
+    validate: bool,
+    layout_hash: bool,
+    lenient: bool,
+    standalone: bool,
+    inventory_properties: Vec<String>,
+    inventory_reference: Option<String>,
+    enclosure_aliases: Option<String>,
+    raw_nvlists: bool,
+    split_initiators: bool,
+    vertex_pages: bool,
+    svgz: bool,
+    instances: bool,
+    fmri_display: FmriDisplay,
+    key_case: KeyCase,
+    live_reload: Option<LiveReload>,
+    max_depth: u32,
+    locale: Locale,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            theme: Theme::default(),
+            layout: Layout::default(),
+            filters: Vec::new(),
+            outputs: vec![OutputFormat::Svg],
+            overlays: Vec::new(),
+            numbering: false,
+            title: None,
+            logo: None,
+            favicon: None,
+            transition_from: None,
+            approved_baseline: None,
+            baseline_diff: false,
+            archival: false,
+            keep_snapshot: false,
+            reachability: false,
+            mark_single_path: false,
+            max_properties: None,
+            lazy_properties: false,
+            history: Vec::new(),
+            merged_snapshots: Vec::new(),
+            anonymize: false,
+            png_dpi: 96,
+            empty_propgroups: EmptyPropGroups::default(),
+            input_format: InputFormat::default(),
+            validate: false,
+            layout_hash: false,
+            lenient: false,
+            standalone: false,
+            inventory_properties: DEFAULT_INVENTORY_PROPERTIES
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            inventory_reference: None,
+            enclosure_aliases: None,
+            raw_nvlists: false,
+            split_initiators: false,
+            vertex_pages: false,
+            svgz: false,
+            instances: false,
+            fmri_display: FmriDisplay::default(),
+            key_case: KeyCase::default(),
+            live_reload: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            locale: Locale::default(),
+        }
+    }
+}
+
+impl RenderOptions {
+    pub fn builder() -> RenderOptionsBuilder {
+        RenderOptionsBuilder::new()
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters
+    }
+
+    pub fn outputs(&self) -> &[OutputFormat] {
+        &self.outputs
+    }
+
+    pub fn overlays(&self) -> &[Overlay] {
+        &self.overlays
+    }
+
+    //
+    // Whether devices are assigned reference numbers (I1, E1, T001, ...).
+    //
+    pub fn numbering(&self) -> bool {
+        self.numbering
+    }
+
+    //
+    // Title of the generated HTML page, overriding the default one derived
+    // from the nodename and snapshot time.
+    //
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    //
+    // Paths of the site logo and favicon images that get embedded into the
+    // generated HTML page.
+    //
+    pub fn logo(&self) -> Option<&str> {
+        self.logo.as_deref()
+    }
+
+    pub fn favicon(&self) -> Option<&str> {
+        self.favicon.as_deref()
+    }
+
+    //
+    // Path of an older snapshot to generate an animated transition from.
+    //
+    pub fn transition_from(&self) -> Option<&str> {
+        self.transition_from.as_deref()
+    }
+
+    //
+    // Path of the approved ("golden") snapshot that every render is compared
+    // against, and whether the individual differences should be listed.
+    //
+    pub fn approved_baseline(&self) -> Option<&str> {
+        self.approved_baseline.as_deref()
+    }
+
+    pub fn baseline_diff(&self) -> bool {
+        self.baseline_diff
+    }
+
+    //
+    // Whether a manifest with integrity hashes of all of the generated files
+    // is written, see archive.rs.
+    //
+    pub fn archival(&self) -> bool {
+        self.archival
+    }
+
+    //
+    // Whether the snapshot that was rendered is written along with the
+    // output as sastopo.xml, e.g. one that was captured for the run.
+    //
+    pub fn keep_snapshot(&self) -> bool {
+        self.keep_snapshot
+    }
+
+    //
+    // Whether devices are marked with the initiators they can be reached
+    // from, see reachability.rs.
+    //
+    pub fn reachability(&self) -> bool {
+        self.reachability
+    }
+
+    //
+    // Whether the targets without a redundant path are outlined, and the
+    // redundancy of each target listed on the page, see redundancy.rs.
+    //
+    pub fn mark_single_path(&self) -> bool {
+        self.mark_single_path
+    }
+
+    //
+    // Maximum number of properties embedded per vertex in the SVG.  The rest
+    // are written to a sidecar file, see sidecar.rs.
+    //
+    pub fn max_properties(&self) -> Option<usize> {
+        self.max_properties
+    }
+
+    //
+    // Whether the properties are left out of the SVG and HTML page entirely
+    // and loaded from the sidecar file when a vertex is clicked.
+    //
+    pub fn lazy_properties(&self) -> bool {
+        self.lazy_properties
+    }
+
+    //
+    // Paths of previous snapshots of the same host.  Devices present in any
+    // of them but missing now are rendered as ghosts.
+    //
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    //
+    // Paths of snapshots of the same fabric taken from other hosts or HBAs,
+    // which are merged into the one being rendered (see merge.rs).
+    //
+    pub fn merged_snapshots(&self) -> &[String] {
+        &self.merged_snapshots
+    }
+
+    //
+    // Whether host names, WWNs and serial numbers are replaced with
+    // pseudonyms in all of the outputs, see anonymize.rs.
+    //
+    pub fn anonymize(&self) -> bool {
+        self.anonymize
+    }
+
+    //
+    // Resolution of the PNG output.
+    //
+    pub fn png_dpi(&self) -> u32 {
+        self.png_dpi
+    }
+
+    //
+    // How property groups without any values are handled when parsing.
+    //
+    pub fn empty_propgroups(&self) -> EmptyPropGroups {
+        self.empty_propgroups
+    }
+
+    //
+    // Format of the snapshot, see InputFormat.
+    //
+    pub fn input_format(&self) -> InputFormat {
+        self.input_format
+    }
+
+    //
+    // Whether XML snapshots are validated against the topo digraph schema
+    // before they are parsed (see validate.rs).
+    //
+    pub fn validate(&self) -> bool {
+        self.validate
+    }
+
+    //
+    // Whether the hash of the layout is computed and reported (see
+    // layout_hash.rs).
+    //
+    pub fn layout_hash(&self) -> bool {
+        self.layout_hash
+    }
+
+    //
+    // Whether vertices with malformed property groups are skipped, rather
+    // than failing the conversion (see lenient.rs).
+    //
+    pub fn lenient(&self) -> bool {
+        self.lenient
+    }
+
+    //
+    // Whether the SVG and its images are inlined into the HTML page, so that
+    // the page doesn't depend on any other files.
+    //
+    pub fn standalone(&self) -> bool {
+        self.standalone
+    }
+
+    //
+    // Properties listed for each vertex in the CSV inventory.
+    //
+    pub fn inventory_properties(&self) -> &[String] {
+        &self.inventory_properties
+    }
+
+    //
+    // Path of the snapshot the inventory is compared against, if only the
+    // changes since are to be listed.
+    //
+    pub fn inventory_reference(&self) -> Option<&str> {
+        self.inventory_reference.as_deref()
+    }
+
+    //
+    // Path of the file naming the enclosures and the expanders in each, used
+    // to confirm and correct the inferred enclosures (see enclosures.rs).
+    //
+    pub fn enclosure_aliases(&self) -> Option<&str> {
+        self.enclosure_aliases.as_deref()
+    }
+
+    //
+    // Whether the raw property groups of each vertex are dumped to the
+    // sidecar file, for debugging.
+    //
+    pub fn raw_nvlists(&self) -> bool {
+        self.raw_nvlists
+    }
+
+    //
+    // Whether each initiator is rendered on a page of its own, with
+    // sastopo2svg.html linking to them, rather than all on one page.
+    //
+    pub fn split_initiators(&self) -> bool {
+        self.split_initiators
+    }
+
+    //
+    // Whether a page listing all of the properties is written for every
+    // vertex, and linked to from the info panel.
+    //
+    pub fn vertex_pages(&self) -> bool {
+        self.vertex_pages
+    }
+
+    //
+    // Whether the SVG is written gzip-compressed, as .svgz.
+    //
+    pub fn svgz(&self) -> bool {
+        self.svgz
+    }
+
+    //
+    // Whether the instance of each vertex is shown in the corner of its icon.
+    //
+    pub fn instances(&self) -> bool {
+        self.instances
+    }
+
+    //
+    // How FMRIs are shortened where space is tight, see FmriDisplay.
+    //
+    pub fn fmri_display(&self) -> FmriDisplay {
+        self.fmri_display
+    }
+
+    //
+    // How the keys of the JSON and CSV data exports are named, see KeyCase.
+    //
+    pub fn key_case(&self) -> KeyCase {
+        self.key_case
+    }
+
+    //
+    // How the generated pages poll for a newer rendering, if they do.  This
+    // is set by the daemon for each snapshot it renders.
+    //
+    pub(crate) fn live_reload(&self) -> Option<&LiveReload> {
+        self.live_reload.as_ref()
+    }
+
+    pub(crate) fn set_live_reload(&mut self, live_reload: LiveReload) {
+        self.live_reload = Some(live_reload);
+    }
+
+    pub(crate) fn set_outputs(&mut self, outputs: Vec<OutputFormat>) {
+        self.outputs = outputs;
+    }
+
+    pub(crate) fn set_keep_snapshot(&mut self, enable: bool) {
+        self.keep_snapshot = enable;
+    }
+
+    //
+    // The maximum depth of the layout, in vertices from the initiators.  A
+    // snapshot deeper than this is rejected rather than laid out.
+    //
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    //
+    // The regional conventions the numbers and dates in the reports are
+    // formatted by.
+    //
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    pub fn has_output(&self, format: OutputFormat) -> bool {
+        self.outputs.contains(&format)
+    }
+
+    //
+    // Returns true if the named property should be embedded in the rendered
+    // vertices.
+    //
+    pub fn includes_property(&self, propname: &str) -> bool {
+        !self.filters.iter().any(|f| match f {
+            Filter::ExcludeProperty(name) => name == propname,
+        })
+    }
+
+    pub fn is_highlighted(&self, fmri: &str) -> bool {
+        self.overlays.iter().any(|o| match o {
+            Overlay::Highlight(f) => f == fmri,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RenderOptionsBuilder {
+    options: RenderOptions,
+    outputs_set: bool,
+}
+
+impl RenderOptionsBuilder {
+    pub fn new() -> RenderOptionsBuilder {
+        RenderOptionsBuilder {
+            options: RenderOptions::default(),
+            outputs_set: false,
+        }
+    }
+
+    pub fn theme(mut self, theme: Theme) -> RenderOptionsBuilder {
+        self.options.theme = theme;
+        self
+    }
+
+    pub fn column_bands(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.theme = match enable {
+            true => self.options.theme.with_column_bands(),
+            false => Theme {
+                column_bands: Vec::new(),
+                ..self.options.theme
+            },
+        };
+        self
+    }
+
+    pub fn monochrome(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.theme = match enable {
+            true => self.options.theme.to_monochrome(),
+            false => Theme {
+                monochrome: false,
+                ..self.options.theme
+            },
+        };
+        self
+    }
+
+    pub fn layout(mut self, layout: Layout) -> RenderOptionsBuilder {
+        self.options.layout = layout;
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> RenderOptionsBuilder {
+        self.options.filters.push(filter);
+        self
+    }
+
+    //
+    // The first call to output() replaces the default set of outputs,
+    // subsequent calls add to it.
+    //
+    pub fn output(mut self, format: OutputFormat) -> RenderOptionsBuilder {
+        if !self.outputs_set {
+            self.options.outputs.clear();
+            self.outputs_set = true;
+        }
+        if !self.options.outputs.contains(&format) {
+            self.options.outputs.push(format);
+        }
+        self
+    }
+
+    pub fn overlay(mut self, overlay: Overlay) -> RenderOptionsBuilder {
+        self.options.overlays.push(overlay);
+        self
+    }
+
+    pub fn numbering(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.numbering = enable;
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> RenderOptionsBuilder {
+        self.options.title = Some(title.to_string());
+        self
+    }
+
+    pub fn logo(mut self, path: &str) -> RenderOptionsBuilder {
+        self.options.logo = Some(path.to_string());
+        self
+    }
+
+    pub fn favicon(mut self, path: &str) -> RenderOptionsBuilder {
+        self.options.favicon = Some(path.to_string());
+        self
+    }
+
+    pub fn transition_from(mut self, path: &str) -> RenderOptionsBuilder {
+        self.options.transition_from = Some(path.to_string());
+        self
+    }
+
+    pub fn approved_baseline(mut self, path: &str, show_diff: bool) -> RenderOptionsBuilder {
+        self.options.approved_baseline = Some(path.to_string());
+        self.options.baseline_diff = show_diff;
+        self
+    }
+
+    pub fn archival(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.archival = enable;
+        self
+    }
+
+    pub fn keep_snapshot(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.keep_snapshot = enable;
+        self
+    }
+
+    pub fn reachability(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.reachability = enable;
+        self
+    }
+
+    pub fn mark_single_path(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.mark_single_path = enable;
+        self
+    }
+
+    pub fn max_properties(mut self, max: usize) -> RenderOptionsBuilder {
+        self.options.max_properties = Some(max);
+        self
+    }
+
+    pub fn lazy_properties(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.lazy_properties = enable;
+        self
+    }
+
+    pub fn history(mut self, path: &str) -> RenderOptionsBuilder {
+        self.options.history.push(path.to_string());
+        self
+    }
+
+    pub fn merge(mut self, path: &str) -> RenderOptionsBuilder {
+        self.options.merged_snapshots.push(path.to_string());
+        self
+    }
+
+    pub fn anonymize(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.anonymize = enable;
+        self
+    }
+
+    pub fn png_dpi(mut self, dpi: u32) -> RenderOptionsBuilder {
+        self.options.png_dpi = dpi;
+        self
+    }
+
+    pub fn empty_propgroups(mut self, policy: EmptyPropGroups) -> RenderOptionsBuilder {
+        self.options.empty_propgroups = policy;
+        self
+    }
+
+    pub fn input_format(mut self, format: InputFormat) -> RenderOptionsBuilder {
+        self.options.input_format = format;
+        self
+    }
+
+    pub fn validate(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.validate = enable;
+        self
+    }
+
+    pub fn layout_hash(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.layout_hash = enable;
+        self
+    }
+
+    pub fn lenient(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.lenient = enable;
+        self
+    }
+
+    pub fn standalone(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.standalone = enable;
+        self
+    }
+
+    pub fn inventory_properties(mut self, names: Vec<String>) -> RenderOptionsBuilder {
+        self.options.inventory_properties = names;
+        self
+    }
+
+    pub fn inventory_reference(mut self, path: &str) -> RenderOptionsBuilder {
+        self.options.inventory_reference = Some(path.to_string());
+        self
+    }
+
+    pub fn enclosure_aliases(mut self, path: &str) -> RenderOptionsBuilder {
+        self.options.enclosure_aliases = Some(path.to_string());
+        self
+    }
+
+    pub fn raw_nvlists(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.raw_nvlists = enable;
+        self
+    }
+
+    pub fn split_initiators(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.split_initiators = enable;
+        self
+    }
+
+    pub fn vertex_pages(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.vertex_pages = enable;
+        self
+    }
+
+    pub fn svgz(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.svgz = enable;
+        self
+    }
+
+    pub fn instances(mut self, enable: bool) -> RenderOptionsBuilder {
+        self.options.instances = enable;
+        self
+    }
+
+    pub fn fmri_display(mut self, rule: FmriDisplay) -> RenderOptionsBuilder {
+        self.options.fmri_display = rule;
+        self
+    }
+
+    pub fn key_case(mut self, case: KeyCase) -> RenderOptionsBuilder {
+        self.options.key_case = case;
+        self
+    }
+
+    pub fn max_depth(mut self, max: u32) -> RenderOptionsBuilder {
+        self.options.max_depth = max;
+        self
+    }
+
+    pub fn locale(mut self, locale: Locale) -> RenderOptionsBuilder {
+        self.options.locale = locale;
+        self
+    }
+
+    pub fn build(self) -> RenderOptions {
+        self.options
+    }
+}
+
+impl Default for RenderOptionsBuilder {
+    fn default() -> RenderOptionsBuilder {
+        RenderOptionsBuilder::new()
+    }
+}
@@ -0,0 +1,797 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Packed nvlist snapshots.
+//
+// The topology snapshot is an nvlist, which sastopo -x renders as XML.  The
+// native snapshot API hands it out packed instead (see nvlist_pack(3NVPAIR)),
+// which can be read directly, skipping the conversion to XML.  The nvlist is
+// laid out the way the XML is:
+//
+//     fmri-scheme (string, optional)
+//     nodename, os-version, product-id, timestamp (string)
+//     vertices (nvlist-array)
+//         name, fmri (string)
+//         instance (uint64)
+//         property-groups (nvlist-array)
+//             ...as in the XML
+//         outgoing-edges (string-array of FMRIs, optional)
+//
+// Both encodings of libnvpair are read: XDR, which is big-endian and aligned
+// to 4 bytes, and native, which is the in-memory layout of the nvpairs in the
+// byte order of the host that packed them.  The packed form starts with a
+// 4-byte header giving the encoding and the byte order, by which it is also
+// told apart from the XML and JSON forms.
+//
+// The nvpairs are turned into the same structures the XML is parsed into
+// (see xml.rs), so the vertices come out exactly as they would from the XML
+// rendering of the same snapshot.
+//
+use std::convert::{TryFrom, TryInto};
+use std::error::Error;
+
+use log::warn;
+use topo_digraph_xml::{NvlistXmlArrayElement, NvpairXML, NvpairXmlArrayElement};
+
+use crate::lenient::{drop_skipped_edges, ParseWarning};
+use crate::xml::{EdgeXml, OutgoingEdgesXml, VertexXml};
+use crate::{build_vertex, RenderOptions, SasDigraph, SimpleError};
+
+// encodings and byte orders, as given in the header
+const NV_ENCODE_NATIVE: u8 = 0;
+const NV_ENCODE_XDR: u8 = 1;
+const NV_LITTLE_ENDIAN: u8 = 1;
+
+// the nvpair data types (see sys/nvpair.h)
+const DATA_TYPE_BOOLEAN: i32 = 1;
+const DATA_TYPE_BYTE: i32 = 2;
+const DATA_TYPE_INT16: i32 = 3;
+const DATA_TYPE_UINT16: i32 = 4;
+const DATA_TYPE_INT32: i32 = 5;
+const DATA_TYPE_UINT32: i32 = 6;
+const DATA_TYPE_INT64: i32 = 7;
+const DATA_TYPE_UINT64: i32 = 8;
+const DATA_TYPE_STRING: i32 = 9;
+const DATA_TYPE_BYTE_ARRAY: i32 = 10;
+const DATA_TYPE_INT16_ARRAY: i32 = 11;
+const DATA_TYPE_UINT16_ARRAY: i32 = 12;
+const DATA_TYPE_INT32_ARRAY: i32 = 13;
+const DATA_TYPE_UINT32_ARRAY: i32 = 14;
+const DATA_TYPE_INT64_ARRAY: i32 = 15;
+const DATA_TYPE_UINT64_ARRAY: i32 = 16;
+const DATA_TYPE_STRING_ARRAY: i32 = 17;
+const DATA_TYPE_HRTIME: i32 = 18;
+const DATA_TYPE_NVLIST: i32 = 19;
+const DATA_TYPE_NVLIST_ARRAY: i32 = 20;
+const DATA_TYPE_BOOLEAN_VALUE: i32 = 21;
+const DATA_TYPE_INT8: i32 = 22;
+const DATA_TYPE_UINT8: i32 = 23;
+const DATA_TYPE_BOOLEAN_ARRAY: i32 = 24;
+const DATA_TYPE_INT8_ARRAY: i32 = 25;
+const DATA_TYPE_UINT8_ARRAY: i32 = 26;
+const DATA_TYPE_DOUBLE: i32 = 27;
+
+// how deep nvlists can be nested, as libnvpair limits it
+const MAX_DEPTH: usize = 20;
+
+// size of the nvpair header in the native encoding
+const NATIVE_NVPAIR_HEADER: usize = 16;
+
+//
+// Name of the type, as the XML gives it, along with the size of the values
+// of the integer types in the native encoding.
+//
+fn type_name(data_type: i32) -> Option<(&'static str, usize)> {
+    Some(match data_type {
+        DATA_TYPE_BOOLEAN => ("boolean", 0),
+        DATA_TYPE_BOOLEAN_VALUE => ("boolean-value", 4),
+        DATA_TYPE_BYTE => ("byte", 1),
+        DATA_TYPE_INT8 => ("int8", 1),
+        DATA_TYPE_UINT8 => ("uint8", 1),
+        DATA_TYPE_INT16 => ("int16", 2),
+        DATA_TYPE_UINT16 => ("uint16", 2),
+        DATA_TYPE_INT32 => ("int32", 4),
+        DATA_TYPE_UINT32 => ("uint32", 4),
+        DATA_TYPE_INT64 => ("int64", 8),
+        DATA_TYPE_UINT64 => ("uint64", 8),
+        DATA_TYPE_HRTIME => ("hrtime", 8),
+        DATA_TYPE_DOUBLE => ("double", 8),
+        DATA_TYPE_STRING => ("string", 0),
+        DATA_TYPE_NVLIST => ("nvlist", 0),
+        DATA_TYPE_BOOLEAN_ARRAY => ("boolean-array", 4),
+        DATA_TYPE_BYTE_ARRAY => ("byte-array", 1),
+        DATA_TYPE_INT8_ARRAY => ("int8-array", 1),
+        DATA_TYPE_UINT8_ARRAY => ("uint8-array", 1),
+        DATA_TYPE_INT16_ARRAY => ("int16-array", 2),
+        DATA_TYPE_UINT16_ARRAY => ("uint16-array", 2),
+        DATA_TYPE_INT32_ARRAY => ("int32-array", 4),
+        DATA_TYPE_UINT32_ARRAY => ("uint32-array", 4),
+        DATA_TYPE_INT64_ARRAY => ("int64-array", 8),
+        DATA_TYPE_UINT64_ARRAY => ("uint64-array", 8),
+        DATA_TYPE_STRING_ARRAY => ("string-array", 0),
+        DATA_TYPE_NVLIST_ARRAY => ("nvlist-array", 0),
+        _ => return None,
+    })
+}
+
+//
+// Whether the data starting with head is a packed nvlist, going by its
+// header.  Neither XML nor JSON can start with these bytes.
+//
+pub(crate) fn is_packed_nvlist(head: &[u8]) -> bool {
+    matches!(head, [encoding, endian, 0, 0, ..]
+        if (*encoding == NV_ENCODE_NATIVE || *encoding == NV_ENCODE_XDR) && *endian <= 1)
+}
+
+#[derive(Debug)]
+enum Value {
+    // a scalar, formatted as in the XML
+    Scalar(String),
+    Array(Vec<String>),
+    Nvlists(Vec<Nvlist>),
+}
+
+#[derive(Debug)]
+struct Nvpair {
+    name: String,
+    data_type: i32,
+    value: Value,
+}
+
+type Nvlist = Vec<Nvpair>;
+
+fn malformed(what: &str) -> Box<dyn Error> {
+    Box::new(SimpleError(format!("malformed packed nvlist: {}", what)))
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    xdr: bool,
+    little_endian: bool,
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len());
+        match end {
+            Some(end) => {
+                let bytes = &self.data[self.pos..end];
+                self.pos = end;
+                Ok(bytes)
+            }
+            None => Err(malformed("truncated")),
+        }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Box<dyn Error>> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn i32(&mut self) -> Result<i32, Box<dyn Error>> {
+        let bytes = self.bytes::<4>()?;
+        Ok(match self.little_endian {
+            true => i32::from_le_bytes(bytes),
+            false => i32::from_be_bytes(bytes),
+        })
+    }
+
+    fn i16(&mut self) -> Result<i16, Box<dyn Error>> {
+        let bytes = self.bytes::<2>()?;
+        Ok(match self.little_endian {
+            true => i16::from_le_bytes(bytes),
+            false => i16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn Error>> {
+        let bytes = self.bytes::<8>()?;
+        Ok(match self.little_endian {
+            true => u64::from_le_bytes(bytes),
+            false => u64::from_be_bytes(bytes),
+        })
+    }
+
+    fn count(&mut self) -> Result<usize, Box<dyn Error>> {
+        usize::try_from(self.i32()?).map_err(|_| malformed("negative count"))
+    }
+
+    //
+    // Reads an integer (or double) of the type, which takes size bytes in the
+    // native encoding and 4 or 8 bytes in XDR, and formats it.
+    //
+    fn number(&mut self, data_type: i32, size: usize) -> Result<String, Box<dyn Error>> {
+        let raw: u64 = match (self.xdr, size) {
+            (_, 8) => self.u64()?,
+            (true, _) | (false, 4) => self.i32()? as u32 as u64,
+            (false, 2) => self.i16()? as u16 as u64,
+            (false, _) => self.take(1)?[0] as u64,
+        };
+        Ok(match data_type {
+            DATA_TYPE_DOUBLE => f64::from_bits(raw).to_string(),
+            DATA_TYPE_INT64 | DATA_TYPE_INT64_ARRAY | DATA_TYPE_HRTIME => (raw as i64).to_string(),
+            DATA_TYPE_INT32 | DATA_TYPE_INT32_ARRAY => (raw as u32 as i32).to_string(),
+            DATA_TYPE_INT16 | DATA_TYPE_INT16_ARRAY => (raw as u16 as i16).to_string(),
+            DATA_TYPE_INT8 | DATA_TYPE_INT8_ARRAY => (raw as u8 as i8).to_string(),
+            DATA_TYPE_BOOLEAN_VALUE | DATA_TYPE_BOOLEAN_ARRAY => (raw != 0).to_string(),
+            DATA_TYPE_UINT8 | DATA_TYPE_BYTE | DATA_TYPE_UINT8_ARRAY | DATA_TYPE_BYTE_ARRAY => {
+                (raw & 0xff).to_string()
+            }
+            DATA_TYPE_UINT16 | DATA_TYPE_UINT16_ARRAY => (raw & 0xffff).to_string(),
+            _ => raw.to_string(),
+        })
+    }
+
+    //
+    // Moves past the nvpair starting at start, which the encoding says is
+    // size bytes long.  The size has to cover what was read of the nvpair
+    // already: anything less is malformed, and a size of zero would have the
+    // decoder go over the same nvpair again and again.
+    //
+    fn skip_nvpair(&mut self, name: &str, start: usize, size: usize) -> Result<(), Box<dyn Error>> {
+        if size == 0 || size < self.pos - start {
+            return Err(malformed(&format!("size of {}", name)));
+        }
+        self.pos = start;
+        self.take(size)?;
+        Ok(())
+    }
+
+    fn nested(&mut self) -> Result<Nvlist, Box<dyn Error>> {
+        if self.depth == MAX_DEPTH {
+            return Err(malformed("nvlists nested too deep"));
+        }
+        self.depth += 1;
+        let nvlist = match self.xdr {
+            true => self.xdr_nvlist(),
+            false => self.native_nvlist(),
+        };
+        self.depth -= 1;
+        nvlist
+    }
+
+    fn xdr_string(&mut self) -> Result<String, Box<dyn Error>> {
+        let len = self.count()?;
+        let bytes = self.take(len)?;
+        self.take((4 - len % 4) % 4)?;
+        Ok(String::from_utf8_lossy(bytes).to_string())
+    }
+
+    //
+    // An nvlist in XDR: its version and flags, the nvpairs, each starting
+    // with its encoded and decoded sizes, and two zeros in place of the sizes
+    // of the nvpair following the last one.
+    //
+    fn xdr_nvlist(&mut self) -> Result<Nvlist, Box<dyn Error>> {
+        self.take(8)?;
+        let mut nvlist = Vec::new();
+        loop {
+            let start = self.pos;
+            let encoded_size = self.count()?;
+            let decoded_size = self.count()?;
+            if encoded_size == 0 && decoded_size == 0 {
+                return Ok(nvlist);
+            }
+            let name = self.xdr_string()?;
+            let data_type = self.i32()?;
+            let nelem = self.count()?;
+            let size = match type_name(data_type) {
+                Some((_, size)) => size,
+                None => {
+                    // skip what we don't know about
+                    self.skip_nvpair(&name, start, encoded_size)?;
+                    continue;
+                }
+            };
+            let value = match data_type {
+                DATA_TYPE_BOOLEAN => Value::Scalar("true".to_string()),
+                DATA_TYPE_STRING => Value::Scalar(self.xdr_string()?),
+                DATA_TYPE_NVLIST => Value::Nvlists(vec![self.nested()?]),
+                DATA_TYPE_NVLIST_ARRAY => {
+                    Value::Nvlists((0..nelem).map(|_| self.nested()).collect::<Result<_, _>>()?)
+                }
+                DATA_TYPE_STRING_ARRAY => {
+                    Value::Array((0..nelem).map(|_| self.xdr_string()).collect::<Result<_, _>>()?)
+                }
+                // the byte arrays are opaque, one byte per element
+                DATA_TYPE_BYTE_ARRAY | DATA_TYPE_INT8_ARRAY | DATA_TYPE_UINT8_ARRAY => {
+                    let bytes = self.take(nelem)?;
+                    self.take((4 - nelem % 4) % 4)?;
+                    let values = bytes.iter().map(|b| match data_type {
+                        DATA_TYPE_INT8_ARRAY => (*b as i8).to_string(),
+                        _ => b.to_string(),
+                    });
+                    Value::Array(values.collect())
+                }
+                // the other arrays are counted
+                _ if type_name(data_type).unwrap().0.ends_with("-array") => {
+                    if self.count()? != nelem {
+                        return Err(malformed(&format!("element count of {}", name)));
+                    }
+                    let values = (0..nelem).map(|_| self.number(data_type, size));
+                    Value::Array(values.collect::<Result<_, _>>()?)
+                }
+                _ => Value::Scalar(self.number(data_type, size)?),
+            };
+            nvlist.push(Nvpair { name, data_type, value });
+        }
+    }
+
+    //
+    // An nvlist in the native encoding: its version and flags, the nvpairs as
+    // they are laid out in memory, with their sizes (aligned to 8 bytes)
+    // first, and a zero in place of the size of the nvpair following the last
+    // one.  The nvlists embedded in an nvpair follow it.
+    //
+    fn native_nvlist(&mut self) -> Result<Nvlist, Box<dyn Error>> {
+        self.take(8)?;
+        let mut nvlist = Vec::new();
+        loop {
+            let start = self.pos;
+            let nvp_size = self.count()?;
+            if nvp_size == 0 {
+                return Ok(nvlist);
+            }
+            let name_size = usize::try_from(self.i16()?).map_err(|_| malformed("name size"))?;
+            self.take(2)?;
+            let nelem = self.count()?;
+            let data_type = self.i32()?;
+            let name = self.take(name_size)?;
+            let name = String::from_utf8_lossy(name.split(|b| *b == 0).next().unwrap_or(name));
+            let value_offset = (NATIVE_NVPAIR_HEADER + name_size + 7) & !7;
+            if value_offset > nvp_size {
+                return Err(malformed(&format!("size of {}", name)));
+            }
+            self.pos = start + value_offset;
+            let value_size = nvp_size - value_offset;
+            let end = start + nvp_size;
+
+            let size = match type_name(data_type) {
+                Some((_, size)) => size,
+                None => {
+                    self.skip_nvpair(&name, start, nvp_size)?;
+                    continue;
+                }
+            };
+            let strings = |bytes: &[u8]| -> Vec<String> {
+                bytes
+                    .split(|b| *b == 0)
+                    .take(nelem)
+                    .map(|s| String::from_utf8_lossy(s).to_string())
+                    .collect()
+            };
+            let value = match data_type {
+                DATA_TYPE_BOOLEAN => Value::Scalar("true".to_string()),
+                DATA_TYPE_STRING => {
+                    let value = strings(self.take(value_size)?).into_iter().next();
+                    Value::Scalar(value.unwrap_or_default())
+                }
+                // the pointers to the strings are followed by the strings
+                DATA_TYPE_STRING_ARRAY => {
+                    self.take(8 * nelem)?;
+                    Value::Array(strings(self.take(end.saturating_sub(self.pos))?))
+                }
+                DATA_TYPE_NVLIST | DATA_TYPE_NVLIST_ARRAY => {
+                    self.skip_nvpair(&name, start, nvp_size)?;
+                    let count = if data_type == DATA_TYPE_NVLIST { 1 } else { nelem };
+                    Value::Nvlists((0..count).map(|_| self.nested()).collect::<Result<_, _>>()?)
+                }
+                _ if type_name(data_type).unwrap().0.ends_with("-array") => {
+                    let values = (0..nelem).map(|_| self.number(data_type, size));
+                    Value::Array(values.collect::<Result<_, _>>()?)
+                }
+                _ => Value::Scalar(self.number(data_type, size)?),
+            };
+            if data_type != DATA_TYPE_NVLIST && data_type != DATA_TYPE_NVLIST_ARRAY {
+                self.skip_nvpair(&name, start, nvp_size)?;
+            }
+            nvlist.push(Nvpair { name: name.to_string(), data_type, value });
+        }
+    }
+}
+
+fn decode(data: &[u8]) -> Result<Nvlist, Box<dyn Error>> {
+    if !is_packed_nvlist(data) {
+        return Err(malformed("no nvlist header"));
+    }
+    let xdr = data[0] == NV_ENCODE_XDR;
+    let mut decoder = Decoder {
+        data,
+        pos: 4,
+        xdr,
+        little_endian: !xdr && data[1] == NV_LITTLE_ENDIAN,
+        depth: 0,
+    };
+    decoder.nested()
+}
+
+//
+// Converts the nvpair to the structure it is parsed into from the XML.
+//
+fn to_xml(nvpair: Nvpair) -> NvpairXML {
+    let mut xml = NvpairXML {
+        name: Some(nvpair.name),
+        nvpair_type: type_name(nvpair.data_type).map_or("", |(name, _)| name).to_string(),
+        value: None,
+        nvlist_elements: None,
+        nvpair_elements: None,
+    };
+    match nvpair.value {
+        Value::Scalar(value) => xml.value = Some(value),
+        Value::Array(values) => {
+            xml.nvpair_elements = Some(
+                values
+                    .into_iter()
+                    .map(|value| NvpairXmlArrayElement { value: Some(value) })
+                    .collect(),
+            )
+        }
+        Value::Nvlists(nvlists) => {
+            xml.nvlist_elements = Some(
+                nvlists
+                    .into_iter()
+                    .map(|nvlist| NvlistXmlArrayElement {
+                        nvpairs: Some(nvlist.into_iter().map(to_xml).collect()),
+                    })
+                    .collect(),
+            )
+        }
+    }
+    xml
+}
+
+fn string(nvpair: &Nvpair) -> Option<String> {
+    match &nvpair.value {
+        Value::Scalar(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn vertex(nvlist: Nvlist) -> Result<VertexXml, Box<dyn Error>> {
+    let (mut name, mut instance, mut fmri) = (None, None, None);
+    let mut vertex = VertexXml {
+        name: String::new(),
+        instance: String::new(),
+        fmri: String::new(),
+        propgroups: Vec::new(),
+        outgoing_edges: None,
+    };
+    for nvpair in nvlist {
+        match (nvpair.name.as_str(), &nvpair.value) {
+            ("name", _) => name = string(&nvpair),
+            // the XML gives the instance in hex
+            ("instance", Value::Scalar(value)) => {
+                instance = match value.parse::<u64>() {
+                    Ok(n) => Some(format!("0x{:x}", n)),
+                    Err(_) => Some(value.clone()),
+                }
+            }
+            ("fmri", _) => fmri = string(&nvpair),
+            ("outgoing-edges", Value::Array(edges)) => {
+                let edges = edges.iter().map(|fmri| EdgeXml { fmri: fmri.clone() }).collect();
+                vertex.outgoing_edges = Some(OutgoingEdgesXml { edges })
+            }
+            _ => vertex.propgroups.push(to_xml(nvpair)),
+        }
+    }
+    let missing = |what: &str| malformed(&format!("vertex without {}", what));
+    vertex.name = name.ok_or_else(|| missing("name"))?;
+    vertex.instance = instance.ok_or_else(|| missing("instance"))?;
+    vertex.fmri = fmri.ok_or_else(|| missing("fmri"))?;
+    Ok(vertex)
+}
+
+//
+// Read the topology from the packed nvlist, parsing the vertices as specified
+// by the options.
+//
+pub(crate) fn read_digraph(
+    data: &[u8],
+    options: &RenderOptions,
+) -> Result<SasDigraph, Box<dyn Error>> {
+    let nvlist = decode(data)?;
+    let host = |name: &str| {
+        nvlist
+            .iter()
+            .find(|nvpair| nvpair.name == name)
+            .and_then(string)
+            .ok_or_else(|| SimpleError(format!("no {} in the snapshot", name)))
+    };
+    let mut digraph = SasDigraph::new(
+        host("product-id")?,
+        host("nodename")?,
+        host("os-version")?,
+        host("timestamp")?,
+    );
+    if let Ok(scheme) = host("fmri-scheme") {
+        digraph.scheme = scheme.trim().parse()?;
+    }
+
+    let mut vertices = Vec::new();
+    for nvpair in nvlist {
+        if let ("vertices", Value::Nvlists(nvlists)) = (nvpair.name.as_str(), nvpair.value) {
+            vertices = nvlists;
+        }
+    }
+
+    for nvlist in vertices {
+        let vertex = vertex(nvlist)?;
+        let fmri = vertex.fmri.clone();
+        let vtx = match build_vertex(vertex, digraph.scheme, options) {
+            Ok(vtx) => vtx,
+            Err(e) if options.lenient() => {
+                warn!("skipping {}: {}", fmri, e);
+                let message = e.to_string();
+                digraph.parse_warnings.push(ParseWarning { fmri, message });
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if digraph.scheme.is_root(&vtx.name) {
+            digraph.initiators.push(vtx.fmri.clone());
+        }
+        digraph.vertices.insert(vtx.fmri.clone(), vtx);
+    }
+    drop_skipped_edges(&mut digraph);
+    Ok(digraph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HBA: &str = "sas://:hba=500605b0000272a0:/initiator=500605b0000272a0";
+    const DISK: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000000";
+
+    //
+    // The values of the nvpairs the tests pack, in either encoding.
+    //
+    enum V {
+        Str(&'static str),
+        U64(u64),
+        U32s(Vec<u32>),
+        Strs(Vec<&'static str>),
+        Nvlists(Vec<Vec<(&'static str, V)>>),
+        // a type the decoder doesn't know about, with its encoded size in XDR
+        Unknown(usize),
+    }
+
+    fn data_type(value: &V) -> (i32, usize) {
+        match value {
+            V::Str(_) => (DATA_TYPE_STRING, 1),
+            V::U64(_) => (DATA_TYPE_UINT64, 1),
+            V::U32s(values) => (DATA_TYPE_UINT32_ARRAY, values.len()),
+            V::Strs(values) => (DATA_TYPE_STRING_ARRAY, values.len()),
+            V::Nvlists(nvlists) => (DATA_TYPE_NVLIST_ARRAY, nvlists.len()),
+            V::Unknown(_) => (99, 1),
+        }
+    }
+
+    fn xdr_string(out: &mut Vec<u8>, value: &str) {
+        out.extend(&(value.len() as i32).to_be_bytes());
+        out.extend(value.as_bytes());
+        out.resize(out.len() + (4 - value.len() % 4) % 4, 0);
+    }
+
+    fn xdr(nvlist: &[(&'static str, V)], out: &mut Vec<u8>) {
+        out.extend(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        for (name, value) in nvlist {
+            let (data_type, nelem) = data_type(value);
+            let mut pair = Vec::new();
+            xdr_string(&mut pair, name);
+            pair.extend(&data_type.to_be_bytes());
+            pair.extend(&(nelem as i32).to_be_bytes());
+            let mut encoded_size = 8 + pair.len();
+            match value {
+                V::Str(value) => xdr_string(&mut pair, value),
+                V::U64(value) => pair.extend(&value.to_be_bytes()),
+                V::U32s(values) => {
+                    pair.extend(&(values.len() as i32).to_be_bytes());
+                    values.iter().for_each(|v| pair.extend(&v.to_be_bytes()));
+                }
+                V::Strs(values) => values.iter().for_each(|v| xdr_string(&mut pair, v)),
+                V::Nvlists(nvlists) => nvlists.iter().for_each(|nvl| xdr(nvl, &mut pair)),
+                V::Unknown(size) => {
+                    encoded_size = *size;
+                    pair.resize(size.saturating_sub(8).max(pair.len()), 0);
+                }
+            }
+            if !matches!(value, V::Unknown(_)) {
+                encoded_size = 8 + pair.len();
+            }
+            out.extend(&(encoded_size as i32).to_be_bytes());
+            out.extend(&(8 + pair.len() as i32).to_be_bytes());
+            out.extend(pair);
+        }
+        out.extend(&[0; 8]);
+    }
+
+    fn native(nvlist: &[(&'static str, V)], le: bool, out: &mut Vec<u8>) {
+        // the big-endian bytes of a number, in the byte order packed
+        let order = |bytes: &[u8]| -> Vec<u8> {
+            match le {
+                true => bytes.iter().rev().cloned().collect(),
+                false => bytes.to_vec(),
+            }
+        };
+        out.extend(&[0; 8]);
+        for (name, value) in nvlist {
+            let (data_type, nelem) = data_type(value);
+            let name_size = name.len() + 1;
+            let value_offset = (NATIVE_NVPAIR_HEADER + name_size + 7) & !7;
+            let mut data = Vec::new();
+            let mut nested = Vec::new();
+            match value {
+                V::Str(value) => data.extend(value.as_bytes().iter().chain(&[0])),
+                V::U64(value) => data.extend(order(&value.to_be_bytes())),
+                V::U32s(values) => values.iter().for_each(|v| data.extend(order(&v.to_be_bytes()))),
+                V::Strs(values) => {
+                    data.resize(8 * values.len(), 0);
+                    values.iter().for_each(|v| data.extend(v.as_bytes().iter().chain(&[0])));
+                }
+                V::Nvlists(nvlists) => {
+                    data.resize(8 * nvlists.len(), 0);
+                    nvlists.iter().for_each(|nvl| native(nvl, le, &mut nested));
+                }
+                V::Unknown(_) => data.resize(8, 0),
+            }
+            let nvp_size = (value_offset + data.len() + 7) & !7;
+            let start = out.len();
+            out.extend(order(&(nvp_size as i32).to_be_bytes()));
+            out.extend(order(&(name_size as i16).to_be_bytes()));
+            out.extend(&[0, 0]);
+            out.extend(order(&(nelem as i32).to_be_bytes()));
+            out.extend(order(&data_type.to_be_bytes()));
+            out.extend(name.as_bytes().iter().chain(&[0]));
+            out.resize(start + value_offset, 0);
+            out.extend(data);
+            out.resize(start + nvp_size, 0);
+            out.extend(nested);
+        }
+        out.extend(&[0; 4]);
+    }
+
+    fn pack_xdr(nvlist: &[(&'static str, V)]) -> Vec<u8> {
+        let mut out = vec![NV_ENCODE_XDR, 0, 0, 0];
+        xdr(nvlist, &mut out);
+        out
+    }
+
+    fn pack_native(nvlist: &[(&'static str, V)], le: bool) -> Vec<u8> {
+        let mut out = vec![NV_ENCODE_NATIVE, le as u8, 0, 0];
+        native(nvlist, le, &mut out);
+        out
+    }
+
+    type Pairs = Vec<(&'static str, V)>;
+
+    fn propgroup(name: &'static str, props: &[(&'static str, &'static str)]) -> Pairs {
+        let props = props
+            .iter()
+            .map(|(name, value)| {
+                vec![
+                    ("property-name", V::Str(name)),
+                    ("property-type", V::Str("string")),
+                    ("property-value", V::Str(value)),
+                ]
+            })
+            .collect();
+        vec![("property-group-name", V::Str(name)), ("property-values", V::Nvlists(props))]
+    }
+
+    fn topology() -> Pairs {
+        vec![
+            ("fmri-scheme", V::Str("sas")),
+            ("nodename", V::Str("testhost")),
+            ("os-version", V::Str("joyent_20200101T000000Z")),
+            ("product-id", V::Str("Joyent-M12G5")),
+            ("timestamp", V::Str("2020-01-01T00:00:00Z")),
+            (
+                "vertices",
+                V::Nvlists(vec![
+                    vec![
+                        ("name", V::Str("initiator")),
+                        ("instance", V::U64(0)),
+                        ("fmri", V::Str(HBA)),
+                        (
+                            "property-groups",
+                            V::Nvlists(vec![propgroup("initiator", &[("manufacturer", "LSI")])]),
+                        ),
+                        ("outgoing-edges", V::Strs(vec![DISK])),
+                    ],
+                    vec![
+                        ("name", V::Str("target")),
+                        ("instance", V::U64(13)),
+                        ("fmri", V::Str(DISK)),
+                        (
+                            "property-groups",
+                            V::Nvlists(vec![vec![
+                                ("property-group-name", V::Str("target")),
+                                (
+                                    "property-values",
+                                    V::Nvlists(vec![vec![
+                                        ("property-name", V::Str("link-rates")),
+                                        ("property-type", V::Str("uint32-array")),
+                                        ("property-value", V::U32s(vec![12, 6])),
+                                    ]]),
+                                ),
+                            ]]),
+                        ),
+                    ],
+                ]),
+            ),
+        ]
+    }
+
+    fn check_topology(data: &[u8]) {
+        let digraph = read_digraph(data, &RenderOptions::default()).unwrap();
+        assert_eq!(digraph.nodename, "testhost");
+        assert_eq!(digraph.initiators, vec![HBA.to_string()]);
+        let hba = digraph.vertices.get(HBA).unwrap();
+        assert_eq!(hba.outgoing_edges(), &[DISK.to_string()]);
+        assert!(hba.properties.iter().any(|p| p.name == "manufacturer" && p.value == "LSI"));
+        let disk = digraph.vertices.get(DISK).unwrap();
+        assert_eq!(disk.instance.to_string(), "0xd");
+        assert!(disk.properties.iter().any(|p| p.name == "link-rates" && p.value == "12,6"));
+    }
+
+    #[test]
+    fn xdr_topology() {
+        check_topology(&pack_xdr(&topology()));
+    }
+
+    #[test]
+    fn native_topology() {
+        check_topology(&pack_native(&topology(), true));
+        check_topology(&pack_native(&topology(), false));
+    }
+
+    #[test]
+    fn truncated() {
+        for data in &[pack_xdr(&topology()), pack_native(&topology(), true)] {
+            for len in 0..data.len() {
+                assert!(read_digraph(&data[..len], &RenderOptions::default()).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nest = |depth: usize| {
+            let mut nvlist = vec![("leaf", V::Str("x"))];
+            for _ in 1..depth {
+                nvlist = vec![("nested", V::Nvlists(vec![nvlist]))];
+            }
+            nvlist
+        };
+        assert!(decode(&pack_xdr(&nest(MAX_DEPTH))).is_ok());
+        assert!(decode(&pack_native(&nest(MAX_DEPTH), true)).is_ok());
+        for data in &[pack_xdr(&nest(MAX_DEPTH + 1)), pack_native(&nest(MAX_DEPTH + 1), true)] {
+            let error = decode(data).unwrap_err().to_string();
+            assert!(error.contains("nested too deep"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn unknown_type() {
+        // skipped, going by its encoded size
+        let nvlist = decode(&pack_xdr(&[("odd", V::Unknown(28)), ("name", V::Str("x"))]));
+        let names: Vec<String> = nvlist.unwrap().into_iter().map(|nvp| nvp.name).collect();
+        assert_eq!(names, vec!["name"]);
+        let nvlist = decode(&pack_native(&[("odd", V::Unknown(0)), ("name", V::Str("x"))], true));
+        assert_eq!(nvlist.unwrap().len(), 1);
+
+        // an encoded size short of what was read of it would go round forever
+        for size in &[0, 8, 23] {
+            let error = decode(&pack_xdr(&[("odd", V::Unknown(*size))])).unwrap_err();
+            assert!(error.to_string().contains("size of odd"), "{}", error);
+        }
+    }
+}
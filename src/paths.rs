@@ -0,0 +1,312 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Comparison of the paths to a target.
+//
+// A drive that is fast through one HBA but slow through the other is
+// usually down to one of the links along the slow path, e.g. a wide port
+// with a PHY that negotiated a lower rate.  This lists every path from each
+// initiator to the target side by side, one column per path, with the PHYs
+// and negotiated link rates of the ports along the way, so the odd one out
+// stands out:
+//
+//     initiator I1 500605b0000272a0 (path 1)   initiator I2 500605b0000272b0 (path 1)
+//     initiator I1 500605b0000272a0            initiator I2 500605b0000272b0
+//       port 0-3 @ 12G                           port 0-3 @ 12G
+//       port 0-3 @ 12G                           port 4-7 @ 3G-12G
+//     expander E1 500304801e0f1abf             expander E1 500304801e0f1abf
+//     ...
+//     slowest link: 12G                        slowest link: 3G
+//
+// The target can be specified by its FMRI, WWN or reference number.  As the
+// same drive shows up once per HBA, under FMRIs that differ in the
+// authority, the paths to all of its vertices are listed.
+//
+// Library consumers get at the paths between two vertices through
+// SasDigraph::paths(), see all_paths(), which this goes through too.
+//
+use petgraph::algo::all_simple_paths;
+
+use std::error::Error;
+use std::fmt;
+
+use crate::analysis::{link_rate_label, parse_count};
+use crate::connections::port_phys;
+use crate::fmri::wwn;
+use crate::{SasDigraph, SasDigraphVertex, SimpleError, PORT, TARGET};
+
+//
+// The number of paths grows exponentially with the number of redundant links,
+// which could get out of hand on a pathological fabric.
+//
+const MAX_PATHS_PER_INITIATOR: usize = 16;
+
+#[derive(Debug)]
+struct PathHop {
+    label: String,
+    // lowest and highest negotiated link rate of the PHYs of a port
+    rates: Option<(u64, u64)>,
+}
+
+#[derive(Debug)]
+struct TargetPath {
+    // label of the initiator, and which of its paths this is
+    title: String,
+    hops: Vec<PathHop>,
+}
+
+impl TargetPath {
+    fn slowest_link(&self) -> Option<u64> {
+        self.hops.iter().filter_map(|h| h.rates).map(|(min, _)| min).min()
+    }
+}
+
+#[derive(Debug)]
+pub struct PathReport {
+    target: String,
+    paths: Vec<TargetPath>,
+}
+
+fn device_label(vtx: &SasDigraphVertex) -> String {
+    let id = match &vtx.reference {
+        Some(reference) => reference.clone(),
+        None => vtx.instance.to_string(),
+    };
+    match wwn(&vtx.fmri) {
+        Some(wwn) => format!("{} {} {}", vtx.name, id, wwn),
+        None => format!("{} {}", vtx.name, id),
+    }
+}
+
+fn hop(digraph: &SasDigraph, vtx: &SasDigraphVertex) -> PathHop {
+    if vtx.name != PORT {
+        return PathHop {
+            label: device_label(vtx),
+            rates: None,
+        };
+    }
+
+    let rates: Vec<u64> = vtx
+        .properties
+        .iter()
+        .find(|p| p.name == "negotiated-link-rate")
+        .map(|p| p.value.split(',').filter_map(parse_count).collect())
+        .unwrap_or_default();
+    let rates = match (rates.iter().min(), rates.iter().max()) {
+        (Some(min), Some(max)) => Some((*min, *max)),
+        _ => None,
+    };
+    let mut label = format!("  port {}", port_phys(digraph, &vtx.fmri));
+    match rates {
+        Some((min, max)) if min == max => {
+            label.push_str(&format!(" @ {}", link_rate_label(Some(min))))
+        }
+        Some((min, max)) => label.push_str(&format!(
+            " @ {}-{}",
+            link_rate_label(Some(min)),
+            link_rate_label(Some(max))
+        )),
+        None => (),
+    }
+    PathHop { label, rates }
+}
+
+fn is_target(vtx: &SasDigraphVertex, target: &str) -> bool {
+    vtx.name == TARGET
+        && (vtx.fmri == target
+            || matches!(wwn(&vtx.fmri), Some(wwn) if wwn.eq_ignore_ascii_case(target))
+            || vtx.reference.as_deref() == Some(target))
+}
+
+//
+// Returns the distinct paths from one vertex to another, i.e. the sequences
+// of vertices (from and to included) along the edges between them that don't
+// visit a vertex twice.  Their number grows exponentially with the number of
+// redundant links, so at most limit of them are returned, the first ones
+// found going by the order of the edges.  The paths are in the order of the
+// FMRIs along them, so they come out the same from one run to the next.
+//
+pub(crate) fn all_paths<'a>(
+    digraph: &'a SasDigraph,
+    from: &str,
+    to: &str,
+    limit: usize,
+) -> Vec<Vec<&'a SasDigraphVertex>> {
+    let (from, to) = match (digraph.node_index(from), digraph.node_index(to)) {
+        (Some(from), Some(to)) if limit > 0 => (from, to),
+        _ => return Vec::new(),
+    };
+    if from == to {
+        return vec![vec![&digraph.graph()[from]]];
+    }
+
+    let graph = digraph.graph();
+    let mut paths: Vec<Vec<&SasDigraphVertex>> =
+        all_simple_paths::<Vec<_>, _>(graph, from, to, 0, None)
+            .take(limit)
+            .map(|path| path.into_iter().map(|node| &graph[node]).collect())
+            .collect();
+    paths.sort_by(|a, b| a.iter().map(|v| &v.fmri).cmp(b.iter().map(|v| &v.fmri)));
+    paths
+}
+
+pub(crate) fn compare_paths(
+    digraph: &SasDigraph,
+    target: &str,
+) -> Result<PathReport, Box<dyn Error>> {
+    let label = match digraph.vertices.values().find(|v| is_target(v, target)) {
+        Some(vtx) => device_label(vtx),
+        None => {
+            let msg = format!("no target matching {} in the snapshot", target);
+            return Err(Box::new(SimpleError(msg)));
+        }
+    };
+
+    let mut report = PathReport {
+        target: label,
+        paths: Vec::new(),
+    };
+    let mut targets: Vec<&str> = digraph
+        .vertices
+        .values()
+        .filter(|v| is_target(v, target))
+        .map(|v| v.fmri.as_str())
+        .collect();
+    targets.sort_unstable();
+    for initiator in &digraph.initiators {
+        let mut paths = Vec::new();
+        for fmri in &targets {
+            let limit = MAX_PATHS_PER_INITIATOR - paths.len();
+            paths.extend(all_paths(digraph, initiator, fmri, limit));
+        }
+        let initiator_label = device_label(&digraph.vertices[initiator]);
+        for (i, path) in paths.iter().enumerate() {
+            report.paths.push(TargetPath {
+                title: format!("{} (path {})", initiator_label, i + 1),
+                hops: path.iter().map(|vtx| hop(digraph, vtx)).collect(),
+            });
+        }
+    }
+    Ok(report)
+}
+
+impl fmt::Display for PathReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "paths to {}", self.target)?;
+        if self.paths.is_empty() {
+            return write!(f, "\nnot reachable from any initiator");
+        }
+        writeln!(f)?;
+
+        //
+        // Lay the paths out in columns, with a row per hop and the slowest
+        // link of each path at the bottom.
+        //
+        let columns: Vec<Vec<String>> = self
+            .paths
+            .iter()
+            .map(|path| {
+                let mut column = vec![path.title.clone()];
+                column.extend(path.hops.iter().map(|h| h.label.clone()));
+                column.push(format!("slowest link: {}", link_rate_label(path.slowest_link())));
+                column
+            })
+            .collect();
+        let widths: Vec<usize> = columns
+            .iter()
+            .map(|c| c.iter().map(|cell| cell.chars().count()).max().unwrap_or(0) + 3)
+            .collect();
+        let rows = columns.iter().map(|c| c.len()).max().unwrap_or(0);
+        for row in 0..rows {
+            let mut line = String::from("\n");
+            for (column, width) in columns.iter().zip(&widths) {
+                // the slowest links line up in the last row
+                let cell = match row + 1 == rows {
+                    true => column.last(),
+                    false if row + 1 < column.len() => column.get(row),
+                    false => None,
+                };
+                let cell = cell.map_or("", |c| c.as_str());
+                line.push_str(&format!("{:<width$}", cell, width = width));
+            }
+            write!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EXPANDER, INITIATOR};
+
+    const HBA: &str = "sas://:hba=1:/initiator=1";
+    const LEFT: &str = "sas://:hba=1:/expander=2";
+    const RIGHT: &str = "sas://:hba=1:/expander=3";
+    const DISK: &str = "sas://:hba=1:/target=4";
+
+    // an initiator with two ways to a target, through either expander
+    fn diamond() -> SasDigraph {
+        SasDigraph::builder()
+            .add_vertex(HBA, INITIATOR, 0)
+            .add_vertex(LEFT, EXPANDER, 0)
+            .add_vertex(RIGHT, EXPANDER, 1)
+            .add_vertex(DISK, TARGET, 0)
+            .add_edge(HBA, RIGHT)
+            .add_edge(HBA, LEFT)
+            .add_edge(LEFT, DISK)
+            .add_edge(RIGHT, DISK)
+            .build()
+            .unwrap()
+    }
+
+    fn fmris(paths: Vec<Vec<&SasDigraphVertex>>) -> Vec<Vec<&str>> {
+        paths.into_iter().map(|path| path.iter().map(|v| v.fmri.as_str()).collect()).collect()
+    }
+
+    #[test]
+    fn diamond_paths() {
+        let digraph = diamond();
+        assert_eq!(
+            fmris(digraph.paths(HBA, DISK)),
+            vec![vec![HBA, LEFT, DISK], vec![HBA, RIGHT, DISK]]
+        );
+        assert_eq!(fmris(digraph.paths_limited(HBA, DISK, 1)).len(), 1);
+        assert!(digraph.paths_limited(HBA, DISK, 0).is_empty());
+        assert_eq!(fmris(digraph.paths(LEFT, LEFT)), vec![vec![LEFT]]);
+        assert!(digraph.paths(DISK, HBA).is_empty());
+        assert!(digraph.paths(HBA, "sas://:hba=1:/target=5").is_empty());
+    }
+
+    #[test]
+    fn diamond_report() {
+        let report = compare_paths(&diamond(), "4").unwrap();
+        let titles: Vec<&str> = report.paths.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["initiator 0x0 1 (path 1)", "initiator 0x0 1 (path 2)"]);
+    }
+
+    #[test]
+    fn limit() {
+        // a chain of 24 diamonds, with 2^24 paths from end to end
+        let mut builder = SasDigraph::builder().add_vertex("v0", INITIATOR, 0);
+        for i in 0..24 {
+            let (from, to) = (format!("v{}", i), format!("v{}", i + 1));
+            for side in &["a", "b"] {
+                let middle = format!("v{}{}", i, side);
+                builder = builder
+                    .add_vertex(&middle, EXPANDER, 0)
+                    .add_edge(&from, &middle)
+                    .add_edge(&middle, &to);
+            }
+            builder = builder.add_vertex(&to, EXPANDER, 0);
+        }
+        let digraph = builder.build().unwrap();
+        assert_eq!(digraph.paths_limited("v0", "v24", MAX_PATHS_PER_INITIATOR).len(), 16);
+    }
+}
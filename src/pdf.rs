@@ -0,0 +1,227 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Printable PDF report.
+//
+// The report starts with a cover page listing the host information and the
+// number of devices.  It is followed by the topology, drawn at a fixed scale
+// and split into as many landscape A4 pages as needed, so that large fabrics
+// remain legible on paper; each page is labeled with its row and column in
+// the grid of pages.  The topology is converted to PDF as vector graphics, so
+// it can be zoomed into on screen as well.
+//
+// Converting SVG to PDF pulls in svg2pdf and its dependencies, so the report
+// is only available if the crate is built with the "pdf" feature.
+//
+use std::error::Error;
+
+#[cfg(feature = "pdf")]
+use crate::expect::inventory;
+use crate::locale::Locale;
+use crate::{SasDigraph, SimpleError};
+
+// landscape A4, in points
+#[cfg(feature = "pdf")]
+const PAGE_WIDTH: f32 = 842.0;
+#[cfg(feature = "pdf")]
+const PAGE_HEIGHT: f32 = 595.0;
+#[cfg(feature = "pdf")]
+const MARGIN: f32 = 36.0;
+// room for the page label at the top of each page
+#[cfg(feature = "pdf")]
+const HEADER: f32 = 20.0;
+// points per SVG user unit
+#[cfg(feature = "pdf")]
+const SCALE: f32 = 0.5;
+
+//
+// Text in the PDF uses the standard Helvetica font, which only covers Latin-1,
+// so anything else is replaced.
+//
+#[cfg(feature = "pdf")]
+fn latin1(s: &str) -> Vec<u8> {
+    s.chars()
+        .map(|c| match c as u32 {
+            n if n < 256 => n as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+//
+// Returns the report for digraph, whose rendering is the SVG document svg.
+// The area of width by height user units at the top-left of the
+// SVG is included.  Relative image references are resolved against
+// resources_dir.
+//
+#[cfg(feature = "pdf")]
+pub(crate) fn render_pdf(
+    digraph: &SasDigraph,
+    svg: &str,
+    resources_dir: &str,
+    (width, height): (u32, u32),
+    locale: Locale,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    use pdf_writer::{Content, Name, Pdf, Rect, Ref, Str};
+    use svg2pdf::usvg::{fontdb, PostProcessingSteps, Size, Tree, TreeParsing, TreePostProc};
+
+    let size = match Size::from_wh(width as f32, height as f32) {
+        Some(size) => size,
+        None => return Err(Box::new(SimpleError("empty topology".to_string()))),
+    };
+    let options = svg2pdf::usvg::Options {
+        resources_dir: Some(std::path::PathBuf::from(resources_dir)),
+        default_size: size,
+        ..svg2pdf::usvg::Options::default()
+    };
+    let mut tree = Tree::from_data(svg.as_bytes(), &options)?;
+    let mut fonts = fontdb::Database::new();
+    fonts.load_system_fonts();
+    tree.postprocess(PostProcessingSteps::default(), &fonts);
+
+    let mut pdf = Pdf::new();
+    let catalog_id = Ref::new(1);
+    let pages_id = Ref::new(2);
+    let font_id = Ref::new(3);
+    let svg_id = Ref::new(4);
+    let font_name = Name(b"F1");
+    let svg_name = Name(b"S1");
+    let mut next_id =
+        svg2pdf::convert_tree_into(&tree, svg2pdf::Options::default(), &mut pdf, svg_id);
+    let mut alloc = || {
+        let id = next_id;
+        next_id = Ref::new(id.get() + 1);
+        id
+    };
+
+    //
+    // Lay out the pages: the cover page, followed by the grid of pages the
+    // topology is split into.
+    //
+    let area_width = PAGE_WIDTH - (2.0 * MARGIN);
+    let area_height = PAGE_HEIGHT - (2.0 * MARGIN) - HEADER;
+    let topo_width = width as f32 * SCALE;
+    let topo_height = height as f32 * SCALE;
+    let columns = (topo_width / area_width).ceil().max(1.0) as u32;
+    let rows = (topo_height / area_height).ceil().max(1.0) as u32;
+
+    let mut contents = Vec::new();
+
+    let mut cover = Content::new();
+    let (counts, _) = inventory(digraph);
+    let mut lines = vec![
+        format!("Host: {}", digraph.nodename),
+        format!("Product: {}", digraph.product_id),
+        format!("OS version: {}", digraph.os_version),
+        format!("Snapshot taken: {}", locale.timestamp(&digraph.timestamp)),
+        String::new(),
+    ];
+    for (name, count) in &counts {
+        lines.push(format!("{}s: {}", name, locale.count(*count)));
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "The topology follows on {} page(s), {} across by {} down.",
+        rows * columns,
+        columns,
+        rows
+    ));
+    cover
+        .begin_text()
+        .set_font(font_name, 24.0)
+        .next_line(MARGIN, PAGE_HEIGHT - MARGIN - 24.0)
+        .show(Str(b"SAS Topology"))
+        .set_font(font_name, 12.0)
+        .set_leading(18.0)
+        .next_line(0.0, -36.0);
+    for line in &lines {
+        cover.show(Str(&latin1(line))).next_line_using_leading();
+    }
+    cover.end_text();
+    contents.push(cover.finish());
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut page = Content::new();
+            let label = format!(
+                "{}: row {} of {}, column {} of {}",
+                digraph.nodename,
+                row + 1,
+                rows,
+                column + 1,
+                columns
+            );
+            page.begin_text()
+                .set_font(font_name, 10.0)
+                .next_line(MARGIN, PAGE_HEIGHT - MARGIN - 10.0)
+                .show(Str(&latin1(&label)))
+                .end_text();
+
+            //
+            // Clip to the printable area and shift the topology so that the
+            // part for this page lands in it.  PDF coordinates start at the
+            // bottom-left of the page.
+            //
+            let top = PAGE_HEIGHT - MARGIN - HEADER;
+            page.save_state()
+                .rect(MARGIN, MARGIN, area_width, area_height)
+                .clip_nonzero()
+                .end_path()
+                .transform([
+                    topo_width,
+                    0.0,
+                    0.0,
+                    topo_height,
+                    MARGIN - (column as f32 * area_width),
+                    top - topo_height + (row as f32 * area_height),
+                ])
+                .x_object(svg_name)
+                .restore_state();
+            contents.push(page.finish());
+        }
+    }
+
+    let mut page_ids = Vec::new();
+    for content in contents {
+        let page_id = alloc();
+        let content_id = alloc();
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+            .parent(pages_id)
+            .contents(content_id);
+        let mut resources = page.resources();
+        resources.x_objects().pair(svg_name, svg_id);
+        resources.fonts().pair(font_name, font_id);
+        // the page is written out once its writers are dropped
+        drop(resources);
+        drop(page);
+        pdf.stream(content_id, &content);
+        page_ids.push(page_id);
+    }
+
+    pdf.catalog(catalog_id).pages(pages_id);
+    pdf.pages(pages_id)
+        .count(page_ids.len() as i32)
+        .kids(page_ids);
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
+    Ok(pdf.finish())
+}
+
+#[cfg(not(feature = "pdf"))]
+pub(crate) fn render_pdf(
+    _digraph: &SasDigraph,
+    _svg: &str,
+    _resources_dir: &str,
+    _size: (u32, u32),
+    _locale: Locale,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    Err(Box::new(SimpleError(
+        "PDF output requires sastopo2svg to be built with the \"pdf\" feature".to_string(),
+    )))
+}
@@ -0,0 +1,296 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Selection and action pipelines.
+//
+// A pipeline composes filtering, annotation and output steps in a single
+// invocation, like a shell pipeline, e.g.:
+//
+//     select name=target model~ST4000 | annotate tier=cold | render svg json
+//
+// The stages act in turn on the topology read from the snapshot:
+//
+//   - select EXPR: keeps the vertices matching the expression, along with the
+//     vertices on the paths leading to them (so that they're still drawn
+//     connected to their initiators), and makes the matching vertices the
+//     selection the following stages act on (initially, all of them)
+//
+//   - annotate KEY=VALUE...: sets the properties on the selected vertices
+//
+//   - render [FORMAT...]: renders the topology as it stands in the specified
+//     formats (by default, those of the options) to the output directory.  A
+//     later render overwrites the files of an earlier one in the same format.
+//
+// An expression is a list of conditions, all of which a vertex has to meet:
+//
+//   - KEY=VALUE: the value is VALUE
+//   - KEY!=VALUE: the value isn't VALUE (or there is none)
+//   - KEY~TEXT: the value contains TEXT, regardless of case
+//   - KEY: there is a value
+//
+// where KEY is the name of a property or one of name, fmri, instance and
+// reference.  Words containing spaces (or "|") are double-quoted, e.g.
+// location="Front Disk 0".
+//
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{OutputFormat, SasDigraph, SasDigraphProperty, SasDigraphVertex};
+
+//
+// Group the annotations are put in, as far as property groups are shown.
+//
+const ANNOTATION_GROUP: &str = "annotations";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Equals(String, String),
+    NotEquals(String, String),
+    Contains(String, String),
+    Present(String),
+}
+
+//
+// Expression of a select stage.
+//
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selector {
+    expr: String,
+    conditions: Vec<Condition>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stage {
+    Select(Selector),
+    Annotate(Vec<(String, String)>),
+    Render(Vec<OutputFormat>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+//
+// Splits the script into words, with the stages separated by "|" words.
+// Double quotes group the characters between them into (part of) a word.
+//
+fn words(script: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in script.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            '|' if !quoted => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                }
+                words.push("|".to_string());
+                started = false;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                }
+                started = false;
+            }
+            c => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+    if quoted {
+        return Err(format!("unterminated quote in pipeline: {}", script));
+    }
+    if started {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+fn condition(word: &str) -> Result<Condition, String> {
+    let (condition, key) = if let Some((key, value)) = word.split_once("!=") {
+        (Condition::NotEquals(key.to_string(), value.to_string()), key)
+    } else if let Some((key, value)) = word.split_once('=') {
+        (Condition::Equals(key.to_string(), value.to_string()), key)
+    } else if let Some((key, text)) = word.split_once('~') {
+        (Condition::Contains(key.to_string(), text.to_lowercase()), key)
+    } else {
+        (Condition::Present(word.to_string()), word)
+    };
+    if key.is_empty() {
+        return Err(format!("condition without a key: {}", word));
+    }
+    Ok(condition)
+}
+
+impl Selector {
+    fn from_words(words: &[String]) -> Result<Selector, String> {
+        if words.is_empty() {
+            return Err("select requires an expression".to_string());
+        }
+        Ok(Selector {
+            expr: words.join(" "),
+            conditions: words.iter().map(|w| condition(w)).collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    pub fn matches(&self, vtx: &SasDigraphVertex) -> bool {
+        let value = |key: &str| match key {
+            "name" => Some(vtx.name.as_str()),
+            "fmri" => Some(vtx.fmri.as_str()),
+            "instance" => Some(vtx.instance.raw.as_str()),
+            "reference" => vtx.reference.as_deref(),
+            _ => vtx.property(key),
+        };
+        self.conditions.iter().all(|condition| match condition {
+            Condition::Equals(key, expected) => value(key) == Some(expected.as_str()),
+            Condition::NotEquals(key, expected) => value(key) != Some(expected.as_str()),
+            Condition::Contains(key, text) => {
+                matches!(value(key), Some(v) if v.to_lowercase().contains(text.as_str()))
+            }
+            Condition::Present(key) => value(key).is_some(),
+        })
+    }
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Selector, String> {
+        Selector::from_words(&words(expr)?)
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+fn stage(words: &[String]) -> Result<Stage, String> {
+    let (name, args) = match words.split_first() {
+        Some((name, args)) => (name.as_str(), args),
+        None => return Err("empty stage in pipeline".to_string()),
+    };
+    match name {
+        "select" => Ok(Stage::Select(Selector::from_words(args)?)),
+        "annotate" if args.is_empty() => Err("annotate requires KEY=VALUE".to_string()),
+        "annotate" => args
+            .iter()
+            .map(|arg| match arg.split_once('=') {
+                Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+                _ => Err(format!("annotate requires KEY=VALUE, not {}", arg)),
+            })
+            .collect::<Result<_, _>>()
+            .map(Stage::Annotate),
+        "render" => {
+            let formats = args
+                .iter()
+                .flat_map(|arg| arg.split(','))
+                .map(|format| format.parse::<OutputFormat>())
+                .collect::<Result<Vec<_>, _>>()?;
+            if formats.contains(&OutputFormat::Text) {
+                return Err("the text tree can't be rendered in a pipeline".to_string());
+            }
+            Ok(Stage::Render(formats))
+        }
+        _ => Err(format!("unknown pipeline stage: {}", name)),
+    }
+}
+
+impl FromStr for Pipeline {
+    type Err = String;
+
+    fn from_str(script: &str) -> Result<Pipeline, String> {
+        let words = words(script)?;
+        let stages = words
+            .split(|word| word == "|")
+            .map(stage)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Pipeline { stages })
+    }
+}
+
+impl Pipeline {
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+}
+
+//
+// Returns the part of the digraph made up of the vertices matching the
+// selector and those on the paths leading to them, and the FMRIs of the
+// former, sorted.
+//
+pub(crate) fn select(digraph: &SasDigraph, selector: &Selector) -> (SasDigraph, Vec<String>) {
+    let mut incoming: HashMap<&str, Vec<&str>> = HashMap::new();
+    for vtx in digraph.vertices.values() {
+        for edge in vtx.outgoing_edges() {
+            incoming.entry(edge.as_str()).or_default().push(vtx.fmri.as_str());
+        }
+    }
+
+    let mut selection: Vec<String> = digraph
+        .vertices
+        .values()
+        .filter(|vtx| selector.matches(vtx))
+        .map(|vtx| vtx.fmri.clone())
+        .collect();
+    selection.sort();
+
+    let mut kept: HashSet<&str> = HashSet::new();
+    let mut queue: Vec<&str> = selection.iter().map(|fmri| fmri.as_str()).collect();
+    while let Some(fmri) = queue.pop() {
+        if kept.insert(fmri) {
+            queue.extend(incoming.get(fmri).into_iter().flatten());
+        }
+    }
+
+    let mut selected = digraph.clone();
+    selected.vertices.retain(|fmri, _| kept.contains(fmri.as_str()));
+    selected.initiators.retain(|fmri| kept.contains(fmri.as_str()));
+    selected.vertices.map_edges(|vtx| {
+        let edges = vtx.outgoing_edges.as_ref()?;
+        Some(edges.iter().filter(|edge| kept.contains(edge.as_str())).cloned().collect())
+    });
+    (selected, selection)
+}
+
+//
+// Sets the properties on the selected vertices, replacing any they already
+// have of the same names.
+//
+pub(crate) fn annotate(
+    digraph: &mut SasDigraph,
+    selection: &[String],
+    annotations: &[(String, String)],
+) {
+    for fmri in selection {
+        if let Some(vtx) = digraph.vertices.get_mut(fmri) {
+            for (key, value) in annotations {
+                vtx.properties.retain(|p| p.name != *key);
+                let mut prop = SasDigraphProperty::new(key.clone(), value.clone());
+                prop.group = Some(ANNOTATION_GROUP.to_string());
+                vtx.properties.push(prop);
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Typed property values.
+//
+// The value of a property is displayed as a string, with the elements of an
+// array joined by commas, which is all the rendering needs.  Consumers of the
+// library (see SasDigraph) compare link rates and error counters, so each
+// property also keeps its value as the type of the nvpair it was read from.
+// Integers are written in decimal or hex (with a leading "0x") in snapshots;
+// either way they are parsed into their numeric value.
+//
+// Properties that didn't come from a snapshot nvpair, e.g. those read back
+// from a JSON export or added by an annotation, are strings.  So are values
+// that don't parse as their nvpair type claims, as they are displayed as is.
+//
+use std::convert::TryFrom;
+
+use crate::analysis::parse_count;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropValue {
+    String(String),
+    // any of the unsigned integer types, uint8 to uint64
+    U64(u64),
+    // any of the signed integer types, int8 to int64
+    I64(i64),
+    Bool(bool),
+    Double(f64),
+    StringArray(Vec<String>),
+    U64Array(Vec<u64>),
+    I64Array(Vec<i64>),
+    BoolArray(Vec<bool>),
+    // a nested nvlist (or array of them), as structured JSON
+    Nvlist(serde_json::Value),
+}
+
+fn parse_signed(value: &str) -> Option<i64> {
+    let value = value.trim();
+    match value.strip_prefix('-') {
+        Some(magnitude) => parse_count(magnitude)
+            .and_then(|m| i64::try_from(m).ok())
+            .map(|m| -m),
+        None => parse_count(value).and_then(|v| i64::try_from(v).ok()),
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+impl PropValue {
+    //
+    // The value of an nvpair of the specified type (e.g. "uint32-array"), the
+    // elements of an array type being passed one by one.
+    //
+    pub(crate) fn parse(nvpair_type: &str, values: &[String]) -> Option<PropValue> {
+        let scalar = || values.first().map(String::as_str);
+        let base = nvpair_type.strip_suffix("-array");
+        match (base, nvpair_type) {
+            (None, "string") => scalar().map(|v| PropValue::String(v.to_string())),
+            (None, "boolean" | "boolean-value") => {
+                scalar().and_then(parse_bool).map(PropValue::Bool)
+            }
+            (None, "double") => {
+                scalar().and_then(|v| v.trim().parse().ok()).map(PropValue::Double)
+            }
+            (None, t) if t.starts_with("uint") || t == "byte" || t == "hrtime" => {
+                scalar().and_then(parse_count).map(PropValue::U64)
+            }
+            (None, t) if t.starts_with("int") => {
+                scalar().and_then(parse_signed).map(PropValue::I64)
+            }
+            (Some("string"), _) => Some(PropValue::StringArray(values.to_vec())),
+            (Some("boolean"), _) => {
+                let values: Option<_> = values.iter().map(|v| parse_bool(v)).collect();
+                values.map(PropValue::BoolArray)
+            }
+            (Some(t), _) if t.starts_with("uint") || t == "byte" => {
+                let values: Option<_> = values.iter().map(|v| parse_count(v)).collect();
+                values.map(PropValue::U64Array)
+            }
+            (Some(t), _) if t.starts_with("int") => {
+                let values: Option<_> = values.iter().map(|v| parse_signed(v)).collect();
+                values.map(PropValue::I64Array)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    //
+    // The value of an unsigned integer, or of a signed one that isn't
+    // negative.
+    //
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            PropValue::U64(value) => Some(*value),
+            PropValue::I64(value) => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            PropValue::I64(value) => Some(*value),
+            PropValue::U64(value) => i64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            PropValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    //
+    // The elements of an unsigned integer array, e.g. the per-PHY link rates
+    // and error counters.  A scalar is an array of one.
+    //
+    pub fn as_u64_array(&self) -> Option<Vec<u64>> {
+        match self {
+            PropValue::U64Array(values) => Some(values.clone()),
+            PropValue::U64(value) => Some(vec![*value]),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Categorization of vertex properties.
+//
+// Some vertices carry dozens of properties.  To keep the info panel readable
+// the properties are sorted into a fixed set of categories, which the viewer
+// renders as separate collapsible sections.
+//
+use std::collections::BTreeMap;
+
+use crate::SasDigraphVertex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PropCategory {
+    Identity,
+    Connectivity,
+    Firmware,
+    Errors,
+    Other,
+}
+
+impl PropCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PropCategory::Identity => "identity",
+            PropCategory::Connectivity => "connectivity",
+            PropCategory::Firmware => "firmware",
+            PropCategory::Errors => "errors",
+            PropCategory::Other => "other",
+        }
+    }
+}
+
+//
+// Well-known properties, in the order they should be listed within their
+// category.  Properties not listed here are categorized by name and listed
+// alphabetically after the well-known ones.
+//
+const IDENTITY_PROPS: &[&str] = &[
+    "name",
+    "wwn",
+    "manufacturer",
+    "model",
+    "serial-number",
+    "location",
+    "logical-disk",
+    "hc-fmri",
+    "dev-fmri",
+    "devfs-path",
+];
+
+const CONNECTIVITY_PROPS: &[&str] = &[
+    "sas-port-type",
+    "local-sas-address",
+    "attached-sas-address",
+    "start-phy",
+    "end-phy",
+    "max-link-rate",
+    "negotiated-link-rate",
+    "lanes",
+    "link-speed",
+];
+
+const FIRMWARE_PROPS: &[&str] = &["firmware-revision", "revision"];
+
+const ERROR_PROPS: &[&str] = &[
+    "invalid-dword",
+    "running-disparity-error",
+    "loss-dword-sync",
+    "reset-problem-count",
+];
+
+pub fn categorize(propname: &str) -> PropCategory {
+    if IDENTITY_PROPS.contains(&propname) {
+        PropCategory::Identity
+    } else if CONNECTIVITY_PROPS.contains(&propname) {
+        PropCategory::Connectivity
+    } else if FIRMWARE_PROPS.contains(&propname) {
+        PropCategory::Firmware
+    } else if ERROR_PROPS.contains(&propname)
+        || propname.contains("error")
+        || propname.ends_with("-count")
+    {
+        PropCategory::Errors
+    } else if propname.contains("firmware") || propname.ends_with("-rev") {
+        PropCategory::Firmware
+    } else if propname.contains("sas-address")
+        || propname.contains("link-rate")
+        || propname.contains("phy")
+    {
+        PropCategory::Connectivity
+    } else if propname.contains("serial") || propname.contains("fmri") {
+        PropCategory::Identity
+    } else {
+        PropCategory::Other
+    }
+}
+
+fn sort_key(category: PropCategory, propname: &str) -> (usize, String) {
+    let known = match category {
+        PropCategory::Identity => IDENTITY_PROPS,
+        PropCategory::Connectivity => CONNECTIVITY_PROPS,
+        PropCategory::Firmware => FIRMWARE_PROPS,
+        PropCategory::Errors => ERROR_PROPS,
+        PropCategory::Other => &[],
+    };
+    match known.iter().position(|p| *p == propname) {
+        Some(pos) => (pos, String::new()),
+        None => (known.len(), propname.to_string()),
+    }
+}
+
+//
+// Returns the names of the properties of a vertex, grouped by category and
+// sorted in display order.  Empty categories are omitted.
+//
+pub fn categorized_properties(vtx: &SasDigraphVertex) -> BTreeMap<PropCategory, Vec<String>> {
+    let mut categories: BTreeMap<PropCategory, Vec<String>> = BTreeMap::new();
+
+    for prop in &vtx.properties {
+        let names = categories.entry(categorize(&prop.name)).or_default();
+        if !names.contains(&prop.name) {
+            names.push(prop.name.clone());
+        }
+    }
+    for (category, names) in categories.iter_mut() {
+        names.sort_by_key(|name| sort_key(*category, name));
+    }
+    categories
+}
+
+//
+// Encode the categorized property names in the compact form that is attached
+// to the vertices in the SVG, e.g.:
+//
+//     identity=manufacturer,model|connectivity=local-sas-address
+//
+pub fn encode_categories(categories: &BTreeMap<PropCategory, Vec<String>>) -> String {
+    categories
+        .iter()
+        .map(|(category, names)| format!("{}={}", category.as_str(), names.join(",")))
+        .collect::<Vec<String>>()
+        .join("|")
+}
+
+//
+// Encode the names of the properties of a vertex by the property group they
+// came from, in the same form as the categories, e.g.:
+//
+//     storage=model,serial-number|authority=product-id
+//
+// Properties of unknown origin are left out.
+//
+pub fn encode_groups(vtx: &SasDigraphVertex) -> String {
+    let mut groups: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for prop in &vtx.properties {
+        if let Some(group) = &prop.group {
+            groups.entry(group).or_default().push(&prop.name);
+        }
+    }
+    groups
+        .iter()
+        .map(|(group, names)| format!("{}={}", group, names.join(",")))
+        .collect::<Vec<String>>()
+        .join("|")
+}
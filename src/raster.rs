@@ -0,0 +1,72 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// PNG rendering of the generated SVG, for places that don't display SVG
+// (e.g. ticketing systems).  Rasterizing pulls in resvg and its dependencies,
+// so it is only available if the crate is built with the "png" feature.
+//
+use std::error::Error;
+
+use crate::SimpleError;
+
+//
+// SVG user units are CSS pixels, i.e. 1/96th of an inch.
+//
+#[cfg(feature = "png")]
+const SVG_DPI: f32 = 96.0;
+
+//
+// Render the SVG document svg to a PNG image.  The area of width by height user
+// units at the top-left of the SVG is rendered at the specified resolution.
+// Relative image references are resolved against resources_dir.
+//
+#[cfg(feature = "png")]
+pub(crate) fn render_png(
+    svg: &str,
+    resources_dir: &str,
+    (width, height): (u32, u32),
+    dpi: u32,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    use resvg::tiny_skia::{Color, Pixmap, Transform};
+    use resvg::usvg::{Options, Tree};
+
+    let mut options = Options {
+        resources_dir: Some(std::path::PathBuf::from(resources_dir)),
+        ..Options::default()
+    };
+    options.fontdb_mut().load_system_fonts();
+    let tree = Tree::from_data(svg.as_bytes(), &options)?;
+
+    let scale = dpi as f32 / SVG_DPI;
+    let mut pixmap = match Pixmap::new(
+        (width as f32 * scale).ceil() as u32,
+        (height as f32 * scale).ceil() as u32,
+    ) {
+        Some(pixmap) => pixmap,
+        None => {
+            let msg = format!("invalid PNG size at {} DPI", dpi);
+            return Err(Box::new(SimpleError(msg)));
+        }
+    };
+    pixmap.fill(Color::WHITE);
+    resvg::render(&tree, Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    Ok(pixmap.encode_png()?)
+}
+
+#[cfg(not(feature = "png"))]
+pub(crate) fn render_png(
+    _svg: &str,
+    _resources_dir: &str,
+    _size: (u32, u32),
+    _dpi: u32,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    Err(Box::new(SimpleError(
+        "PNG output requires sastopo2svg to be built with the \"png\" feature".to_string(),
+    )))
+}
@@ -0,0 +1,120 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Initiator reachability.
+//
+// In a multipathed configuration the same physical device is reachable from
+// more than one initiator, and shows up once under each of them.  Each
+// initiator is assigned a color, and every device gets a strip above its icon
+// which is split into one segment per initiator that can reach it.  A device
+// that is only visible to one of the HBAs thus stands out from its dual-pathed
+// neighbours.
+//
+use svg::node::element::{Group, Rectangle, Title};
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::fmri::device_key;
+use crate::{html_escape, SasDigraph};
+
+pub(crate) const PALETTE: &[&str] = &[
+    "#1E88E5", "#D81B60", "#43A047", "#FB8C00", "#8E24AA", "#00ACC1", "#6D4C41", "#FDD835",
+];
+
+// height of the strip drawn above a vertex
+const STRIP_HEIGHT: u32 = 8;
+
+pub(crate) struct Reachability {
+    // initiator labels, in the order their colors are assigned
+    labels: Vec<String>,
+    // indexes of the initiators that reach each device, hashed by device key
+    reach: HashMap<String, BTreeSet<usize>>,
+}
+
+impl Reachability {
+    pub(crate) fn new(digraph: &SasDigraph) -> Reachability {
+        let mut initiators = digraph.initiators.clone();
+        initiators.sort();
+
+        let mut reach: HashMap<String, BTreeSet<usize>> = HashMap::new();
+        for (index, fmri) in initiators.iter().enumerate() {
+            let mut stack = vec![fmri.as_str()];
+            let mut seen = BTreeSet::new();
+            while let Some(fmri) = stack.pop() {
+                if !seen.insert(fmri) {
+                    continue;
+                }
+                reach
+                    .entry(device_key(fmri).to_string())
+                    .or_default()
+                    .insert(index);
+                if let Some(vtx) = digraph.vertices.get(fmri) {
+                    if let Some(edges) = &vtx.outgoing_edges {
+                        stack.extend(edges.iter().map(|e| e.as_str()));
+                    }
+                }
+            }
+        }
+        let labels = initiators
+            .iter()
+            .map(|fmri| match digraph.vertices.get(fmri).and_then(|v| v.reference.as_ref()) {
+                Some(reference) => format!("{} ({})", reference, device_key(fmri)),
+                None => device_key(fmri).to_string(),
+            })
+            .collect();
+        Reachability { labels, reach }
+    }
+
+    fn color(index: usize) -> &'static str {
+        PALETTE[index % PALETTE.len()]
+    }
+
+    //
+    // Returns the strip to draw above the vertex with the specified FMRI and
+    // position, or None if the vertex isn't reachable from any initiator.
+    //
+    pub(crate) fn strip(&self, fmri: &str, x: u32, y: u32, width: u32) -> Option<Group> {
+        let indexes = self.reach.get(device_key(fmri))?;
+        let seg_width = width / indexes.len() as u32;
+        let labels: Vec<&str> = indexes.iter().map(|i| self.labels[*i].as_str()).collect();
+
+        let mut group = Group::new()
+            .set("class", "reachability")
+            .add(Title::new().add(svg::node::Text::new(format!(
+                "reachable from: {}",
+                labels.join(", ")
+            ))));
+        for (n, index) in indexes.iter().enumerate() {
+            let seg = Rectangle::new()
+                .set("x", x + (n as u32 * seg_width))
+                .set("y", y.saturating_sub(STRIP_HEIGHT + 2))
+                .set("width", seg_width)
+                .set("height", STRIP_HEIGHT)
+                .set("fill", Reachability::color(*index));
+            group = group.add(seg);
+        }
+        Some(group)
+    }
+
+    //
+    // Generate the legend mapping the strip colors to the initiators.
+    //
+    pub(crate) fn legend_html(&self) -> String {
+        let mut html = String::from("<div id=\"reachability-legend\">Reachable from: ");
+        for (index, label) in self.labels.iter().enumerate() {
+            html.push_str(&format!(
+                "<span class=\"swatch\" style=\"background-color: {}\"></span>{} ",
+                Reachability::color(index),
+                html_escape(label)
+            ));
+        }
+        html.push_str("</div>\n");
+        html
+    }
+}
@@ -0,0 +1,565 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Pull-based reader of the XML output of sastopo -x.
+//
+// TopoXmlReader reads the host information at the top of the snapshot when
+// it is created, and then yields the vertices one at a time as they are read
+// from the underlying reader, without ever holding more than the vertex being
+// parsed.  This lets consumers count, filter or forward the vertices of
+// arbitrarily large snapshots in bounded memory:
+//
+//     let file = BufReader::new(File::open("sastopo.xml")?);
+//     let mut targets = 0;
+//     for vtx in TopoXmlReader::new(file)? {
+//         if vtx?.name() == sastopo2svg::TARGET {
+//             targets += 1;
+//         }
+//     }
+//
+// The XML is parsed into the same structures the whole snapshot is otherwise
+// deserialized into (see xml.rs), one vertex at a time, so the vertices come
+// out exactly as they would from a full parse.  How is up to the version of
+// the schema the snapshot follows, see schema_version.rs.
+//
+use log::warn;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io::BufRead;
+
+use topo_digraph_xml::{NvlistXmlArrayElement, NvpairXML, NvpairXmlArrayElement};
+
+use crate::lenient::ParseWarning;
+use crate::schema_version::{SchemaVersion, LATEST_SCHEMA_VERSION};
+use crate::xml::{EdgeXml, OutgoingEdgesXml, VertexXml};
+use crate::{build_vertex, RenderOptions, SasDigraph, SasDigraphVertex, Scheme, SimpleError};
+
+// the elements in the header of a snapshot describing the host
+const HOST_ELEMENTS: &[&str] = &["product-id", "nodename", "os-version", "timestamp"];
+
+// the element naming the FMRI scheme, which older snapshots lack (see scheme.rs)
+const SCHEME_ELEMENT: &str = "fmri-scheme";
+
+//
+// Element being parsed within a vertex.
+//
+enum Open {
+    Nvpair(NvpairXML),
+    // an element of an array nvpair
+    Element(NvpairXmlArrayElement),
+    Nvlist(NvlistXmlArrayElement),
+    OutgoingEdges(Vec<EdgeXml>),
+    // anything else, which is skipped along with its children
+    Other,
+}
+
+fn attribute(start: &BytesStart, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    for attr in start.attributes() {
+        let attr = attr?;
+        if attr.key.as_ref() == name.as_bytes() {
+            return Ok(Some(attr.unescape_value()?.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+fn required(start: &BytesStart, name: &str) -> Result<String, Box<dyn Error>> {
+    match attribute(start, name)? {
+        Some(value) => Ok(value),
+        None => Err(Box::new(SimpleError(format!(
+            "<{}> without {} attribute",
+            element_name(start),
+            name
+        )))),
+    }
+}
+
+fn element_name(start: &BytesStart) -> String {
+    String::from_utf8_lossy(start.name().as_ref()).to_string()
+}
+
+pub struct TopoXmlReader<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    options: RenderOptions,
+    // the host information, without any vertices
+    host: SasDigraph,
+    version: SchemaVersion,
+    // the elements and attributes of a newer schema that were skipped
+    ignored: BTreeSet<String>,
+    // the vertices skipped in lenient mode (see lenient.rs)
+    parse_warnings: Vec<ParseWarning>,
+    done: bool,
+}
+
+impl<R: BufRead> TopoXmlReader<R> {
+    pub fn new(source: R) -> Result<TopoXmlReader<R>, Box<dyn Error>> {
+        TopoXmlReader::with_options(source, &RenderOptions::default())
+    }
+
+    //
+    // Like new(), parsing the vertices as specified by the options (e.g. how
+    // to handle property groups without values).
+    //
+    pub fn with_options(
+        source: R,
+        options: &RenderOptions,
+    ) -> Result<TopoXmlReader<R>, Box<dyn Error>> {
+        let mut reader = Reader::from_reader(source);
+        reader.config_mut().trim_text(true);
+        let mut topo = TopoXmlReader {
+            reader,
+            buf: Vec::new(),
+            options: options.clone(),
+            host: SasDigraph::new(String::new(), String::new(), String::new(), String::new()),
+            version: SchemaVersion::default(),
+            ignored: BTreeSet::new(),
+            parse_warnings: Vec::new(),
+            done: false,
+        };
+        topo.read_host().map_err(|e| topo.explain(e))?;
+        if topo.version.is_newer() {
+            warn!(
+                "the snapshot follows version {} of the topology schema, which is newer than \
+                 the supported version {}; reading it as the latter",
+                topo.version, LATEST_SCHEMA_VERSION
+            );
+        }
+        Ok(topo)
+    }
+
+    //
+    // Read the host information, up to the start of the vertices.
+    //
+    fn read_host(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut values: Vec<Option<String>> = vec![None; HOST_ELEMENTS.len() + 1];
+        let mut current: Option<usize> = None;
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into(&mut self.buf)? {
+                Event::DocType(doctype) => {
+                    let doctype = String::from_utf8_lossy(&doctype).to_string();
+                    if let Some(version) = SchemaVersion::from_doctype(&doctype)? {
+                        self.version = version;
+                    }
+                }
+                Event::Start(start) => {
+                    let name = element_name(&start);
+                    if name == "vertices" {
+                        break;
+                    }
+                    if name == "topology" {
+                        // the attribute takes precedence over the DTD
+                        if let Some(version) = attribute(&start, "version")? {
+                            self.version = version.parse()?;
+                        }
+                    } else if !self.version.schema().host_elements.contains(&name.as_str()) {
+                        self.ignore(format!("<{}>", name));
+                    }
+                    current = HOST_ELEMENTS.iter().position(|e| *e == name);
+                    if name == SCHEME_ELEMENT {
+                        current = Some(HOST_ELEMENTS.len());
+                    }
+                }
+                Event::Empty(start) if start.name().as_ref() == b"vertices" => {
+                    self.done = true;
+                    break;
+                }
+                Event::Text(text) => {
+                    if let Some(i) = current {
+                        values[i] = Some(text.unescape()?.to_string());
+                    }
+                }
+                Event::End(_) => current = None,
+                Event::Eof => {
+                    return Err(Box::new(SimpleError(
+                        "no <vertices> element in the snapshot".to_string(),
+                    )))
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(scheme) = values.pop().unwrap() {
+            self.host.scheme = scheme.trim().parse()?;
+        }
+        let mut values = values.into_iter().zip(HOST_ELEMENTS).map(|(value, name)| {
+            value.ok_or_else(|| SimpleError(format!("no <{}> element in the snapshot", name)))
+        });
+        self.host.product_id = values.next().unwrap()?;
+        self.host.nodename = values.next().unwrap()?;
+        self.host.os_version = values.next().unwrap()?;
+        self.host.timestamp = values.next().unwrap()?;
+        Ok(())
+    }
+
+    //
+    // The digraph describing the host, without any vertices.
+    //
+    pub(crate) fn host(&self) -> &SasDigraph {
+        &self.host
+    }
+
+    pub fn product_id(&self) -> &str {
+        &self.host.product_id
+    }
+
+    pub fn nodename(&self) -> &str {
+        &self.host.nodename
+    }
+
+    pub fn os_version(&self) -> &str {
+        &self.host.os_version
+    }
+
+    pub fn timestamp(&self) -> &str {
+        &self.host.timestamp
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        self.host.scheme
+    }
+
+    pub fn schema_version(&self) -> SchemaVersion {
+        self.version
+    }
+
+    //
+    // The vertices that were skipped so far, in lenient mode.
+    //
+    pub fn parse_warnings(&self) -> &[ParseWarning] {
+        &self.parse_warnings
+    }
+
+    //
+    // Note the element or attribute of a newer schema that isn't understood,
+    // which is skipped.
+    //
+    fn ignore(&mut self, what: String) {
+        if self.version.is_newer() {
+            self.ignored.insert(what);
+        }
+    }
+
+    //
+    // Points out an error parsing a snapshot of a newer schema, which is
+    // likely to be caused by the changes made to it.
+    //
+    fn explain(&self, e: Box<dyn Error>) -> Box<dyn Error> {
+        match self.version.is_newer() {
+            true => Box::new(SimpleError(format!(
+                "{} (the snapshot follows version {} of the topology schema, and only versions \
+                 up to {} are supported)",
+                e, self.version, LATEST_SCHEMA_VERSION
+            ))),
+            false => e,
+        }
+    }
+
+    //
+    // Read the next vertex, or None at the end of the vertices.
+    //
+    fn read_vertex(&mut self) -> Result<Option<VertexXml>, Box<dyn Error>> {
+        let mut vertex: Option<VertexXml> = None;
+        let mut open: Vec<Open> = Vec::new();
+        loop {
+            self.buf.clear();
+            let (start, empty) = match self.reader.read_event_into(&mut self.buf)? {
+                Event::Start(start) => (start.into_owned(), false),
+                Event::Empty(start) => (start.into_owned(), true),
+                Event::End(end) => {
+                    if vertex.is_none() {
+                        if end.name().as_ref() == b"vertices" {
+                            return Ok(None);
+                        }
+                        continue;
+                    }
+                    match open.pop() {
+                        Some(element) => close(&mut vertex, &mut open, element),
+                        None => return Ok(vertex),
+                    }
+                    continue;
+                }
+                Event::Eof => {
+                    return Err(Box::new(SimpleError(
+                        "unexpected end of the snapshot".to_string(),
+                    )))
+                }
+                _ => continue,
+            };
+
+            let element = match (start.name().as_ref(), &vertex, open.last()) {
+                (b"vertex", None, _) => {
+                    let schema = self.version.schema();
+                    for attr in start.attributes() {
+                        let key = String::from_utf8_lossy(attr?.key.as_ref()).to_string();
+                        if !schema.vertex_attributes.contains(&key.as_str()) {
+                            self.ignore(format!("<vertex {}>", key));
+                        }
+                    }
+                    vertex = Some(VertexXml {
+                        name: required(&start, "name")?,
+                        instance: required(&start, "instance")?,
+                        fmri: required(&start, "fmri")?,
+                        propgroups: Vec::new(),
+                        outgoing_edges: None,
+                    });
+                    if empty {
+                        return Ok(vertex);
+                    }
+                    continue;
+                }
+                // anything between the vertices is skipped
+                (_, None, _) => {
+                    self.ignore(format!("<{}>", element_name(&start)));
+                    continue;
+                }
+                (b"nvpair", _, Some(Open::Nvpair(_))) => {
+                    Open::Element(NvpairXmlArrayElement {
+                        value: attribute(&start, "value")?,
+                    })
+                }
+                (b"nvpair", _, None) | (b"nvpair", _, Some(Open::Nvlist(_))) => {
+                    Open::Nvpair(NvpairXML {
+                        name: attribute(&start, "name")?,
+                        nvpair_type: attribute(&start, "type")?.unwrap_or_default(),
+                        value: attribute(&start, "value")?,
+                        nvlist_elements: None,
+                        nvpair_elements: None,
+                    })
+                }
+                (b"nvlist", _, Some(Open::Nvpair(_))) => {
+                    Open::Nvlist(NvlistXmlArrayElement { nvpairs: None })
+                }
+                (b"outgoing-edges", _, None) => Open::OutgoingEdges(Vec::new()),
+                (b"edge", _, Some(Open::OutgoingEdges(_))) => {
+                    if let Some(Open::OutgoingEdges(edges)) = open.last_mut() {
+                        edges.push(EdgeXml {
+                            fmri: required(&start, "fmri")?,
+                        });
+                    }
+                    Open::Other
+                }
+                // (the children of an element that is ignored go with it)
+                (name, _, parent) => {
+                    let name = String::from_utf8_lossy(name).to_string();
+                    let known = self.version.schema().vertex_elements.contains(&name.as_str());
+                    if !known && !matches!(parent, Some(Open::Other)) {
+                        self.ignore(format!("<{}>", name));
+                    }
+                    Open::Other
+                }
+            };
+            if empty {
+                close(&mut vertex, &mut open, element);
+            } else {
+                open.push(element);
+            }
+        }
+    }
+}
+
+//
+// Attach an element that has been read completely to its parent.
+//
+fn close(vertex: &mut Option<VertexXml>, open: &mut [Open], element: Open) {
+    let vertex = match vertex {
+        Some(vertex) => vertex,
+        None => return,
+    };
+    match (element, open.last_mut()) {
+        (Open::Nvpair(nvpair), None) => vertex.propgroups.push(nvpair),
+        (Open::Nvpair(nvpair), Some(Open::Nvlist(nvlist))) => {
+            nvlist.nvpairs.get_or_insert_with(Vec::new).push(nvpair)
+        }
+        (Open::Element(elem), Some(Open::Nvpair(nvpair))) => {
+            nvpair.nvpair_elements.get_or_insert_with(Vec::new).push(elem)
+        }
+        (Open::Nvlist(nvlist), Some(Open::Nvpair(nvpair))) => {
+            nvpair.nvlist_elements.get_or_insert_with(Vec::new).push(nvlist)
+        }
+        (Open::OutgoingEdges(edges), None) => {
+            vertex.outgoing_edges = Some(OutgoingEdgesXml { edges })
+        }
+        _ => (),
+    }
+}
+
+impl<R: BufRead> Iterator for TopoXmlReader<R> {
+    type Item = Result<SasDigraphVertex, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let vertex = loop {
+            match self.read_vertex() {
+                Ok(Some(vertex)) => {
+                    let fmri = vertex.fmri.clone();
+                    match build_vertex(vertex, self.host.scheme, &self.options) {
+                        Err(e) if self.options.lenient() => {
+                            warn!("skipping {}: {}", fmri, e);
+                            let message = e.to_string();
+                            self.parse_warnings.push(ParseWarning { fmri, message });
+                            continue;
+                        }
+                        vertex => break vertex,
+                    }
+                }
+                Ok(None) => {
+                    self.done = true;
+                    if !self.ignored.is_empty() {
+                        let ignored: Vec<&str> =
+                            self.ignored.iter().map(|s| s.as_str()).collect();
+                        warn!(
+                            "ignored what version {} of the topology schema doesn't have: {}",
+                            LATEST_SCHEMA_VERSION,
+                            ignored.join(", ")
+                        );
+                    }
+                    return None;
+                }
+                Err(e) => break Err(self.explain(e)),
+            }
+        };
+        // there is no recovering from a malformed vertex, short of skipping it
+        if vertex.is_err() {
+            self.done = true;
+        }
+        Some(vertex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "<topology><nodename>testhost</nodename>\
+        <os-version>joyent_20200101T000000Z</os-version><product-id>Joyent-M12G5</product-id>\
+        <timestamp>2020-01-01T00:00:00Z</timestamp><vertices>";
+
+    // a vertex with one property, whose property group can lack its name
+    fn vertex(wwn: u32, named: bool) -> String {
+        format!(
+            "<vertex name=\"target\" instance=\"0x0\" fmri=\"sas://:hba=1:/target={:x}\">\
+             <nvpair name=\"property-groups\" type=\"nvlist-array\"><nvlist>\
+             <nvpair {}type=\"string\" value=\"target\"/>\
+             <nvpair name=\"property-values\" type=\"nvlist-array\"><nvlist>\
+             <nvpair name=\"property-name\" type=\"string\" value=\"model\"/>\
+             <nvpair name=\"property-type\" type=\"string\" value=\"string\"/>\
+             <nvpair name=\"property-value\" type=\"string\" value=\"ST4000NM0025\"/>\
+             </nvlist></nvpair></nvlist></nvpair></vertex>",
+            wwn,
+            if named { "name=\"property-group-name\" " } else { "" }
+        )
+    }
+
+    fn snapshot(vertices: &[String]) -> String {
+        format!("{}{}</vertices></topology>", HEADER, vertices.concat())
+    }
+
+    fn reader(xml: &str, lenient: bool) -> TopoXmlReader<&[u8]> {
+        let options = RenderOptions::builder().lenient(lenient).build();
+        TopoXmlReader::with_options(xml.as_bytes(), &options).unwrap()
+    }
+
+    // a vertex with the specified property groups, as the nvpairs within them
+    fn vertex_with_groups(groups: &[&str]) -> String {
+        let groups: Vec<String> =
+            groups.iter().map(|group| format!("<nvlist>{}</nvlist>", group)).collect();
+        format!(
+            "<vertex name=\"target\" instance=\"0x0\" fmri=\"sas://:hba=1:/target=1\">\
+             <nvpair name=\"property-groups\" type=\"nvlist-array\">{}</nvpair></vertex>",
+            groups.concat()
+        )
+    }
+
+    fn group_name(name: &str) -> String {
+        format!("<nvpair name=\"property-group-name\" type=\"string\" value=\"{}\"/>", name)
+    }
+
+    const VALUES: &str = "<nvpair name=\"property-values\" type=\"nvlist-array\"><nvlist>\
+        <nvpair name=\"property-name\" type=\"string\" value=\"model\"/>\
+        <nvpair name=\"property-type\" type=\"string\" value=\"string\"/>\
+        <nvpair name=\"property-value\" type=\"string\" value=\"ST4000NM0025\"/>\
+        </nvlist></nvpair>";
+
+    #[test]
+    fn vertices() {
+        let xml = snapshot(&[vertex(1, true), vertex(2, true)]);
+        let mut reader = reader(&xml, false);
+        assert_eq!(reader.nodename(), "testhost");
+        let vertices: Vec<SasDigraphVertex> = reader.by_ref().map(|v| v.unwrap()).collect();
+        let fmris: Vec<&str> = vertices.iter().map(|v| v.fmri()).collect();
+        assert_eq!(fmris, vec!["sas://:hba=1:/target=1", "sas://:hba=1:/target=2"]);
+        let props: Vec<(&str, &str)> = vertices[0].properties().collect();
+        assert_eq!(props, vec![("model", "ST4000NM0025")]);
+        assert!(reader.parse_warnings().is_empty());
+    }
+
+    #[test]
+    fn malformed_vertex_strict() {
+        let xml = snapshot(&[vertex(1, true), vertex(2, false), vertex(3, true)]);
+        let mut reader = reader(&xml, false);
+        assert!(reader.next().unwrap().is_ok());
+        let error = reader.next().unwrap().unwrap_err().to_string();
+        assert!(error.contains("nvpair without a name"), "{}", error);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn malformed_vertex_lenient() {
+        let xml = snapshot(&[vertex(1, true), vertex(2, false), vertex(3, true)]);
+        let mut reader = reader(&xml, true);
+        let fmris: Vec<String> = reader.by_ref().map(|v| v.unwrap().fmri().to_string()).collect();
+        assert_eq!(fmris, vec!["sas://:hba=1:/target=1", "sas://:hba=1:/target=3"]);
+        let warnings = reader.parse_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].fmri, "sas://:hba=1:/target=2");
+    }
+
+    #[test]
+    fn malformed_vertices_in_a_row() {
+        let mut vertices: Vec<String> = (0..20_000).map(|wwn| vertex(wwn, false)).collect();
+        vertices.push(vertex(0xffff_ffff, true));
+        let xml = snapshot(&vertices);
+        let mut reader = reader(&xml, true);
+        assert_eq!(reader.by_ref().count(), 1);
+        assert_eq!(reader.parse_warnings().len(), 20_000);
+    }
+
+    #[test]
+    fn empty_propgroup() {
+        // the properties go with the group they are in, not the empty one before it
+        let xml = snapshot(&[vertex_with_groups(&[
+            &group_name("protocol"),
+            &format!("{}{}", group_name("target"), VALUES),
+        ])]);
+        let vtx = reader(&xml, false).next().unwrap().unwrap();
+        let groups: Vec<Option<&str>> = vtx.properties.iter().map(|p| p.group.as_deref()).collect();
+        assert_eq!(groups, vec![Some("target")]);
+
+        let options =
+            RenderOptions::builder().empty_propgroups(crate::EmptyPropGroups::Error).build();
+        let mut reader = TopoXmlReader::with_options(xml.as_bytes(), &options).unwrap();
+        let error = reader.next().unwrap().unwrap_err().to_string();
+        assert!(error.contains("propgroup protocol"), "{}", error);
+    }
+
+    #[test]
+    fn propgroup_named_twice() {
+        let group = format!("{}{}{}", group_name("target"), group_name("protocol"), VALUES);
+        let xml = snapshot(&[vertex_with_groups(&[&group])]);
+        let error = reader(&xml, false).next().unwrap().unwrap_err().to_string();
+        assert!(error.contains("property-group-name set twice"), "{}", error);
+    }
+}
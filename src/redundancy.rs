@@ -0,0 +1,370 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Multipath redundancy.
+//
+// What matters about the paths to a drive is how many of them can fail
+// independently, i.e. how many paths there are from the initiators to the
+// drive that don't share a link.  Two HBAs that are both cabled to the same
+// expander, which has a single link to the drive, give the drive two paths
+// but no redundancy: losing that one link cuts it off.
+//
+// The paths are counted on the physical fabric rather than on the digraph,
+// which has the devices once per HBA they are visible through (see
+// fmri::device_key()), and the two ports of a dual-ported drive as target
+// vertices of their own (see dual_port.rs).  On the physical fabric, the
+// number of link-disjoint paths from the initiators to a drive is the maximum
+// flow to it with every link having a capacity of one.
+//
+// Targets with a single path (or none) are listed in the report, and can be
+// marked in the SVG with a dashed outline.
+//
+use svg::node::element::{Rectangle, Title};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+
+use crate::fmri::device_key;
+use crate::{dual_port, html_escape, SasDigraph, TARGET};
+
+// color of the outline of the targets with a single path
+const SINGLE_PATH_COLOR: &str = "#AD1457";
+
+#[derive(Clone, Debug)]
+pub struct TargetRedundancy {
+    // the physical device, e.g. "target=5000c50090000000"
+    pub target: String,
+    // reference number (e.g. T047), if device numbering was requested
+    pub reference: Option<String>,
+    // number of initiators the target can be reached from
+    pub initiators: usize,
+    // number of paths to the target that don't share a link
+    pub disjoint_paths: usize,
+}
+
+impl TargetRedundancy {
+    pub fn is_single_path(&self) -> bool {
+        self.disjoint_paths < 2
+    }
+
+    fn label(&self) -> String {
+        match &self.reference {
+            Some(reference) => format!("{} {}", reference, self.target),
+            None => self.target.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RedundancyReport {
+    pub targets: Vec<TargetRedundancy>,
+    // the drive each second port of a dual-ported drive belongs to
+    drives: HashMap<String, String>,
+}
+
+impl RedundancyReport {
+    pub fn single_path(&self) -> impl Iterator<Item = &TargetRedundancy> {
+        self.targets.iter().filter(|t| t.is_single_path())
+    }
+
+    //
+    // Returns the outline to draw around the target with the specified FMRI
+    // and geometry, if it has a single path.  The FMRI can be that of either
+    // port of a dual-ported drive.
+    //
+    pub(crate) fn outline(
+        &self,
+        fmri: &str,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Option<Rectangle> {
+        let drive = drive_key(&self.drives, fmri);
+        let target = self.targets.iter().find(|t| t.target == drive)?;
+        if !target.is_single_path() {
+            return None;
+        }
+        let title = format!("{} path(s) not sharing a link", target.disjoint_paths);
+        Some(
+            Rectangle::new()
+                .set("x", x.saturating_sub(4))
+                .set("y", y.saturating_sub(4))
+                .set("width", width + 8)
+                .set("height", height + 8)
+                .set("fill", "none")
+                .set("stroke", SINGLE_PATH_COLOR)
+                .set("stroke-width", 3)
+                .set("stroke-dasharray", "6,3")
+                .set("class", "single-path")
+                .add(Title::new().add(svg::node::Text::new(title))),
+        )
+    }
+
+    pub(crate) fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<table id=\"redundancy\">\n<thead><tr><th>Target</th><th>Initiators</th>\
+             <th>Disjoint paths</th></tr></thead>\n",
+        );
+        for target in &self.targets {
+            let class = match target.is_single_path() {
+                true => " class=\"single-path\"",
+                false => "",
+            };
+            html.push_str(&format!(
+                "<tr{}><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                class,
+                html_escape(&target.label()),
+                target.initiators,
+                target.disjoint_paths
+            ));
+        }
+        html.push_str("</table>\n");
+        html
+    }
+}
+
+impl fmt::Display for RedundancyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let labels: Vec<String> = self.targets.iter().map(|t| t.label()).collect();
+        let width = labels.iter().map(|l| l.len()).max().unwrap_or(0).max("target".len());
+        writeln!(f, "{:<width$}  initiators  disjoint paths", "target", width = width)?;
+        for (target, label) in self.targets.iter().zip(&labels) {
+            writeln!(
+                f,
+                "{:<width$}  {:>10}  {:>14}",
+                label,
+                target.initiators,
+                target.disjoint_paths,
+                width = width
+            )?;
+        }
+        write!(
+            f,
+            "{} of {} targets have a single path",
+            self.single_path().count(),
+            self.targets.len()
+        )
+    }
+}
+
+//
+// Returns the device key of the physical device with the specified FMRI, which
+// for either port of a dual-ported drive is that of the drive.
+//
+fn drive_key<'a>(drives: &'a HashMap<String, String>, fmri: &'a str) -> &'a str {
+    let key = device_key(fmri);
+    drives.get(key).map_or(key, |drive| drive.as_str())
+}
+
+//
+// The physical fabric: the devices and ports, each once, and the links
+// between them.
+//
+struct Fabric {
+    // device keys, by node number
+    nodes: Vec<String>,
+    // node numbers, by device key
+    numbers: HashMap<String, usize>,
+    links: BTreeSet<(usize, usize)>,
+    // the nodes with a link to each node, by node number
+    incoming: Vec<Vec<usize>>,
+    // node numbers of the initiators
+    initiators: BTreeSet<usize>,
+}
+
+impl Fabric {
+    fn new(digraph: &SasDigraph, drives: &HashMap<String, String>) -> Fabric {
+        let mut numbers: HashMap<String, usize> = HashMap::new();
+        let mut nodes = Vec::new();
+        let mut number = |fmri: &str| -> usize {
+            let key = drive_key(drives, fmri);
+            if let Some(n) = numbers.get(key) {
+                return *n;
+            }
+            nodes.push(key.to_string());
+            numbers.insert(key.to_string(), nodes.len() - 1);
+            nodes.len() - 1
+        };
+
+        let mut fmris: Vec<&String> = digraph.vertices.keys().collect();
+        fmris.sort();
+        let mut links = BTreeSet::new();
+        for fmri in fmris {
+            let from = number(fmri);
+            for edge in digraph.vertices[fmri].outgoing_edges() {
+                if digraph.vertices.contains_key(edge) {
+                    links.insert((from, number(edge)));
+                }
+            }
+        }
+        let initiators = digraph.initiators.iter().map(|fmri| number(fmri)).collect();
+        let mut incoming = vec![Vec::new(); nodes.len()];
+        for (from, to) in &links {
+            incoming[*to].push(*from);
+        }
+        Fabric {
+            nodes,
+            numbers,
+            links,
+            incoming,
+            initiators,
+        }
+    }
+
+    //
+    // Returns the number of initiators the node can be reached from, and the
+    // number of link-disjoint paths from them to it, which is the maximum flow
+    // from the initiators to the node (found with Edmonds-Karp).
+    //
+    fn paths_to(&self, sink: usize) -> (usize, usize) {
+        // the initiators are fed from a source of their own, without limit
+        let source = self.nodes.len();
+        let mut capacity: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut adjacent: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len() + 1];
+        let mut connect = |from: usize, to: usize, cap: usize| {
+            *capacity.entry((from, to)).or_insert(0) += cap;
+            capacity.entry((to, from)).or_insert(0);
+            adjacent[from].push(to);
+            adjacent[to].push(from);
+        };
+        for (from, to) in &self.links {
+            connect(*from, *to, 1);
+        }
+        for initiator in &self.initiators {
+            connect(source, *initiator, usize::MAX / 2);
+        }
+
+        let mut flow = 0;
+        loop {
+            let mut previous: HashMap<usize, usize> = HashMap::new();
+            let mut queue = VecDeque::from(vec![source]);
+            while let Some(node) = queue.pop_front() {
+                if node == sink {
+                    break;
+                }
+                for next in &adjacent[node] {
+                    if *next != source
+                        && !previous.contains_key(next)
+                        && capacity[&(node, *next)] > 0
+                    {
+                        previous.insert(*next, node);
+                        queue.push_back(*next);
+                    }
+                }
+            }
+            if !previous.contains_key(&sink) {
+                break;
+            }
+            let mut node = sink;
+            while node != source {
+                let prev = previous[&node];
+                *capacity.get_mut(&(prev, node)).unwrap() -= 1;
+                *capacity.get_mut(&(node, prev)).unwrap() += 1;
+                node = prev;
+            }
+            flow += 1;
+        }
+        (self.initiators_reaching(sink), flow)
+    }
+
+    fn initiators_reaching(&self, node: usize) -> usize {
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if seen.insert(node) {
+                stack.extend(&self.incoming[node]);
+            }
+        }
+        self.initiators.intersection(&seen).count()
+    }
+}
+
+pub(crate) fn analyze(digraph: &SasDigraph) -> RedundancyReport {
+    // the two ports of a dual-ported drive make up one target
+    let drives = dual_port::drive_keys(digraph);
+    let fabric = Fabric::new(digraph, &drives);
+
+    let mut targets: BTreeMap<&str, Option<String>> = BTreeMap::new();
+    for vtx in digraph.vertices.values().filter(|v| v.name == TARGET) {
+        let reference = targets.entry(drive_key(&drives, &vtx.fmri)).or_insert(None);
+        if reference.is_none() {
+            *reference = vtx.reference.clone();
+        }
+    }
+
+    let mut report = RedundancyReport::default();
+    for (target, reference) in targets {
+        let node = fabric.numbers.get(target);
+        let (initiators, disjoint_paths) = node.map_or((0, 0), |node| fabric.paths_to(*node));
+        report.targets.push(TargetRedundancy {
+            target: target.to_string(),
+            reference,
+            initiators,
+            disjoint_paths,
+        });
+    }
+    report.drives = drives;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HBA0: &str = "sas://:hba=500605b0000272a0:/initiator=500605b0000272a0";
+    const HBA1: &str = "sas://:hba=500605b0000272b0:/initiator=500605b0000272b0";
+    const EXP0: &str = "sas://:hba=500605b0000272a0:/expander=500304801e0f1a00";
+    const EXP1: &str = "sas://:hba=500605b0000272b0:/expander=500304801e0f1b00";
+    // the two ports of a dual-ported drive, each cabled to an HBA of its own
+    const PORT1: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000001";
+    const PORT2: &str = "sas://:hba=500605b0000272b0:/target=5000c50090000002";
+    // a drive cabled to a single HBA
+    const SINGLE: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000101";
+
+    fn topology() -> SasDigraph {
+        SasDigraph::builder()
+            .add_vertex(HBA0, "initiator", 0)
+            .add_vertex(HBA1, "initiator", 1)
+            .add_vertex(EXP0, "expander", 0)
+            .add_vertex(EXP1, "expander", 1)
+            .add_vertex(PORT1, "target", 0)
+            .add_property(PORT1, "target", "serial-number", "ZA1234")
+            .add_vertex(PORT2, "target", 1)
+            .add_property(PORT2, "target", "serial-number", "ZA1234")
+            .add_vertex(SINGLE, "target", 2)
+            .add_property(SINGLE, "target", "serial-number", "ZA5678")
+            .add_edge(HBA0, EXP0)
+            .add_edge(HBA1, EXP1)
+            .add_edge(EXP0, PORT1)
+            .add_edge(EXP0, SINGLE)
+            .add_edge(EXP1, PORT2)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn dual_ported() {
+        let report = analyze(&topology());
+        let targets: Vec<(&str, usize, usize)> = report
+            .targets
+            .iter()
+            .map(|t| (t.target.as_str(), t.initiators, t.disjoint_paths))
+            .collect();
+        assert_eq!(
+            targets,
+            vec![("target=5000c50090000001", 2, 2), ("target=5000c50090000101", 1, 1)]
+        );
+
+        // the drive is drawn under the FMRI of either port
+        assert!(report.outline(PORT1, 0, 0, 10, 10).is_none());
+        assert!(report.outline(PORT2, 0, 0, 10, 10).is_none());
+        assert!(report.outline(SINGLE, 0, 0, 10, 10).is_some());
+    }
+}
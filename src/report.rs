@@ -0,0 +1,208 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Markdown fabric report.
+//
+// A summary of the topology in tables, for pasting into change-review
+// documents alongside the SVG:
+//
+//   - the host the snapshot was taken on
+//   - the number of devices reachable from each initiator
+//   - the fan-out of each expander, i.e. the devices cabled to its ports
+//   - every target, with its model and serial number
+//
+// The same device shows up once per HBA it is reachable from, so devices are
+// counted and listed once per physical device (see fmri::device_key), with
+// the references of all of its vertices.
+//
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::analysis::prop;
+use crate::fmri::{device_key, wwn};
+use crate::locale::Locale;
+use crate::{SasDigraph, SasDigraphVertex, EXPANDER, PORT, TARGET};
+
+//
+// Pipes delimit the cells of a table, and a newline would end it.
+//
+fn cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut md = format!("| {} |\n", header.join(" | "));
+    md.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|c| cell(c)).collect();
+        md.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    md
+}
+
+fn vertex_label(vtx: &SasDigraphVertex) -> String {
+    match &vtx.reference {
+        Some(reference) => reference.clone(),
+        None => format!("{} {}", vtx.name, vtx.instance),
+    }
+}
+
+//
+// Returns the number of physical devices of the specified type among the
+// vertices with the specified FMRIs.
+//
+fn devices<'a>(digraph: &'a SasDigraph, fmris: &HashSet<&'a str>, name: &str) -> usize {
+    fmris
+        .iter()
+        .filter(|f| digraph.vertices[**f].name == name)
+        .map(|f| device_key(f))
+        .collect::<BTreeSet<&str>>()
+        .len()
+}
+
+//
+// Returns the FMRIs of the vertices reachable from the specified one,
+// excluding itself.
+//
+fn reachable<'a>(digraph: &'a SasDigraph, fmri: &'a str) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![fmri];
+    while let Some(next) = stack.pop() {
+        for edge in digraph.vertices[next].outgoing_edges.iter().flatten() {
+            if digraph.vertices.contains_key(edge) && seen.insert(edge.as_str()) {
+                stack.push(edge);
+            }
+        }
+    }
+    seen.remove(fmri);
+    seen
+}
+
+//
+// Returns the FMRIs of the devices cabled to the ports of an expander, i.e.
+// the first vertices other than ports downstream of it.
+//
+fn attached<'a>(digraph: &'a SasDigraph, fmri: &'a str) -> HashSet<&'a str> {
+    let mut attached = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut stack = vec![fmri];
+    while let Some(next) = stack.pop() {
+        for edge in digraph.vertices[next].outgoing_edges.iter().flatten() {
+            let vtx = match digraph.vertices.get(edge) {
+                Some(vtx) => vtx,
+                None => continue,
+            };
+            if vtx.name != PORT {
+                attached.insert(edge.as_str());
+            } else if seen.insert(edge.as_str()) {
+                stack.push(edge);
+            }
+        }
+    }
+    attached
+}
+
+//
+// Returns the vertices of the specified type, grouped by physical device and
+// sorted by the first of their labels.
+//
+fn grouped<'a>(digraph: &'a SasDigraph, name: &str) -> Vec<Vec<&'a SasDigraphVertex>> {
+    let mut groups: BTreeMap<&str, Vec<&SasDigraphVertex>> = BTreeMap::new();
+    for vtx in digraph.vertices.values().filter(|v| v.name == name) {
+        groups.entry(device_key(&vtx.fmri)).or_default().push(vtx);
+    }
+    let mut groups: Vec<Vec<&SasDigraphVertex>> = groups.into_values().collect();
+    for group in &mut groups {
+        group.sort_by_key(|v| (vertex_label(v), &v.fmri));
+    }
+    groups.sort_by_key(|g| (vertex_label(g[0]), &g[0].fmri));
+    groups
+}
+
+fn labels(group: &[&SasDigraphVertex]) -> String {
+    let labels: Vec<String> = group.iter().map(|v| vertex_label(v)).collect();
+    labels.join(", ")
+}
+
+fn property(group: &[&SasDigraphVertex], name: &str) -> String {
+    group
+        .iter()
+        .find_map(|v| prop(v, name))
+        .unwrap_or("")
+        .to_string()
+}
+
+pub(crate) fn render_report(digraph: &SasDigraph, locale: Locale) -> String {
+    let mut md = format!("# SAS fabric of {}\n\n## Host\n\n", digraph.nodename);
+    md.push_str(&table(
+        &["", ""],
+        &[
+            vec!["Nodename".to_string(), digraph.nodename.clone()],
+            vec!["Product".to_string(), digraph.product_id.clone()],
+            vec!["OS version".to_string(), digraph.os_version.clone()],
+            vec!["Snapshot taken".to_string(), locale.timestamp(&digraph.timestamp)],
+        ],
+    ));
+
+    md.push_str("\n## Initiators\n\n");
+    let rows: Vec<Vec<String>> = digraph
+        .initiators
+        .iter()
+        .map(|fmri| {
+            let vtx = &digraph.vertices[fmri];
+            let below = reachable(digraph, fmri);
+            vec![
+                vertex_label(vtx),
+                wwn(fmri).unwrap_or("").to_string(),
+                locale.count(devices(digraph, &below, EXPANDER)),
+                locale.count(devices(digraph, &below, TARGET)),
+            ]
+        })
+        .collect();
+    md.push_str(&table(&["Initiator", "WWN", "Expanders", "Targets"], &rows));
+
+    md.push_str("\n## Expanders\n\n");
+    let mut rows = Vec::new();
+    for group in grouped(digraph, EXPANDER) {
+        let mut below = HashSet::new();
+        for vtx in &group {
+            below.extend(attached(digraph, &vtx.fmri));
+        }
+        rows.push(vec![
+            labels(&group),
+            wwn(&group[0].fmri).unwrap_or("").to_string(),
+            property(&group, "model"),
+            locale.count(devices(digraph, &below, EXPANDER)),
+            locale.count(devices(digraph, &below, TARGET)),
+        ]);
+    }
+    md.push_str(&table(
+        &["Expander", "WWN", "Model", "Expanders", "Targets"],
+        &rows,
+    ));
+
+    md.push_str("\n## Targets\n\n");
+    let rows: Vec<Vec<String>> = grouped(digraph, TARGET)
+        .iter()
+        .map(|group| {
+            vec![
+                labels(group),
+                wwn(&group[0].fmri).unwrap_or("").to_string(),
+                property(group, "manufacturer"),
+                property(group, "model"),
+                property(group, "serial-number"),
+                property(group, "firmware-revision"),
+            ]
+        })
+        .collect();
+    md.push_str(&table(
+        &["Target", "WWN", "Manufacturer", "Model", "Serial number", "Firmware"],
+        &rows,
+    ));
+    md
+}
@@ -5,6 +5,18 @@ var chassis_images = [
     'Joyent-S10G5'
 ];
 
+//
+// Returns the source of the named chassis picture.  A standalone page embeds
+// the pictures in chassis_image_uris, otherwise they're in the assets.
+//
+function chassisImageSrc(img_file) {
+    var uris = window.chassis_image_uris;
+    if (uris !== undefined && uris[img_file] !== undefined) {
+        return uris[img_file];
+    }
+    return 'assets/' + product_id + '/' + img_file;
+}
+
 //
 // Populate the Host Information table when the document is loaded.
 //
@@ -27,7 +39,8 @@ document.addEventListener('DOMContentLoaded', function () {
     cell.innerHTML = hostprops.getAttribute('os-version');
 
     cell = parentDoc.getElementById('timestamp');
-    cell.innerHTML = hostprops.getAttribute('timestamp');
+    cell.innerHTML = hostprops.getAttribute('display-timestamp') ||
+        hostprops.getAttribute('timestamp');
 
     if (chassis_images.includes(product_id)) {
         var hostinfo = parentDoc.getElementById('hostinfo');
@@ -35,15 +48,15 @@ document.addEventListener('DOMContentLoaded', function () {
         front_image_cell = imgrow.insertCell(-1);
         front_image_cell.colSpan = 2;
         let img_file = product_id + '-front.png';
-        front_image_cell.innerHTML = '<center><img src=\'assets/' +
-            product_id + '/' + img_file + '\'></img></center>';
+        front_image_cell.innerHTML = '<center><img src=\'' +
+            chassisImageSrc(img_file) + '\'></img></center>';
 
         imgrow = hostinfo.insertRow(-1);
         rear_image_cell = imgrow.insertCell(-1);
         rear_image_cell.colSpan = 2;
         img_file = product_id + '-rear.png';
-        rear_image_cell.innerHTML = '<center><img src=\'assets/' +
-            product_id + '/' + img_file + '\'></img></center>';
+        rear_image_cell.innerHTML = '<center><img src=\'' +
+            chassisImageSrc(img_file) + '\'></img></center>';
     }
 });
 
@@ -63,14 +76,251 @@ var link_rate_strings = [
     '22.5 GBits/s'
 ];
 
+var prop_category_titles = {
+    'identity': 'Identity',
+    'connectivity': 'Connectivity',
+    'firmware': 'Firmware',
+    'errors': 'Errors',
+    'other': 'Other Properties'
+};
+
+//
+// Properties that weren't embedded in the SVG are stored in a sidecar script,
+// keyed by vertex id, which the SVG loads along with it.  It assigns them to
+// the sastopo_props global, as pages opened from file:// URLs can load scripts
+// but not fetch files.
+//
+var sidecar_file = 'sastopo-props.js';
+
+function loadSidecarProps(callback) {
+    if (window.sastopo_props === undefined) {
+        console.log('failed to load ' + sidecar_file);
+        return;
+    }
+    callback(window.sastopo_props);
+}
+
+function appendPropRows(parentDoc, tbody, props) {
+    for (const name of Object.keys(props)) {
+        let row = tbody.insertRow(-1);
+        let namecell = row.insertCell(-1);
+        namecell.innerHTML = name.bold();
+        let valuecell = row.insertCell(-1);
+        valuecell.textContent = props[name];
+    }
+}
+
+//
+// Render all of the properties of the clicked vertex in collapsible sections,
+// one per property category.  The categories and the order of the properties
+// within them are computed by sastopo2svg and attached to the vertex as the
+// prop-categories attribute, in the form:
+//
+//     identity=manufacturer,model|connectivity=local-sas-address
+//
+function showPropSections(parentDoc, group) {
+    var sections = parentDoc.getElementById('propsections');
+    while (sections.firstChild) {
+        sections.removeChild(sections.firstChild);
+    }
+
+    var encoded = group.getAttribute('prop-categories');
+    var sectionlist = (encoded === null || encoded === '') ? [] : encoded.split('|');
+
+    //
+    // The property group each property came from is attached as the
+    // prop-groups attribute, in the same form, e.g.:
+    //
+    //     storage=model,serial-number|authority=product-id
+    //
+    var propgroups = {};
+    var encodedGroups = group.getAttribute('prop-groups');
+    if (encodedGroups !== null && encodedGroups !== '') {
+        for (const entry of encodedGroups.split('|')) {
+            let sep = entry.indexOf('=');
+            for (const name of entry.substring(sep + 1).split(',')) {
+                propgroups[name] = entry.substring(0, sep);
+            }
+        }
+    }
+
+    for (const section of sectionlist) {
+        let sep = section.indexOf('=');
+        let category = section.substring(0, sep);
+        let names = section.substring(sep + 1).split(',');
+
+        let details = parentDoc.createElement('details');
+        details.open = (category === 'identity');
+        let summary = parentDoc.createElement('summary');
+        let title = prop_category_titles[category] || category;
+        summary.textContent = title + ' (' + names.length + ')';
+        details.appendChild(summary);
+
+        let table = parentDoc.createElement('table');
+        let tbody = parentDoc.createElement('tbody');
+        for (const name of names) {
+            let row = tbody.insertRow(-1);
+            let namecell = row.insertCell(-1);
+            namecell.innerHTML = name.bold();
+            if (propgroups[name] !== undefined) {
+                let note = parentDoc.createElement('span');
+                note.className = 'prop-group';
+                note.textContent = propgroups[name];
+                namecell.appendChild(note);
+            }
+            let valuecell = row.insertCell(-1);
+            valuecell.textContent = group.getAttribute(name);
+        }
+        table.appendChild(tbody);
+        details.appendChild(table);
+        sections.appendChild(details);
+    }
+
+    //
+    // If the number of properties embedded per vertex was capped, offer the
+    // rest, which are loaded from the sidecar file when expanded.
+    //
+    var more = group.getAttribute('more-props');
+    if (more !== null) {
+        let details = parentDoc.createElement('details');
+        let summary = parentDoc.createElement('summary');
+        summary.textContent = more + ' more\u2026';
+        details.appendChild(summary);
+        let table = parentDoc.createElement('table');
+        let tbody = parentDoc.createElement('tbody');
+        table.appendChild(tbody);
+        details.appendChild(table);
+        details.addEventListener('toggle', function () {
+            if (!details.open || tbody.rows.length > 0) {
+                return;
+            }
+            loadSidecarProps(function (data) {
+                appendPropRows(parentDoc, tbody, data.vertices[group.id] || {});
+            });
+        });
+        sections.appendChild(details);
+    }
+
+    //
+    // If the raw property groups were dumped for debugging, offer them too.
+    //
+    if (group.getAttribute('raw-nvlist') === 'true') {
+        let details = parentDoc.createElement('details');
+        let summary = parentDoc.createElement('summary');
+        summary.textContent = 'Raw nvlist';
+        details.appendChild(summary);
+        let pre = parentDoc.createElement('pre');
+        details.appendChild(pre);
+        details.addEventListener('toggle', function () {
+            if (!details.open || pre.textContent !== '') {
+                return;
+            }
+            loadSidecarProps(function (data) {
+                pre.textContent = (data.raw || {})[group.id] || '';
+            });
+        });
+        sections.appendChild(details);
+    }
+}
+
 //
 // When a graph vertex is clicked in the SVG, highlight the clicked vertex and
 // and populate the info panel on the left side with the properties of that
 // vertex.
 //
+//
+// Labels of the copy controls generated for the identifiers of a vertex, in
+// the order they're shown in.
+//
+var identifier_labels = {
+    'fmri': 'FMRI',
+    'wwn': 'WWN',
+    'serial-number': 'serial',
+    'devfs-path': 'devfs path',
+    'logical-disk': 'disk'
+};
+
+//
+// Copy the text to the clipboard, falling back to a selected textarea where
+// the Clipboard API isn't available (e.g. pages opened from file:// in some
+// browsers), and briefly confirm it on the button.
+//
+function copyToClipboard(parentDoc, text, button) {
+    var done = function () {
+        var label = button.textContent;
+        button.textContent = 'Copied';
+        setTimeout(function () {
+            button.textContent = label;
+        }, 1000);
+    };
+    var fallback = function () {
+        let area = parentDoc.createElement('textarea');
+        area.value = text;
+        parentDoc.body.appendChild(area);
+        area.select();
+        parentDoc.execCommand('copy');
+        parentDoc.body.removeChild(area);
+        done();
+    };
+    var clipboard = window.parent.navigator.clipboard;
+    if (clipboard !== undefined) {
+        clipboard.writeText(text).then(done, fallback);
+    } else {
+        fallback();
+    }
+}
+
+//
+// Add a row with a copy control for each of the identifiers of the vertex.
+//
+function appendCopyRow(parentDoc, nodeinfo, group) {
+    var encoded = group.getAttribute('identifiers');
+    if (encoded === null) {
+        return;
+    }
+    var ids = JSON.parse(encoded);
+    var row = nodeinfo.insertRow(-1);
+    var fieldcell = row.insertCell(-1);
+    fieldcell.innerHTML = 'copy'.bold();
+    var cell = row.insertCell(-1);
+    cell.colSpan = 4;
+    for (const name of Object.keys(identifier_labels)) {
+        if (ids[name] === undefined) {
+            continue;
+        }
+        let button = parentDoc.createElement('button');
+        button.className = 'copy-identifier';
+        button.textContent = identifier_labels[name];
+        button.title = ids[name];
+        button.addEventListener('click', function () {
+            copyToClipboard(parentDoc, ids[name], button);
+        });
+        cell.appendChild(button);
+    }
+}
+
 function showInfo(evt) { // eslint-disable-line no-unused-vars
     var parentDoc = window.parent.document;
 
+    //
+    // If the properties are loaded lazily, fetch them from the sidecar file
+    // and attach them to the vertex the first time it's clicked.
+    //
+    var clicked = evt.target.parentElement;
+    if (clicked.getAttribute('lazy-props') === 'true') {
+        loadSidecarProps(function (data) {
+            let props = data.vertices[clicked.id] || {};
+            for (const name of Object.keys(props)) {
+                clicked.setAttribute(name, props[name]);
+            }
+            clicked.setAttribute('prop-categories',
+                (data.categories || {})[clicked.id] || '');
+            clicked.setAttribute('lazy-props', 'loaded');
+            showInfo(evt);
+        });
+        return;
+    }
+
     //
     // Iterate through the DOM <img> elements, which represent the graph
     // vertices and set the fill color to white.
@@ -113,6 +363,8 @@ function showInfo(evt) { // eslint-disable-line no-unused-vars
     }
 
     var group = evt.target.parentElement;
+    showPropSections(parentDoc, group);
+
     var link_rate_props = ['max-link-rate', 'negotiated-link-rate'];
     var link_err_props = ['invalid-dword', 'running-disparity-error',
         'loss-dword-sync', 'reset-problem-count'];
@@ -124,21 +376,61 @@ function showInfo(evt) { // eslint-disable-line no-unused-vars
             'model', 'location'];
     } else if (name === 'port') {
         props = ['fmri', 'name', 'sas-port-type', 'local-sas-address',
-            'attached-sas-address'];
+            'attached-sas-address', 'lanes', 'link-speed'];
     } else if (name === 'expander') {
         props = ['fmri', 'name', 'devfs-path', 'manufacturer', 'model'];
     } else if (name === 'target') {
         props = ['fmri', 'hc-fmri', 'dev-fmri', 'name', 'logical-disk', 'manufacturer',
             'model', 'serial-number', 'location'];
+    } else {
+        // hc-scheme nodes, whose properties are mapped onto the above names
+        props = ['fmri', 'name', 'location', 'devfs-path', 'logical-disk',
+            'manufacturer', 'model', 'serial-number'];
+    }
+
+    // Show the instance of the topo node right after its name
+    if (props !== undefined && props.includes('name')) {
+        props.splice(props.indexOf('name') + 1, 0, 'instance');
+    }
+
+    //
+    // Vertices from old snapshots without any property groups only carry the
+    // identity derived from their FMRI.
+    //
+    if (group.getAttribute('wwn') !== null) {
+        props = props.concat(['wwn']);
+    }
+
+    // Show the device reference number first, if numbering was requested
+    if (group.getAttribute('reference') !== null) {
+        props = ['reference'].concat(props);
+    }
+
+    // Show any warnings (e.g. duplicate serial numbers) above everything else
+    if (group.getAttribute('warnings') !== null) {
+        props = ['warnings'].concat(props);
     }
 
     for (const prop of props) {
         let value = group.getAttribute(prop);
+        if (value === null) {
+            continue;
+        }
         //
-        // The value for hc-fmri can be quite long, so to make it fit better in
-        // the info panel, we strip out the authority portion of the fmri.
+        // FMRIs may come with a shortened form to display, in which case the
+        // full one is shown as the tooltip of the cell.
         //
-        if (prop === 'hc-fmri') {
+        let display = group.getAttribute(prop + '-display');
+        let tooltip = null;
+        if (display !== null) {
+            tooltip = value;
+            value = display;
+        } else if (prop === 'hc-fmri') {
+            //
+            // The value for hc-fmri can be quite long, so to make it fit
+            // better in the info panel, we strip out the authority portion of
+            // the fmri.
+            //
             let end_auth = value.indexOf('/', 6);
             if (end_auth !== -1) {
                 value = 'hc://' + value.substring(end_auth);
@@ -150,23 +442,41 @@ function showInfo(evt) { // eslint-disable-line no-unused-vars
         var valuecell = row.insertCell(-1);
         valuecell.colSpan = 4;
         valuecell.innerHTML = value;
+        if (tooltip !== null) {
+            valuecell.title = tooltip;
+        }
+    }
+
+    appendCopyRow(parentDoc, nodeinfo, group);
+
+    // Link to the page listing all of the properties, if there is one
+    let page = group.getAttribute('property-page');
+    if (page !== null) {
+        let row = nodeinfo.insertRow(-1);
+        let cell = row.insertCell(-1);
+        cell.colSpan = 5;
+        let link = parentDoc.createElement('a');
+        link.href = page;
+        link.target = '_blank';
+        link.textContent = 'All properties\u2026';
+        cell.appendChild(link);
     }
     let location = group.getAttribute('location');
     if (chassis_images.includes(product_id)) {
         if (location !== null) {
             location = location.replace(/ /g, '-');
             let img_file = product_id + '-' + location;
-            front_image_cell.innerHTML = '<center><img src=\'assets/' +
-                product_id + '/' + img_file + '-front.png\'></img></center>';
-            rear_image_cell.innerHTML = '<center><img src=\'assets/' +
-                product_id + '/' + img_file + '-rear.png\'></img></center>';
+            front_image_cell.innerHTML = '<center><img src=\'' +
+                chassisImageSrc(img_file + '-front.png') + '\'></img></center>';
+            rear_image_cell.innerHTML = '<center><img src=\'' +
+                chassisImageSrc(img_file + '-rear.png') + '\'></img></center>';
         } else {
             let img_file = product_id + '-front.png';
-            front_image_cell.innerHTML = '<center><img src=\'assets/' +
-                product_id + '/' + img_file + '\'></img></center>';
+            front_image_cell.innerHTML = '<center><img src=\'' +
+                chassisImageSrc(img_file) + '\'></img></center>';
             img_file = product_id + '-rear.png';
-            rear_image_cell.innerHTML = '<center><img src=\'assets/' +
-                product_id + '/' + img_file + '\'></img></center>';
+            rear_image_cell.innerHTML = '<center><img src=\'' +
+                chassisImageSrc(img_file) + '\'></img></center>';
         }
     }
 
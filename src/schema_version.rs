@@ -0,0 +1,115 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Versions of the topo digraph XML schema.
+//
+// A snapshot names the version of the schema it follows in the version
+// attribute of its <topology> element or, as the snapshots written by
+// libtopo do, in the name of the DTD its document type declaration refers to:
+//
+//     <!DOCTYPE topology SYSTEM "/usr/share/lib/xml/dtd/digraph-topology.dtd.1">
+//
+// Snapshots naming neither are version 1, the only version there is so far.
+//
+// Each version is parsed as described by its Schema, which lists the elements
+// and attributes that make up the vertices and the host information.  A
+// snapshot of a newer version than this crate knows about (i.e. written by a
+// newer OS) is parsed as the latest version, which works as long as the newer
+// one only adds to it, but the elements and attributes that aren't understood
+// are reported as ignored rather than silently dropped, and any error parsing
+// it points out the version mismatch.
+//
+use std::fmt;
+use std::str::FromStr;
+
+// prefix of the name of the DTD in the document type declaration
+const DTD_PREFIX: &str = "digraph-topology.dtd.";
+
+//
+// The latest version of the schema this crate understands.
+//
+pub const LATEST_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SchemaVersion {
+    #[default]
+    V1,
+    // a version newer than LATEST_SCHEMA_VERSION, parsed as the latter
+    Newer(u32),
+}
+
+//
+// The elements and attributes of a version of the schema.
+//
+pub(crate) struct Schema {
+    // the elements of <topology> preceding the vertices
+    pub host_elements: &'static [&'static str],
+    pub vertex_attributes: &'static [&'static str],
+    // the elements making up a vertex, at any depth
+    pub vertex_elements: &'static [&'static str],
+}
+
+const SCHEMA_V1: Schema = Schema {
+    host_elements: &["fmri-scheme", "nodename", "os-version", "product-id", "timestamp"],
+    vertex_attributes: &["name", "instance", "fmri"],
+    vertex_elements: &["nvpair", "nvlist", "outgoing-edges", "edge"],
+};
+
+impl SchemaVersion {
+    pub fn number(self) -> u32 {
+        match self {
+            SchemaVersion::V1 => 1,
+            SchemaVersion::Newer(version) => version,
+        }
+    }
+
+    //
+    // Whether the snapshot is of a newer version than this crate knows about.
+    //
+    pub fn is_newer(self) -> bool {
+        matches!(self, SchemaVersion::Newer(_))
+    }
+
+    pub(crate) fn schema(self) -> &'static Schema {
+        match self {
+            SchemaVersion::V1 | SchemaVersion::Newer(_) => &SCHEMA_V1,
+        }
+    }
+
+    //
+    // Returns the version named by a document type declaration, if any.
+    //
+    pub(crate) fn from_doctype(doctype: &str) -> Result<Option<SchemaVersion>, String> {
+        let version = doctype
+            .split(DTD_PREFIX)
+            .nth(1)
+            .map(|rest| rest.split(['"', '\'']).next().unwrap_or(rest));
+        version.map(|version| version.parse()).transpose()
+    }
+}
+
+impl FromStr for SchemaVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // only the major version matters, e.g. for "2.1"
+        let major = s.trim().split('.').next().unwrap_or("");
+        match major.parse::<u32>() {
+            Ok(1) => Ok(SchemaVersion::V1),
+            Ok(version) if version > LATEST_SCHEMA_VERSION => Ok(SchemaVersion::Newer(version)),
+            _ => Err(format!("invalid topology schema version: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.number())
+    }
+}
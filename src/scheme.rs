@@ -0,0 +1,126 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// FMRI schemes of the topology snapshots.
+//
+// Besides the SAS scheme topology written by sastopo -x, libtopo serializes
+// the hc-scheme topology (the chassis/motherboard/PCIe/bay hierarchy) as a
+// digraph in the same XML format, e.g.:
+//
+//     hc://:product-id=Joyent-M12G5:server-id=testhost/chassis=0/bay=3/disk=0
+//
+// The snapshot names its scheme in the <fmri-scheme> element.  The vertices
+// are laid out the same way for both schemes, starting from the initiators on
+// SAS scheme and from the chassis on hc-scheme.  The hc-scheme nodes are drawn
+// with an icon of their own, and their properties are mapped onto the names
+// the SAS scheme uses for the same things (see HC_PROPERTIES), so that the info
+// panel, the inventory and the identifiers work the same for both.
+//
+use std::fmt;
+use std::str::FromStr;
+
+use crate::INITIATOR;
+
+//
+// Constants for topo node names in hc-scheme topology
+//
+pub const CHASSIS: &str = "chassis";
+pub const DISK: &str = "disk";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Scheme {
+    #[default]
+    Sas,
+    Hc,
+}
+
+impl FromStr for Scheme {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Scheme, String> {
+        match name {
+            "sas" => Ok(Scheme::Sas),
+            "hc" => Ok(Scheme::Hc),
+            _ => Err(format!("unsupported FMRI scheme: {}", name)),
+        }
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scheme::Sas => write!(f, "sas"),
+            Scheme::Hc => write!(f, "hc"),
+        }
+    }
+}
+
+//
+// Icons of the hc-scheme nodes.  Disks are drawn like SAS targets, and all
+// of the other nodes with the generic hc-scheme icon.
+//
+const HC_ICON: &str = "assets/icons/hc.png";
+const HC_ICONS: &[(&str, &str)] = &[(DISK, "assets/icons/target.png")];
+
+//
+// Mapping of hc-scheme properties, by property group and name, to the names
+// the same properties go by on SAS scheme.  Properties that aren't listed
+// keep their names (e.g. the serial-number of a disk, in the storage group),
+// except for those of the protocol group, which holds little more than the
+// FMRI in other forms and is skipped.
+//
+const HC_PROPERTIES: &[(&str, &str, &str)] = &[
+    ("protocol", "label", "location"),
+    ("io", "dev", "devfs-path"),
+    ("pci", "vendor-name", "manufacturer"),
+    ("pci", "device-name", "model"),
+];
+
+impl Scheme {
+    //
+    // Whether the vertices of the specified node name are the roots of the
+    // topology, which are laid out in the first column.
+    //
+    pub(crate) fn is_root(self, name: &str) -> bool {
+        match self {
+            Scheme::Sas => name == INITIATOR,
+            Scheme::Hc => name == CHASSIS,
+        }
+    }
+
+    //
+    // Returns the name a property of the specified group is kept under, or
+    // None if it is skipped.
+    //
+    pub(crate) fn property_name<'a>(self, group: &str, name: &'a str) -> Option<&'a str> {
+        let mapped = match self {
+            Scheme::Sas => None,
+            Scheme::Hc => HC_PROPERTIES
+                .iter()
+                .find(|(g, n, _)| *g == group && *n == name)
+                .map(|(_, _, to)| *to),
+        };
+        match mapped {
+            Some(to) => Some(to),
+            None if group == "protocol" => None,
+            None => Some(name),
+        }
+    }
+}
+
+//
+// Returns the icon of the hc-scheme node with the specified name.  Any node
+// name can show up on hc-scheme, so there always is one.
+//
+pub(crate) fn hc_icon(name: &str) -> &'static str {
+    HC_ICONS
+        .iter()
+        .find(|(node, _)| *node == name)
+        .map_or(HC_ICON, |(_, icon)| *icon)
+}
@@ -0,0 +1,93 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Sidecar property store.
+//
+// On fabrics where every drive carries dozens of properties, embedding all of
+// them as attributes of the vertices makes the SVG very large.  Properties
+// that aren't embedded are written to a separate script instead, which the
+// SVG loads along with it, and which assigns them to a global the viewer
+// looks them up in when they are asked for.  (The generated pages are mostly
+// opened from file:// URLs, where browsers block fetching a file but do load
+// scripts.)  The vertices are keyed by their stable id, e.g.:
+//
+//     var sastopo_props = {
+//       "format_version": "1.3",
+//       "vertices": {
+//         "v1e9ac0bfb971d22d": { "invalid-dword": "0", ... }
+//       },
+//       "categories": {
+//         "v1e9ac0bfb971d22d": "identity=manufacturer,model|errors=invalid-dword"
+//       }
+//     };
+//
+// The categories (in the encoding used by the prop-categories attribute) are
+// only present if the properties are loaded lazily, in which case none of
+// them are embedded in the SVG.  With the raw nvlist dump enabled, the
+// pretty-printed property groups of each vertex are stored under "raw".
+//
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::{RenderOptions, FORMAT_VERSION};
+
+pub(crate) const SIDECAR_FILE: &str = "sastopo-props.js";
+
+// the global the properties are assigned to
+const SIDECAR_GLOBAL: &str = "sastopo_props";
+
+//
+// Whether the options have any properties go to the sidecar file, in which
+// case it is written and loaded by the SVG.
+//
+pub(crate) fn needed(options: &RenderOptions) -> bool {
+    options.lazy_properties() || options.raw_nvlists() || options.max_properties().is_some()
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct PropertyStore {
+    format_version: String,
+    vertices: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    categories: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    raw: BTreeMap<String, String>,
+}
+
+impl PropertyStore {
+    pub(crate) fn new() -> PropertyStore {
+        PropertyStore {
+            format_version: FORMAT_VERSION.to_string(),
+            vertices: BTreeMap::new(),
+            categories: BTreeMap::new(),
+            raw: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, id: &str, name: &str, value: &str) {
+        self.vertices
+            .entry(id.to_string())
+            .or_default()
+            .insert(name.to_string(), value.to_string());
+    }
+
+    pub(crate) fn set_categories(&mut self, id: &str, encoded: String) {
+        self.categories.insert(id.to_string(), encoded);
+    }
+
+    pub(crate) fn set_raw(&mut self, id: &str, dump: &str) {
+        self.raw.insert(id.to_string(), dump.to_string());
+    }
+
+    pub(crate) fn to_script(&self) -> Result<String, Box<dyn Error>> {
+        Ok(format!("var {} = {};\n", SIDECAR_GLOBAL, serde_json::to_string(self)?))
+    }
+}
@@ -0,0 +1,191 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Output sinks.
+//
+// Everything a run generates (the HTML page, the SVG, the assets it refers
+// to, the exports, ...) is handed to an OutputSink under a relative path
+// like "assets/icons/port.png", rather than written to the output directory
+// directly.  run() writes to a DirectorySink for Config::outdir, while
+// run_with_sink() accepts any sink, so that e.g. a service can keep the
+// artifacts in memory (MemorySink), stream them into a zip archive
+// (ZipSink) or, with the "s3" feature, upload them to a bucket (S3Sink),
+// without going through a temporary directory.
+//
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use crate::archive::integrity;
+use crate::SimpleError;
+
+pub trait OutputSink {
+    //
+    // Store an artifact under name, a relative path using '/' as separator.
+    //
+    fn write(&mut self, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>>;
+
+    //
+    // Called once all of the artifacts of a run have been written.
+    //
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    //
+    // Where the named artifact ended up, for reporting it to the user.
+    //
+    fn location(&self, name: &str) -> String {
+        name.to_string()
+    }
+}
+
+//
+// Writes the artifacts into a directory, creating it (and any subdirectories)
+// as needed.
+//
+pub struct DirectorySink {
+    dir: String,
+}
+
+impl DirectorySink {
+    pub fn new(dir: &str) -> DirectorySink {
+        DirectorySink {
+            dir: dir.to_string(),
+        }
+    }
+}
+
+impl OutputSink for DirectorySink {
+    fn write(&mut self, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        let path = format!("{}/{}", self.dir, name);
+        if let Some(parent) = Path::new(&path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+        Ok(())
+    }
+
+    fn location(&self, name: &str) -> String {
+        format!("{}/{}", self.dir, name)
+    }
+}
+
+//
+// Keeps the artifacts in memory, keyed by name.
+//
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemorySink {
+    pub fn new() -> MemorySink {
+        MemorySink::default()
+    }
+
+    pub fn files(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.files
+    }
+
+    pub fn into_files(self) -> BTreeMap<String, Vec<u8>> {
+        self.files
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn write(&mut self, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.files.insert(name.to_string(), contents.to_vec());
+        Ok(())
+    }
+}
+
+//
+// Streams the artifacts into a zip archive, which is completed by finish().
+//
+pub struct ZipSink<W: Write + Seek> {
+    // None once the archive has been completed
+    zip: Option<zip::ZipWriter<W>>,
+}
+
+impl<W: Write + Seek> ZipSink<W> {
+    pub fn new(writer: W) -> ZipSink<W> {
+        ZipSink {
+            zip: Some(zip::ZipWriter::new(writer)),
+        }
+    }
+}
+
+impl ZipSink<fs::File> {
+    pub fn create(path: &str) -> Result<ZipSink<fs::File>, Box<dyn Error>> {
+        Ok(ZipSink::new(fs::File::create(path)?))
+    }
+}
+
+impl<W: Write + Seek> OutputSink for ZipSink<W> {
+    fn write(&mut self, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        let zip = match &mut self.zip {
+            Some(zip) => zip,
+            None => {
+                let msg = format!("can't add {}, the zip archive is complete", name);
+                return Err(Box::new(SimpleError(msg)));
+            }
+        };
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file(name, options)?;
+        zip.write_all(contents)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(zip) = self.zip.take() {
+            zip.finish()?;
+        }
+        Ok(())
+    }
+}
+
+//
+// The sink a run writes to, along with the integrity hashes of what has been
+// written to it so far, from which the archive manifest is made.
+//
+pub(crate) struct Artifacts<'a> {
+    sink: &'a mut dyn OutputSink,
+    hashes: BTreeMap<String, String>,
+}
+
+impl<'a> Artifacts<'a> {
+    pub(crate) fn new(sink: &'a mut dyn OutputSink) -> Artifacts<'a> {
+        Artifacts {
+            sink,
+            hashes: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn write(&mut self, name: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.sink.write(name, contents)?;
+        self.hashes.insert(name.to_string(), integrity(contents));
+        Ok(())
+    }
+
+    pub(crate) fn location(&self, name: &str) -> String {
+        self.sink.location(name)
+    }
+
+    pub(crate) fn hashes(&self) -> &BTreeMap<String, String> {
+        &self.hashes
+    }
+
+    pub(crate) fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.sink.finish()
+    }
+}
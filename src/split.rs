@@ -0,0 +1,122 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Per-initiator rendering.
+//
+// On a server with a couple of HBAs and a few hundred drives, the single SVG
+// gets too tall to comfortably scroll through.  With the split_initiators
+// option, each initiator is instead rendered on a page of its own, showing
+// only the vertices reachable from it, and sastopo2svg.html becomes an index
+// linking to those pages.  A vertex reachable from more than one initiator
+// (e.g. a dual-ported drive) appears on each of their pages.
+//
+use std::collections::{HashSet, VecDeque};
+
+use crate::locale::Locale;
+use crate::{html_escape, SasDigraph, VertexGraph, TARGET};
+
+//
+// A page rendered for an initiator, as listed in the index.
+//
+pub(crate) struct InitiatorPage {
+    pub(crate) initiator: String,
+    pub(crate) label: String,
+    pub(crate) html_file: String,
+    pub(crate) svg_file: String,
+    pub(crate) vertices: usize,
+    pub(crate) targets: usize,
+}
+
+impl InitiatorPage {
+    //
+    // Pages are numbered in the order the initiators are laid out in.
+    //
+    pub(crate) fn new(index: usize, subtree: &SasDigraph) -> InitiatorPage {
+        let initiator = &subtree.initiators[0];
+        let vtx = &subtree.vertices[initiator];
+        let label = match &vtx.reference {
+            Some(reference) => format!("{} ({} {})", reference, vtx.name, vtx.instance),
+            None => format!("{} {}", vtx.name, vtx.instance),
+        };
+        InitiatorPage {
+            initiator: initiator.clone(),
+            label,
+            html_file: format!("sastopo-initiator-{}.html", index),
+            svg_file: format!("sastopo-initiator-{}.svg", index),
+            vertices: subtree.vertices.len(),
+            targets: subtree.vertices.values().filter(|v| v.name == TARGET).count(),
+        }
+    }
+}
+
+//
+// Returns the part of digraph reachable from the specified initiators.  Those
+// of the initiators that aren't in digraph are ignored, which allows older
+// snapshots (for the missing device and drift reports) to be cut down to the
+// same initiators as the page they are compared against.
+//
+pub(crate) fn initiator_subtree(digraph: &SasDigraph, initiators: &[String]) -> SasDigraph {
+    let mut subtree = SasDigraph::new(
+        digraph.product_id.clone(),
+        digraph.nodename.clone(),
+        digraph.os_version.clone(),
+        digraph.timestamp.clone(),
+    );
+    subtree.scheme = digraph.scheme;
+    subtree.initiators = initiators
+        .iter()
+        .filter(|fmri| digraph.vertices.contains_key(fmri))
+        .cloned()
+        .collect();
+
+    let mut seen: HashSet<&str> = subtree.initiators.iter().map(|i| i.as_str()).collect();
+    let mut queue: VecDeque<&str> = subtree.initiators.iter().map(|i| i.as_str()).collect();
+    let mut vertices = VertexGraph::new();
+    while let Some(fmri) = queue.pop_front() {
+        let vtx = &digraph.vertices[fmri];
+        for edge in vtx.outgoing_edges.iter().flatten() {
+            if digraph.vertices.contains_key(edge) && seen.insert(edge) {
+                queue.push_back(edge);
+            }
+        }
+        vertices.insert(fmri.to_string(), vtx.clone());
+    }
+    subtree.vertices = vertices;
+    subtree
+}
+
+//
+// Generate the index page linking to the pages of the initiators.
+//
+pub(crate) fn index_html(title: &str, pages: &[InitiatorPage], locale: Locale) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n\
+         <style>\n\
+         body {{ font-family: Arial, Helvetica, sans-serif; }}\n\
+         td, th {{ padding: 2px 12px; text-align: left; }}\n\
+         </style>\n</head>\n<body>\n<h1>{}</h1>\n\
+         <table id=\"initiators\">\n\
+         <tr><th>Initiator</th><th>FMRI</th><th>Vertices</th><th>Targets</th></tr>\n",
+        html_escape(title),
+        html_escape(title)
+    );
+    for page in pages {
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            page.html_file,
+            html_escape(&page.label),
+            html_escape(&page.initiator),
+            locale.count(page.vertices),
+            locale.count(page.targets)
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
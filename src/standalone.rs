@@ -0,0 +1,122 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Self-contained HTML output.
+//
+// Normally the HTML page displays the SVG in an iframe, and both refer to the
+// images in the assets directory, so sharing a rendering means sharing the
+// whole output directory.  In standalone mode the SVG is inlined into the
+// page instead, and the images the page refers to (icons in the SVG and the
+// legend) are embedded as data URIs, so that it is a single portable file.
+//
+// The chassis pictures in the host information table are picked by the
+// JavaScript code when the page is loaded and when a vertex is clicked.  The
+// pictures it can pick for the vertices at hand are embedded up front, in the
+// chassis_image_uris table the code looks them up in.
+//
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::path::Path;
+
+use crate::{data_uri, SasDigraph};
+
+// attributes that refer to the assets
+const ASSET_REFS: &[&str] = &["href=\"assets/", "src=\"assets/"];
+
+//
+// Returns markup with every reference to an image under root/assets replaced
+// with the image itself.
+//
+pub(crate) fn embed_assets(markup: &str, root: &str) -> Result<String, Box<dyn Error>> {
+    //
+    // The same few icons are used over and over, so each is only read in
+    // once.
+    //
+    let mut uris: BTreeMap<&str, String> = BTreeMap::new();
+    let mut embedded = String::with_capacity(markup.len());
+    let mut rest = markup;
+    loop {
+        let next = ASSET_REFS
+            .iter()
+            .filter_map(|attr| rest.find(attr).map(|start| (start, attr)))
+            .min();
+        let (start, attr) = match next {
+            Some(next) => next,
+            None => break,
+        };
+        let path_start = start + attr.find('"').unwrap() + 1;
+        let path_len = match rest[path_start..].find('"') {
+            Some(len) => len,
+            None => break,
+        };
+        let path = &rest[path_start..path_start + path_len];
+        if !uris.contains_key(path) {
+            uris.insert(path, data_uri(&format!("{}/{}", root, path))?);
+        }
+        embedded.push_str(&rest[..path_start]);
+        embedded.push_str(&uris[path]);
+        rest = &rest[path_start + path_len..];
+    }
+    embedded.push_str(rest);
+    Ok(embedded)
+}
+
+//
+// Returns the SVG document svg as markup that can be placed in an HTML page,
+// with its size set to width by height and all of the images under
+// root/assets embedded.
+//
+pub(crate) fn inline_svg(
+    svg: &str,
+    root: &str,
+    (width, height): (u32, u32),
+) -> Result<String, Box<dyn Error>> {
+    let svg = match svg.find("<svg") {
+        Some(start) => &svg[start..],
+        None => svg,
+    };
+
+    Ok(embed_assets(svg, root)?.replacen(
+        "<svg ",
+        &format!("<svg width=\"{}\" height=\"{}\" ", width, height),
+        1,
+    ))
+}
+
+//
+// Returns a script defining the table of embedded chassis pictures, keyed by
+// file name: the front and rear of the chassis, and those highlighting the
+// locations of the vertices of digraph.
+//
+pub(crate) fn chassis_images_script(
+    root: &str,
+    digraph: &SasDigraph,
+) -> Result<String, Box<dyn Error>> {
+    let product_id = &digraph.product_id;
+    let mut prefixes = BTreeSet::new();
+    prefixes.insert(product_id.to_string());
+    for vtx in digraph.vertices.values() {
+        for prop in vtx.properties.iter().filter(|p| p.name == "location") {
+            prefixes.insert(format!("{}-{}", product_id, prop.value.replace(' ', "-")));
+        }
+    }
+
+    let mut script = String::from("<script>\nvar chassis_image_uris = {\n");
+    for prefix in &prefixes {
+        for side in &["front", "rear"] {
+            let file = format!("{}-{}.png", prefix, side);
+            let path = format!("{}/assets/{}/{}", root, product_id, file);
+            if Path::new(&path).exists() {
+                script.push_str(&format!("    '{}': '{}',\n", file, data_uri(&path)?));
+            }
+        }
+    }
+    script.push_str("};\n</script>\n");
+    Ok(script)
+}
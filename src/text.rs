@@ -0,0 +1,97 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Plain text rendering, for looking at a topology over SSH without a
+// browser.  The digraph is printed as an indented tree rooted at the
+// initiators, with the WWN and a selection of properties (the same as in the
+// inventory) on the line of each vertex:
+//
+//     testhost (Joyent-M12G5, 2020-01-01T00:00:00Z)
+//     `-- initiator 0x0 500605b0000272a0
+//         `-- port 0x0
+//             `-- expander 0x0 500304801e0f1abf  manufacturer=LSI model=SAS3x40
+//                 |-- port 0x0
+//                 |   `-- target 0x0 5000c50090000000  model=ST4000NM0025
+//                 ...
+//
+// Only ASCII is used for the branches, so the tree survives serial consoles
+// and terminals with odd locales.  A vertex reachable along more than one
+// path (e.g. a drive behind two expanders) is expanded the first time it is
+// reached only.
+//
+use std::collections::HashSet;
+
+use crate::fmri::wwn;
+use crate::{layout_roots, SasDigraph, SasDigraphVertex};
+
+fn vertex_line(vtx: &SasDigraphVertex, properties: &[String]) -> String {
+    let mut line = format!("{} {}", vtx.name, vtx.instance);
+    if let Some(reference) = &vtx.reference {
+        line.push_str(&format!(" [{}]", reference));
+    }
+    if let Some(wwn) = wwn(&vtx.fmri) {
+        line.push_str(&format!(" {}", wwn));
+    }
+    let props: Vec<String> = properties
+        .iter()
+        .filter_map(|name| vtx.properties.iter().find(|p| &p.name == name))
+        .map(|p| format!("{}={}", p.name, p.value))
+        .collect();
+    if !props.is_empty() {
+        line.push_str(&format!("  {}", props.join(" ")));
+    }
+    line
+}
+
+fn render_subtree<'a>(
+    digraph: &'a SasDigraph,
+    fmri: &'a str,
+    properties: &[String],
+    prefix: &str,
+    last: bool,
+    seen: &mut HashSet<&'a str>,
+    out: &mut String,
+) {
+    let vtx = &digraph.vertices[fmri];
+    let branch = if last { "`-- " } else { "|-- " };
+    out.push_str(&format!("{}{}{}", prefix, branch, vertex_line(vtx, properties)));
+    if !seen.insert(fmri) {
+        out.push_str(" (see above)\n");
+        return;
+    }
+    out.push('\n');
+
+    let mut children: Vec<&str> = vtx
+        .outgoing_edges
+        .iter()
+        .flatten()
+        .map(|e| e.as_str())
+        .filter(|e| digraph.vertices.contains_key(e))
+        .collect();
+    children.sort_by_key(|c| (digraph.vertices[*c].instance.value, *c));
+    let prefix = format!("{}{}", prefix, if last { "    " } else { "|   " });
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        render_subtree(digraph, child, properties, &prefix, last, seen, out);
+    }
+}
+
+pub(crate) fn render_text(digraph: &SasDigraph, properties: &[String]) -> String {
+    let mut out = format!(
+        "{} ({}, {})\n",
+        digraph.nodename, digraph.product_id, digraph.timestamp
+    );
+    let roots = layout_roots(digraph);
+    let mut seen = HashSet::new();
+    for (i, root) in roots.iter().enumerate() {
+        let last = i + 1 == roots.len();
+        render_subtree(digraph, root, properties, "", last, &mut seen, &mut out);
+    }
+    out
+}
@@ -0,0 +1,124 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// TikZ output, for including the topology in LaTeX documents.
+//
+// The output is a tikzpicture environment, which can be \input into a
+// document loading the tikz package.  The vertices are placed where they are
+// in the SVG: the picture uses the coordinates of the SVG as is, scaled so
+// that 100 units are a centimeter and with the y axis pointing down.  The
+// edges are drawn the same way as in the SVG, leaving the right side of the
+// source vertex and entering the left side of the destination vertex.  Like
+// the draw.io output, the vertices are drawn as plain shapes, one style per
+// type of vertex, which can be redefined to match the style of the document.
+//
+use std::error::Error;
+
+use crate::{plan_layout, stable_id, Layout, SasDigraph};
+use crate::{EXPANDER, INITIATOR, PORT, TARGET};
+
+// length of the stub leaving the source vertex, as drawn by build_svg()
+const EDGE_STUB: u32 = 50;
+
+//
+// Escapes the characters that are special to LaTeX.
+//
+fn latex_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub(crate) fn render_tikz(
+    digraph: &SasDigraph,
+    layout: &Layout,
+    depth_limit: u32,
+) -> Result<String, Box<dyn Error>> {
+    let plan = plan_layout(digraph, depth_limit)?;
+    let positions = plan.positions(layout, 0);
+    let size = format!(
+        "minimum width={}cm, minimum height={}cm",
+        layout.vertex_width as f64 / 100.0,
+        layout.vertex_height as f64 / 100.0
+    );
+
+    let mut tex = format!(
+        "% SAS topology of {} ({}, {})\n\
+         % generated by {} {}\n",
+        latex_escape(&digraph.nodename),
+        latex_escape(&digraph.product_id),
+        latex_escape(&digraph.timestamp),
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    tex.push_str(&format!(
+        "\\begin{{tikzpicture}}[x=0.01cm, y=-0.01cm,\n\
+         \x20 every node/.style={{draw, align=center, font=\\footnotesize, inner sep=2pt}},\n\
+         \x20 vertex/.style={{{}}},\n\
+         \x20 initiator/.style={{vertex, rounded corners, fill=blue!10}},\n\
+         \x20 expander/.style={{vertex, rounded corners, fill=green!10}},\n\
+         \x20 target/.style={{vertex, fill=yellow!15}},\n\
+         \x20 port/.style={{vertex, circle, fill=gray!10}},\n\
+         \x20 edge/.style={{draw, thin}}]\n",
+        size
+    ));
+
+    let mut fmris: Vec<&String> = positions.keys().collect();
+    fmris.sort();
+    for fmri in &fmris {
+        let vtx = &digraph.vertices[*fmri];
+        let (x, y) = positions[*fmri];
+        let mut label = format!("{} {}", vtx.name, vtx.instance);
+        if let Some(reference) = &vtx.reference {
+            label = format!("{}\\\\{}", latex_escape(reference), latex_escape(&label));
+        } else {
+            label = latex_escape(&label);
+        }
+        let style = match vtx.name.as_str() {
+            INITIATOR | EXPANDER | TARGET | PORT => &vtx.name,
+            _ => "vertex",
+        };
+        tex.push_str(&format!(
+            "  \\node[{}] ({}) at ({}, {}) {{{}}};\n",
+            style,
+            stable_id(fmri),
+            x + (layout.vertex_width / 2),
+            y + (layout.vertex_height / 2),
+            label
+        ));
+    }
+
+    for fmri in &fmris {
+        for edge in digraph.vertices[*fmri].outgoing_edges.iter().flatten() {
+            // only the vertices that were laid out can be connected
+            if !positions.contains_key(edge) {
+                continue;
+            }
+            tex.push_str(&format!(
+                "  \\draw[edge] ({}.east) -- ++({}, 0) |- ({}.west);\n",
+                stable_id(fmri),
+                EDGE_STUB,
+                stable_id(edge)
+            ));
+        }
+    }
+    tex.push_str("\\end{tikzpicture}\n");
+    Ok(tex)
+}
@@ -0,0 +1,228 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Animated transition between two snapshots.
+//
+// Generates an SVG in which the vertices move from their position in the
+// "before" layout to their position in the "after" layout, removed vertices
+// and edges fade out and added ones fade in.  The animation (SMIL) loops, so
+// the changes are easy to follow when the picture is shown during a
+// change-review meeting.
+//
+// For scripts post-processing the SVG (e.g. to count the changes), the
+// vertices and edges that changed carry a data-change attribute saying how:
+// "added", "removed", or for vertices present in both snapshots whose
+// properties differ, "modified".  Edges carry the FMRIs of their endpoints as
+// data-source and data-target.
+//
+use svg::node::element::{Element, Group, Image, Line, Text};
+use svg::{Document, Node};
+
+use std::cmp;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+
+use crate::diff::TopoDiff;
+use crate::{plan_layout, vertex_icon, Config, SasDigraph, SasDigraphVertex};
+
+// duration of one iteration of the animation
+const DURATION: &str = "6s";
+// the animation holds the "before" state for the first 20% of an iteration,
+// transitions during the next 50% and then holds the "after" state
+const KEY_TIMES: &str = "0;0.2;0.7;1";
+
+const ADDED_COLOR: &str = "#008000";
+const REMOVED_COLOR: &str = "#C00000";
+
+// space reserved above the graph for the caption
+const CAPTION_HEIGHT: u32 = 50;
+
+fn animate(attr: &str, from: u32, to: u32) -> Element {
+    let mut anim = Element::new("animate");
+    anim.assign("attributeName", attr);
+    anim.assign("values", format!("{};{};{};{}", from, from, to, to));
+    anim.assign("keyTimes", KEY_TIMES);
+    anim.assign("dur", DURATION);
+    anim.assign("repeatCount", "indefinite");
+    anim
+}
+
+fn fade(out: bool) -> Element {
+    let mut anim = Element::new("animate");
+    anim.assign("attributeName", "opacity");
+    anim.assign("values", if out { "1;1;0;0" } else { "0;0;1;1" });
+    anim.assign("keyTimes", KEY_TIMES);
+    anim.assign("dur", DURATION);
+    anim.assign("repeatCount", "indefinite");
+    anim
+}
+
+fn properties_differ(before: &SasDigraphVertex, after: &SasDigraphVertex) -> bool {
+    let props = |vtx: &SasDigraphVertex| -> BTreeSet<(String, String)> {
+        vtx.properties
+            .iter()
+            .map(|p| (p.name.clone(), p.value.clone()))
+            .collect()
+    };
+    props(before) != props(after)
+}
+
+pub(crate) fn build_transition_svg(
+    config: &Config,
+    before: &SasDigraph,
+    after: &SasDigraph,
+) -> Result<Document, Box<dyn Error>> {
+    let layout = config.options.layout();
+    let theme = config.options.theme();
+    let diff = TopoDiff::new(before, after);
+
+    let before_plan = plan_layout(before, config.options.max_depth())?;
+    let after_plan = plan_layout(after, config.options.max_depth())?;
+    let before_pos = before_plan.positions(layout, CAPTION_HEIGHT);
+    let after_pos = after_plan.positions(layout, CAPTION_HEIGHT);
+
+    let max_depth = cmp::max(before_plan.max_depth, after_plan.max_depth);
+    let max_height = cmp::max(before_plan.max_height, after_plan.max_height) as u32;
+    let width = cmp::max(1, max_depth) * layout.column_width + layout.x_margin;
+    let height = cmp::max(1, max_height) * layout.row_height + layout.y_margin + CAPTION_HEIGHT;
+
+    let caption = Text::new()
+        .set("x", layout.x_margin)
+        .set("y", layout.y_margin + 20)
+        .set("font-family", "Arial, Helvetica, sans-serif")
+        .set("font-size", 18)
+        .add(svg::node::Text::new(format!(
+            "{} \u{2192} {}: {} vertices added, {} removed, {} edges added, {} removed",
+            before.timestamp,
+            after.timestamp,
+            diff.added_vertices.len(),
+            diff.removed_vertices.len(),
+            diff.added_edges.len(),
+            diff.removed_edges.len()
+        )));
+
+    let document = Document::new()
+        .set("width", width)
+        .set("height", height)
+        .set("viewBox", (0, 0, width, height))
+        .add(caption);
+
+    //
+    // Edges are drawn as straight lines from the right side of the source
+    // vertex to the left side of the destination vertex.
+    //
+    let edge_coords = |pos: &HashMap<String, (u32, u32)>, from: &str, to: &str| {
+        match (pos.get(from), pos.get(to)) {
+            (Some(src), Some(dst)) => Some((
+                src.0 + layout.vertex_width,
+                src.1 + (layout.vertex_height / 2),
+                dst.0,
+                dst.1 + (layout.vertex_height / 2),
+            )),
+            _ => None,
+        }
+    };
+
+    let mut edges = BTreeSet::new();
+    for digraph in &[before, after] {
+        for vtx in digraph.vertices.values() {
+            if let Some(outgoing) = &vtx.outgoing_edges {
+                for edge in outgoing {
+                    edges.insert((vtx.fmri.clone(), edge.clone()));
+                }
+            }
+        }
+    }
+
+    let mut edge_layer = Group::new().set("id", "layer-edges");
+    for (from, to) in &edges {
+        let old = edge_coords(&before_pos, from, to);
+        let new = edge_coords(&after_pos, from, to);
+        let (start, end, color) = if diff.added_edges.contains(&(from.clone(), to.clone())) {
+            (new, new, ADDED_COLOR)
+        } else if diff.removed_edges.contains(&(from.clone(), to.clone())) {
+            (old, old, REMOVED_COLOR)
+        } else {
+            (old, new, theme.edge_color.as_str())
+        };
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) => (start, end),
+            // one of the endpoints isn't part of the layout
+            _ => continue,
+        };
+
+        let mut line = Line::new()
+            .set("data-source", from.clone())
+            .set("data-target", to.clone())
+            .set("x1", start.0)
+            .set("y1", start.1)
+            .set("x2", start.2)
+            .set("y2", start.3)
+            .set("stroke", color)
+            .set("stroke-width", theme.edge_width);
+        if color == ADDED_COLOR {
+            line = line.set("data-change", "added").add(fade(false));
+        } else if color == REMOVED_COLOR {
+            line = line.set("data-change", "removed").add(fade(true));
+        } else {
+            line = line
+                .add(animate("x1", start.0, end.0))
+                .add(animate("y1", start.1, end.1))
+                .add(animate("x2", start.2, end.2))
+                .add(animate("y2", start.3, end.3));
+        }
+        edge_layer = edge_layer.add(line);
+    }
+
+    let mut vertex_layer = Group::new().set("id", "layer-vertices");
+    let mut fmris: Vec<&String> = before.vertices.keys().chain(after.vertices.keys()).collect();
+    fmris.sort();
+    fmris.dedup();
+    for fmri in fmris {
+        let (vtx, old, new) = match (after.vertices.get(fmri), before.vertices.get(fmri)) {
+            (Some(vtx), _) => (vtx, before_pos.get(fmri), after_pos.get(fmri)),
+            (None, Some(vtx)) => (vtx, before_pos.get(fmri), None),
+            (None, None) => continue,
+        };
+        let change = match (before.vertices.get(fmri), after.vertices.get(fmri)) {
+            (None, _) => Some("added"),
+            (_, None) => Some("removed"),
+            (Some(old), Some(new)) if properties_differ(old, new) => Some("modified"),
+            _ => None,
+        };
+
+        let (start, anims) = match (old, new) {
+            (Some(old), Some(new)) => (
+                old,
+                vec![animate("x", old.0, new.0), animate("y", old.1, new.1)],
+            ),
+            (Some(old), None) => (old, vec![fade(true)]),
+            (None, Some(new)) => (new, vec![fade(false)]),
+            // not reachable in either layout
+            (None, None) => continue,
+        };
+
+        let mut img = Image::new()
+            .set("href", vertex_icon(&vtx.name)?)
+            .set("fmri", fmri.clone())
+            .set("x", start.0)
+            .set("y", start.1)
+            .set("width", layout.vertex_width)
+            .set("height", layout.vertex_height);
+        if let Some(change) = change {
+            img = img.set("data-change", change);
+        }
+        for anim in anims {
+            img = img.add(anim);
+        }
+        vertex_layer = vertex_layer.add(img);
+    }
+
+    Ok(document.add(edge_layer).add(vertex_layer))
+}
@@ -0,0 +1,417 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Validation of snapshots against the topo digraph XML schema.
+//
+// The parser is lenient (it skips elements it doesn't know, see reader.rs),
+// and what it does trip over is reported without saying where, so a
+// truncated or hand-edited snapshot fails with little to go by.  Validation
+// is an optional pass over the XML before the parse, which checks its
+// structure against the schema of the snapshots libtopo writes:
+//
+//     topology
+//         fmri-scheme (optional), nodename, os-version, product-id, timestamp
+//         vertices
+//             vertex (name, instance, fmri)
+//                 nvpair (name, type, value), with nested nvpairs (the
+//                 elements of arrays) or nvlists (those of nvlist arrays)
+//                 outgoing-edges
+//                     edge (fmri)
+//
+// along with the layout of the property groups within the nvpairs.  Each
+// violation is reported with the path of the element, e.g.:
+//
+//     /topology/vertices/vertex[12]/nvpair[1]/nvlist[2]: property group
+//     without property-group-name
+//
+// Like the parse, validation streams the snapshot, so it only ever holds the
+// path to the element being checked.
+//
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+
+use crate::Scheme;
+
+//
+// The elements of the schema and the elements they may appear in, the root
+// element appearing in "".
+//
+const ELEMENTS: &[(&str, &[&str])] = &[
+    ("topology", &[""]),
+    ("fmri-scheme", &["topology"]),
+    ("nodename", &["topology"]),
+    ("os-version", &["topology"]),
+    ("product-id", &["topology"]),
+    ("timestamp", &["topology"]),
+    ("vertices", &["topology"]),
+    ("vertex", &["vertices"]),
+    ("nvpair", &["vertex", "nvlist", "nvpair"]),
+    ("nvlist", &["nvpair"]),
+    ("outgoing-edges", &["vertex"]),
+    ("edge", &["outgoing-edges"]),
+];
+
+// the attributes each element requires
+const REQUIRED_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("vertex", &["name", "instance", "fmri"]),
+    ("edge", &["fmri"]),
+];
+
+// the elements the topology has to contain exactly one of
+const TOPOLOGY_ELEMENTS: &[&str] =
+    &["nodename", "os-version", "product-id", "timestamp", "vertices"];
+
+// the types of nvpairs, as libnvpair names them
+const NVPAIR_TYPES: &[&str] = &[
+    "boolean",
+    "boolean-value",
+    "byte",
+    "int8",
+    "uint8",
+    "int16",
+    "uint16",
+    "int32",
+    "uint32",
+    "int64",
+    "uint64",
+    "double",
+    "hrtime",
+    "string",
+    "nvlist",
+    "boolean-array",
+    "byte-array",
+    "int8-array",
+    "uint8-array",
+    "int16-array",
+    "uint16-array",
+    "int32-array",
+    "uint32-array",
+    "int64-array",
+    "uint64-array",
+    "string-array",
+    "nvlist-array",
+];
+
+//
+// The nvpairs the nvlists in an nvlist array of the specified name have to
+// contain, i.e. those describing a property group and a property.  Property
+// groups without values are valid, they're handled as the options say (see
+// EmptyPropGroups).
+//
+const NVLIST_MEMBERS: &[(&str, &[&str])] = &[
+    ("property-groups", &["property-group-name"]),
+    ("property-values", &["property-name", "property-value"]),
+];
+
+// the elements that can appear more than once, whose paths are indexed
+const REPEATED_ELEMENTS: &[&str] = &["vertex", "nvpair", "nvlist", "edge"];
+
+//
+// Violations reported at most, the rest are only counted.
+//
+pub const MAX_VIOLATIONS: usize = 20;
+
+//
+// A way in which a snapshot doesn't match the schema.
+//
+#[derive(Debug)]
+pub struct SchemaViolation {
+    // path of the element, e.g. /topology/vertices/vertex[3]
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+//
+// An element that is open, i.e. whose end hasn't been read yet.
+//
+struct Frame {
+    name: String,
+    path: String,
+    // name and type of an nvpair
+    nvpair_name: Option<String>,
+    nvpair_type: Option<String>,
+    text: String,
+    // number of the children read so far, by element name
+    children: HashMap<String, usize>,
+    // names of the nvpairs of an nvlist
+    members: Vec<String>,
+}
+
+struct Validator {
+    stack: Vec<Frame>,
+    violations: Vec<SchemaViolation>,
+    // violations found beyond MAX_VIOLATIONS
+    more: usize,
+}
+
+fn attributes(start: &BytesStart) -> HashMap<String, String> {
+    start
+        .attributes()
+        .flatten()
+        .map(|attr| {
+            let key = String::from_utf8_lossy(attr.key.as_ref()).to_string();
+            let value = match attr.unescape_value() {
+                Ok(value) => value.to_string(),
+                Err(_) => String::from_utf8_lossy(&attr.value).to_string(),
+            };
+            (key, value)
+        })
+        .collect()
+}
+
+fn is_integer(value: &str) -> bool {
+    let value = value.trim();
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).is_ok(),
+        None => digits.parse::<u64>().is_ok(),
+    }
+}
+
+impl Validator {
+    fn violation(&mut self, path: &str, message: String) {
+        if self.violations.len() == MAX_VIOLATIONS {
+            self.more += 1;
+            return;
+        }
+        self.violations.push(SchemaViolation {
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    fn open(&mut self, start: &BytesStart) {
+        let name = String::from_utf8_lossy(start.name().as_ref()).to_string();
+        let attrs = attributes(start);
+        let (parent, path) = match self.stack.last_mut() {
+            Some(parent) => {
+                let count = parent.children.entry(name.clone()).or_insert(0);
+                *count += 1;
+                let path = match REPEATED_ELEMENTS.contains(&name.as_str()) {
+                    true => format!("{}/{}[{}]", parent.path, name, count),
+                    false => format!("{}/{}", parent.path, name),
+                };
+                (parent.name.clone(), path)
+            }
+            None => (String::new(), format!("/{}", name)),
+        };
+
+        match ELEMENTS.iter().find(|(element, _)| *element == name) {
+            None => self.violation(&path, format!("unknown element <{}>", name)),
+            Some((_, parents)) if !parents.contains(&parent.as_str()) => {
+                let parent = match parent.as_str() {
+                    "" => "the document".to_string(),
+                    parent => format!("<{}>", parent),
+                };
+                self.violation(&path, format!("<{}> can't appear in {}", name, parent));
+            }
+            _ => (),
+        }
+        if let Some((_, required)) = REQUIRED_ATTRIBUTES.iter().find(|(e, _)| *e == name) {
+            for attr in required.iter().filter(|attr| !attrs.contains_key(**attr)) {
+                self.violation(&path, format!("<{}> without {} attribute", name, attr));
+            }
+        }
+
+        let parent_type = self.stack.last().and_then(|frame| frame.nvpair_type.clone());
+        match name.as_str() {
+            "nvpair" if parent == "nvpair" => self.array_element(&path, parent_type, &attrs),
+            "nvpair" => self.nvpair(&path, &attrs),
+            "nvlist" => match parent_type.as_deref() {
+                Some("nvlist") | Some("nvlist-array") | None => (),
+                Some(t) => self.violation(&path, format!("<nvlist> in an nvpair of type {}", t)),
+            },
+            _ => (),
+        }
+        if let (Some(frame), Some(nvpair)) = (self.stack.last_mut(), attrs.get("name")) {
+            if frame.name == "nvlist" && name == "nvpair" {
+                frame.members.push(nvpair.clone());
+            }
+        }
+
+        self.stack.push(Frame {
+            name,
+            path,
+            nvpair_name: attrs.get("name").cloned(),
+            nvpair_type: attrs.get("type").cloned(),
+            text: String::new(),
+            children: HashMap::new(),
+            members: Vec::new(),
+        });
+    }
+
+    fn nvpair(&mut self, path: &str, attrs: &HashMap<String, String>) {
+        if !attrs.contains_key("name") {
+            self.violation(path, "<nvpair> without name attribute".to_string());
+        }
+        let nvpair_type = match attrs.get("type") {
+            Some(nvpair_type) => nvpair_type,
+            None => {
+                self.violation(path, "<nvpair> without type attribute".to_string());
+                return;
+            }
+        };
+        if !NVPAIR_TYPES.contains(&nvpair_type.as_str()) {
+            self.violation(path, format!("unknown nvpair type {}", nvpair_type));
+            return;
+        }
+        let scalar = !nvpair_type.ends_with("-array") && nvpair_type != "nvlist";
+        match attrs.get("value") {
+            None if scalar && nvpair_type != "boolean" => {
+                self.violation(path, format!("{} nvpair without value attribute", nvpair_type));
+            }
+            Some(value) if nvpair_type.contains("int") && !is_integer(value) => {
+                self.violation(path, format!("{} nvpair with value {}", nvpair_type, value));
+            }
+            _ => (),
+        }
+    }
+
+    fn array_element(
+        &mut self,
+        path: &str,
+        parent_type: Option<String>,
+        attrs: &HashMap<String, String>,
+    ) {
+        match parent_type.as_deref() {
+            Some("nvlist-array") | Some("nvlist") => {
+                let message = "<nvpair> in an nvlist nvpair, outside an <nvlist>";
+                self.violation(path, message.to_string())
+            }
+            Some(t) if !t.ends_with("-array") => {
+                self.violation(path, format!("array element in an nvpair of type {}", t))
+            }
+            _ => (),
+        }
+        match (attrs.get("value"), parent_type.as_deref()) {
+            (None, _) => self.violation(path, "array element without value attribute".to_string()),
+            (Some(value), Some(t)) if t.contains("int") && !is_integer(value) => {
+                self.violation(path, format!("{} element with value {}", t, value))
+            }
+            _ => (),
+        }
+    }
+
+    fn close(&mut self) {
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        match frame.name.as_str() {
+            "topology" => {
+                for element in TOPOLOGY_ELEMENTS {
+                    match frame.children.get(*element) {
+                        None => self.violation(&frame.path, format!("no <{}> element", element)),
+                        Some(count) if *count > 1 => self.violation(
+                            &frame.path,
+                            format!("{} <{}> elements, rather than one", count, element),
+                        ),
+                        _ => (),
+                    }
+                }
+            }
+            "fmri-scheme" => {
+                if let Err(e) = frame.text.trim().parse::<Scheme>() {
+                    self.violation(&frame.path, e);
+                }
+            }
+            "nvlist" => {
+                let array = self.stack.last().and_then(|parent| parent.nvpair_name.as_deref());
+                let required = NVLIST_MEMBERS.iter().find(|(name, _)| Some(*name) == array);
+                if let Some((array, members)) = required {
+                    let what = match *array {
+                        "property-groups" => "property group",
+                        _ => "property",
+                    };
+                    for member in members.iter() {
+                        if !frame.members.iter().any(|name| name == member) {
+                            self.violation(&frame.path, format!("{} without {}", what, member));
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+//
+// Validate the XML read from source, returning the violations of the schema
+// found (at most MAX_VIOLATIONS of them, plus a last one counting the rest).
+// Malformed XML ends the validation, as a violation at the element it was
+// found in.
+//
+pub(crate) fn validate(source: &mut dyn BufRead) -> Vec<SchemaViolation> {
+    let mut reader = Reader::from_reader(source);
+    reader.config_mut().trim_text(true);
+    let mut validator = Validator {
+        stack: Vec::new(),
+        violations: Vec::new(),
+        more: 0,
+    };
+    let mut root = false;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(start)) => {
+                root = true;
+                validator.open(&start);
+            }
+            Ok(Event::Empty(start)) => {
+                root = true;
+                validator.open(&start);
+                validator.close();
+            }
+            Ok(Event::End(_)) => validator.close(),
+            Ok(Event::Text(text)) => {
+                if let Some(frame) = validator.stack.last_mut() {
+                    frame.text.push_str(&String::from_utf8_lossy(&text));
+                }
+            }
+            Ok(Event::Eof) => {
+                if let Some(frame) = validator.stack.last() {
+                    let message = format!("unexpected end of the snapshot in <{}>", frame.name);
+                    let path = frame.path.clone();
+                    validator.violation(&path, message);
+                } else if !root {
+                    validator.violation("/", "no <topology> element".to_string());
+                }
+                break;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                let path = validator.stack.last().map_or("/".to_string(), |f| f.path.clone());
+                let message =
+                    format!("malformed XML at byte {}: {}", reader.error_position(), e);
+                validator.violation(&path, message);
+                break;
+            }
+        }
+    }
+
+    let mut violations = validator.violations;
+    if validator.more > 0 {
+        violations.push(SchemaViolation {
+            path: "/".to_string(),
+            message: format!("... and {} more", validator.more),
+        });
+    }
+    violations
+}
@@ -0,0 +1,93 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Per-vertex property pages.
+//
+// The info panel only shows the properties embedded in the SVG, which may be
+// capped or filtered.  When requested, every vertex additionally gets a small
+// static page of its own listing all of its properties by property group
+// (and the raw nvlist dump, if enabled), which the info panel links to.  The
+// pages are named by the stable id of the FMRI of the vertex, e.g.
+// vertices/v1e9ac0bfb971d22d.html, so links to them keep working across
+// snapshots.
+//
+use crate::{html_escape, stable_id, RenderOptions, SasDigraphVertex};
+
+pub(crate) fn page_file(fmri: &str) -> String {
+    format!("vertices/{}.html", stable_id(fmri))
+}
+
+pub(crate) fn render_page(vtx: &SasDigraphVertex, options: &RenderOptions) -> String {
+    let mut title = format!("{} {}", vtx.name, vtx.instance);
+    if let Some(reference) = &vtx.reference {
+        title = format!("{} ({})", reference, title);
+    }
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n\
+         <style>\n\
+         body {{ font-family: Arial, Helvetica, sans-serif; }}\n\
+         td, th {{ padding: 2px 12px; text-align: left; vertical-align: top; }}\n\
+         td.value {{ font-family: monospace; word-break: break-all; }}\n\
+         </style>\n</head>\n<body>\n<h1>{}</h1>\n<p><code>{}</code></p>\n",
+        html_escape(&title),
+        html_escape(&title),
+        html_escape(&vtx.fmri)
+    );
+
+    //
+    // The groups are listed in the order they appear in the snapshot.
+    // Properties that don't come from a property group (e.g. the identity
+    // derived from the FMRI of an old snapshot) are listed last.
+    //
+    let mut groups: Vec<Option<&str>> = Vec::new();
+    for prop in &vtx.properties {
+        if !groups.contains(&prop.group.as_deref()) {
+            groups.push(prop.group.as_deref());
+        }
+    }
+    groups.sort_by_key(|group| group.is_none());
+    for group in groups {
+        let props: Vec<_> = vtx
+            .properties
+            .iter()
+            .filter(|p| p.group.as_deref() == group && options.includes_property(&p.name))
+            .collect();
+        if props.is_empty() {
+            continue;
+        }
+        html.push_str(&format!(
+            "<h2>{}</h2>\n<table>\n<tr><th>Property</th><th>Type</th><th>Value</th></tr>\n",
+            html_escape(group.unwrap_or("other"))
+        ));
+        for prop in props {
+            // nested nvlists are easier to read pretty-printed
+            let value = match &prop.structured {
+                Some(json) => format!(
+                    "<pre>{}</pre>",
+                    html_escape(&serde_json::to_string_pretty(json).unwrap_or_default())
+                ),
+                None => html_escape(&prop.value),
+            };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td class=\"value\">{}</td></tr>\n",
+                html_escape(&prop.name),
+                html_escape(prop.proptype.as_deref().unwrap_or("")),
+                value
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    if let Some(dump) = &vtx.raw_nvlist {
+        html.push_str(&format!("<h2>Raw nvlist</h2>\n<pre>{}</pre>\n", html_escape(dump)));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
@@ -0,0 +1,303 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Wide ports.
+//
+// A wide port is a group of PHYs of a device that are attached to the same
+// remote device, usually the four lanes of a cable.  Most snapshots describe
+// it as one port vertex spanning the range of PHYs, e.g.:
+//
+//     .../expander=500304801e0f1abf/port=500304801e0f1abf:start-phy=0:end-phy=3
+//
+// but some HBA and expander drivers report every PHY as a port of its own, so
+// that a cable shows up as four sibling port vertices:
+//
+//     .../expander=500304801e0f1abf/port=500304801e0f1abf:start-phy=0:end-phy=0
+//     ...
+//     .../expander=500304801e0f1abf/port=500304801e0f1abf:start-phy=3:end-phy=3
+//
+// Sibling ports of the same device that are attached to the same SAS address
+// through consecutive PHYs are correlated into the logical wide port they make
+// up, which has the FMRI the snapshot would have given it, the lanes of all of
+// them, and their aggregate speed.
+//
+// For the rendering and the cabling table, the sibling port vertices are
+// merged into one vertex of the logical port, like the ports of dual-ported
+// drives are (see dual_port.rs), so that a cable is drawn and listed once.
+// The JSON export keeps the vertices, and lists the logical ports alongside.
+//
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::analysis::{link_rate_gbps, parse_count, prop};
+use crate::fmri::{device_fmri, field};
+use crate::properties::{categorize, PropCategory};
+use crate::{SasDigraph, SasDigraphProperty, SasDigraphVertex, PORT};
+
+//
+// Names of the properties a merged port vertex carries the lane count, the
+// aggregate speed and the FMRIs of the port vertices it was merged from in.
+//
+const LANES_PROPERTY: &str = "lanes";
+const SPEED_PROPERTY: &str = "link-speed";
+const MEMBERS_PROPERTY: &str = "wide-port-members";
+
+//
+// A logical wide port made up of sibling port vertices.
+//
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WidePort {
+    // FMRI of the logical port, spanning the PHYs of all of its members
+    pub fmri: String,
+    pub device_fmri: String,
+    pub sas_address: String,
+    pub attached_sas_address: String,
+    pub start_phy: u32,
+    pub end_phy: u32,
+    pub lanes: u32,
+    // sum of the negotiated rates of the lanes that are up, in Gbit/s, if any
+    // of them report one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed_gbps: Option<f64>,
+    // FMRIs of the port vertices, in the order of their PHYs
+    pub members: Vec<String>,
+}
+
+fn phy_range(vtx: &SasDigraphVertex) -> Option<(u32, u32)> {
+    let start = field(&vtx.fmri, "start-phy")?.parse().ok()?;
+    let end = field(&vtx.fmri, "end-phy")?.parse().ok()?;
+    Some((start, end))
+}
+
+//
+// Whether the property holds one value per PHY, which are concatenated when
+// ports are merged.  The type isn't known for digraphs read from a JSON
+// export, so the link rates and error counters are recognized by name too.
+//
+fn per_phy(prop: &SasDigraphProperty) -> bool {
+    matches!(&prop.proptype, Some(t) if t.ends_with("-array"))
+        || prop.name.contains("link-rate")
+        || categorize(&prop.name) == PropCategory::Errors
+}
+
+fn wide_port(members: &[&SasDigraphVertex]) -> WidePort {
+    let first = members[0];
+    let (start_phy, _) = phy_range(first).unwrap_or_default();
+    let (_, end_phy) = phy_range(members[members.len() - 1]).unwrap_or_default();
+    let sas_address = prop(first, "local-sas-address")
+        .or_else(|| field(&first.fmri, "port"))
+        .unwrap_or_default()
+        .to_string();
+    let fmri = format!(
+        "{}/port={}:start-phy={}:end-phy={}",
+        device_fmri(&first.fmri),
+        field(&first.fmri, "port").unwrap_or(&sas_address),
+        start_phy,
+        end_phy
+    );
+
+    let speeds: Vec<f64> = members
+        .iter()
+        .filter_map(|vtx| prop(vtx, "negotiated-link-rate"))
+        .flat_map(|rates| rates.split(','))
+        .filter_map(|rate| parse_count(rate).and_then(link_rate_gbps))
+        .collect();
+
+    WidePort {
+        fmri,
+        device_fmri: device_fmri(&first.fmri).to_string(),
+        sas_address,
+        attached_sas_address: prop(first, "attached-sas-address").unwrap_or_default().to_string(),
+        start_phy,
+        end_phy,
+        lanes: end_phy - start_phy + 1,
+        speed_gbps: if speeds.is_empty() { None } else { Some(speeds.iter().sum()) },
+        members: members.iter().map(|vtx| vtx.fmri.clone()).collect(),
+    }
+}
+
+//
+// Returns the logical wide ports made up of more than one port vertex,
+// sorted by FMRI.
+//
+pub(crate) fn wide_ports(digraph: &SasDigraph) -> Vec<WidePort> {
+    let mut siblings: BTreeMap<(&str, &str), Vec<&SasDigraphVertex>> = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        if vtx.name != PORT || phy_range(vtx).is_none() {
+            continue;
+        }
+        match prop(vtx, "attached-sas-address") {
+            Some(attached) if !attached.is_empty() => {
+                siblings.entry((device_fmri(&vtx.fmri), attached)).or_default().push(vtx)
+            }
+            _ => (),
+        }
+    }
+
+    let mut ports = Vec::new();
+    for mut ports_of in siblings.into_values() {
+        ports_of.sort_by_key(|vtx| phy_range(vtx));
+        //
+        // Only consecutive PHYs make up a wide port; a device attached to the
+        // same address through PHYs that are apart is attached by two cables.
+        //
+        let mut run: Vec<&SasDigraphVertex> = Vec::new();
+        for vtx in ports_of {
+            if let Some(last) = run.last() {
+                let (_, end) = phy_range(last).unwrap_or_default();
+                let (start, _) = phy_range(vtx).unwrap_or_default();
+                if start != end + 1 {
+                    if run.len() > 1 {
+                        ports.push(wide_port(&run));
+                    }
+                    run.clear();
+                }
+            }
+            run.push(vtx);
+        }
+        if run.len() > 1 {
+            ports.push(wide_port(&run));
+        }
+    }
+    ports.sort_by(|a, b| a.fmri.cmp(&b.fmri));
+    ports
+}
+
+//
+// Returns the vertex of the logical port, made from the first of the port
+// vertices it is made up of, with the per-PHY values of all of them.
+//
+fn merged_vertex(digraph: &SasDigraph, port: &WidePort) -> SasDigraphVertex {
+    let members: Vec<&SasDigraphVertex> =
+        port.members.iter().map(|fmri| &digraph.vertices[fmri]).collect();
+
+    let mut vtx = members[0].clone();
+    vtx.fmri = port.fmri.clone();
+    for prop in vtx.properties.iter_mut().filter(|p| per_phy(p)) {
+        let values: Vec<&str> = members
+            .iter()
+            .filter_map(|member| member.properties.iter().find(|p| p.name == prop.name))
+            .map(|p| p.value.as_str())
+            .collect();
+        prop.value = values.join(",");
+        prop.structured = None;
+        prop.typed = None;
+    }
+
+    let mut edges: Vec<String> = Vec::new();
+    for edge in members.iter().flat_map(|member| member.outgoing_edges()) {
+        if !edges.contains(edge) {
+            edges.push(edge.clone());
+        }
+    }
+    vtx.outgoing_edges = Some(edges);
+
+    let group = Some("sas-port".to_string());
+    let mut push = |name: &str, value: String| {
+        let mut prop = SasDigraphProperty::new(name.to_string(), value);
+        prop.group = group.clone();
+        vtx.properties.push(prop);
+    };
+    push(LANES_PROPERTY, port.lanes.to_string());
+    if let Some(speed) = port.speed_gbps {
+        push(SPEED_PROPERTY, format!("{}G", speed));
+    }
+    push(MEMBERS_PROPERTY, port.members.join(","));
+    vtx
+}
+
+//
+// Returns a copy of the digraph with the port vertices of each logical wide
+// port merged into one, or None if there aren't any.  The edges into the
+// merged vertices are redirected to the logical ports, which leaves the
+// edges between the PHYs of the two ends of a cable as one edge.
+//
+pub(crate) fn merged(digraph: &SasDigraph) -> Option<SasDigraph> {
+    let ports = wide_ports(digraph);
+    if ports.is_empty() {
+        return None;
+    }
+
+    let mut merged = digraph.clone();
+    let mut logical: HashMap<&str, &str> = HashMap::new();
+    for port in &ports {
+        let vtx = merged_vertex(digraph, port);
+        for member in &port.members {
+            merged.vertices.remove(member);
+            logical.insert(member, &port.fmri);
+        }
+        merged.vertices.insert(vtx.fmri.clone(), vtx);
+    }
+
+    merged.vertices.map_edges(|vtx| {
+        let edges = vtx.outgoing_edges.as_ref()?;
+        let mut redirected: Vec<String> = Vec::new();
+        for edge in edges.iter() {
+            let edge = logical.get(edge.as_str()).map_or(edge.as_str(), |fmri| *fmri);
+            if !redirected.iter().any(|e| e == edge) {
+                redirected.push(edge.to_string());
+            }
+        }
+        Some(redirected)
+    });
+    Some(merged)
+}
+
+//
+// Returns the FMRIs of the port vertices that were merged into the given one
+// by merged(), if any.
+//
+pub(crate) fn absorbed(vtx: &SasDigraphVertex) -> Vec<&str> {
+    match prop(vtx, MEMBERS_PROPERTY) {
+        Some(members) if !members.is_empty() => members.split(',').collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXP: &str = "sas://:hba=500605b0000272a0:/expander=500304801e0f1abf";
+    const DISK: &str = "sas://:hba=500605b0000272a0:/target=5000c50090000000";
+
+    fn phy(n: u32) -> String {
+        format!("{}/port=500304801e0f1abf:start-phy={}:end-phy={}", EXP, n, n)
+    }
+
+    fn topology() -> SasDigraph {
+        let mut builder = SasDigraph::builder()
+            .add_vertex(EXP, "expander", 0)
+            .add_vertex(DISK, "target", 0);
+        for n in 0..4 {
+            builder = builder
+                .add_vertex(&phy(n), PORT, 0)
+                .add_property(&phy(n), "sas-port", "attached-sas-address", "5000c50090000000")
+                .add_edge(EXP, &phy(n))
+                .add_edge(&phy(n), DISK);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn merged_members() {
+        let merged = merged(&topology()).unwrap();
+        let logical = format!("{}/port=500304801e0f1abf:start-phy=0:end-phy=3", EXP);
+        for n in 0..4 {
+            assert!(!merged.vertices.contains_key(&phy(n)));
+        }
+        assert_eq!(merged.vertices[EXP].outgoing_edges(), &[logical.as_str()]);
+        assert_eq!(merged.vertices[&logical].outgoing_edges(), &[DISK]);
+
+        let members: Vec<String> = (0..4).map(phy).collect();
+        assert_eq!(absorbed(&merged.vertices[&logical]), members);
+        assert!(absorbed(&merged.vertices[EXP]).is_empty());
+    }
+}
@@ -0,0 +1,37 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// Serialized (XML) representation of a vertex of a SAS topology, as generated
+// by sastopo -x, which the vertices are parsed into by TopoXmlReader.
+//
+// These mirror the structures in topo_digraph_xml, except that the elements
+// which are missing from snapshots taken by older versions of sastopo are
+// optional here.  In particular, some of those snapshots don't contain any
+// property groups for a vertex.
+//
+use topo_digraph_xml::NvpairXML;
+
+#[derive(Debug)]
+pub(crate) struct EdgeXml {
+    pub fmri: String,
+}
+
+#[derive(Debug)]
+pub(crate) struct OutgoingEdgesXml {
+    pub edges: Vec<EdgeXml>,
+}
+
+#[derive(Debug)]
+pub(crate) struct VertexXml {
+    pub name: String,
+    pub instance: String,
+    pub fmri: String,
+    pub propgroups: Vec<NvpairXML>,
+    pub outgoing_edges: Option<OutgoingEdgesXml>,
+}
@@ -0,0 +1,159 @@
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright 2020 Joyent, Inc.
+//
+
+//
+// SAS zoning.
+//
+// On a zoned fabric the expanders assign each of their PHYs (and thus the
+// devices attached to them) to a zone group.  If the snapshot carries that
+// information, the vertices in each column are grouped by zone, each group is
+// drawn on a labeled background band, and edges that cross from one zone into
+// another are reported.
+//
+use svg::node::element::{Group, Rectangle, Text};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::fmri::device_fmri;
+use crate::reachability::PALETTE;
+use crate::{ColumnHash, Layout, SasDigraph};
+
+// properties identifying the zone (group) a vertex is in
+const ZONE_PROPS: &[&str] = &["zone-group", "zone-group-id", "zone", "sas-zone", "domain"];
+
+//
+// Returns the zone of each vertex, hashed by FMRI.  Ports that don't carry a
+// zone themselves are in the zone of the device they belong to.  The result
+// is empty if the fabric isn't zoned.
+//
+pub(crate) fn vertex_zones(digraph: &SasDigraph) -> BTreeMap<String, String> {
+    let mut zones = BTreeMap::new();
+    for vtx in digraph.vertices.values() {
+        let prop = vtx.properties.iter().find(|p| ZONE_PROPS.contains(&p.name.as_str()));
+        if let Some(prop) = prop {
+            //
+            // Zone groups are assigned per PHY, so the value may be an array.
+            // A wide port whose PHYs are in different zones is listed under
+            // all of them.
+            //
+            let values: BTreeSet<&str> = prop.value.split(',').map(|v| v.trim()).collect();
+            zones.insert(
+                vtx.fmri.clone(),
+                values.into_iter().collect::<Vec<&str>>().join("+"),
+            );
+        }
+    }
+
+    for vtx in digraph.vertices.values() {
+        if zones.contains_key(&vtx.fmri) {
+            continue;
+        }
+        if let Some(zone) = zones.get(device_fmri(&vtx.fmri)).cloned() {
+            zones.insert(vtx.fmri.clone(), zone);
+        }
+    }
+    zones
+}
+
+//
+// Order the vertices within each column by zone, so that the members of a
+// zone are placed next to each other.  The sort is stable, so the order
+// within a zone is preserved.
+//
+pub(crate) fn group_columns(column_hash: &mut ColumnHash, zones: &BTreeMap<String, String>) {
+    for fmris in column_hash.values_mut() {
+        fmris.sort_by_key(|fmri| zones.get(fmri).cloned().unwrap_or_default());
+    }
+}
+
+//
+// Returns the edges whose endpoints are in different zones.
+//
+pub(crate) fn cross_zone_edges(
+    digraph: &SasDigraph,
+    zones: &BTreeMap<String, String>,
+) -> Vec<(String, String)> {
+    let mut edges = Vec::new();
+    for vtx in digraph.vertices.values() {
+        let from_zone = match zones.get(&vtx.fmri) {
+            Some(zone) => zone,
+            None => continue,
+        };
+        if let Some(outgoing) = &vtx.outgoing_edges {
+            for edge in outgoing {
+                if let Some(to_zone) = zones.get(edge) {
+                    if to_zone != from_zone {
+                        edges.push((vtx.fmri.clone(), edge.clone()));
+                    }
+                }
+            }
+        }
+    }
+    edges.sort();
+    edges
+}
+
+//
+// Draw a labeled background band behind each run of vertices in a column that
+// are in the same zone.  This has to be called after the vertices have been
+// placed.
+//
+pub(crate) fn render_zones(
+    digraph: &SasDigraph,
+    column_hash: &ColumnHash,
+    zones: &BTreeMap<String, String>,
+    layout: &Layout,
+) -> Group {
+    let mut layer = Group::new().set("id", "layer-zones");
+    let colors: BTreeMap<&str, &str> = zones
+        .values()
+        .collect::<BTreeSet<&String>>()
+        .into_iter()
+        .enumerate()
+        .map(|(i, zone)| (zone.as_str(), PALETTE[i % PALETTE.len()]))
+        .collect();
+
+    let mut depths: Vec<&u32> = column_hash.keys().collect();
+    depths.sort();
+    for depth in depths {
+        let mut runs: Vec<(&str, u32, u32, u32)> = Vec::new();
+        for fmri in &column_hash[depth] {
+            let (zone, geom) = match (zones.get(fmri), digraph.vertices.get(fmri)) {
+                (Some(zone), Some(vtx)) => (zone.as_str(), &vtx.geometry),
+                _ => continue,
+            };
+            match runs.last_mut() {
+                Some(run) if run.0 == zone => run.3 = geom.y + geom.height,
+                _ => runs.push((zone, geom.x, geom.y, geom.y + geom.height)),
+            }
+        }
+
+        for (zone, x, top, bottom) in runs {
+            let color = colors[zone];
+            let band = Rectangle::new()
+                .set("x", x.saturating_sub(10))
+                .set("y", top.saturating_sub(24))
+                .set("width", layout.vertex_width + 20)
+                .set("height", bottom - top + 34)
+                .set("rx", 8)
+                .set("fill", color)
+                .set("fill-opacity", 0.12)
+                .set("stroke", color)
+                .set("stroke-opacity", 0.6);
+            let label = Text::new()
+                .set("x", x)
+                .set("y", top.saturating_sub(10))
+                .set("font-family", "Arial, Helvetica, sans-serif")
+                .set("font-size", 12)
+                .set("fill", color)
+                .add(svg::node::Text::new(format!("zone {}", zone)));
+            layer = layer.add(band).add(label);
+        }
+    }
+    layer
+}